@@ -0,0 +1,210 @@
+//! # Summary
+//! Arbitrary output variables (AOVs) describe the first hit of the primary rays (e.g. its normal
+//! or depth) instead of the light arriving at the sensor.
+//!
+//! They are rendered alongside the beauty image and help to debug the setup of a scene or feed a
+//! denoiser.
+//! Primary rays missing the scene leave all AOVs black.
+
+use crate::bxdf::{BxDFFlag, BxDFSamples};
+use crate::geometry::Ray;
+use crate::sampler::FloatSampler;
+use crate::scene::Scene;
+use crate::{Float, Image, UVec2, Vec3};
+use cgmath::{Array, InnerSpace, Zero};
+use image::{ImageBuffer, Rgb};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// An arbitrary output variable.
+#[derive(Copy, Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
+pub enum Aov {
+    /// The surface normal (in world space).
+    Normal,
+    /// The distance to the camera along the primary ray.
+    Depth,
+    /// The position (in world space).
+    Position,
+    /// The reflectance of the BSDF into the direction of the camera.
+    Albedo,
+    /// The index of the object plus one (to distinguish the first object from misses).
+    ObjectIndex,
+    /// A color hashed from the tag of the object.
+    TagHash,
+    /// The number of objects tested for an intersection.
+    Cost,
+}
+
+impl Aov {
+    /// Returns the name of this AOV, which is appended to the image path.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Aov::Normal => "normal",
+            Aov::Depth => "depth",
+            Aov::Position => "position",
+            Aov::Albedo => "albedo",
+            Aov::ObjectIndex => "object-index",
+            Aov::TagHash => "tag-hash",
+            Aov::Cost => "cost",
+        }
+    }
+}
+
+/// A pixel of the [AovBuffer], summing up all AOVs.
+#[derive(Clone, Debug)]
+struct AovPixel {
+    sums: Vec<Vec3>,
+    samples: u32,
+}
+
+/// A thread-safe buffer for the AOVs of a sensor.
+///
+/// Every primary ray adds a sample to its pixel, which are averaged in the final images.
+#[derive(Debug)]
+pub struct AovBuffer {
+    aovs: Vec<Aov>,
+    resolution: UVec2,
+    pixels: Vec<Mutex<AovPixel>>,
+}
+
+impl Default for AovBuffer {
+    fn default() -> Self {
+        Self::new(Vec::new(), UVec2::new(0, 0))
+    }
+}
+
+impl AovBuffer {
+    /// Creates a new, empty AOV buffer.
+    ///
+    /// # Arguments
+    /// * `aovs` - The AOVs to render (none for an empty buffer)
+    /// * `resolution` - The resolution of the sensor
+    pub fn new(aovs: Vec<Aov>, resolution: UVec2) -> Self {
+        let num_pixels = if aovs.is_empty() {
+            0
+        } else {
+            (resolution.x * resolution.y) as usize
+        };
+        let pixel = AovPixel {
+            sums: vec![Vec3::zero(); aovs.len()],
+            samples: 0,
+        };
+
+        Self {
+            aovs,
+            resolution,
+            pixels: (0..num_pixels).map(|_| Mutex::new(pixel.clone())).collect(),
+        }
+    }
+
+    /// Returns whether no AOVs are rendered.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.pixels.is_empty()
+    }
+
+    /// Returns the rendered AOVs.
+    pub fn aovs(&self) -> &[Aov] {
+        &self.aovs
+    }
+
+    pub fn reset(&mut self) {
+        for p in &mut self.pixels {
+            let p = p.get_mut();
+            p.sums.iter_mut().for_each(|s| *s = Vec3::zero());
+            p.samples = 0;
+        }
+    }
+
+    /// Adds the AOVs of a primary ray to its pixel.
+    ///
+    /// # Arguments
+    /// * `scene` - The scene
+    /// * `ray` - The primary ray
+    /// * `position` - The pixel the ray was shot through
+    /// * `sampler` - The sampler for the albedo
+    #[allow(clippy::unnecessary_cast)]
+    pub fn add(&self, scene: &Scene, ray: Ray, position: UVec2, sampler: FloatSampler) {
+        if self.is_empty() {
+            return;
+        }
+
+        let (hit, cost) = scene.intersect_traced(ray);
+
+        let mut pixel = self.pixels[(position.y * self.resolution.x + position.x) as usize].lock();
+        pixel.samples += 1;
+
+        let (index, hit) = match hit {
+            Some(h) => h,
+            None => return,
+        };
+
+        for (sum, aov) in pixel.sums.iter_mut().zip(&self.aovs) {
+            *sum += match aov {
+                Aov::Normal => hit.i.normal,
+                Aov::Depth => Vec3::from_value(hit.i.t * ray.direction.magnitude()),
+                Aov::Position => hit.i.point,
+                Aov::Albedo => {
+                    let outgoing = -hit.i.incoming;
+                    let normal = hit.i.normal;
+                    let bsdf = hit.object.bsdf();
+
+                    match bsdf
+                        .sample(normal, outgoing, sampler.sample(), BxDFFlag::empty(), &())
+                        .and_then(BxDFSamples::bundle)
+                    {
+                        Some(s) if s.pdf > 0.0 => {
+                            let cos_abs = if s.flag.specular() {
+                                // division of cosine omitted in specular bxdfs
+                                1.0
+                            } else {
+                                s.incident.dot(normal).abs()
+                            };
+
+                            let rgb = Rgb::<f32>::from(s.spectrum * (cos_abs / s.pdf));
+                            Vec3::new(rgb[0] as Float, rgb[1] as Float, rgb[2] as Float)
+                        }
+                        _ => Vec3::zero(),
+                    }
+                }
+                Aov::ObjectIndex => Vec3::from_value((index + 1) as Float),
+                Aov::TagHash => {
+                    let mut hasher = DefaultHasher::new();
+                    hit.object.tag().hash(&mut hasher);
+                    let hash = hasher.finish();
+
+                    let channel = |shift: u64| ((hash >> shift) & 0xFF) as Float / 255.0;
+                    Vec3::new(channel(0), channel(8), channel(16))
+                }
+                Aov::Cost => Vec3::from_value(cost as Float),
+            };
+        }
+    }
+
+    /// Returns the averaged image of the given AOV.
+    ///
+    /// # Arguments
+    /// * `aov` - The AOV (black if not rendered)
+    #[allow(clippy::unnecessary_cast)]
+    pub fn get_image(&self, aov: Aov) -> Image<f32> {
+        let mut image = ImageBuffer::new(self.resolution.x, self.resolution.y);
+
+        if let Some(i) = self.aovs.iter().position(|&a| a == aov) {
+            for (index, p) in self.pixels.iter().enumerate() {
+                let p = p.lock();
+                if p.samples == 0 {
+                    continue;
+                }
+
+                let value = p.sums[i] / p.samples as Float;
+                let x = index as u32 % self.resolution.x;
+                let y = index as u32 / self.resolution.x;
+                image.put_pixel(x, y, Rgb([value.x as f32, value.y as f32, value.z as f32]));
+            }
+        }
+
+        image
+    }
+}
@@ -1,6 +1,9 @@
-use crate::bxdf::{same_hemisphere, world_to_bxdf, BxDF, BxDFFlag, BxDFSample, BxDFSamplePacket};
+use crate::bxdf::{
+    same_hemisphere, world_to_bxdf, BxDF, BxDFFlag, BxDFSample, BxDFSamples, FresnelDielectric,
+};
+use crate::color::{SampledSpectrum, SpectralIndices, Wavelengths};
 use crate::sampler::Sample;
-use crate::{Float, Spectrum, Vec3, PACKET_SIZE};
+use crate::{Float, Spectrum, Vec3};
 use serde::{Deserialize, Serialize};
 
 #[derive(Default, Serialize, Deserialize)]
@@ -26,6 +29,26 @@ impl BSDF {
         self.bxdfs.is_empty()
     }
 
+    /// Returns whether any BxDF is not specular, so that the BSDF can be evaluated for arbitrary
+    /// directions.
+    pub fn is_non_specular(&self) -> bool {
+        self.bxdfs.iter().any(|bxdf| bxdf.flag().non_specular())
+    }
+
+    /// Returns the refraction of the only BxDF, if it specularly transmits light through an
+    /// interface (see [BxDF::refraction]).
+    pub fn refraction(&self) -> Option<(FresnelDielectric, Spectrum)> {
+        match self.bxdfs.as_slice() {
+            [bxdf] => bxdf.refraction(),
+            _ => None,
+        }
+    }
+
+    /// Returns whether any BxDF matches the flag.
+    pub fn matches(&self, f: BxDFFlag) -> bool {
+        self.bxdfs.iter().any(|bxdf| bxdf.match_flag(f))
+    }
+
     fn num_types(&self, f: BxDFFlag) -> usize {
         self.bxdfs.iter().filter(|bxdf| bxdf.match_flag(f)).count()
     }
@@ -57,50 +80,40 @@ impl BSDF {
     /// * `outgoing_world` - The outgoing incident vector in world space.
     /// * `sample`: The random sample
     /// * `flags` - The flags to match a BxDF randomly.
-    pub fn evaluate(
+    /// * `indices` - The spectral indices to evaluate
+    pub fn evaluate<I: SpectralIndices>(
         &self,
         normal: Vec3,
         incident_world: Vec3,
         outgoing_world: Vec3,
         sample: Float,
-        mut flags: BxDFFlag,
-    ) -> Spectrum {
-        let rotation = world_to_bxdf(normal);
-
-        let incident = rotation.rotate_vector(incident_world);
-        let outgoing = rotation.rotate_vector(outgoing_world);
-
-        // transmission or reflection
-        if same_hemisphere(incident, outgoing) {
-            flags.remove(BxDFFlag::TRANSMISSION);
-        } else {
-            flags.remove(BxDFFlag::REFLECTION);
-        }
-
-        self.random_matching_bxdf(flags, sample)
-            .map_or(Spectrum::splat(0.0), |bxdf| {
-                bxdf.evaluate(incident, outgoing)
-            })
+        flags: BxDFFlag,
+        indices: &I,
+    ) -> I::Sampled {
+        self.evaluate_with_flag(
+            normal,
+            incident_world,
+            outgoing_world,
+            sample,
+            flags,
+            indices,
+        )
+        .0
     }
 
-    /// Evaluates a random BxDF.
+    /// Evaluates a random BxDF like [Self::evaluate].
     ///
-    /// # Arguments
-    /// * `normal` - The surface normal. Used to rotate into the local BxDF space.
-    /// * `incident_world` - The incoming incident vector in world space.
-    /// * `outgoing_world` - The outgoing incident vector in world space.
-    /// * `sample`: The random sample
-    /// * `flags` - The flags to match a BxDF randomly.
-    /// * `indices` - The spectral indices
-    pub fn evaluate_packet(
+    /// # Returns
+    /// * The evaluated spectrum and the flag of the evaluated BxDF (empty if none matches)
+    pub fn evaluate_with_flag<I: SpectralIndices>(
         &self,
         normal: Vec3,
         incident_world: Vec3,
         outgoing_world: Vec3,
         sample: Float,
         mut flags: BxDFFlag,
-        indices: &[usize; PACKET_SIZE],
-    ) -> [Float; PACKET_SIZE] {
+        indices: &I,
+    ) -> (I::Sampled, BxDFFlag) {
         let rotation = world_to_bxdf(normal);
         let incident = rotation.rotate_vector(incident_world);
         let outgoing = rotation.rotate_vector(outgoing_world);
@@ -112,139 +125,144 @@ impl BSDF {
             flags.remove(BxDFFlag::REFLECTION);
         }
 
-        self.random_matching_bxdf(flags, sample)
-            .map_or([0.0; PACKET_SIZE], |bxdf| {
-                bxdf.evaluate_packet(incident, outgoing, indices)
-            })
+        let mut spectrum = I::Sampled::splat(0.0);
+        match self.random_matching_bxdf(flags, sample) {
+            Some(bxdf) => {
+                bxdf.evaluate(
+                    incident,
+                    outgoing,
+                    indices.wavelengths(),
+                    spectrum.as_mut_slice(),
+                );
+
+                (spectrum, bxdf.flag())
+            }
+            None => (spectrum, BxDFFlag::empty()),
+        }
     }
 
-    /// Evaluates a random BxDF.
+    /// Computes the pdf of sampling the incident direction, averaged over all matching BxDFs as
+    /// they are chosen uniformly by the sampling functions.
     ///
     /// # Arguments
     /// * `normal` - The surface normal. Used to rotate into the local BxDF space.
     /// * `incident_world` - The incoming incident vector in world space.
     /// * `outgoing_world` - The outgoing incident vector in world space.
-    /// * `sample`: The random sample
-    /// * `flags` - The flags to match a BxDF randomly.
-    /// * `index` - The spectral index
-    pub fn evaluate_lambda(
+    /// * `flags` - The flags to match the BxDFs.
+    pub fn pdf(
         &self,
         normal: Vec3,
         incident_world: Vec3,
         outgoing_world: Vec3,
-        sample: Float,
-        mut flags: BxDFFlag,
-        index: usize,
+        flags: BxDFFlag,
     ) -> Float {
+        let count = self.num_types(flags);
+        if count == 0 {
+            return 0.0;
+        }
+
         let rotation = world_to_bxdf(normal);
         let incident = rotation.rotate_vector(incident_world);
         let outgoing = rotation.rotate_vector(outgoing_world);
 
-        // transmission or reflection
-        if same_hemisphere(incident, outgoing) {
-            flags.remove(BxDFFlag::TRANSMISSION);
-        } else {
-            flags.remove(BxDFFlag::REFLECTION);
-        }
+        let pdf: Float = self
+            .bxdfs
+            .iter()
+            .filter(|bxdf| bxdf.match_flag(flags))
+            .map(|bxdf| bxdf.pdf(incident, outgoing))
+            .sum();
 
-        self.random_matching_bxdf(flags, sample)
-            .map_or(0.0, |bxdf| bxdf.evaluate_lambda(incident, outgoing, index))
+        pdf / count as Float
     }
 
     /// Samples a random BxDF.
     ///
+    /// Dispersive BxDFs are sampled for every wavelength on its own.
+    /// The wavelengths stay bundled if they still scatter into the same direction.
+    ///
     /// # Arguments
     /// * `normal` - The surface normal. Used to rotate into the local BxDF space.
     /// * `outgoing_world` - The outgoing incident vector in world space.
     /// * `sample`: The random sample
     /// * `flags` - The flags to match a BxDF randomly.
-    /// * `index` - The spectral index
-    pub fn sample(
+    /// * `indices` - The spectral indices to sample
+    pub fn sample<I: SpectralIndices>(
         &self,
         normal: Vec3,
         outgoing_world: Vec3,
         sample: Sample,
         flags: BxDFFlag,
-    ) -> Option<BxDFSample<Spectrum>> {
+        indices: &I,
+    ) -> Option<BxDFSamples<I::Sampled>> {
         let rotation = world_to_bxdf(normal);
         let outgoing = rotation.rotate_vector(outgoing_world);
+        let inv_rotation = rotation.invert();
 
         let bxdf = self.random_matching_bxdf(flags, sample.float)?;
 
-        bxdf.sample(outgoing, sample.vec2).map(|mut s| {
-            s.incident = rotation.invert().rotate_vector(s.incident);
-            s
-        })
-    }
-
-    /// Samples a random BxDF.
-    ///
-    /// # Arguments
-    /// * `normal` - The surface normal. Used to rotate into the local BxDF space.
-    /// * `outgoing_world` - The outgoing incident vector in world space.
-    /// * `sample`: The random sample
-    /// * `flags` - The flags to match a BxDF randomly.
-    /// * `indices` - The spectral indices
-    pub fn sample_packet(
-        &self,
-        normal: Vec3,
-        outgoing_world: Vec3,
-        sample: Sample,
-        flags: BxDFFlag,
-        indices: &[usize; PACKET_SIZE],
-    ) -> BxDFSamplePacket {
-        let rotation = world_to_bxdf(normal);
-        let outgoing = rotation.rotate_vector(outgoing_world);
-
-        let bxdf = if let Some(b) = self.random_matching_bxdf(flags, sample.float) {
-            b
-        } else {
-            return BxDFSamplePacket::Bundle(None);
-        };
+        match indices.wavelengths() {
+            Wavelengths::Indices(indices) if indices.len() > 1 && bxdf.is_dispersive() => {
+                let split: Vec<_> = indices
+                    .iter()
+                    .map(|index| {
+                        let mut spectrum: Float = 0.0;
+                        let s = bxdf.sample(
+                            outgoing,
+                            sample.vec2,
+                            index.wavelengths(),
+                            spectrum.as_mut_slice(),
+                        )?;
+                        let incident = inv_rotation.rotate_vector(s.incident);
 
-        let mut packet = bxdf.sample_packet(outgoing, sample.vec2, indices);
-        let inv_rotation = rotation.invert();
+                        Some(BxDFSample::new(spectrum, incident, s.pdf, s.flag))
+                    })
+                    .collect();
 
-        match &mut packet {
-            BxDFSamplePacket::Bundle(Some(ref mut s)) => {
-                s.incident = inv_rotation.rotate_vector(s.incident)
+                Some(Self::bundle_coherent(split))
             }
-            BxDFSamplePacket::Split(ref mut samples) => {
-                for s in samples.iter_mut().flatten() {
-                    s.incident = inv_rotation.rotate_vector(s.incident);
-                }
+            wavelengths => {
+                let mut spectrum = I::Sampled::splat(0.0);
+                let s = bxdf.sample(outgoing, sample.vec2, wavelengths, spectrum.as_mut_slice())?;
+                let incident = inv_rotation.rotate_vector(s.incident);
+
+                Some(BxDFSamples::Bundle(BxDFSample::new(
+                    spectrum, incident, s.pdf, s.flag,
+                )))
             }
-            _ => {}
         }
-
-        packet
     }
 
-    /// Samples a random BxDF.
-    ///
-    /// # Arguments
-    /// * `normal` - The surface normal. Used to rotate into the local BxDF space.
-    /// * `outgoing_world` - The outgoing incident vector in world space.
-    /// * `sample`: The random sample
-    /// * `flags` - The flags to match a BxDF randomly.
-    /// * `index` - The spectral index
-    pub fn sample_lambda(
-        &self,
-        normal: Vec3,
-        outgoing_world: Vec3,
-        sample: Sample,
-        flags: BxDFFlag,
-        index: usize,
-    ) -> Option<BxDFSample<Float>> {
-        let rotation = world_to_bxdf(normal);
-        let outgoing = rotation.rotate_vector(outgoing_world);
+    /// Bundles the samples of single wavelengths again if they all scatter into the same direction.
+    fn bundle_coherent<S: SampledSpectrum>(
+        split: Vec<Option<BxDFSample<Float>>>,
+    ) -> BxDFSamples<S> {
+        let first = match split.first() {
+            Some(Some(first)) => *first,
+            _ => return BxDFSamples::Split(split),
+        };
 
-        let bxdf = self.random_matching_bxdf(flags, sample.float)?;
+        let coherent = split.iter().all(|s| {
+            s.is_some_and(|s| s.incident == first.incident && s.flag == first.flag && s.pdf > 0.0)
+        });
+        if !coherent {
+            return BxDFSamples::Split(split);
+        }
 
-        bxdf.sample_lambda(outgoing, sample.vec2, index)
-            .map(|mut s| {
-                s.incident = rotation.invert().rotate_vector(s.incident);
-                s
-            })
+        // scale to the pdf of the first wavelength
+        let mut spectrum = S::splat(0.0);
+        for (value, s) in spectrum
+            .as_mut_slice()
+            .iter_mut()
+            .zip(split.iter().flatten())
+        {
+            *value = s.spectrum * first.pdf / s.pdf;
+        }
+
+        BxDFSamples::Bundle(BxDFSample::new(
+            spectrum,
+            first.incident,
+            first.pdf,
+            first.flag,
+        ))
     }
 }
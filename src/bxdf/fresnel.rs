@@ -1,5 +1,5 @@
 use crate::bxdf::refraction::RefractiveType;
-use crate::{Float, Spectrum, PACKET_SIZE};
+use crate::Float;
 use core::mem;
 use serde::{Deserialize, Serialize};
 
@@ -14,28 +14,9 @@ pub enum FresnelType {
 
 impl Fresnel for FresnelType {
     #[inline]
-    fn evaluate(&self, cos_i: Float) -> Spectrum {
+    fn evaluate(&self, cos_i: Float, lambda: Option<Float>) -> Float {
         match self {
-            FresnelType::Dielectric(t) => t.evaluate(cos_i),
-            FresnelType::Noop => Spectrum::splat(1.0),
-        }
-    }
-
-    fn evaluate_packet(
-        &self,
-        cos_i: Float,
-        lambdas: &[Float; PACKET_SIZE],
-    ) -> [Float; PACKET_SIZE] {
-        match self {
-            FresnelType::Dielectric(d) => d.evaluate_packet(cos_i, lambdas),
-            FresnelType::Noop => [1.0; PACKET_SIZE],
-        }
-    }
-
-    #[inline]
-    fn evaluate_lambda(&self, cos_i: Float, lambda: Float) -> Float {
-        match self {
-            FresnelType::Dielectric(f) => f.evaluate_lambda(cos_i, lambda),
+            FresnelType::Dielectric(d) => d.evaluate(cos_i, lambda),
             FresnelType::Noop => 1.0,
         }
     }
@@ -107,22 +88,9 @@ pub trait Fresnel {
     ///
     /// # Arguments
     /// * `cos_i` - The cosine of the angle between the normal and the incident
-    fn evaluate(&self, cos_i: Float) -> Spectrum;
-
-    /// Computes the surface reflectance at an angle.
-    ///
-    /// # Arguments
-    /// * `cos_i` - The cosine of the angle between the normal and the incident
-    /// * `lambdas`: The wavelengths to compute for
-    fn evaluate_packet(&self, cos_i: Float, lambdas: &[Float; PACKET_SIZE])
-        -> [Float; PACKET_SIZE];
-
-    /// Computes the surface reflectance at an angle.
-    ///
-    /// # Arguments
-    /// * `cos_i` - The cosine of the angle between the normal and the incident
-    /// * `lambda` - The wavelength to compute for
-    fn evaluate_lambda(&self, cos_i: Float, lambda: Float) -> Float;
+    /// * `lambda` - The wavelength to compute for, or `None` for all wavelengths of a spectrum
+    ///   (see [Wavelengths::lambda](crate::color::Wavelengths::lambda))
+    fn evaluate(&self, cos_i: Float, lambda: Option<Float>) -> Float;
 }
 
 /// An implementation of `Fresnel` for dielectric materials.
@@ -147,22 +115,8 @@ impl FresnelDielectric {
 }
 
 impl Fresnel for FresnelDielectric {
-    fn evaluate(&self, cos_i: Float) -> Spectrum {
-        let fresnel = fresnel_dielectric(cos_i, self.eta_i.n_uniform(), self.eta_t.n_uniform());
-
-        Spectrum::splat(fresnel)
-    }
-
-    fn evaluate_packet(
-        &self,
-        cos_i: Float,
-        lambdas: &[Float; PACKET_SIZE],
-    ) -> [Float; PACKET_SIZE] {
-        lambdas.map(|l| fresnel_dielectric(cos_i, self.eta_i.n(l), self.eta_t.n(l)))
-    }
-
     #[inline]
-    fn evaluate_lambda(&self, cos_i: Float, lambda: Float) -> Float {
-        fresnel_dielectric(cos_i, self.eta_i.n(lambda), self.eta_t.n(lambda))
+    fn evaluate(&self, cos_i: Float, lambda: Option<Float>) -> Float {
+        fresnel_dielectric(cos_i, self.eta_i.n_at(lambda), self.eta_t.n_at(lambda))
     }
 }
@@ -1,5 +1,6 @@
 use crate::bxdf::{BxDF, BxDFFlag};
-use crate::{Float, Spectrum, Vec3, PACKET_SIZE};
+use crate::color::Wavelengths;
+use crate::{Float, Spectrum, Vec3};
 use serde::{Deserialize, Serialize};
 #[cfg(not(feature = "f64"))]
 use std::f32::consts::FRAC_1_PI;
@@ -29,22 +30,11 @@ impl BxDF for LambertianReflection {
         BxDFFlag::REFLECTION | BxDFFlag::DIFFUSE
     }
 
-    fn evaluate(&self, _: Vec3, _: Vec3) -> Spectrum {
-        self.r * FRAC_1_PI
-    }
-
-    fn evaluate_packet(
-        &self,
-        _: Vec3,
-        _: Vec3,
-        indices: &[usize; PACKET_SIZE],
-    ) -> [Float; PACKET_SIZE] {
-        indices.map(|i| self.r[i] * FRAC_1_PI)
-    }
-
-    #[inline]
-    fn evaluate_lambda(&self, _: Vec3, _: Vec3, index: usize) -> Float {
-        self.r[index] * FRAC_1_PI
+    fn evaluate(&self, _: Vec3, _: Vec3, wavelengths: Wavelengths, spectrum: &mut [Float]) {
+        wavelengths.select(&self.r, spectrum);
+        for s in spectrum {
+            *s *= FRAC_1_PI;
+        }
     }
 }
 
@@ -71,21 +61,10 @@ impl BxDF for LambertianTransmission {
         BxDFFlag::DIFFUSE | BxDFFlag::TRANSMISSION
     }
 
-    fn evaluate(&self, _: Vec3, _: Vec3) -> Spectrum {
-        self.t * FRAC_1_PI
-    }
-
-    fn evaluate_packet(
-        &self,
-        _: Vec3,
-        _: Vec3,
-        indices: &[usize; PACKET_SIZE],
-    ) -> [Float; PACKET_SIZE] {
-        indices.map(|i| self.t[i] * FRAC_1_PI)
-    }
-
-    #[inline]
-    fn evaluate_lambda(&self, _: Vec3, _: Vec3, index: usize) -> Float {
-        self.t[index] * FRAC_1_PI
+    fn evaluate(&self, _: Vec3, _: Vec3, wavelengths: Wavelengths, spectrum: &mut [Float]) {
+        wavelengths.select(&self.t, spectrum);
+        for s in spectrum {
+            *s *= FRAC_1_PI;
+        }
     }
 }
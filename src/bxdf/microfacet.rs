@@ -0,0 +1,477 @@
+use crate::bxdf::{
+    bxdf_normal, cos2_phi, cos2_theta, cos_phi, cos_theta, face_forward, flip, is_neg,
+    same_hemisphere, sin2_phi, sin_phi, tan2_theta, BxDF, BxDFFlag, BxDFSample, Fresnel,
+    FresnelType,
+};
+use crate::color::Wavelengths;
+use crate::{Float, Spectrum, Vec2, Vec3};
+use cgmath::InnerSpace;
+use serde::{Deserialize, Serialize};
+#[cfg(not(feature = "f64"))]
+use std::f32::consts::{FRAC_1_PI, TAU};
+#[cfg(feature = "f64")]
+use std::f64::consts::{FRAC_1_PI, TAU};
+
+/// The smallest roughness, as smoother distributions are numerically unstable.
+pub const MIN_ROUGHNESS: Float = 1e-3;
+
+/// The roughness `α` of a microfacet distribution.
+///
+/// For Beckmann, it is the root mean square slope of the microfacets.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum Roughness {
+    /// The same roughness in every direction
+    Isotropic(Float),
+    /// The roughnesses along the `x` and `z` tangents of the BxDF space
+    Anisotropic(Float, Float),
+}
+
+impl Roughness {
+    /// Returns the roughnesses along the `x` and `z` tangents, clamped to at least
+    /// [MIN_ROUGHNESS].
+    #[inline]
+    pub fn alphas(self) -> (Float, Float) {
+        let (alpha_x, alpha_z) = match self {
+            Roughness::Isotropic(alpha) => (alpha, alpha),
+            Roughness::Anisotropic(alpha_x, alpha_z) => (alpha_x, alpha_z),
+        };
+
+        (alpha_x.max(MIN_ROUGHNESS), alpha_z.max(MIN_ROUGHNESS))
+    }
+}
+
+/// Describes the distribution of microfacet normals of a rough surface.
+///
+/// All directions are in BxDF space and the microfacets face the normal `+y`.
+#[derive(Copy, Clone, Debug, Serialize, Deserialize)]
+pub enum MicrofacetDistribution {
+    /// The Trowbridge-Reitz distribution, whose long tails cause a glow around highlights
+    Ggx(Roughness),
+    /// The Beckmann-Spizzichino distribution of Gaussian slopes
+    Beckmann(Roughness),
+}
+
+impl MicrofacetDistribution {
+    #[inline]
+    fn alphas(&self) -> (Float, Float) {
+        match self {
+            MicrofacetDistribution::Ggx(r) | MicrofacetDistribution::Beckmann(r) => r.alphas(),
+        }
+    }
+
+    /// Computes the differential area of microfacets with the given normal.
+    ///
+    /// # Arguments
+    /// * `half` - The normalized microfacet normal
+    pub fn d(&self, half: Vec3) -> Float {
+        let tan2_theta = tan2_theta(half);
+        if !tan2_theta.is_finite() {
+            return 0.0;
+        }
+
+        let (alpha_x, alpha_z) = self.alphas();
+        let cos4_theta = cos2_theta(half) * cos2_theta(half);
+        let e = tan2_theta
+            * (cos2_phi(half) / (alpha_x * alpha_x) + sin2_phi(half) / (alpha_z * alpha_z));
+
+        match self {
+            MicrofacetDistribution::Ggx(_) => {
+                FRAC_1_PI / (alpha_x * alpha_z * cos4_theta * (1.0 + e) * (1.0 + e))
+            }
+            MicrofacetDistribution::Beckmann(_) => {
+                FRAC_1_PI * (-e).exp() / (alpha_x * alpha_z * cos4_theta)
+            }
+        }
+    }
+
+    /// Computes the ratio of microfacet area masked by other microfacets to the visible area
+    /// seen from a direction.
+    ///
+    /// # Arguments
+    /// * `w` - The normalized direction
+    pub fn lambda(&self, w: Vec3) -> Float {
+        let tan2_theta = tan2_theta(w);
+        if !tan2_theta.is_finite() {
+            return 0.0;
+        }
+
+        let (alpha_x, alpha_z) = self.alphas();
+        let alpha2 = cos2_phi(w) * alpha_x * alpha_x + sin2_phi(w) * alpha_z * alpha_z;
+
+        match self {
+            MicrofacetDistribution::Ggx(_) => ((1.0 + alpha2 * tan2_theta).sqrt() - 1.0) / 2.0,
+            MicrofacetDistribution::Beckmann(_) => {
+                let a = 1.0 / (alpha2 * tan2_theta).sqrt();
+                if a >= 1.6 {
+                    0.0
+                } else {
+                    (1.0 - 1.259 * a + 0.396 * a * a) / (3.535 * a + 2.181 * a * a)
+                }
+            }
+        }
+    }
+
+    /// Computes the fraction of microfacets visible from a direction.
+    ///
+    /// # Arguments
+    /// * `w` - The normalized direction
+    #[inline]
+    pub fn g1(&self, w: Vec3) -> Float {
+        1.0 / (1.0 + self.lambda(w))
+    }
+
+    /// Computes the fraction of microfacets visible from both directions.
+    ///
+    /// # Arguments
+    /// * `incident` - The normalized incident direction
+    /// * `outgoing` - The normalized outgoing direction
+    #[inline]
+    pub fn g(&self, incident: Vec3, outgoing: Vec3) -> Float {
+        1.0 / (1.0 + self.lambda(incident) + self.lambda(outgoing))
+    }
+
+    /// Samples a microfacet normal visible from the outgoing direction.
+    ///
+    /// # Constraints
+    /// * `outgoing` - Should be normalized and not parallel to the surface.
+    /// * `sample` - All values should be within `[0, 1]`.
+    ///
+    /// # Arguments
+    /// * `outgoing` - The outgoing direction
+    /// * `sample` - The sample space for randomization
+    ///
+    /// # Returns
+    /// * The normalized microfacet normal in the hemisphere of `outgoing`
+    pub fn sample_half(&self, outgoing: Vec3, sample: Vec2) -> Vec3 {
+        let neg = is_neg(outgoing);
+        let outgoing = if neg { flip(outgoing) } else { outgoing };
+        let (alpha_x, alpha_z) = self.alphas();
+
+        // sample the distribution stretched to a roughness of `1`
+        let stretched =
+            Vec3::new(alpha_x * outgoing.x, outgoing.y, alpha_z * outgoing.z).normalize();
+        let half = match self {
+            MicrofacetDistribution::Ggx(_) => sample_ggx_visible(stretched, sample),
+            MicrofacetDistribution::Beckmann(_) => {
+                let (slope_x, slope_z) = sample_beckmann_slopes(cos_theta(stretched), sample);
+                let (cos_phi, sin_phi) = (cos_phi(stretched), sin_phi(stretched));
+
+                Vec3::new(
+                    -(cos_phi * slope_x - sin_phi * slope_z),
+                    1.0,
+                    -(sin_phi * slope_x + cos_phi * slope_z),
+                )
+            }
+        };
+        let half = Vec3::new(alpha_x * half.x, half.y.max(0.0), alpha_z * half.z).normalize();
+
+        if neg {
+            flip(half)
+        } else {
+            half
+        }
+    }
+
+    /// Computes the pdf of sampling a microfacet normal with [Self::sample_half].
+    ///
+    /// # Arguments
+    /// * `outgoing` - The normalized outgoing direction
+    /// * `half` - The normalized microfacet normal
+    pub fn pdf_half(&self, outgoing: Vec3, half: Vec3) -> Float {
+        let cos_o = cos_theta(outgoing).abs();
+        if cos_o == 0.0 {
+            return 0.0;
+        }
+
+        self.d(half) * self.g1(outgoing) * outgoing.dot(half).abs() / cos_o
+    }
+}
+
+/// Samples a normal of the GGX distribution with a roughness of `1` visible from a direction
+/// (see Heitz, "Sampling the GGX Distribution of Visible Normals", 2018).
+fn sample_ggx_visible(w: Vec3, sample: Vec2) -> Vec3 {
+    let len2 = w.x * w.x + w.z * w.z;
+    let t1 = if len2 > 0.0 {
+        Vec3::new(-w.z, 0.0, w.x) / len2.sqrt()
+    } else {
+        Vec3::unit_x()
+    };
+    let t2 = t1.cross(w);
+
+    // sample the projected disk, of which only the visible part is kept
+    let r = sample.x.sqrt();
+    let phi = TAU * sample.y;
+    let x = r * phi.cos();
+    let s = 0.5 * (1.0 + w.y);
+    let z = (1.0 - s) * (1.0 - x * x).max(0.0).sqrt() + s * r * phi.sin();
+    let y = (1.0 - x * x - z * z).max(0.0).sqrt();
+
+    x * t1 + z * t2 + y * w
+}
+
+/// Samples the slopes of the Beckmann distribution with a roughness of `1` visible from a
+/// direction rotated to `φ = 0` (see Jakob, "An Improved Visible Normal Sampling Routine for the
+/// Beckmann Distribution", 2014).
+///
+/// # Returns
+/// * The slopes along `x` and `z`
+fn sample_beckmann_slopes(cos_theta: Float, sample: Vec2) -> (Float, Float) {
+    // special case of normal incidence
+    if cos_theta > 0.9999 {
+        let r = (-(1.0 - sample.x).ln()).sqrt();
+        let phi = TAU * sample.y;
+        return (r * phi.cos(), r * phi.sin());
+    }
+
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let tan_theta = sin_theta / cos_theta;
+    let cot_theta = 1.0 / tan_theta;
+    let inv_sqrt_pi = (FRAC_1_PI).sqrt();
+
+    // invert the cdf of the slopes along `x` in the domain of `erf`
+    let mut a = -1.0;
+    let mut c = erf(cot_theta);
+    let x = sample.x.max(1e-6);
+
+    let theta = cos_theta.acos();
+    let fit = 1.0 + theta * (-0.876 + theta * (0.4265 - 0.0594 * theta));
+    let mut b = c - (1.0 + c) * (1.0 - x).powf(fit);
+
+    let normalization = 1.0 / (1.0 + c + inv_sqrt_pi * tan_theta * (-cot_theta * cot_theta).exp());
+
+    for _ in 0..10 {
+        // bisect if newton's method leaves the interval (or is `NaN`)
+        if !(a..=c).contains(&b) {
+            b = 0.5 * (a + c);
+        }
+
+        let inv_erf = erf_inv(b);
+        let value =
+            normalization * (1.0 + b + inv_sqrt_pi * tan_theta * (-inv_erf * inv_erf).exp()) - x;
+        if value.abs() < 1e-5 {
+            break;
+        }
+
+        if value > 0.0 {
+            c = b;
+        } else {
+            a = b;
+        }
+
+        let derivative = normalization * (1.0 - inv_erf * tan_theta);
+        b -= value / derivative;
+    }
+
+    (erf_inv(b), erf_inv(2.0 * sample.y.max(1e-6) - 1.0))
+}
+
+/// Approximates the error function (see Abramowitz and Stegun, 7.1.26).
+#[allow(clippy::excessive_precision)]
+fn erf(x: Float) -> Float {
+    let t = 1.0 / (1.0 + 0.3275911 * x.abs());
+    let polynomial = ((((1.061405429 * t - 1.453152027) * t + 1.421413741) * t - 0.284496736) * t
+        + 0.254829592)
+        * t;
+
+    (1.0 - polynomial * (-x * x).exp()).copysign(x)
+}
+
+/// Approximates the inverse error function (see Giles, "Approximating the erfinv function", 2010).
+#[allow(clippy::excessive_precision)]
+fn erf_inv(x: Float) -> Float {
+    let x = x.clamp(-0.99999, 0.99999);
+    let w = -((1.0 - x) * (1.0 + x)).ln();
+
+    let p = if w < 5.0 {
+        let w = w - 2.5;
+        [
+            3.43273939e-07,
+            -3.5233877e-06,
+            -4.39150654e-06,
+            0.00021858087,
+            -0.00125372503,
+            -0.00417768164,
+            0.246640727,
+            1.50140941,
+        ]
+        .iter()
+        .fold(2.81022636e-08, |p, &c| c + p * w)
+    } else {
+        let w = w.sqrt() - 3.0;
+        [
+            0.000100950558,
+            0.00134934322,
+            -0.00367342844,
+            0.00573950773,
+            -0.0076224613,
+            0.00943887047,
+            1.00167406,
+            2.83297682,
+        ]
+        .iter()
+        .fold(-0.000200214257, |p, &c| c + p * w)
+    };
+
+    p * x
+}
+
+/// Describes a glossy reflection off microfacets that are perfectly specular on their own
+/// (see Torrance and Sparrow).
+#[derive(Serialize, Deserialize)]
+pub struct MicrofacetReflection {
+    r: Spectrum,
+    distribution: MicrofacetDistribution,
+    fresnel: FresnelType,
+}
+
+impl MicrofacetReflection {
+    /// Creates a new microfacet reflection.
+    ///
+    /// # Arguments
+    /// * `r` - The reflection
+    /// * `distribution` - The distribution of microfacet normals
+    /// * `fresnel` - The fresnel of a microfacet
+    pub fn new(r: Spectrum, distribution: MicrofacetDistribution, fresnel: FresnelType) -> Self {
+        Self {
+            r,
+            distribution,
+            fresnel,
+        }
+    }
+}
+
+#[typetag::serde]
+impl BxDF for MicrofacetReflection {
+    #[inline(always)]
+    fn flag(&self) -> BxDFFlag {
+        BxDFFlag::GLOSSY | BxDFFlag::REFLECTION
+    }
+
+    fn evaluate(
+        &self,
+        incident: Vec3,
+        outgoing: Vec3,
+        wavelengths: Wavelengths,
+        spectrum: &mut [Float],
+    ) {
+        let cos_i = cos_theta(incident).abs();
+        let cos_o = cos_theta(outgoing).abs();
+        let half = incident + outgoing;
+        if !same_hemisphere(incident, outgoing) || half.magnitude2() == 0.0 {
+            spectrum.fill(0.0);
+            return;
+        }
+
+        let half = half.normalize();
+        let microfacets = self.distribution.d(half) * self.distribution.g(incident, outgoing)
+            / (4.0 * cos_i * cos_o);
+
+        // the fresnel of a microfacet with its normal on the side of the surface normal
+        let cos_h = incident.dot(face_forward(half, bxdf_normal()));
+        wavelengths.select(&self.r, spectrum);
+        for (i, s) in spectrum.iter_mut().enumerate() {
+            *s *= microfacets * self.fresnel.evaluate(cos_h, wavelengths.lambda(i));
+        }
+    }
+
+    fn sample(
+        &self,
+        outgoing: Vec3,
+        sample: Vec2,
+        wavelengths: Wavelengths,
+        spectrum: &mut [Float],
+    ) -> Option<BxDFSample<()>> {
+        if cos_theta(outgoing) == 0.0 {
+            return None;
+        }
+
+        let half = self.distribution.sample_half(outgoing, sample);
+        let incident = 2.0 * outgoing.dot(half) * half - outgoing;
+        if !same_hemisphere(incident, outgoing) {
+            return None;
+        }
+
+        self.evaluate(incident, outgoing, wavelengths, spectrum);
+        let pdf = self.pdf(incident, outgoing);
+
+        Some(BxDFSample::new((), incident, pdf, self.flag()))
+    }
+
+    fn pdf(&self, incident: Vec3, outgoing: Vec3) -> Float {
+        let half = incident + outgoing;
+        if !same_hemisphere(incident, outgoing) || half.magnitude2() == 0.0 {
+            return 0.0;
+        }
+
+        let half = half.normalize();
+        self.distribution.pdf_half(outgoing, half) / (4.0 * outgoing.dot(half).abs())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::mc::sample_unit_sphere;
+
+    fn stratified(n: usize) -> impl Iterator<Item = Vec2> {
+        (0..n * n).map(move |i| {
+            Vec2::new(
+                ((i / n) as Float + 0.5) / n as Float,
+                ((i % n) as Float + 0.5) / n as Float,
+            )
+        })
+    }
+
+    #[test]
+    fn sampling_matches_evaluation() {
+        let outgoing = Vec3::new(0.5, 0.6, -0.3).normalize();
+        let distributions = [
+            MicrofacetDistribution::Ggx(Roughness::Isotropic(0.3)),
+            MicrofacetDistribution::Ggx(Roughness::Anisotropic(0.1, 0.5)),
+            MicrofacetDistribution::Beckmann(Roughness::Isotropic(0.3)),
+            MicrofacetDistribution::Beckmann(Roughness::Anisotropic(0.5, 0.1)),
+        ];
+
+        for distribution in distributions.iter() {
+            let bxdf =
+                MicrofacetReflection::new(Spectrum::splat(1.0), *distribution, FresnelType::Noop);
+            let mut spectrum = vec![0.0; Spectrum::size()];
+            let n = 256;
+
+            // the reflectance estimated by importance sampling
+            let mut sampled = 0.0;
+            for sample in stratified(n) {
+                if let Some(s) = bxdf.sample(outgoing, sample, Wavelengths::All, &mut spectrum) {
+                    let pdf = bxdf.pdf(s.incident, outgoing);
+                    assert!((pdf - s.pdf).abs() <= 1e-3 * pdf);
+
+                    sampled += spectrum[0] * cos_theta(s.incident) / s.pdf;
+                }
+            }
+            sampled /= (n * n) as Float;
+
+            // the reflectance and the total pdf estimated by uniform sampling
+            let mut uniform = 0.0;
+            let mut pdf = 0.0;
+            for sample in stratified(n) {
+                let incident = sample_unit_sphere(sample);
+                bxdf.evaluate(incident, outgoing, Wavelengths::All, &mut spectrum);
+                uniform += spectrum[0] * cos_theta(incident).max(0.0);
+                pdf += bxdf.pdf(incident, outgoing);
+            }
+            let scale = 2.0 * TAU / (n * n) as Float;
+            uniform *= scale;
+            pdf *= scale;
+
+            assert!(sampled <= 1.0, "{:?}: {}", distribution, sampled);
+            assert!(
+                (sampled - uniform).abs() < 2e-2,
+                "{:?}: {} vs {}",
+                distribution,
+                sampled,
+                uniform
+            );
+            assert!(pdf <= 1.02, "{:?}: {}", distribution, pdf);
+        }
+    }
+}
@@ -1,6 +1,7 @@
 pub mod bsdf;
 pub mod fresnel;
 pub mod lambertian;
+pub mod microfacet;
 pub mod oren_nayar;
 pub mod refraction;
 pub mod specular;
@@ -8,11 +9,13 @@ pub mod specular;
 pub use bsdf::*;
 pub use fresnel::*;
 pub use lambertian::*;
+pub use microfacet::*;
 pub use oren_nayar::*;
 pub use specular::*;
 
+use crate::color::Wavelengths;
 use crate::util::mc::sample_unit_hemisphere;
-use crate::{Float, Rot3, Spectrum, Vec2, Vec3, PACKET_SIZE};
+use crate::{Float, Rot3, Spectrum, Vec2, Vec3};
 use cgmath::{InnerSpace, Rotation as cgRot};
 #[cfg(not(feature = "f64"))]
 use std::f32::consts::FRAC_1_PI;
@@ -267,10 +270,25 @@ impl BxDFFlag {
     }
 }
 
+/// A BSDF sample for all wavelengths of a sampled spectrum.
 #[derive(Clone, Debug)]
-pub enum BxDFSamplePacket {
-    Bundle(Option<BxDFSample<[Float; PACKET_SIZE]>>),
-    Split([Option<BxDFSample<Float>>; PACKET_SIZE]),
+pub enum BxDFSamples<S> {
+    /// All wavelengths scatter into the same direction.
+    Bundle(BxDFSample<S>),
+    /// The wavelengths scatter into different directions (e.g. by dispersion), so that every
+    /// wavelength has its own sample (if any).
+    Split(Vec<Option<BxDFSample<Float>>>),
+}
+
+impl<S> BxDFSamples<S> {
+    /// Returns the sample of all wavelengths, if they were not split.
+    #[inline]
+    pub fn bundle(self) -> Option<BxDFSample<S>> {
+        match self {
+            BxDFSamples::Bundle(s) => Some(s),
+            BxDFSamples::Split(_) => None,
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -302,121 +320,67 @@ pub trait BxDF: Send + Sync {
         self.flag().contains(f)
     }
 
-    /// Evaluates the BxDF.
+    /// Evaluates the BxDF for the wavelengths of a sampled spectrum.
     ///
     /// # Constraints
     /// * `incident`: All values should be finite (neither infinite nor `NaN`).
     ///                Should be normalized.
     /// * `outgoing`: All values should be finite.
     ///                Should be normalized.
+    /// * `spectrum`: Should have the length of `wavelengths`.
     ///
     /// # Arguments
     /// * `incident`: The incident direction onto the intersection we evaluate
     /// * `outgoing`: The outgoing light direction
-    fn evaluate(&self, incident: Vec3, outgoing: Vec3) -> Spectrum;
-
-    /// Evaluates the BxDF with possible spectral dependencies in a packet size of [PACKET_SIZE].
-    ///
-    /// # Constraints
-    /// * `incident`: All values should be finite (neither infinite nor `NaN`).
-    ///               Should be normalized.
-    /// * `outgoing` All values should be finite.
-    ///              Should be normalized.
-    /// * `indices`: All values should be within `[0, `[Spectrum::size]`)`.
-    ///
-    /// # Arguments
-    /// * `incident`: The incident direction onto the intersection we evaluate
-    /// * `outgoing`: The outgoing light direction
-    /// * `indices`: The indices of the spectrum to evaluate
-    // TODO: Use u16 or usize?
-    fn evaluate_packet(
+    /// * `wavelengths`: The wavelengths to evaluate
+    /// * `spectrum`: The spectrum to write, one value per wavelength
+    fn evaluate(
         &self,
         incident: Vec3,
         outgoing: Vec3,
-        indices: &[usize; PACKET_SIZE],
-    ) -> [Float; PACKET_SIZE] {
-        indices.map(|i| self.evaluate_lambda(incident, outgoing, i))
-    }
+        wavelengths: Wavelengths,
+        spectrum: &mut [Float],
+    );
 
-    /// Evaluates the BxDF with possible spectral dependencies.
-    ///
-    /// # Constraints
-    /// * `incident`: All values should be finite (neither infinite nor `NaN`).
-    ///               Should be normalized.
-    /// * `outgoing` All values should be finite.
-    ///              Should be normalized.
-    /// * `index`: Should be inside `[0, `[Spectrum::size]`)`.
+    /// Samples the BxDF for the wavelengths of a sampled spectrum.
     ///
-    /// # Arguments
-    /// * `incident`: The incident direction onto the intersection we evaluate
-    /// * `outgoing`: The outgoing light direction
-    /// * `index`: The spectral index
-    fn evaluate_lambda(&self, incident: Vec3, outgoing: Vec3, index: usize) -> Float;
-
-    /// Samples the BxDF.
+    /// The direction may only depend on the wavelength if the BxDF is dispersive (see
+    /// [Self::is_dispersive]), in which case it is chosen by the first wavelength.
     ///
     /// # Constraints
     /// * `outgoing`: All values should be finite.
     ///                Should be normalized.
     /// * `sample`: All values should be within `[0, 1]`.
+    /// * `spectrum`: Should have the length of `wavelengths`.
     ///
     /// # Arguments
     /// * `outgoing`: The outgoing light direction
     /// * `sample`: The sample space for randomization
-    fn sample(&self, outgoing: Vec3, sample: Vec2) -> Option<BxDFSample<Spectrum>> {
-        let incident = sample_unit_hemisphere(sample);
-        let spectrum = self.evaluate(incident, outgoing);
-        let pdf = self.pdf(incident, outgoing);
-
-        Some(BxDFSample::new(spectrum, incident, pdf, self.flag()))
-    }
-
-    /// Samples the BxDF with possible spectral dependencies in a packet size of [PACKET_SIZE].
-    ///
-    /// # Constraints
-    /// * `outgoing`: All values should be finite.
-    ///                Should be normalized.
-    /// * `sample`: All values should be within `[0, 1]`.
+    /// * `wavelengths`: The wavelengths to sample
+    /// * `spectrum`: The spectrum of the sample to write, one value per wavelength
     ///
-    /// # Arguments
-    /// * `outgoing`: The outgoing light direction
-    /// * `sample`: The sample space for randomization
-    fn sample_packet(
+    /// # Returns
+    /// * The sampled direction, pdf and flag
+    fn sample(
         &self,
         outgoing: Vec3,
         sample: Vec2,
-        indices: &[usize; PACKET_SIZE],
-    ) -> BxDFSamplePacket {
+        wavelengths: Wavelengths,
+        spectrum: &mut [Float],
+    ) -> Option<BxDFSample<()>> {
         let incident = sample_unit_hemisphere(sample);
-        let spectrum = self.evaluate_packet(incident, outgoing, indices);
+        self.evaluate(incident, outgoing, wavelengths, spectrum);
         let pdf = self.pdf(incident, outgoing);
 
-        let bundle = Some(BxDFSample::new(spectrum, incident, pdf, self.flag()));
-
-        BxDFSamplePacket::Bundle(bundle)
+        Some(BxDFSample::new((), incident, pdf, self.flag()))
     }
 
-    /// Samples the BxDF with possible spectral dependencies.
+    /// Returns whether the sampled direction depends on the wavelength (e.g. by dispersion).
     ///
-    /// # Constraints
-    /// * `outgoing`: All values should be finite.
-    ///                Should be normalized.
-    /// * `sample`: All values should be within `[0, 1]`.
-    ///
-    /// # Arguments
-    /// * `outgoing`: The outgoing light direction
-    /// * `sample`: The sample space for randomization
-    fn sample_lambda(
-        &self,
-        outgoing: Vec3,
-        sample: Vec2,
-        index: usize,
-    ) -> Option<BxDFSample<Float>> {
-        let incident = sample_unit_hemisphere(sample);
-        let lambda = self.evaluate_lambda(incident, outgoing, index);
-        let pdf = self.pdf(incident, outgoing);
-
-        Some(BxDFSample::new(lambda, incident, pdf, self.flag()))
+    /// The BSDF then samples every wavelength of a packet on its own.
+    #[inline]
+    fn is_dispersive(&self) -> bool {
+        false
     }
 
     /// Computes the probability density function (`pdf`) for the pair of directions.
@@ -438,4 +402,14 @@ pub trait BxDF: Send + Sync {
             0.0
         }
     }
+
+    /// Returns the dielectric interface and the transmission color, if this BxDF specularly
+    /// transmits light through an interface.
+    ///
+    /// This allows connecting emitters through the interface with
+    /// [manifold next event estimation](crate::integrator::manifold).
+    #[inline]
+    fn refraction(&self) -> Option<(FresnelDielectric, Spectrum)> {
+        None
+    }
 }
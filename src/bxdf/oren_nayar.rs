@@ -1,4 +1,5 @@
 use crate::bxdf::{cos_phi, cos_theta, sin_phi, sin_theta, BxDF, BxDFFlag};
+use crate::color::Wavelengths;
 use crate::util::floats::EPSILON;
 use crate::*;
 use serde::{Deserialize, Deserializer, Serialize};
@@ -40,9 +41,9 @@ impl OrenNayar {
     ///
     /// # Constraints
     /// * `incident` - All values should be finite (neither infinite nor `NaN`).
-    ///                Should be normalized.
+    ///   Should be normalized.
     /// * `outgoing` - All values should be finite.
-    ///                Should be normalized.
+    ///   Should be normalized.
     ///
     /// # Arguments
     /// * `incident` - The incident direction onto the intersection we evaluate
@@ -90,28 +91,20 @@ impl BxDF for OrenNayar {
         BxDFFlag::DIFFUSE | BxDFFlag::REFLECTION
     }
 
-    fn evaluate(&self, incident: Vec3, outgoing: Vec3) -> Spectrum {
-        let oren_nayar = self.calc_param(incident, outgoing);
-        debug_assert!(oren_nayar >= 0.0, "Oren Nayar parameter is >= 0");
-
-        self.r * oren_nayar
-    }
-
-    fn evaluate_packet(
+    fn evaluate(
         &self,
         incident: Vec3,
         outgoing: Vec3,
-        indices: &[usize; PACKET_SIZE],
-    ) -> [Float; PACKET_SIZE] {
-        let oren_nayar = self.calc_param(incident, outgoing);
-
-        indices.map(|i| self.r[i] * oren_nayar)
-    }
-
-    fn evaluate_lambda(&self, incident: Vec3, outgoing: Vec3, index: usize) -> Float {
+        wavelengths: Wavelengths,
+        spectrum: &mut [Float],
+    ) {
         let oren_nayar = self.calc_param(incident, outgoing);
+        debug_assert!(oren_nayar >= 0.0, "Oren Nayar parameter is >= 0");
 
-        self.r[index] * oren_nayar
+        wavelengths.select(&self.r, spectrum);
+        for s in spectrum {
+            *s *= oren_nayar;
+        }
     }
 }
 
@@ -2,7 +2,7 @@
 //!
 //! # Resources
 //! * Data taken from [here](https://refractiveindex.info/?shelf=other&book=air&page=Borzsonyi) on
-//! 2021-10-11.
+//!   2021-10-11.
 use crate::Float;
 
 #[inline(always)]
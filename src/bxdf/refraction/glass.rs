@@ -4,7 +4,7 @@
 //!
 //! # Resources
 //! * Data taken from [here](https://refractiveindex.info/?shelf=3d&book=glass&page=BK7) on
-//! 2021-10-11.
+//!   2021-10-11.
 
 use crate::Float;
 
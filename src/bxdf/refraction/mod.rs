@@ -88,6 +88,16 @@ impl RefractiveType {
         }
     }
 
+    /// Returns the refractive index at a given wavelength of a sampled spectrum (see
+    /// [Wavelengths::lambda](crate::color::Wavelengths::lambda)).
+    ///
+    /// # Arguments
+    /// * `lambda` - The wavelength in **µm**, or `None` for the uniform refractive index
+    #[inline]
+    pub fn n_at(self, lambda: Option<Float>) -> Float {
+        lambda.map_or_else(|| self.n_uniform(), |l| self.n(l))
+    }
+
     /// Returns the extinction coefficient at a given wavelength (if it exists).
     ///
     /// # Arguments
@@ -4,7 +4,7 @@
 //!
 //! # Resources
 //! * Data taken from [here](https://refractiveindex.info/?shelf=main&book=Al2O3&page=Querry-o) on
-//! 2021-10-11.
+//!   2021-10-11.
 
 use crate::Float;
 #[inline(always)]
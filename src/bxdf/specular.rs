@@ -3,10 +3,10 @@
 use crate::bxdf::refraction::RefractiveType;
 use crate::bxdf::{
     bxdf_incident_to, bxdf_normal, cos_theta, fresnel_dielectric, refract, BxDF, BxDFFlag,
-    BxDFSample, BxDFSamplePacket, Fresnel, FresnelDielectric, FresnelType,
+    BxDFSample, Fresnel, FresnelDielectric, FresnelType,
 };
-use crate::util::PacketOps;
-use crate::{Float, Spectrum, Vec2, Vec3, PACKET_SIZE};
+use crate::color::Wavelengths;
+use crate::{Float, Spectrum, Vec2, Vec3};
 use serde::{Deserialize, Serialize};
 
 #[inline]
@@ -48,63 +48,34 @@ impl BxDF for SpecularReflection {
         BxDFFlag::REFLECTION | BxDFFlag::SPECULAR
     }
 
+    /// No scattering for specular reflection.
+    ///
+    /// # Arguments
+    /// Ignored, apart from `spectrum`
+    ///
+    /// # Returns
+    /// * `0.0` spectrum
     #[inline]
-    fn evaluate(&self, _: Vec3, _: Vec3) -> Spectrum {
-        Spectrum::splat(0.0)
-    }
-
-    #[inline]
-    fn evaluate_packet(&self, _: Vec3, _: Vec3, _: &[usize; PACKET_SIZE]) -> [Float; PACKET_SIZE] {
-        [0.0; PACKET_SIZE]
-    }
-
-    #[inline(always)]
-    fn evaluate_lambda(&self, _: Vec3, _: Vec3, _: usize) -> Float {
-        0.0
-    }
-
-    fn sample(&self, outgoing: Vec3, _: Vec2) -> Option<BxDFSample<Spectrum>> {
-        let incident = bxdf_incident_to(outgoing);
-
-        let cos_i = cos_theta(incident);
-        let spectrum = self.fresnel.evaluate(cos_i) * self.r;
-
-        Some(BxDFSample::new(spectrum, incident, 1.0, self.flag()))
+    fn evaluate(&self, _: Vec3, _: Vec3, _: Wavelengths, spectrum: &mut [Float]) {
+        spectrum.fill(0.0);
     }
 
-    fn sample_packet(
+    fn sample(
         &self,
         outgoing: Vec3,
         _: Vec2,
-        indices: &[usize; PACKET_SIZE],
-    ) -> BxDFSamplePacket {
+        wavelengths: Wavelengths,
+        spectrum: &mut [Float],
+    ) -> Option<BxDFSample<()>> {
         let incident = bxdf_incident_to(outgoing);
 
         let cos_i = cos_theta(incident);
+        wavelengths.select(&self.r, spectrum);
+        for (i, s) in spectrum.iter_mut().enumerate() {
+            *s *= self.fresnel.evaluate(cos_i, wavelengths.lambda(i));
+        }
 
-        let lambdas = indices.map(Spectrum::lambda);
-        let fresnel = self.fresnel.evaluate_packet(cos_i, &lambdas);
-        let packet = indices.map(|i| self.r[i]).mul(fresnel);
-
-        let bundle = Some(BxDFSample::new(packet, incident, 1.0, self.flag()));
-
-        BxDFSamplePacket::Bundle(bundle)
-    }
-
-    fn sample_lambda(
-        &self,
-        outgoing: Vec3,
-        _: Vec2,
-        light_wave_index: usize,
-    ) -> Option<BxDFSample<Float>> {
-        let incident = bxdf_incident_to(outgoing);
-
-        let cos_i = cos_theta(incident);
-        let light_wave = self.r.as_light_wave(light_wave_index);
-        let spectrum =
-            self.fresnel.evaluate_lambda(light_wave.lambda, cos_i) * light_wave.intensity;
-
-        Some(BxDFSample::new(spectrum, incident, 1.0, self.flag()))
+        Some(BxDFSample::new((), incident, 1.0, self.flag()))
     }
 
     /// No scattering for specular reflection leads to no pdf.
@@ -153,74 +124,39 @@ impl BxDF for SpecularTransmission {
     /// No scattering for specular transmission.
     ///
     /// # Arguments
-    /// Ignored
+    /// Ignored, apart from `spectrum`
     ///
     /// # Returns
     /// * `0.0` spectrum
     #[inline]
-    fn evaluate(&self, _: Vec3, _: Vec3) -> Spectrum {
-        Spectrum::splat(0.0)
-    }
-
-    #[inline]
-    fn evaluate_packet(&self, _: Vec3, _: Vec3, _: &[usize; PACKET_SIZE]) -> [Float; PACKET_SIZE] {
-        [0.0; PACKET_SIZE]
-    }
-
-    #[inline(always)]
-    fn evaluate_lambda(&self, _: Vec3, _: Vec3, _: usize) -> Float {
-        0.0
+    fn evaluate(&self, _: Vec3, _: Vec3, _: Wavelengths, spectrum: &mut [Float]) {
+        spectrum.fill(0.0);
     }
 
-    fn sample(&self, outgoing: Vec3, _: Vec2) -> Option<BxDFSample<Spectrum>> {
-        let (eta_i, eta_t, normal) = etas(self.fresnel.eta_i, self.fresnel.eta_t, outgoing);
-        let incident = refract(outgoing, normal, eta_i.n_uniform() / eta_t.n_uniform())?;
-
-        let cos_i = cos_theta(incident);
-        let spectrum = self.t * (Spectrum::splat(1.0) - self.fresnel.evaluate(cos_i));
-
-        Some(BxDFSample::new(spectrum, incident, 1.0, self.flag()))
-    }
-
-    fn sample_packet(
+    fn sample(
         &self,
         outgoing: Vec3,
         _: Vec2,
-        indices: &[usize; PACKET_SIZE],
-    ) -> BxDFSamplePacket {
+        wavelengths: Wavelengths,
+        spectrum: &mut [Float],
+    ) -> Option<BxDFSample<()>> {
         let (eta_i, eta_t, normal) = etas(self.fresnel.eta_i, self.fresnel.eta_t, outgoing);
 
-        let mut split = [None; PACKET_SIZE];
-
-        for i in 0..PACKET_SIZE {
-            let index = indices[i];
-            let lambda = Spectrum::lambda(index);
-            let eta = eta_i.n(lambda) / eta_t.n(lambda);
+        let lambda = wavelengths.lambda(0);
+        let incident = refract(outgoing, normal, eta_i.n_at(lambda) / eta_t.n_at(lambda))?;
 
-            let incident = match refract(outgoing, normal, eta) {
-                Some(v) => v,
-                None => continue,
-            };
-
-            let cos_i = cos_theta(incident);
-            let spectrum = self.t[index] * (1.0 - self.fresnel.evaluate_lambda(cos_i, lambda));
-
-            split[i] = Some(BxDFSample::new(spectrum, incident, 1.0, self.flag()));
+        let cos_i = cos_theta(incident);
+        wavelengths.select(&self.t, spectrum);
+        for (i, s) in spectrum.iter_mut().enumerate() {
+            *s *= 1.0 - self.fresnel.evaluate(cos_i, wavelengths.lambda(i));
         }
 
-        BxDFSamplePacket::Split(split)
+        Some(BxDFSample::new((), incident, 1.0, self.flag()))
     }
 
-    fn sample_lambda(&self, outgoing: Vec3, _: Vec2, index: usize) -> Option<BxDFSample<Float>> {
-        let (eta_i, eta_t, normal) = etas(self.fresnel.eta_i, self.fresnel.eta_t, outgoing);
-
-        let incident = refract(outgoing, normal, eta_i.n_uniform() / eta_t.n_uniform())?;
-
-        let cos_i = cos_theta(incident);
-        let lambda = Spectrum::lambda(index);
-        let spectrum = self.t[index] * (1.0 - self.fresnel.evaluate_lambda(cos_i, lambda));
-
-        Some(BxDFSample::new(spectrum, incident, 1.0, self.flag()))
+    #[inline]
+    fn is_dispersive(&self) -> bool {
+        true
     }
 
     /// No scattering for specular transmission leads to no pdf.
@@ -234,6 +170,11 @@ impl BxDF for SpecularTransmission {
     fn pdf(&self, _: Vec3, _: Vec3) -> Float {
         0.0
     }
+
+    #[inline]
+    fn refraction(&self) -> Option<(FresnelDielectric, Spectrum)> {
+        Some((self.fresnel, self.t))
+    }
 }
 
 /// Combines specular reflection and transmission for better efficiency.
@@ -292,29 +233,27 @@ impl BxDF for FresnelSpecular {
     /// No scattering for specular reflection/transmission.
     ///
     /// # Arguments
-    /// Ignored
+    /// Ignored, apart from `spectrum`
     ///
     /// # Returns
     /// * `0.0` spectrum
-    fn evaluate(&self, _: Vec3, _: Vec3) -> Spectrum {
-        Spectrum::splat(0.0)
-    }
-
     #[inline]
-    fn evaluate_packet(&self, _: Vec3, _: Vec3, _: &[usize; PACKET_SIZE]) -> [Float; PACKET_SIZE] {
-        [0.0; PACKET_SIZE]
-    }
-
-    #[inline(always)]
-    fn evaluate_lambda(&self, _: Vec3, _: Vec3, _: usize) -> Float {
-        0.0
+    fn evaluate(&self, _: Vec3, _: Vec3, _: Wavelengths, spectrum: &mut [Float]) {
+        spectrum.fill(0.0);
     }
 
-    fn sample(&self, outgoing: Vec3, sample: Vec2) -> Option<BxDFSample<Spectrum>> {
+    fn sample(
+        &self,
+        outgoing: Vec3,
+        sample: Vec2,
+        wavelengths: Wavelengths,
+        spectrum: &mut [Float],
+    ) -> Option<BxDFSample<()>> {
         let cos_outgoing = cos_theta(outgoing);
 
-        let eta_i_orig = self.fresnel.eta_i.n_uniform();
-        let eta_t_orig = self.fresnel.eta_t.n_uniform();
+        let lambda = wavelengths.lambda(0);
+        let eta_i_orig = self.fresnel.eta_i.n_at(lambda);
+        let eta_t_orig = self.fresnel.eta_t.n_at(lambda);
         let f = fresnel_dielectric(cos_outgoing, eta_i_orig, eta_t_orig);
 
         if sample.x < f {
@@ -322,10 +261,14 @@ impl BxDF for FresnelSpecular {
 
             let incident = bxdf_incident_to(outgoing);
             let flag = BxDFFlag::SPECULAR | BxDFFlag::REFLECTION;
-            let spectrum = self.r * f;
             let pdf = f;
 
-            Some(BxDFSample::new(spectrum, incident, pdf, flag))
+            wavelengths.select(&self.r, spectrum);
+            for s in spectrum {
+                *s *= f;
+            }
+
+            Some(BxDFSample::new((), incident, pdf, flag))
         } else {
             // specular transmission
 
@@ -338,97 +281,20 @@ impl BxDF for FresnelSpecular {
 
             let incident = refract(outgoing, normal, eta_i / eta_t)?;
             let pdf = 1.0 - f;
-            let spectrum = self.t * pdf;
             let flag = BxDFFlag::SPECULAR | BxDFFlag::TRANSMISSION;
 
-            Some(BxDFSample::new(spectrum, incident, pdf, flag))
-        }
-    }
-
-    fn sample_packet(
-        &self,
-        outgoing: Vec3,
-        sample: Vec2,
-        indices: &[usize; PACKET_SIZE],
-    ) -> BxDFSamplePacket {
-        let cos_outgoing = cos_theta(outgoing);
-
-        let split = indices.map(|i| {
-            let lambda = Spectrum::lambda(i);
-            let eta_i_orig = self.fresnel.eta_i.n(lambda);
-            let eta_t_orig = self.fresnel.eta_t.n(lambda);
-            let f = fresnel_dielectric(cos_outgoing, eta_i_orig, eta_t_orig);
-
-            if sample.x < f {
-                // specular reflection
-
-                let spectrum = self.r[i] * f;
-                let incident = bxdf_incident_to(outgoing);
-                let pdf = f;
-                let flag = BxDFFlag::SPECULAR | BxDFFlag::REFLECTION;
-
-                Some(BxDFSample::new(spectrum, incident, pdf, flag))
-            } else {
-                // specular transmission
-
-                let entering = cos_outgoing > 0.0;
-                let (eta_i, eta_t, normal) = if entering {
-                    (eta_i_orig, eta_t_orig, bxdf_normal())
-                } else {
-                    (eta_t_orig, eta_i_orig, -bxdf_normal())
-                };
-
-                let incident = refract(outgoing, normal, eta_i / eta_t)?;
-                let pdf = 1.0 - f;
-                let spectrum = self.t[i] * pdf;
-                let flag = BxDFFlag::SPECULAR | BxDFFlag::TRANSMISSION;
-
-                Some(BxDFSample::new(spectrum, incident, pdf, flag))
+            wavelengths.select(&self.t, spectrum);
+            for s in spectrum {
+                *s *= pdf;
             }
-        });
 
-        BxDFSamplePacket::Split(split)
+            Some(BxDFSample::new((), incident, pdf, flag))
+        }
     }
 
-    fn sample_lambda(
-        &self,
-        outgoing: Vec3,
-        sample: Vec2,
-        index: usize,
-    ) -> Option<BxDFSample<Float>> {
-        let cos_outgoing = cos_theta(outgoing);
-
-        let lambda = Spectrum::lambda(index);
-        let eta_i_orig = self.fresnel.eta_i.n(lambda);
-        let eta_t_orig = self.fresnel.eta_t.n(lambda);
-        let f = fresnel_dielectric(cos_outgoing, eta_i_orig, eta_t_orig);
-
-        if sample.x < f {
-            // specular reflection
-
-            let spectrum = self.r[index] * f;
-            let incident = bxdf_incident_to(outgoing);
-            let pdf = f;
-            let flag = BxDFFlag::SPECULAR | BxDFFlag::REFLECTION;
-
-            Some(BxDFSample::new(spectrum, incident, pdf, flag))
-        } else {
-            // specular transmission
-
-            let entering = cos_outgoing > 0.0;
-            let (eta_i, eta_t, normal) = if entering {
-                (eta_i_orig, eta_t_orig, bxdf_normal())
-            } else {
-                (eta_t_orig, eta_i_orig, -bxdf_normal())
-            };
-
-            let incident = refract(outgoing, normal, eta_i / eta_t)?;
-            let pdf = 1.0 - f;
-            let spectrum = self.t[index] * pdf;
-            let flag = BxDFFlag::SPECULAR | BxDFFlag::TRANSMISSION;
-
-            Some(BxDFSample::new(spectrum, incident, pdf, flag))
-        }
+    #[inline]
+    fn is_dispersive(&self) -> bool {
+        true
     }
 
     /// No scattering for specular reflection/transmission leads to no pdf.
@@ -442,4 +308,9 @@ impl BxDF for FresnelSpecular {
     fn pdf(&self, _: Vec3, _: Vec3) -> Float {
         0.0
     }
+
+    #[inline]
+    fn refraction(&self) -> Option<(FresnelDielectric, Spectrum)> {
+        Some((self.fresnel, self.t))
+    }
 }
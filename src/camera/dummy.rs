@@ -1,6 +1,6 @@
-use crate::camera::Camera;
+use crate::camera::{Camera, CameraSample};
 use crate::geometry::Ray;
-use crate::{UVec2, SENSOR_TILE_WIDTH};
+use crate::{Float, UVec2, Vec3, SENSOR_TILE_WIDTH};
 use serde::{Deserialize, Serialize};
 
 #[derive(Copy, Clone, Debug, Deserialize, Serialize)]
@@ -15,4 +15,16 @@ impl Camera for DummyCamera {
     fn primary_ray(&self, _: UVec2) -> Ray {
         unimplemented!()
     }
+
+    fn importance(&self, _: Ray) -> Float {
+        unimplemented!()
+    }
+
+    fn pdf(&self, _: Ray) -> (Float, Float) {
+        unimplemented!()
+    }
+
+    fn sample_importance(&self, _: Vec3) -> Option<CameraSample> {
+        unimplemented!()
+    }
 }
@@ -2,7 +2,7 @@ pub use orthographic::*;
 pub use perspective::*;
 
 use crate::geometry::Ray;
-use crate::UVec2;
+use crate::{Float, UVec2, Vec2, Vec3};
 
 pub mod dummy;
 pub mod orthographic;
@@ -16,4 +16,50 @@ pub trait Camera: Send + Sync {
 
     /// Creates a "primary" (camera) ray
     fn primary_ray(&self, pixel: UVec2) -> Ray;
+
+    /// Evaluates the importance the camera emits along the given ray.
+    ///
+    /// The importance is normalized over the whole sensor, so rays outside of the field of view
+    /// have no importance.
+    ///
+    /// # Arguments
+    /// * `ray` - A ray starting at the camera
+    fn importance(&self, ray: Ray) -> Float;
+
+    /// Computes the pdfs of generating the given ray, assuming the pixel is chosen uniformly on the
+    /// sensor.
+    ///
+    /// # Arguments
+    /// * `ray` - A ray starting at the camera
+    ///
+    /// # Returns
+    /// * The pdf with respect to the area of the ray origin
+    /// * The pdf with respect to the solid angle of the ray direction
+    fn pdf(&self, ray: Ray) -> (Float, Float);
+
+    /// Connects a point in the scene to the camera.
+    ///
+    /// # Constraints
+    /// * `point` - All values should be finite (neither infinite nor `NaN`).
+    ///
+    /// # Arguments
+    /// * `point` - The point to connect to the camera
+    ///
+    /// # Returns
+    /// * The connection, if the point is visible on the sensor
+    fn sample_importance(&self, point: Vec3) -> Option<CameraSample>;
+}
+
+/// A connection from a point in the scene to the camera.
+#[derive(Copy, Clone, Debug)]
+pub struct CameraSample {
+    /// The raster position on the sensor.
+    pub raster: Vec2,
+    /// The point on the camera.
+    pub point: Vec3,
+    /// The importance the camera emits towards the connected point.
+    pub importance: Float,
+    /// The pdf with respect to the solid angle of the connected point.
+    /// Cameras without a spatial extent along the connection use `1`, like delta emitters.
+    pub pdf: Float,
 }
@@ -1,6 +1,7 @@
-use crate::camera::Camera;
+use crate::camera::{Camera, CameraSample};
 use crate::geometry::Ray;
 use crate::sampler::CameraSampler;
+use crate::util::floats::approx_eq;
 use crate::{Float, UVec2, Vec2, Vec3};
 use cgmath::InnerSpace;
 use serde::{Deserialize, Serialize};
@@ -33,6 +34,70 @@ impl Camera for OrthographicCamera {
         let origin = self.top_left + right + down;
         Ray::new(origin, self.z_dir)
     }
+
+    fn importance(&self, ray: Ray) -> Float {
+        match self.raster(ray) {
+            Some(_) => 1.0 / self.film_area(),
+            None => 0.0,
+        }
+    }
+
+    /// As all rays share the same direction, its pdf is a delta distribution (`1`).
+    fn pdf(&self, ray: Ray) -> (Float, Float) {
+        match self.raster(ray) {
+            Some(_) => (1.0 / self.film_area(), 1.0),
+            None => (0.0, 0.0),
+        }
+    }
+
+    /// The point is projected along the viewing direction, so no distance falloff applies.
+    fn sample_importance(&self, point: Vec3) -> Option<CameraSample> {
+        let distance = (point - self.top_left).dot(self.z_dir);
+        if distance <= 0.0 {
+            return None;
+        }
+
+        let origin = point - distance * self.z_dir;
+        let raster = self.raster(Ray::new(origin, self.z_dir))?;
+
+        Some(CameraSample {
+            raster,
+            point: origin,
+            importance: 1.0 / self.film_area(),
+            pdf: 1.0,
+        })
+    }
+}
+
+impl OrthographicCamera {
+    /// The area of the image plane.
+    #[inline]
+    fn film_area(&self) -> Float {
+        self.config.fov.x * self.config.fov.y
+    }
+
+    /// Projects the origin of a ray along the viewing direction onto the image plane.
+    ///
+    /// # Returns
+    /// * The raster position, if the ray is visible
+    fn raster(&self, ray: Ray) -> Option<Vec2> {
+        if !approx_eq(ray.direction.dot(self.z_dir), 1.0) {
+            return None;
+        }
+
+        let plane = ray.origin - self.top_left;
+        let raster = Vec2::new(
+            plane.dot(self.x_dir) / self.x_dir.magnitude2(),
+            plane.dot(self.y_dir) / self.y_dir.magnitude2(),
+        );
+
+        let res = self.resolution.cast().unwrap();
+        if raster.x < 0.0 || raster.x >= res.x || raster.y < 0.0 || raster.y >= res.y {
+            return None;
+        }
+
+        Some(raster)
+    }
 }
 
 #[derive(Copy, Clone, Debug, Deserialize, Serialize)]
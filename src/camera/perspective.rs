@@ -1,4 +1,4 @@
-use crate::camera::Camera;
+use crate::camera::{Camera, CameraSample};
 use crate::geometry::Ray;
 use crate::sampler::CameraSampler;
 use crate::{Float, Mat4, UVec2, Vec2, Vec3};
@@ -48,6 +48,67 @@ impl Camera for PerspectiveCamera {
 
         Ray::new(origin, direction)
     }
+
+    fn importance(&self, ray: Ray) -> Float {
+        match self.raster(ray.direction) {
+            Some((_, cos)) => 1.0 / (self.film_area() * cos.powi(4)),
+            None => 0.0,
+        }
+    }
+
+    fn pdf(&self, ray: Ray) -> (Float, Float) {
+        match self.raster(ray.direction) {
+            Some((_, cos)) => (1.0, 1.0 / (self.film_area() * cos.powi(3))),
+            None => (0.0, 0.0),
+        }
+    }
+
+    fn sample_importance(&self, point: Vec3) -> Option<CameraSample> {
+        let origin = self.look_at.transform_vector(Vec3::zero());
+        let direction = origin - point;
+        let distance2 = direction.magnitude2();
+        let direction = direction / distance2.sqrt();
+
+        let (raster, cos) = self.raster(-direction)?;
+
+        Some(CameraSample {
+            raster,
+            point: origin,
+            importance: 1.0 / (self.film_area() * cos.powi(4)),
+            pdf: distance2 / cos,
+        })
+    }
+}
+impl PerspectiveCamera {
+    /// The area of the image plane at distance `1`.
+    #[inline]
+    fn film_area(&self) -> Float {
+        let extent = self.top_right - self.bot_left;
+        extent.x * extent.y
+    }
+
+    /// Projects a direction onto the image plane.
+    ///
+    /// # Arguments
+    /// * `direction` - The normalized direction in world space
+    ///
+    /// # Returns
+    /// * The raster position and the cosine to the viewing direction, if the direction is visible
+    fn raster(&self, direction: Vec3) -> Option<(Vec2, Float)> {
+        let local = self.look_at.inverse_transform_vector(direction)?;
+        let cos = -local.z;
+        if cos <= 0.0 {
+            return None;
+        }
+
+        let plane = local.truncate() / cos;
+        let uv = (plane - self.bot_left).div_element_wise(self.top_right - self.bot_left);
+        if uv.x < 0.0 || uv.x >= 1.0 || uv.y < 0.0 || uv.y >= 1.0 {
+            return None;
+        }
+
+        Some((uv.mul_element_wise(self.res.cast().unwrap()), cos))
+    }
 }
 impl From<CameraConfig> for PerspectiveCamera {
     fn from(conf: CameraConfig) -> Self {
@@ -87,6 +148,8 @@ pub struct NaiveCamera {
     conf: CameraConfig,
     sampler: CameraSampler,
     eye: Vec3,
+    view: Vec3,
+    distance: Float,
     x_dir: Vec3,
     y_dir: Vec3,
     lower_left: Vec3,
@@ -110,6 +173,72 @@ impl Camera for NaiveCamera {
 
         Ray::new(self.eye, direction)
     }
+
+    fn importance(&self, ray: Ray) -> Float {
+        match self.raster(ray.direction) {
+            Some((_, cos)) => 1.0 / (self.film_area() * cos.powi(4)),
+            None => 0.0,
+        }
+    }
+
+    fn pdf(&self, ray: Ray) -> (Float, Float) {
+        match self.raster(ray.direction) {
+            Some((_, cos)) => (1.0, 1.0 / (self.film_area() * cos.powi(3))),
+            None => (0.0, 0.0),
+        }
+    }
+
+    fn sample_importance(&self, point: Vec3) -> Option<CameraSample> {
+        let direction = self.eye - point;
+        let distance2 = direction.magnitude2();
+        let direction = direction / distance2.sqrt();
+
+        let (raster, cos) = self.raster(-direction)?;
+
+        Some(CameraSample {
+            raster,
+            point: self.eye,
+            importance: 1.0 / (self.film_area() * cos.powi(4)),
+            pdf: distance2 / cos,
+        })
+    }
+}
+impl NaiveCamera {
+    /// The area of the image plane at distance `1`.
+    #[inline]
+    fn film_area(&self) -> Float {
+        let width = self.res.x as Float * self.x_dir.magnitude();
+        let height = self.res.y as Float * self.y_dir.magnitude();
+
+        width * height / (self.distance * self.distance)
+    }
+
+    /// Projects a direction onto the image plane.
+    ///
+    /// # Arguments
+    /// * `direction` - The normalized direction in world space
+    ///
+    /// # Returns
+    /// * The raster position and the cosine to the viewing direction, if the direction is visible
+    fn raster(&self, direction: Vec3) -> Option<(Vec2, Float)> {
+        let cos = direction.dot(self.view);
+        if cos <= 0.0 {
+            return None;
+        }
+
+        let plane = self.eye + direction * (self.distance / cos) - self.lower_left;
+        let raster = Vec2::new(
+            plane.dot(self.x_dir) / self.x_dir.magnitude2(),
+            plane.dot(self.y_dir) / self.y_dir.magnitude2(),
+        );
+
+        let res = self.res.cast().unwrap();
+        if raster.x < 0.0 || raster.x >= res.x || raster.y < 0.0 || raster.y >= res.y {
+            return None;
+        }
+
+        Some((raster, cos))
+    }
 }
 impl From<CameraConfig> for NaiveCamera {
     fn from(conf: CameraConfig) -> Self {
@@ -136,6 +265,8 @@ impl From<CameraConfig> for NaiveCamera {
             conf,
             sampler: conf.sampler,
             eye: conf.eye,
+            view,
+            distance,
             x_dir,
             y_dir,
             lower_left,
@@ -1,11 +1,14 @@
 pub mod pixel;
+pub mod splat;
 pub mod tile;
 
 use parking_lot::Mutex;
 pub use pixel::*;
+pub use splat::*;
 pub use tile::*;
 
 use crate::{UVec2, SENSOR_TILE_WIDTH};
+use serde::ser::SerializeTupleVariant;
 use serde::{Deserialize, Serialize, Serializer};
 
 #[derive(Debug, Deserialize)]
@@ -13,6 +16,7 @@ use serde::{Deserialize, Serialize, Serializer};
 pub struct Sensor {
     pub resolution: UVec2,
     pub tiles: Vec<Mutex<SensorTile>>,
+    pub splats: SplatBuffer,
 }
 
 impl Default for Sensor {
@@ -26,7 +30,7 @@ impl Sensor {
     ///
     /// # Conditions
     /// * `resolution`: The resolution must be a multiple of [SENSOR_TILE_WIDTH].
-    ///                 This function will otherwise panic.
+    ///   This function will otherwise panic.
     ///
     /// # Arguments
     /// * `resolution`: The resolution of the camera.
@@ -58,11 +62,25 @@ impl Sensor {
             }
         }
 
-        Self { resolution, tiles }
+        let splats = SplatBuffer::new(resolution);
+
+        Self {
+            resolution,
+            tiles,
+            splats,
+        }
     }
 
-    pub const fn new2(resolution: UVec2, tiles: Vec<Mutex<SensorTile>>) -> Self {
-        Self { resolution, tiles }
+    pub const fn new2(
+        resolution: UVec2,
+        tiles: Vec<Mutex<SensorTile>>,
+        splats: SplatBuffer,
+    ) -> Self {
+        Self {
+            resolution,
+            tiles,
+            splats,
+        }
     }
 
     #[cold]
@@ -71,12 +89,14 @@ impl Sensor {
         self.tiles
             .iter()
             .all(|t| t.lock().pixels.iter().all(|p| p.is_empty()))
+            && self.splats.is_empty()
     }
 
     pub fn reset(&mut self) {
         for t in &self.tiles {
             t.lock().pixels.iter_mut().for_each(Pixel::reset);
         }
+        self.splats.reset();
     }
 
     pub fn num_tiles(&self) -> usize {
@@ -92,7 +112,17 @@ impl From<SensorDe> for Sensor {
     fn from(de: SensorDe) -> Self {
         match de {
             SensorDe::Config(resolution) => Self::new(resolution),
-            SensorDe::Checkpoint(resolution, tiles) => Self::new2(resolution, tiles),
+            SensorDe::Checkpoint(resolution, tiles) => {
+                Self::new2(resolution, tiles, SplatBuffer::new(resolution))
+            }
+            SensorDe::SplatCheckpoint(resolution, tiles, mut splats) => {
+                if splats.resolution() != resolution {
+                    log::warn!(target: "Sensor", "discarding splats of resolution {:?} for a sensor of resolution {:?}", splats.resolution(), resolution);
+                    splats = SplatBuffer::new(resolution);
+                }
+
+                Self::new2(resolution, tiles, splats)
+            }
         }
     }
 }
@@ -102,17 +132,55 @@ impl Serialize for Sensor {
     where
         S: Serializer,
     {
-        SensorSer::Checkpoint(self.resolution, &self.tiles).serialize(serializer)
+        // written as the variant of [SensorDe] by both name and index
+        let mut ser = serializer.serialize_tuple_variant("SensorDe", 2, "SplatCheckpoint", 3)?;
+        ser.serialize_field(&self.resolution)?;
+        ser.serialize_field(&self.tiles)?;
+        ser.serialize_field(&self.splats)?;
+        ser.end()
     }
 }
 
-#[derive(Serialize)]
-enum SensorSer<'a> {
-    Checkpoint(UVec2, &'a [Mutex<SensorTile>]),
-}
-
+/// The serialized sensor, whose variants must only be appended to keep older checkpoints loadable.
 #[derive(Deserialize)]
 enum SensorDe {
+    /// A checkpoint prior to splatting, which starts with an empty splat buffer
     Checkpoint(UVec2, Vec<Mutex<SensorTile>>),
     Config(UVec2),
+    SplatCheckpoint(UVec2, Vec<Mutex<SensorTile>>, SplatBuffer),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn loads_checkpoints_prior_to_splatting() {
+        // tiles are deserialized on the stack, which exceeds the default of test threads
+        std::thread::Builder::new()
+            .stack_size(64 << 20)
+            .spawn(load_legacy_checkpoint)
+            .unwrap()
+            .join()
+            .unwrap();
+    }
+
+    fn load_legacy_checkpoint() {
+        #[derive(Serialize)]
+        enum LegacySensor<'a> {
+            Checkpoint(UVec2, &'a [Mutex<SensorTile>]),
+        }
+
+        let resolution = UVec2::new(SENSOR_TILE_WIDTH, 2 * SENSOR_TILE_WIDTH);
+        let sensor = Sensor::new(resolution);
+
+        let legacy = ron::to_string(&LegacySensor::Checkpoint(resolution, &sensor.tiles)).unwrap();
+        let loaded: Sensor = ron::from_str(&legacy).unwrap();
+        assert_eq!(resolution, loaded.splats.resolution());
+        assert_eq!(sensor.tiles.len(), loaded.tiles.len());
+
+        let current = ron::to_string(&sensor).unwrap();
+        let loaded: Sensor = ron::from_str(&current).unwrap();
+        assert_eq!(resolution, loaded.splats.resolution());
+    }
 }
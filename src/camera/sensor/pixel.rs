@@ -75,6 +75,19 @@ impl Pixel {
         self.samples.inc(index);
         self.average[index] = avg / self.samples[index] as Float;
     }
+
+    /// Returns the number of wavelengths sampled at least once.
+    pub fn sampled(&self) -> usize {
+        self.samples.data.iter().filter(|&&s| s > 0).count()
+    }
+
+    /// Overwrites the estimate of a single wavelength instead of averaging it.
+    /// Used by progressive integrators keeping their own statistics per pixel.
+    #[inline]
+    pub fn set_lambda(&mut self, lambda: Float, index: usize) {
+        self.samples.inc(index);
+        self.average[index] = lambda;
+    }
 }
 
 impl Default for Pixel {
@@ -0,0 +1,166 @@
+use core::sync::atomic::{AtomicU64, Ordering};
+
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
+
+use crate::{Float, Spectrum, UVec2, Vec2, PACKET_SIZE};
+
+/// A thread-safe buffer for contributions that do not belong to the pixel being integrated (e.g.
+/// light subpaths connected to the camera).
+///
+/// Contrary to the running average of a [Pixel](crate::camera::sensor::Pixel), splats are summed
+/// up and normalized by the number of splatting paths traced for the whole sensor.
+/// Splats into an empty buffer (see [Default]) are discarded.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SplatBuffer {
+    resolution: UVec2,
+    pixels: Vec<Mutex<Spectrum>>,
+    samples: Vec<AtomicU64>,
+}
+
+impl Default for SplatBuffer {
+    fn default() -> Self {
+        Self::new(UVec2::new(0, 0))
+    }
+}
+
+impl SplatBuffer {
+    /// Creates a new, empty splat buffer.
+    ///
+    /// # Arguments
+    /// * `resolution`: The resolution of the sensor.
+    pub fn new(resolution: UVec2) -> Self {
+        let num_pixels = (resolution.x * resolution.y) as usize;
+
+        Self {
+            resolution,
+            pixels: (0..num_pixels)
+                .map(|_| Mutex::new(Spectrum::splat(0.0)))
+                .collect(),
+            samples: (0..Spectrum::size()).map(|_| AtomicU64::new(0)).collect(),
+        }
+    }
+
+    pub const fn resolution(&self) -> UVec2 {
+        self.resolution
+    }
+
+    #[cold]
+    #[inline(never)]
+    pub fn is_empty(&self) -> bool {
+        self.samples.iter().all(|s| s.load(Ordering::Relaxed) == 0)
+            && self.pixels.iter().all(|p| p.lock().is_black())
+    }
+
+    pub fn reset(&self) {
+        self.pixels
+            .iter()
+            .for_each(|p| *p.lock() = Spectrum::splat(0.0));
+        self.samples
+            .iter()
+            .for_each(|s| s.store(0, Ordering::Relaxed));
+    }
+
+    /// Returns the index of the pixel containing the given raster position, if there are any
+    /// pixels.
+    #[inline]
+    fn index(&self, raster: Vec2) -> Option<usize> {
+        if self.pixels.is_empty() {
+            return None;
+        }
+
+        let x = (raster.x.max(0.0) as u32).min(self.resolution.x - 1);
+        let y = (raster.y.max(0.0) as u32).min(self.resolution.y - 1);
+
+        Some((y * self.resolution.x + x) as usize)
+    }
+
+    /// Adds a spectrum to the pixel at the given raster position.
+    ///
+    /// # Constraints
+    /// * `raster`: Should be within the resolution of the sensor.
+    pub fn add(&self, raster: Vec2, spectrum: Spectrum) {
+        if let Some(i) = self.index(raster) {
+            *self.pixels[i].lock() += spectrum;
+        }
+    }
+
+    /// Adds a packet to the pixel at the given raster position.
+    ///
+    /// # Constraints
+    /// * `raster`: Should be within the resolution of the sensor.
+    /// * `indices`: All values should be within `[0, `[Spectrum::size]`)`.
+    pub fn add_packet(
+        &self,
+        raster: Vec2,
+        spectrum: &[Float; PACKET_SIZE],
+        indices: &[usize; PACKET_SIZE],
+    ) {
+        if let Some(i) = self.index(raster) {
+            let mut pixel = self.pixels[i].lock();
+            for i in 0..PACKET_SIZE {
+                pixel[indices[i]] += spectrum[i];
+            }
+        }
+    }
+
+    /// Adds a single wavelength to the pixel at the given raster position.
+    ///
+    /// # Constraints
+    /// * `raster`: Should be within the resolution of the sensor.
+    /// * `index`: Should be within `[0, `[Spectrum::size]`)`.
+    pub fn add_lambda(&self, raster: Vec2, lambda: Float, index: usize) {
+        if let Some(i) = self.index(raster) {
+            self.pixels[i].lock()[index] += lambda;
+        }
+    }
+
+    /// Counts a splatting path carrying all wavelengths.
+    ///
+    /// Every path has to be counted, regardless of whether it splatted anything.
+    pub fn count(&self) {
+        for s in &self.samples {
+            s.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Counts a splatting path carrying the given wavelengths.
+    ///
+    /// Every path has to be counted, regardless of whether it splatted anything.
+    pub fn count_packet(&self, indices: &[usize; PACKET_SIZE]) {
+        indices.iter().for_each(|&i| self.count_lambda(i));
+    }
+
+    /// Counts a splatting path carrying a single wavelength.
+    ///
+    /// Every path has to be counted, regardless of whether it splatted anything.
+    #[inline]
+    pub fn count_lambda(&self, index: usize) {
+        self.samples[index].fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Returns the normalized splats of the given pixel.
+    ///
+    /// As splatting paths are spread over the whole sensor, the sums are scaled by the number of
+    /// pixels per path.
+    pub fn get(&self, position: UVec2) -> Spectrum {
+        if self.pixels.is_empty() {
+            return Spectrum::splat(0.0);
+        }
+
+        let num_pixels = self.pixels.len() as Float;
+        let mut spectrum =
+            *self.pixels[(position.y * self.resolution.x + position.x) as usize].lock();
+
+        for (i, samples) in self.samples.iter().enumerate() {
+            let samples = samples.load(Ordering::Relaxed);
+            spectrum[i] = if samples == 0 {
+                0.0
+            } else {
+                spectrum[i] * num_pixels / samples as Float
+            };
+        }
+
+        spectrum
+    }
+}
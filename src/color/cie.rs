@@ -7,6 +7,9 @@ use crate::Float;
 
 pub const CIE_Y_INTEGRAL: Float = 0.106856895;
 
+/// The maximum luminous efficacy of radiation in `lm/W`, reached at `555nm`.
+pub const MAX_LUMINOUS_EFFICACY: Float = 683.0;
+
 #[inline]
 pub fn lambda_to_xyz_approx(lambda: Float) -> Xyz {
     let lambda = micrometer_to_nanometer(lambda);
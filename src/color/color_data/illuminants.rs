@@ -0,0 +1,180 @@
+//! Tabulated data of the CIE standard illuminants and gas-discharge lamps.
+
+#![allow(clippy::approx_constant)]
+
+use crate::Float;
+
+/// The mean component of the CIE daylight series, from `380nm` to `730nm` in `10nm` steps.
+pub static DAYLIGHT_S0: [Float; 36] = [
+    63.4, 65.8, 94.8, 104.8, 105.9, 96.8, 113.9, 125.6, 125.5, 121.3, 121.3, 113.5, 113.1, 110.8,
+    106.5, 108.8, 105.3, 104.4, 100.0, 96.0, 95.1, 89.1, 90.5, 90.3, 88.4, 84.0, 85.1, 81.9, 82.6,
+    84.9, 81.3, 71.9, 74.3, 76.4, 63.3, 71.7,
+];
+
+/// The first characteristic vector of the CIE daylight series, from `380nm` to `730nm` in
+/// `10nm` steps.
+pub static DAYLIGHT_S1: [Float; 36] = [
+    38.5, 35.0, 43.4, 46.3, 43.9, 37.1, 36.7, 35.9, 32.6, 27.9, 24.3, 20.1, 16.2, 13.2, 8.6, 6.1,
+    4.2, 1.9, 0.0, -1.6, -3.5, -3.5, -5.8, -7.2, -8.6, -9.5, -10.9, -10.7, -12.0, -14.0, -13.6,
+    -12.0, -13.3, -12.9, -10.6, -11.6,
+];
+
+/// The second characteristic vector of the CIE daylight series, from `380nm` to `730nm` in
+/// `10nm` steps.
+pub static DAYLIGHT_S2: [Float; 36] = [
+    3.0, 1.2, -1.1, -0.5, -0.7, -1.2, -2.6, -2.9, -2.8, -2.6, -2.6, -1.8, -1.5, -1.3, -1.2, -1.0,
+    -0.5, -0.3, 0.0, 0.2, 0.5, 2.1, 3.2, 4.1, 4.7, 5.1, 6.7, 7.3, 8.6, 9.8, 10.2, 8.3, 9.6, 8.5,
+    7.0, 7.6,
+];
+
+/// The CIE fluorescent illuminant F1 (daylight), from `380nm` to `730nm` in `5nm` steps.
+pub static F1: [Float; 71] = [
+    1.87, 2.36, 2.94, 3.47, 5.17, 19.49, 6.13, 6.24, 7.01, 7.79, 8.56, 43.67, 16.94, 10.72, 11.35,
+    11.89, 12.37, 12.75, 13.0, 13.15, 13.23, 13.17, 13.13, 12.85, 12.52, 12.2, 11.83, 11.5, 11.22,
+    11.05, 11.03, 11.18, 11.53, 27.74, 17.05, 13.55, 14.33, 15.01, 15.52, 18.29, 19.55, 15.48,
+    14.91, 14.15, 13.22, 12.19, 11.12, 10.03, 8.95, 7.96, 7.02, 6.2, 5.42, 4.73, 4.15, 3.64, 3.2,
+    2.81, 2.47, 2.18, 1.93, 1.72, 1.67, 1.43, 1.29, 1.19, 1.08, 0.96, 0.88, 0.81, 0.77,
+];
+
+/// The CIE fluorescent illuminant F2 (cool white), from `380nm` to `730nm` in `5nm` steps.
+pub static F2: [Float; 71] = [
+    1.18, 1.48, 1.84, 2.15, 3.44, 15.69, 3.85, 3.74, 4.19, 4.62, 5.06, 34.98, 11.81, 6.27, 6.63,
+    6.93, 7.19, 7.4, 7.54, 7.62, 7.65, 7.62, 7.62, 7.45, 7.28, 7.15, 7.05, 7.04, 7.16, 7.47, 8.04,
+    8.88, 10.01, 24.88, 16.64, 14.59, 16.16, 17.56, 18.62, 21.47, 22.79, 19.29, 18.66, 17.73,
+    16.54, 15.21, 13.8, 12.36, 10.95, 9.65, 8.4, 7.32, 6.31, 5.43, 4.68, 4.02, 3.45, 2.96, 2.55,
+    2.19, 1.89, 1.64, 1.53, 1.27, 1.1, 0.99, 0.88, 0.76, 0.68, 0.61, 0.56,
+];
+
+/// The CIE fluorescent illuminant F3 (white), from `380nm` to `730nm` in `5nm` steps.
+pub static F3: [Float; 71] = [
+    0.82, 1.02, 1.26, 1.44, 2.57, 14.36, 2.7, 2.45, 2.73, 3.0, 3.28, 31.85, 9.47, 4.02, 4.25, 4.44,
+    4.59, 4.72, 4.8, 4.86, 4.87, 4.85, 4.88, 4.77, 4.67, 4.62, 4.62, 4.73, 4.99, 5.48, 6.25, 7.34,
+    8.78, 23.82, 16.14, 14.59, 16.63, 18.49, 19.95, 23.11, 24.69, 21.41, 20.85, 19.93, 18.67,
+    17.22, 15.65, 14.04, 12.45, 10.95, 9.51, 8.27, 7.11, 6.09, 5.22, 4.45, 3.8, 3.23, 2.75, 2.33,
+    1.99, 1.7, 1.55, 1.27, 1.09, 0.96, 0.83, 0.71, 0.62, 0.54, 0.49,
+];
+
+/// The CIE fluorescent illuminant F4 (warm white), from `380nm` to `730nm` in `5nm` steps.
+pub static F4: [Float; 71] = [
+    0.57, 0.7, 0.87, 0.98, 2.01, 13.75, 1.95, 1.59, 1.76, 1.93, 2.1, 30.28, 8.03, 2.55, 2.7, 2.82,
+    2.91, 2.99, 3.04, 3.08, 3.09, 3.09, 3.14, 3.06, 3.0, 2.98, 3.01, 3.14, 3.41, 3.9, 4.69, 5.81,
+    7.32, 22.59, 15.11, 13.88, 16.33, 18.68, 20.64, 24.28, 26.26, 23.28, 22.94, 22.14, 20.91,
+    19.43, 17.74, 16.0, 14.42, 12.56, 10.93, 9.52, 8.18, 7.01, 6.0, 5.11, 4.36, 3.69, 3.13, 2.64,
+    2.24, 1.91, 1.7, 1.39, 1.18, 1.03, 0.88, 0.74, 0.64, 0.54, 0.49,
+];
+
+/// The CIE fluorescent illuminant F5 (daylight), from `380nm` to `730nm` in `5nm` steps.
+pub static F5: [Float; 71] = [
+    1.87, 2.35, 2.92, 3.45, 5.1, 18.91, 6.0, 6.11, 6.85, 7.58, 8.31, 40.76, 16.06, 10.32, 10.91,
+    11.4, 11.83, 12.17, 12.4, 12.54, 12.58, 12.52, 12.47, 12.2, 11.89, 11.61, 11.33, 11.1, 10.96,
+    10.97, 11.16, 11.54, 12.12, 27.78, 17.73, 14.47, 15.2, 15.77, 16.1, 18.54, 19.5, 15.39, 14.64,
+    13.72, 12.69, 11.57, 10.45, 9.35, 8.29, 7.32, 6.41, 5.63, 4.9, 4.26, 3.72, 3.25, 2.83, 2.49,
+    2.19, 1.93, 1.71, 1.52, 1.48, 1.26, 1.13, 1.05, 0.96, 0.85, 0.78, 0.72, 0.68,
+];
+
+/// The CIE fluorescent illuminant F6 (lite white), from `380nm` to `730nm` in `5nm` steps.
+pub static F6: [Float; 71] = [
+    1.05, 1.31, 1.63, 1.9, 3.11, 14.8, 3.43, 3.3, 3.68, 4.07, 4.45, 32.61, 10.74, 5.48, 5.78, 6.03,
+    6.25, 6.41, 6.52, 6.58, 6.59, 6.56, 6.56, 6.42, 6.28, 6.2, 6.19, 6.3, 6.6, 7.12, 7.94, 9.07,
+    10.49, 25.22, 17.46, 15.63, 17.22, 18.53, 19.43, 21.97, 23.01, 19.41, 18.56, 17.42, 16.09,
+    14.64, 13.15, 11.68, 10.25, 8.95, 7.74, 6.69, 5.71, 4.87, 4.16, 3.55, 3.02, 2.57, 2.2, 1.87,
+    1.6, 1.37, 1.29, 1.05, 0.91, 0.81, 0.71, 0.61, 0.54, 0.48, 0.44,
+];
+
+/// The CIE fluorescent illuminant F7 (broadband daylight (D65 simulator)), from `380nm` to `730nm` in `5nm` steps.
+pub static F7: [Float; 71] = [
+    2.56, 3.18, 3.84, 4.53, 6.15, 19.37, 7.37, 7.05, 7.71, 8.41, 9.15, 44.14, 17.52, 11.35, 12.0,
+    12.58, 13.08, 13.45, 13.71, 13.88, 13.95, 13.93, 13.82, 13.64, 13.43, 13.25, 13.08, 12.93,
+    12.78, 12.6, 12.44, 12.33, 12.26, 29.52, 17.05, 12.44, 12.58, 12.72, 12.83, 15.46, 16.75,
+    12.83, 12.67, 12.45, 12.19, 11.89, 11.6, 11.35, 11.12, 10.95, 10.76, 10.42, 10.11, 10.04,
+    10.02, 10.11, 9.87, 8.65, 7.27, 6.44, 5.83, 5.41, 5.04, 4.57, 4.12, 3.77, 3.46, 3.08, 2.73,
+    2.47, 2.25,
+];
+
+/// The CIE fluorescent illuminant F8 (broadband (D50 simulator)), from `380nm` to `730nm` in `5nm` steps.
+pub static F8: [Float; 71] = [
+    1.21, 1.5, 1.81, 2.13, 3.17, 13.08, 3.83, 3.45, 3.86, 4.42, 5.09, 34.1, 12.42, 7.68, 8.6, 9.46,
+    10.24, 10.84, 11.33, 11.71, 11.98, 12.17, 12.28, 12.32, 12.35, 12.44, 12.55, 12.68, 12.77,
+    12.72, 12.6, 12.43, 12.22, 28.96, 16.51, 11.79, 11.76, 11.77, 11.84, 14.61, 16.11, 12.34,
+    12.53, 12.72, 12.92, 13.12, 13.34, 13.61, 13.87, 14.07, 14.2, 14.16, 14.13, 14.34, 14.5, 14.46,
+    14.0, 12.58, 10.99, 9.98, 9.22, 8.62, 8.07, 7.39, 6.71, 6.16, 5.63, 5.03, 4.46, 4.02, 3.66,
+];
+
+/// The CIE fluorescent illuminant F9 (broadband cool white deluxe), from `380nm` to `730nm` in `5nm` steps.
+pub static F9: [Float; 71] = [
+    0.9, 1.12, 1.36, 1.6, 2.59, 12.8, 3.05, 2.56, 2.86, 3.3, 3.82, 32.62, 10.77, 5.84, 6.57, 7.25,
+    7.86, 8.35, 8.75, 9.06, 9.31, 9.48, 9.61, 9.68, 9.74, 9.88, 10.04, 10.26, 10.48, 10.63, 10.78,
+    10.96, 11.18, 27.71, 16.29, 12.28, 12.74, 13.21, 13.65, 16.57, 18.14, 14.55, 14.65, 14.66,
+    14.61, 14.5, 14.39, 14.4, 14.47, 14.62, 14.72, 14.55, 14.4, 14.58, 14.88, 15.51, 15.47, 13.2,
+    10.57, 9.18, 8.25, 7.57, 7.03, 6.35, 5.72, 5.25, 4.8, 4.29, 3.8, 3.43, 3.12,
+];
+
+/// The CIE fluorescent illuminant F10 (narrow tri-band (5000K)), from `380nm` to `730nm` in `5nm` steps.
+pub static F10: [Float; 71] = [
+    1.11, 0.8, 0.62, 0.57, 1.48, 12.16, 2.12, 2.7, 3.74, 5.14, 6.75, 34.39, 14.86, 10.4, 10.76,
+    10.67, 10.11, 9.27, 8.29, 7.29, 7.91, 16.64, 16.73, 10.44, 5.94, 3.34, 2.35, 1.88, 1.59, 1.47,
+    1.8, 5.71, 40.98, 73.69, 33.61, 8.24, 3.38, 2.47, 2.14, 4.86, 11.45, 14.79, 12.16, 8.97, 6.52,
+    8.31, 44.12, 34.55, 12.09, 12.15, 10.52, 4.43, 1.95, 2.19, 3.19, 2.77, 2.29, 2.0, 1.52, 1.35,
+    1.47, 1.79, 1.74, 1.02, 1.14, 3.32, 4.49, 2.05, 0.49, 0.24, 0.21,
+];
+
+/// The CIE fluorescent illuminant F11 (narrow tri-band (4000K)), from `380nm` to `730nm` in `5nm` steps.
+pub static F11: [Float; 71] = [
+    0.91, 0.63, 0.46, 0.37, 1.29, 12.68, 1.59, 1.79, 2.46, 3.33, 4.49, 33.94, 12.13, 6.95, 7.19,
+    7.12, 6.72, 6.13, 5.46, 4.79, 5.66, 14.29, 14.96, 8.97, 4.72, 2.33, 1.47, 1.1, 0.89, 0.83,
+    1.18, 4.9, 39.59, 72.84, 32.61, 7.52, 2.83, 1.96, 1.67, 4.43, 11.28, 14.76, 12.73, 9.74, 7.33,
+    9.72, 55.27, 42.58, 13.18, 13.16, 12.26, 5.11, 2.07, 2.34, 3.58, 3.01, 2.48, 2.14, 1.54, 1.33,
+    1.46, 1.94, 2.0, 1.2, 1.35, 4.1, 5.58, 2.51, 0.57, 0.27, 0.23,
+];
+
+/// The CIE fluorescent illuminant F12 (narrow tri-band (3000K)), from `380nm` to `730nm` in `5nm` steps.
+pub static F12: [Float; 71] = [
+    0.96, 0.64, 0.45, 0.33, 1.19, 12.48, 1.12, 0.94, 1.08, 1.37, 1.78, 29.05, 7.9, 2.65, 2.71,
+    2.65, 2.49, 2.33, 2.1, 1.91, 3.01, 10.83, 11.88, 6.88, 3.43, 1.49, 0.92, 0.71, 0.6, 0.63, 1.1,
+    4.56, 34.4, 65.4, 29.48, 7.16, 3.08, 2.47, 2.27, 5.09, 11.96, 15.32, 14.27, 11.86, 9.28, 12.31,
+    68.53, 53.02, 14.67, 14.38, 14.71, 6.46, 2.57, 2.75, 4.18, 3.44, 2.81, 2.42, 1.64, 1.36, 1.49,
+    1.94, 2.01, 1.04, 1.11, 2.84, 3.88, 2.23, 0.55, 0.31, 0.26,
+];
+
+/// The lines of a low-pressure sodium lamp in `nm` with their approximate relative intensities.
+pub const SODIUM_LINES: [(Float, Float); 2] = [(588.995, 1.0), (589.592, 0.5)];
+
+/// The visible lines of a low-pressure mercury lamp in `nm` with their approximate relative
+/// intensities.
+pub const MERCURY_LINES: [(Float, Float); 6] = [
+    (404.656, 0.35),
+    (407.783, 0.03),
+    (435.833, 0.8),
+    (546.074, 1.0),
+    (576.96, 0.1),
+    (579.066, 0.11),
+];
+
+/// The visible lines of a neon lamp in `nm` with their approximate relative intensities.
+pub const NEON_LINES: [(Float, Float); 25] = [
+    (540.056, 0.06),
+    (585.249, 0.5),
+    (588.19, 0.25),
+    (594.483, 0.25),
+    (597.553, 0.12),
+    (603.0, 0.25),
+    (607.434, 0.25),
+    (609.616, 0.25),
+    (614.306, 0.25),
+    (616.359, 0.25),
+    (621.728, 0.25),
+    (626.65, 0.25),
+    (630.479, 0.25),
+    (633.443, 0.25),
+    (638.299, 0.25),
+    (640.225, 0.5),
+    (650.653, 0.4),
+    (653.288, 0.25),
+    (659.895, 0.25),
+    (667.828, 0.12),
+    (671.704, 0.18),
+    (692.947, 1.0),
+    (703.241, 0.85),
+    (717.394, 0.1),
+    (724.517, 0.1),
+];
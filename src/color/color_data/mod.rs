@@ -1,7 +1,9 @@
 use crate::Float;
 
+pub mod illuminants;
 pub mod spectral;
 pub mod srgb;
+pub mod upsampling;
 pub mod xyz;
 
 /// micro meters
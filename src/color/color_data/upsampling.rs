@@ -0,0 +1,65 @@
+//! Spectral bases to upsample linear sRGB to spectra (see
+//! [Spectrum::from_linear_rgb](crate::color::Spectrum::from_linear_rgb)).
+//!
+//! Following Smits (1999), each basis is the smoothest non-negative spectrum that maps exactly to
+//! its color under the CIE approximation in [cie](crate::color::cie).
+
+#![allow(clippy::excessive_precision)]
+
+use crate::Float;
+
+/// The white basis, from `380nm` to `730nm` in `10nm` steps.
+pub static WHITE: [Float; 36] = [
+    1.112477, 1.112469, 1.112451, 1.112464, 1.112695, 1.113492, 1.115246, 1.118312, 1.122365,
+    1.126722, 1.130657, 1.133421, 1.134110, 1.131670, 1.124887, 1.112586, 1.094298, 1.070525,
+    1.042416, 1.011671, 0.980316, 0.950478, 0.924093, 0.902614, 0.886650, 0.875858, 0.869250,
+    0.865606, 0.863810, 0.863029, 0.862735, 0.862644, 0.862623, 0.862621, 0.862623, 0.862623,
+];
+
+/// The cyan basis, from `380nm` to `730nm` in `10nm` steps.
+pub static CYAN: [Float; 36] = [
+    1.024657, 1.024631, 1.024598, 1.024875, 1.026785, 1.033250, 1.048134, 1.074126, 1.109619,
+    1.150754, 1.192731, 1.230333, 1.257822, 1.268966, 1.256754, 1.214399, 1.138966, 1.032779,
+    0.901665, 0.754416, 0.601632, 0.454517, 0.323374, 0.216053, 0.136048, 0.081891, 0.048743,
+    0.030506, 0.021564, 0.017712, 0.016293, 0.015872, 0.015792, 0.015797, 0.015811, 0.015817,
+];
+
+/// The magenta basis, from `380nm` to `730nm` in `10nm` steps.
+pub static MAGENTA: [Float; 36] = [
+    1.305085, 1.305033, 1.304714, 1.303099, 1.296210, 1.273379, 1.218513, 1.122856, 0.988442,
+    0.823089, 0.640067, 0.455176, 0.283528, 0.139801, 0.040009, 0.000000, 0.000000, 0.001920,
+    0.063604, 0.172010, 0.310497, 0.460658, 0.604686, 0.727976, 0.822261, 0.886806, 0.926252,
+    0.947620, 0.957733, 0.961793, 0.963078, 0.963317, 0.963267, 0.963189, 0.963144, 0.963127,
+];
+
+/// The yellow basis, from `380nm` to `730nm` in `10nm` steps.
+pub static YELLOW: [Float; 36] = [
+    0.000000, 0.000000, 0.000000, 0.000000, 0.000000, 0.000000, 0.000000, 0.028666, 0.103611,
+    0.219262, 0.363229, 0.520158, 0.677126, 0.823577, 0.949334, 1.044857, 1.104686, 1.128704,
+    1.120238, 1.085529, 1.032747, 0.971077, 0.909538, 0.855676, 0.813987, 0.785301, 0.767778,
+    0.758352, 0.753962, 0.752260, 0.751766, 0.751709, 0.751758, 0.751805, 0.751830, 0.751838,
+];
+
+/// The red basis, from `380nm` to `730nm` in `10nm` steps.
+pub static RED: [Float; 36] = [
+    0.046852, 0.046999, 0.047419, 0.047854, 0.046877, 0.042747, 0.034774, 0.020995, 0.006416,
+    0.000000, 0.000000, 0.000000, 0.000000, 0.000000, 0.000000, 0.000000, 0.000000, 0.000000,
+    0.000000, 0.078642, 0.222114, 0.400240, 0.583205, 0.745830, 0.872712, 0.960315, 1.013796,
+    1.042426, 1.055602, 1.060577, 1.061909, 1.061969, 1.061755, 1.061579, 1.061488, 1.061457,
+];
+
+/// The green basis, from `380nm` to `730nm` in `10nm` steps.
+pub static GREEN: [Float; 36] = [
+    0.000000, 0.000000, 0.000000, 0.000000, 0.000000, 0.000000, 0.000000, 0.000000, 0.030004,
+    0.150871, 0.334675, 0.547237, 0.759850, 0.949484, 1.094694, 1.176132, 1.183921, 1.120334,
+    0.995594, 0.826557, 0.634241, 0.441390, 0.269444, 0.135344, 0.047647, 0.005176, 0.000000,
+    0.000000, 0.000000, 0.000000, 0.000000, 0.000000, 0.000000, 0.000050, 0.000090, 0.000106,
+];
+
+/// The blue basis, from `380nm` to `730nm` in `10nm` steps.
+pub static BLUE: [Float; 36] = [
+    1.231817, 1.231733, 1.231347, 1.229877, 1.224393, 1.206340, 1.162215, 1.085354, 0.976324,
+    0.839646, 0.684906, 0.524119, 0.368117, 0.226959, 0.111674, 0.033566, 0.000000, 0.000000,
+    0.000000, 0.000000, 0.000000, 0.000000, 0.000969, 0.009211, 0.018620, 0.025980, 0.030406,
+    0.032384, 0.032857, 0.032656, 0.032294, 0.031995, 0.031808, 0.031711, 0.031670, 0.031658,
+];
@@ -0,0 +1,291 @@
+//! Emission spectra of physical light sources.
+//!
+//! All spectra are evaluated on the wavelengths of a [Spectrum] (see
+//! [LAMBDAS](crate::color::color_data::spectral::LAMBDAS)).
+//! Except for [planck], they are normalized to a luminance of `1`, so that they can replace a
+//! white emission without changing the brightness.
+
+use crate::color::color_data::illuminants::*;
+use crate::color::color_data::LAMBDA_STEP;
+use crate::color::{Spectrum, Xyz};
+use crate::Float;
+use serde::{Deserialize, Serialize};
+
+/// The first radiation constant for spectral radiance `2hc²` in `W m²/sr`.
+const C1: Float = 1.191_043e-16;
+/// The second radiation constant `hc/k` in `m K`.
+const C2: Float = 1.438_777e-2;
+
+/// The CIE standard illuminants and the lines of common gas-discharge lamps.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Illuminant {
+    /// Incandescent light (a blackbody of about `2856K`)
+    A,
+    /// Horizon light
+    D50,
+    /// Mid-morning and mid-afternoon daylight
+    D55,
+    /// Noon daylight
+    D65,
+    /// North sky daylight
+    D75,
+    /// Daylight of a correlated color temperature in `K` within `[4000, 25000]`
+    Daylight(Float),
+    F1,
+    F2,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
+    F8,
+    F9,
+    F10,
+    F11,
+    F12,
+    /// A low-pressure sodium lamp
+    Sodium,
+    /// A low-pressure mercury lamp
+    Mercury,
+    /// A neon lamp
+    Neon,
+}
+
+impl Illuminant {
+    /// Computes the spectrum of this illuminant, normalized to a luminance of `1`.
+    ///
+    /// # Returns
+    /// * The spectrum, or `None` if the temperature of [Illuminant::Daylight] is out of range
+    pub fn spectrum(self) -> Option<Spectrum> {
+        // the CIE defines the daylight series for the revised second radiation constant
+        const DAYLIGHT_SCALE: Float = 1.4388 / 1.4380;
+
+        let spectrum = match self {
+            Self::A => incandescent(),
+            Self::D50 => daylight(5000.0 * DAYLIGHT_SCALE)?,
+            Self::D55 => daylight(5500.0 * DAYLIGHT_SCALE)?,
+            Self::D65 => daylight(6500.0 * DAYLIGHT_SCALE)?,
+            Self::D75 => daylight(7500.0 * DAYLIGHT_SCALE)?,
+            Self::Daylight(kelvin) => daylight(kelvin)?,
+            Self::F1 => bin(&F1),
+            Self::F2 => bin(&F2),
+            Self::F3 => bin(&F3),
+            Self::F4 => bin(&F4),
+            Self::F5 => bin(&F5),
+            Self::F6 => bin(&F6),
+            Self::F7 => bin(&F7),
+            Self::F8 => bin(&F8),
+            Self::F9 => bin(&F9),
+            Self::F10 => bin(&F10),
+            Self::F11 => bin(&F11),
+            Self::F12 => bin(&F12),
+            Self::Sodium => lines(&SODIUM_LINES),
+            Self::Mercury => lines(&MERCURY_LINES),
+            Self::Neon => lines(&NEON_LINES),
+        };
+
+        normalize(spectrum)
+    }
+}
+
+/// Computes the spectral radiance of a blackbody by Planck's law.
+///
+/// # Arguments
+/// * `kelvin` - The temperature in `K`
+/// * `lambda` - The wavelength in `μm`
+///
+/// # Returns
+/// * The spectral radiance in `W/(sr m² nm)`
+pub fn planck_at(kelvin: Float, lambda: Float) -> Float {
+    let lambda = lambda * 1e-6;
+
+    1e-9 * C1 / (lambda.powi(5) * ((C2 / (lambda * kelvin)).exp() - 1.0))
+}
+
+/// Computes the spectrum of a blackbody by Planck's law.
+///
+/// # Arguments
+/// * `kelvin` - The temperature in `K`
+///
+/// # Returns
+/// * The spectral radiance in `W/(sr m² nm)`, or `None` if the temperature is not positive
+pub fn planck(kelvin: Float) -> Option<Spectrum> {
+    if !(kelvin > 0.0 && kelvin.is_finite()) {
+        return None;
+    }
+
+    let mut spectrum = Spectrum::splat(0.0);
+    for (i, value) in spectrum.data.iter_mut().enumerate() {
+        *value = planck_at(kelvin, Spectrum::lambda(i));
+    }
+
+    Some(spectrum)
+}
+
+/// Computes the spectrum of a blackbody, normalized to a luminance of `1`.
+///
+/// # Arguments
+/// * `kelvin` - The temperature in `K`
+pub fn blackbody(kelvin: Float) -> Option<Spectrum> {
+    normalize(planck(kelvin)?)
+}
+
+/// Computes the relative spectrum of the CIE illuminant A.
+fn incandescent() -> Spectrum {
+    // the CIE defines A with the former second radiation constant
+    let exp = |nm: Float| (1.435e7 / (2848.0 * nm)).exp() - 1.0;
+
+    let mut spectrum = Spectrum::splat(0.0);
+    for (i, value) in spectrum.data.iter_mut().enumerate() {
+        let nm = 1000.0 * Spectrum::lambda(i);
+        *value = 100.0 * (560.0 / nm).powi(5) * exp(560.0) / exp(nm);
+    }
+
+    spectrum
+}
+
+/// Computes the relative spectrum of the CIE daylight series.
+///
+/// # Arguments
+/// * `kelvin` - The correlated color temperature in `K`
+fn daylight(kelvin: Float) -> Option<Spectrum> {
+    if !(4000.0..=25000.0).contains(&kelvin) {
+        log::warn!(target: "Illuminant", "daylight is only defined from 4000K to 25000K");
+        return None;
+    }
+
+    let t = 1.0 / kelvin;
+    let x = if kelvin <= 7000.0 {
+        0.244063 + t * (0.09911e3 + t * (2.9678e6 - t * 4.6070e9))
+    } else {
+        0.237040 + t * (0.24748e3 + t * (1.9018e6 - t * 2.0064e9))
+    };
+    let y = -3.0 * x * x + 2.870 * x - 0.275;
+
+    let (m1, m2) = daylight_weights(x, y);
+    let [s0, s1, s2] = daylight_basis();
+
+    Some(s0 + s1 * m1 + s2 * m2)
+}
+
+/// Computes the weights `M1` and `M2` of the characteristic vectors of the CIE daylight series
+/// (see [daylight_basis]) for a chromaticity.
+///
+/// # Constraints
+/// * `x`, `y` - Should lie on (or close to) the daylight locus.
+pub fn daylight_weights(x: Float, y: Float) -> (Float, Float) {
+    let m = 0.0241 + 0.2562 * x - 0.7341 * y;
+    let m1 = (-1.3515 - 1.7703 * x + 5.9114 * y) / m;
+    let m2 = (0.0300 - 31.4424 * x + 30.0717 * y) / m;
+
+    (m1, m2)
+}
+
+/// Returns the mean and the two characteristic vectors of the CIE daylight series.
+pub fn daylight_basis() -> [Spectrum; 3] {
+    [
+        Spectrum::new(DAYLIGHT_S0),
+        Spectrum::new(DAYLIGHT_S1),
+        Spectrum::new(DAYLIGHT_S2),
+    ]
+}
+
+/// Averages a table in half steps into the wavelengths of a [Spectrum], so that narrow lines in
+/// between keep their energy.
+fn bin(table: &[Float; 2 * Spectrum::size() - 1]) -> Spectrum {
+    let mut spectrum = Spectrum::splat(0.0);
+    for (i, value) in spectrum.data.iter_mut().enumerate() {
+        let j = 2 * i;
+        let before = table[j.saturating_sub(1)];
+        let after = table[(j + 1).min(table.len() - 1)];
+
+        *value = 0.25 * before + 0.5 * table[j] + 0.25 * after;
+    }
+
+    spectrum
+}
+
+/// Distributes emission lines onto the two nearest wavelengths of a [Spectrum].
+///
+/// # Arguments
+/// * `lines` - The wavelengths of the lines in `nm` with their intensities
+fn lines(lines: &[(Float, Float)]) -> Spectrum {
+    let mut spectrum = Spectrum::splat(0.0);
+    let start = Spectrum::lambda(0);
+
+    for &(nm, intensity) in lines {
+        let x = (nm / 1000.0 - start) / LAMBDA_STEP;
+        let i = (x.floor().max(0.0) as usize).min(Spectrum::size() - 2);
+        let t = (x - i as Float).clamp(0.0, 1.0);
+
+        spectrum[i] += (1.0 - t) * intensity;
+        spectrum[i + 1] += t * intensity;
+    }
+
+    spectrum
+}
+
+/// Scales a spectrum to a luminance of `1`.
+fn normalize(spectrum: Spectrum) -> Option<Spectrum> {
+    let luminance = Xyz::from(spectrum)[1];
+
+    if luminance > 0.0 && luminance.is_finite() {
+        Some(spectrum / luminance)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Computes the chromaticity of a spectrum.
+    fn chromaticity(spectrum: Spectrum) -> (Float, Float) {
+        let xyz = Xyz::from(spectrum);
+        let sum = xyz.sum_values();
+
+        (xyz[0] / sum, xyz[1] / sum)
+    }
+
+    #[test]
+    fn chromaticities() {
+        // the chromaticities published by the CIE, deviating by the approximated color matching
+        // functions and the binning of the fluorescent lines
+        let illuminants = [
+            (Illuminant::A, (0.4476, 0.4074)),
+            (Illuminant::D50, (0.3457, 0.3585)),
+            (Illuminant::D65, (0.3127, 0.3290)),
+            (Illuminant::F2, (0.3721, 0.3751)),
+            (Illuminant::F7, (0.3129, 0.3292)),
+            (Illuminant::F11, (0.3805, 0.3769)),
+        ];
+
+        for (illuminant, (x, y)) in illuminants {
+            let spectrum = illuminant.spectrum().unwrap();
+            assert!((Xyz::from(spectrum)[1] - 1.0).abs() < 1e-4);
+
+            let (cx, cy) = chromaticity(spectrum);
+            assert!((cx - x).abs() < 3e-3, "{:?}: x = {}", illuminant, cx);
+            assert!((cy - y).abs() < 3e-3, "{:?}: y = {}", illuminant, cy);
+        }
+    }
+
+    #[test]
+    fn blackbody_matches_daylight() {
+        // a blackbody of 6504K is close to D65
+        let (x, y) = chromaticity(blackbody(6504.0).unwrap());
+        assert!((x - 0.3135).abs() < 3e-3 && (y - 0.3237).abs() < 3e-3);
+
+        // Wien's displacement law
+        let peak = 2897.77 / 5000.0;
+        assert!(planck_at(5000.0, peak) > planck_at(5000.0, peak * 1.01));
+        assert!(planck_at(5000.0, peak) > planck_at(5000.0, peak * 0.99));
+
+        let parsed: Spectrum = ron::from_str("Blackbody(6504)").unwrap();
+        assert_eq!(blackbody(6504.0), Some(parsed));
+
+        assert!(blackbody(0.0).is_none());
+        assert!(Illuminant::Daylight(2000.0).spectrum().is_none());
+    }
+}
@@ -2,6 +2,8 @@ use core::convert::TryFrom;
 
 use crate::Float;
 use color_data::LAMBDA_NUM;
+pub use illuminant::Illuminant;
+pub use sampled::*;
 use serde_big_array::BigArray;
 pub use spectrum::*;
 pub use srgb::*;
@@ -9,7 +11,9 @@ pub use xyz::*;
 
 pub mod cie;
 pub mod color_data;
+pub mod illuminant;
 pub mod image_conv;
+pub mod sampled;
 pub mod spectrum;
 pub mod srgb;
 pub mod xyz;
@@ -24,6 +28,12 @@ pub enum ColorSerde {
     Color(Color),
     MulColor(Float, Color),
     Constant(Float),
+    /// A blackbody of a temperature in `K`, normalized to a luminance of `1`
+    Blackbody(Float),
+    /// A blackbody of a temperature in `K` with its spectral radiance in `W/(sr m² nm)`
+    Planck(Float),
+    Illuminant(Illuminant),
+    MulIlluminant(Float, Illuminant),
 }
 
 /// A light wave is described by a wavelength (lambda) in `μm` and an intensity (associated with amplitude).
@@ -0,0 +1,241 @@
+//! Spectra sampled at some wavelengths of a [Spectrum].
+//!
+//! Integrators either trace all wavelengths of a [Spectrum] at once, a packet of [PACKET_SIZE]
+//! wavelengths or a single wavelength.
+//! [SampledSpectrum] abstracts over these, so that BxDFs, emitters and integrators only have to be
+//! written once.
+//! The spectral indices of the wavelengths ([SpectralIndices]) determine the sampled spectrum:
+//! * `()` - All wavelengths of a [Spectrum]
+//! * `[usize; PACKET_SIZE]` - A packet of wavelengths, sampled as `[Float; PACKET_SIZE]`
+//! * `usize` - A single wavelength, sampled as `Float`
+
+use crate::{Float, Spectrum, PACKET_SIZE};
+use core::fmt::Debug;
+use core::slice;
+
+/// The wavelengths of a sampled spectrum.
+#[derive(Copy, Clone, Debug)]
+pub enum Wavelengths<'a> {
+    /// All wavelengths of a [Spectrum], which do not disperse (e.g. refractions use the uniform
+    /// index of refraction).
+    All,
+    /// The wavelengths of the spectral indices.
+    Indices(&'a [usize]),
+}
+
+impl Wavelengths<'_> {
+    /// Returns the number of wavelengths.
+    #[inline]
+    pub fn len(&self) -> usize {
+        match self {
+            Wavelengths::All => Spectrum::size(),
+            Wavelengths::Indices(indices) => indices.len(),
+        }
+    }
+
+    /// Returns whether there are no wavelengths.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the spectral index of the `i`-th wavelength.
+    #[inline]
+    pub fn index(&self, i: usize) -> usize {
+        match self {
+            Wavelengths::All => i,
+            Wavelengths::Indices(indices) => indices[i],
+        }
+    }
+
+    /// Returns the `i`-th wavelength, or `None` if all wavelengths are sampled at once and do not
+    /// disperse.
+    #[inline]
+    pub fn lambda(&self, i: usize) -> Option<Float> {
+        match self {
+            Wavelengths::All => None,
+            Wavelengths::Indices(indices) => Some(Spectrum::lambda(indices[i])),
+        }
+    }
+
+    /// Writes the values of a spectrum at the wavelengths.
+    ///
+    /// # Arguments
+    /// * `spectrum` - The spectrum to read
+    /// * `values` - The values to write, one per wavelength
+    #[inline]
+    pub fn select(&self, spectrum: &Spectrum, values: &mut [Float]) {
+        for (i, value) in values.iter_mut().enumerate() {
+            *value = spectrum[self.index(i)];
+        }
+    }
+}
+
+/// A spectrum sampled at the wavelengths of some [SpectralIndices].
+pub trait SampledSpectrum: Copy + Debug + Send + Sync {
+    /// Creates a sampled spectrum with a splatted value.
+    fn splat(value: Float) -> Self;
+
+    /// Returns the values of all sampled wavelengths.
+    fn as_slice(&self) -> &[Float];
+
+    /// Returns the values of all sampled wavelengths.
+    fn as_mut_slice(&mut self) -> &mut [Float];
+
+    #[inline]
+    #[must_use]
+    fn mul(mut self, rhs: Self) -> Self {
+        for (a, b) in self.as_mut_slice().iter_mut().zip(rhs.as_slice()) {
+            *a *= b;
+        }
+        self
+    }
+
+    #[inline]
+    #[must_use]
+    fn mul_t(mut self, rhs: Float) -> Self {
+        for a in self.as_mut_slice() {
+            *a *= rhs;
+        }
+        self
+    }
+
+    #[inline]
+    fn add_assign(&mut self, rhs: Self) {
+        for (a, b) in self.as_mut_slice().iter_mut().zip(rhs.as_slice()) {
+            *a += b;
+        }
+    }
+
+    /// Returns whether all values are `0`.
+    #[inline]
+    fn is_black(&self) -> bool {
+        self.as_slice().iter().all(|&v| v == 0.0)
+    }
+
+    #[inline]
+    fn max_value(&self) -> Float {
+        self.as_slice().iter().copied().fold(Float::MIN, Float::max)
+    }
+
+    /// Returns the average of all values.
+    #[inline]
+    fn average(&self) -> Float {
+        self.as_slice().iter().sum::<Float>() / self.as_slice().len() as Float
+    }
+}
+
+/// The spectral indices of the wavelengths a spectrum is sampled at.
+pub trait SpectralIndices: Copy + Debug + Send + Sync {
+    /// The spectrum sampled at these indices.
+    type Sampled: SampledSpectrum;
+
+    /// Returns the wavelengths of the indices.
+    fn wavelengths(&self) -> Wavelengths<'_>;
+
+    /// Samples a spectrum at the indices.
+    #[inline]
+    fn select(&self, spectrum: &Spectrum) -> Self::Sampled {
+        let mut sampled = Self::Sampled::splat(0.0);
+        self.wavelengths().select(spectrum, sampled.as_mut_slice());
+        sampled
+    }
+}
+
+impl SampledSpectrum for Spectrum {
+    #[inline]
+    fn splat(value: Float) -> Self {
+        Spectrum::splat(value)
+    }
+
+    #[inline]
+    fn as_slice(&self) -> &[Float] {
+        &self.data
+    }
+
+    #[inline]
+    fn as_mut_slice(&mut self) -> &mut [Float] {
+        &mut self.data
+    }
+
+    #[inline]
+    fn mul(self, rhs: Self) -> Self {
+        self * rhs
+    }
+
+    #[inline]
+    fn mul_t(self, rhs: Float) -> Self {
+        self * rhs
+    }
+
+    #[inline]
+    fn add_assign(&mut self, rhs: Self) {
+        *self += rhs;
+    }
+}
+
+impl SpectralIndices for () {
+    type Sampled = Spectrum;
+
+    #[inline]
+    fn wavelengths(&self) -> Wavelengths<'_> {
+        Wavelengths::All
+    }
+
+    #[inline]
+    fn select(&self, spectrum: &Spectrum) -> Spectrum {
+        *spectrum
+    }
+}
+
+impl SampledSpectrum for [Float; PACKET_SIZE] {
+    #[inline]
+    fn splat(value: Float) -> Self {
+        [value; PACKET_SIZE]
+    }
+
+    #[inline]
+    fn as_slice(&self) -> &[Float] {
+        self
+    }
+
+    #[inline]
+    fn as_mut_slice(&mut self) -> &mut [Float] {
+        self
+    }
+}
+
+impl SpectralIndices for [usize; PACKET_SIZE] {
+    type Sampled = [Float; PACKET_SIZE];
+
+    #[inline]
+    fn wavelengths(&self) -> Wavelengths<'_> {
+        Wavelengths::Indices(self)
+    }
+}
+
+impl SampledSpectrum for Float {
+    #[inline]
+    fn splat(value: Float) -> Self {
+        value
+    }
+
+    #[inline]
+    fn as_slice(&self) -> &[Float] {
+        slice::from_ref(self)
+    }
+
+    #[inline]
+    fn as_mut_slice(&mut self) -> &mut [Float] {
+        slice::from_mut(self)
+    }
+}
+
+impl SpectralIndices for usize {
+    type Sampled = Float;
+
+    #[inline]
+    fn wavelengths(&self) -> Wavelengths<'_> {
+        Wavelengths::Indices(slice::from_ref(self))
+    }
+}
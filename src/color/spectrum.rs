@@ -1,6 +1,6 @@
 use crate::color::cie::{lambda_to_xyz_approx, CIE_Y_INTEGRAL};
-use crate::color::color_data::LAMBDA_RANGE;
-use crate::color::{ColorSerde, Srgb};
+use crate::color::color_data::{upsampling, LAMBDA_RANGE};
+use crate::color::{illuminant, ColorSerde, Srgb};
 use crate::Float;
 use core::convert::TryFrom;
 
@@ -17,6 +17,10 @@ impl TryFrom<ColorSerde> for Spectrum {
             ColorSerde::Color(c) => Self::from(c),
             ColorSerde::MulColor(mul, c) => mul * Self::from(c),
             ColorSerde::Constant(c) => Self::splat(c),
+            ColorSerde::Blackbody(kelvin) => illuminant::blackbody(kelvin).ok_or(())?,
+            ColorSerde::Planck(kelvin) => illuminant::planck(kelvin).ok_or(())?,
+            ColorSerde::Illuminant(i) => i.spectrum().ok_or(())?,
+            ColorSerde::MulIlluminant(mul, i) => mul * i.spectrum().ok_or(())?,
             _ => return Err(()),
         };
 
@@ -24,6 +28,44 @@ impl TryFrom<ColorSerde> for Spectrum {
     }
 }
 
+impl Spectrum {
+    /// Upsamples a linear sRGB color to a smooth spectrum (Smits, 1999).
+    ///
+    /// # Arguments
+    /// * `rgb` - The linear red, green and blue components (negative ones are clamped to `0`)
+    ///
+    /// # Returns
+    /// * A non-negative spectrum mapping back to `rgb`
+    pub fn from_linear_rgb(rgb: [Float; 3]) -> Self {
+        let [r, g, b] = rgb.map(|c| c.max(0.0));
+        let basis = |data: &[Float; 36], weight: Float| Self::new(*data) * weight;
+
+        // the smallest component is white, the middle one a secondary and the rest a primary
+        if r <= g && r <= b {
+            basis(&upsampling::WHITE, r)
+                + if g <= b {
+                    basis(&upsampling::CYAN, g - r) + basis(&upsampling::BLUE, b - g)
+                } else {
+                    basis(&upsampling::CYAN, b - r) + basis(&upsampling::GREEN, g - b)
+                }
+        } else if g <= r && g <= b {
+            basis(&upsampling::WHITE, g)
+                + if r <= b {
+                    basis(&upsampling::MAGENTA, r - g) + basis(&upsampling::BLUE, b - r)
+                } else {
+                    basis(&upsampling::MAGENTA, b - g) + basis(&upsampling::RED, r - b)
+                }
+        } else {
+            basis(&upsampling::WHITE, b)
+                + if r <= g {
+                    basis(&upsampling::YELLOW, r - b) + basis(&upsampling::GREEN, g - r)
+                } else {
+                    basis(&upsampling::YELLOW, g - b) + basis(&upsampling::RED, r - g)
+                }
+        }
+    }
+}
+
 impl From<Spectrum> for Srgb {
     fn from(spectrum: Spectrum) -> Self {
         Srgb::from(Xyz::from(spectrum))
@@ -43,3 +85,27 @@ impl From<Spectrum> for Xyz {
         xyz * SCALE
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn upsampling_round_trips() {
+        for rgb in [
+            [0.2, 0.5, 0.9],
+            [3.0, 0.1, 0.4],
+            [0.7, 0.7, 0.2],
+            [1.0, 1.0, 1.0],
+        ] {
+            let spectrum = Spectrum::from_linear_rgb(rgb);
+            assert!(spectrum.min_value() >= 0.0);
+
+            let xyz = Xyz::from(spectrum);
+            let expected = Xyz::from_linear_rgb(rgb);
+            for i in 0..3 {
+                assert!((xyz[i] - expected[i]).abs() < 1e-3, "{:?}: {:?}", rgb, xyz);
+            }
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use crate::color::xyz::compand;
 use crate::color::{ColorSerde, Spectrum, Xyz};
 use crate::{Float, Vec3};
 use cgmath::Matrix3;
@@ -18,6 +19,10 @@ impl TryFrom<ColorSerde> for Srgb {
             ColorSerde::Color(c) => Self::from(c),
             ColorSerde::MulColor(mul, c) => Self::from(c) * mul,
             ColorSerde::Constant(c) => Self::splat(c),
+            ColorSerde::Blackbody(_)
+            | ColorSerde::Planck(_)
+            | ColorSerde::Illuminant(_)
+            | ColorSerde::MulIlluminant(..) => Spectrum::try_from(value)?.into(),
         };
 
         Ok(srgb)
@@ -26,11 +31,25 @@ impl TryFrom<ColorSerde> for Srgb {
 
 #[rustfmt::skip]
 #[allow(clippy::excessive_precision)]
-const RGB_TO_XYZ: Matrix3<Float> = Matrix3::new(
+pub(crate) const RGB_TO_XYZ: Matrix3<Float> = Matrix3::new(
     0.4124564,0.2126729,0.0193339,
     0.3575761, 0.7151522, 0.1191920,
     0.1804375, 0.0721750, 0.9503041,
 );
+impl Srgb {
+    /// Compands a linear sRGB color.
+    ///
+    /// # Arguments
+    /// * `rgb` - The linear red, green and blue components
+    ///
+    /// # Returns
+    /// * The color, ranked by wavelength like the named colors (i.e. blue first)
+    pub fn from_linear_rgb(rgb: [Float; 3]) -> Self {
+        let [r, g, b] = rgb.map(|c| compand(c.max(0.0)));
+        Self::new([b, g, r])
+    }
+}
+
 impl From<Srgb> for Xyz {
     #[allow(clippy::excessive_precision)]
     fn from(srgb: Srgb) -> Self {
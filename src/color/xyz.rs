@@ -1,3 +1,4 @@
+use crate::color::srgb::RGB_TO_XYZ;
 use crate::color::{ColorSerde, Spectrum, Srgb};
 use crate::{Float, Vec3};
 use cgmath::Matrix3;
@@ -18,6 +19,10 @@ impl TryFrom<ColorSerde> for Xyz {
             ColorSerde::Color(c) => Self::from(c),
             ColorSerde::MulColor(mul, c) => Self::from(c) * mul,
             ColorSerde::Constant(c) => Self::splat(c),
+            ColorSerde::Blackbody(_)
+            | ColorSerde::Planck(_)
+            | ColorSerde::Illuminant(_)
+            | ColorSerde::MulIlluminant(..) => Spectrum::try_from(value)?.into(),
         };
 
         Ok(xyz)
@@ -31,6 +36,16 @@ const XYZ_TO_RGB: Matrix3<Float> = Matrix3::new(
     -1.5371388501025753, 1.8760109288424913, -0.20402585426769815,
     -0.498531546868481, 0.041556082346673524, 1.0572251624579287,
 );
+impl Xyz {
+    /// Converts a linear sRGB color.
+    ///
+    /// # Arguments
+    /// * `rgb` - The linear red, green and blue components
+    pub fn from_linear_rgb(rgb: [Float; 3]) -> Self {
+        Self::new(*(RGB_TO_XYZ * Vec3::from(rgb)).as_ref())
+    }
+}
+
 impl From<Xyz> for Srgb {
     #[allow(clippy::excessive_precision)]
     fn from(xyz: Xyz) -> Self {
@@ -43,7 +58,7 @@ impl From<Xyz> for Srgb {
 
 #[allow(clippy::excessive_precision)]
 #[inline]
-fn compand(val: Float) -> Float {
+pub(crate) fn compand(val: Float) -> Float {
     // https://entropymine.com/imageworsener/srgbformula/
     if val <= 0.00313066844250063 {
         val * 12.92
@@ -1,3 +1,4 @@
+use crate::aov::Aov;
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -5,4 +6,7 @@ pub struct Config {
     pub output: String,
     pub passes: usize,
     pub threads: Option<usize>,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub aovs: Vec<Aov>,
 }
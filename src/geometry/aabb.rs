@@ -1,4 +1,4 @@
-use crate::geometry::{Geometry, Intersection, Ray};
+use crate::geometry::{Geometry, Intersection, Patch, Ray};
 use crate::{Float, Vec3};
 use cgmath::{Bounded, ElementWise, InnerSpace};
 use serde::{Deserialize, Serialize};
@@ -14,11 +14,12 @@ impl Aabb {
     ///
     /// # Example
     /// ```rust
-    /// use rust_v2::geometry::{Aabb, Vec3};
+    /// use rust_v2::geometry::Aabb;
+    /// use rust_v2::Vec3;
     ///
-    /// let aabb = Aabb::new(Vec3::ZERO, Vec3::ONE);
-    /// assert_eq!(Vec3::ZERO, aabb.min);
-    /// assert_eq!(Vec3::ONE, aabb.max);
+    /// let aabb = Aabb::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(1.0, 1.0, 1.0));
+    /// assert_eq!(Vec3::new(0.0, 0.0, 0.0), aabb.min);
+    /// assert_eq!(Vec3::new(1.0, 1.0, 1.0), aabb.max);
     /// ```
     #[inline]
     pub const fn new(min: Vec3, max: Vec3) -> Self {
@@ -37,7 +38,8 @@ impl Aabb {
     ///
     /// # Example
     /// ```rust
-    /// use rust_v2::geometry::{Aabb, Vec3};
+    /// use rust_v2::geometry::Aabb;
+    /// use rust_v2::Vec3;
     /// use cgmath::Bounded;
     ///
     /// let aabb = Aabb::empty();
@@ -53,7 +55,8 @@ impl Aabb {
     ///
     /// # Example
     /// ```rust
-    /// use rust_v2::geometry::{Aabb, Vec3};
+    /// use rust_v2::geometry::Aabb;
+    /// use rust_v2::Vec3;
     /// use cgmath::Bounded;
     ///
     /// let aabb = Aabb::max();
@@ -184,4 +187,23 @@ impl Geometry for Aabb {
 
         t_min <= t_max && (ray.contains(t_min) || ray.contains(t_max))
     }
+
+    /// The six faces, facing outwards. Faces of a flat cube without any area are omitted.
+    fn patches(&self, _bounds: Aabb) -> Option<Vec<Patch>> {
+        let size = self.size();
+        let x = Vec3::new(size.x, 0.0, 0.0);
+        let y = Vec3::new(0.0, size.y, 0.0);
+        let z = Vec3::new(0.0, 0.0, size.z);
+
+        let faces = [
+            Patch::quad(self.min, z, y),
+            Patch::quad(self.min + x, y, z),
+            Patch::quad(self.min, x, z),
+            Patch::quad(self.min + y, z, x),
+            Patch::quad(self.min, y, x),
+            Patch::quad(self.min + z, x, y),
+        ];
+
+        Some(faces.iter().copied().filter(|p| p.area() > 0.0).collect())
+    }
 }
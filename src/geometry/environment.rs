@@ -0,0 +1,291 @@
+//! # Summary
+//! An environment map at an infinite distance, emitting the light of an image into the scene.
+//!
+//! # File format
+//! The image is an equirectangular (latitude-longitude) map of linear RGB values, read from an
+//! OpenEXR (`.exr`) or Radiance HDR (`.hdr`) file.
+//!
+//! # Placement
+//! The top row of the image lies towards `+y` and the bottom row towards `-y`.
+//! The center of the image lies towards `-z`, with `x` increasing to the right.
+//! The map is then rotated into the scene.
+
+use crate::color::SampledSpectrum;
+use crate::geometry::{Aabb, Geometry, Intersection, Ray};
+use crate::util::distribution::PiecewiseConstant2D;
+use crate::{Float, Rot3, Spectrum, Vec2, Vec3};
+use cgmath::{One, Rotation};
+use core::convert::TryFrom;
+use serde::{Deserialize, Serialize, Serializer};
+#[cfg(not(feature = "f64"))]
+use std::f32::consts::{PI, TAU};
+#[cfg(feature = "f64")]
+use std::f64::consts::{PI, TAU};
+
+/// An equirectangular image of linear RGB radiance surrounding the scene.
+///
+/// The texels are constant over their area and sampled proportional to their luminance and the
+/// solid angle they cover.
+#[derive(Deserialize)]
+#[serde(try_from = "EnvironmentSerde")]
+pub struct Environment {
+    width: usize,
+    height: usize,
+    /// The linear RGB texels, rows first and starting at the top
+    texels: Vec<[Float; 3]>,
+    rotation: Rot3,
+    rotation_inv: Rot3,
+    /// The distribution over the image, with `x` to the right and `y` downwards
+    pub(crate) distribution: PiecewiseConstant2D,
+    /// The average radiance integrated over all directions
+    irradiance: Float,
+}
+
+impl Environment {
+    /// Creates a new environment map.
+    ///
+    /// # Arguments
+    /// * `width` - The number of texels per row
+    /// * `height` - The number of rows
+    /// * `texels` - The linear RGB texels, rows first and starting at the top
+    /// * `rotation` - The rotation of the map into the scene
+    pub fn new(
+        width: usize,
+        height: usize,
+        texels: Vec<[Float; 3]>,
+        rotation: Rot3,
+    ) -> Result<Self, String> {
+        if width == 0 || height == 0 {
+            return Err(format!("Invalid environment resolution: {width}x{height}"));
+        }
+        if texels.len() != width * height {
+            return Err(format!(
+                "Expected {} texels for an environment of {width}x{height}, got {}",
+                width * height,
+                texels.len()
+            ));
+        }
+        if texels.iter().flatten().any(|c| !c.is_finite()) {
+            return Err("Environment texels must be finite".into());
+        }
+
+        let mut weights = Vec::with_capacity(texels.len());
+        let mut irradiance = 0.0;
+        for (row, texels) in texels.chunks_exact(width).enumerate() {
+            let sin_theta = (PI * (row as Float + 0.5) / height as Float).sin();
+            let solid_angle = equirectangular_solid_angle(row, width, height);
+
+            for &[r, g, b] in texels {
+                weights.push((0.2126 * r + 0.7152 * g + 0.0722 * b) * sin_theta);
+                irradiance += Spectrum::from_linear_rgb([r, g, b]).average() * solid_angle;
+            }
+        }
+
+        Ok(Self {
+            width,
+            height,
+            texels,
+            rotation,
+            rotation_inv: rotation.invert(),
+            distribution: PiecewiseConstant2D::new(&weights, width, height),
+            irradiance,
+        })
+    }
+
+    /// Loads the texels of an environment map from an `.exr` or `.hdr` file.
+    ///
+    /// # Arguments
+    /// * `path` - The path of the image file
+    ///
+    /// # Returns
+    /// * The width, height and linear RGB texels of the image
+    #[allow(clippy::type_complexity, clippy::unnecessary_cast)]
+    pub fn load(path: &str) -> Result<(usize, usize, Vec<[Float; 3]>), String> {
+        match path.rsplit_once('.') {
+            Some((_, "exr" | "hdr")) => {}
+            Some((_, ending)) => return Err(format!("Unknown file ending: {ending}")),
+            None => return Err(format!("Unknown file type: {path}")),
+        }
+
+        let image = image::open(path)
+            .map_err(|e| format!("Cannot load environment {path}: {e}"))?
+            .into_rgb32f();
+        let texels = image
+            .pixels()
+            .map(|p| [p[0] as Float, p[1] as Float, p[2] as Float])
+            .collect();
+
+        Ok((image.width() as usize, image.height() as usize, texels))
+    }
+
+    /// Returns the radiance integrated over all directions, averaged over the wavelengths.
+    #[inline]
+    pub fn irradiance(&self) -> Float {
+        self.irradiance
+    }
+
+    /// Maps a direction in the scene to its point on the image.
+    ///
+    /// # Constraints
+    /// * `direction` - Should be normalized.
+    ///
+    /// # Returns
+    /// * The point within `[0, 1)²`, with `x` to the right and `y` downwards
+    /// * The sine of the angle to `+y`
+    #[inline]
+    pub fn to_image(&self, direction: Vec3) -> (Vec2, Float) {
+        direction_to_equirectangular(self.rotation_inv.rotate_vector(direction))
+    }
+
+    /// Maps a point on the image to its direction in the scene.
+    ///
+    /// # Constraints
+    /// * `point` - All values should be within `[0, 1]`.
+    ///
+    /// # Returns
+    /// * The normalized direction
+    /// * The sine of the angle to `+y`
+    #[inline]
+    pub fn to_direction(&self, point: Vec2) -> (Vec3, Float) {
+        let (direction, sin_theta) = equirectangular_to_direction(point);
+
+        (self.rotation.rotate_vector(direction), sin_theta)
+    }
+
+    /// Looks up the radiance of the texel at a point on the image.
+    ///
+    /// # Constraints
+    /// * `point` - All values should be within `[0, 1)`.
+    pub fn lookup(&self, point: Vec2) -> Spectrum {
+        let x = ((point.x * self.width as Float) as usize).min(self.width - 1);
+        let y = ((point.y * self.height as Float) as usize).min(self.height - 1);
+
+        Spectrum::from_linear_rgb(self.texels[y * self.width + x])
+    }
+}
+
+/// Maps a direction to its point on an equirectangular image (see the [placement](self)).
+///
+/// # Constraints
+/// * `direction` - Should be normalized.
+///
+/// # Returns
+/// * The point within `[0, 1)²`, with `x` to the right and `y` downwards
+/// * The sine of the angle to `+y`
+pub fn direction_to_equirectangular(direction: Vec3) -> (Vec2, Float) {
+    let theta = direction.y.clamp(-1.0, 1.0).acos();
+    let phi = direction.x.atan2(-direction.z);
+
+    let clamp = |x: Float| x.clamp(0.0, 1.0 - Float::EPSILON);
+    let point = Vec2::new(clamp((phi + PI) / TAU), clamp(theta / PI));
+
+    (point, theta.sin())
+}
+
+/// Maps a point on an equirectangular image to its direction (see the [placement](self)).
+///
+/// # Constraints
+/// * `point` - All values should be within `[0, 1]`.
+///
+/// # Returns
+/// * The normalized direction
+/// * The sine of the angle to `+y`
+pub fn equirectangular_to_direction(point: Vec2) -> (Vec3, Float) {
+    let (sin_theta, cos_theta) = (PI * point.y).sin_cos();
+    let (sin_phi, cos_phi) = (TAU * point.x - PI).sin_cos();
+    let direction = Vec3::new(sin_theta * sin_phi, cos_theta, -sin_theta * cos_phi);
+
+    (direction, sin_theta)
+}
+
+/// The solid angle covered by a row of an equirectangular image, per texel.
+///
+/// # Arguments
+/// * `row` - The index of the row, starting at the top
+/// * `width` - The number of texels per row
+/// * `height` - The number of rows
+pub fn equirectangular_solid_angle(row: usize, width: usize, height: usize) -> Float {
+    let theta_start = PI * row as Float / height as Float;
+    let theta_end = PI * (row + 1) as Float / height as Float;
+
+    TAU / width as Float * (theta_start.cos() - theta_end.cos())
+}
+
+/// The map is infinitely far away, so it cannot be intersected.
+/// Rays escaping the scene see it instead (see [Sampleable](crate::scene::Sampleable)).
+#[typetag::serde]
+impl Geometry for Environment {
+    #[inline(always)]
+    fn contains(&self, _point: Vec3) -> Option<bool> {
+        None
+    }
+
+    #[inline]
+    fn bounds(&self) -> Aabb {
+        Aabb::max()
+    }
+
+    #[inline(always)]
+    fn intersect(&self, _ray: Ray) -> Option<Intersection> {
+        None
+    }
+
+    #[inline(always)]
+    fn intersects(&self, _ray: Ray) -> bool {
+        false
+    }
+}
+
+impl Serialize for Environment {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        EnvironmentSerde::Checkpoint(EnvironmentCheckpoint {
+            resolution: [self.width, self.height],
+            texels: self.texels.clone(),
+            rotation: self.rotation,
+        })
+        .serialize(serializer)
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+enum EnvironmentSerde {
+    Config(EnvironmentConfig),
+    Checkpoint(EnvironmentCheckpoint),
+}
+impl TryFrom<EnvironmentSerde> for Environment {
+    type Error = String;
+
+    fn try_from(serde: EnvironmentSerde) -> Result<Self, Self::Error> {
+        match serde {
+            EnvironmentSerde::Config(c) => {
+                let (width, height, texels) = Environment::load(&c.path)?;
+
+                Environment::new(width, height, texels, c.rotation.unwrap_or_else(Rot3::one))
+            }
+            EnvironmentSerde::Checkpoint(c) => {
+                let [width, height] = c.resolution;
+
+                Environment::new(width, height, c.texels, c.rotation)
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct EnvironmentConfig {
+    /// The path of the image file
+    path: String,
+    /// Optional rotation into the scene
+    #[serde(default)]
+    rotation: Option<Rot3>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct EnvironmentCheckpoint {
+    resolution: [usize; 2],
+    texels: Vec<[Float; 3]>,
+    rotation: Rot3,
+}
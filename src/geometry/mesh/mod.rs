@@ -1,8 +1,10 @@
 pub mod obj;
 
 use crate::geometry::bvh::Tree;
-use crate::geometry::{max3, min3, Aabb, Geometry, Intersection, Ray};
-use crate::{Float, Rot3, Vec3};
+use crate::geometry::{max3, min3, Aabb, Geometry, Intersection, Patch, Ray};
+use crate::util::distribution::PiecewiseConstant1D;
+use crate::util::mc::sample_unit_triangle;
+use crate::{Float, Rot3, Vec2, Vec3};
 use cgmath::{ElementWise, InnerSpace, Rotation, Zero};
 use core::convert::TryFrom;
 use obj::ObjFile;
@@ -46,6 +48,25 @@ impl Face {
         (v1 - v0).cross(v2 - v0)
     }
 
+    /// Computes the (normalized) normal at a point of this face, following the shading mode.
+    ///
+    /// # Arguments
+    /// * `mesh` - The mesh of this face
+    /// * `beta` - The barycentric coordinate of the second vertex
+    /// * `gamma` - The barycentric coordinate of the third vertex
+    pub fn normal_at(&self, mesh: &Mesh, beta: Float, gamma: Float) -> Vec3 {
+        match mesh.shading_mode {
+            ShadingMode::Flat => self.face_normal(&mesh.vertices),
+            ShadingMode::Phong => {
+                let (n0, n1, n2) = self.get_normals(&mesh.normals);
+                let alpha = 1.0 - beta - gamma;
+
+                alpha * n0 + beta * n1 + gamma * n2
+            }
+        }
+        .normalize()
+    }
+
     pub fn bounds(&self, vertices: &[Vec3]) -> Aabb {
         let (v0, v1, v2) = self.get_vertices(vertices);
         Aabb::new(min3(v0, v1, v2), max3(v0, v1, v2))
@@ -88,17 +109,7 @@ impl Face {
         }
 
         let point = ray.at(t);
-
-        let normal = match mesh.shading_mode {
-            ShadingMode::Flat => edge1.cross(edge2),
-            ShadingMode::Phong => {
-                let (n0, n1, n2) = self.get_normals(&mesh.normals);
-                let alpha = 1.0 - beta - gamma;
-
-                alpha * n0 + beta * n1 + gamma * n2
-            }
-        }
-        .normalize();
+        let normal = self.normal_at(mesh, beta, gamma);
 
         Some(Intersection::new(point, normal, ray.direction, t))
     }
@@ -210,20 +221,7 @@ impl Face {
         }
 
         let point = ray.at(t);
-
-        let normal = match mesh.shading_mode {
-            ShadingMode::Flat => (v1 - v0).cross(v2 - v0),
-            ShadingMode::Phong => {
-                let (n0, n1, n2) = self.get_normals(&mesh.normals);
-
-                let beta = u * inv_det;
-                let gamma = v * inv_det;
-                let alpha = 1.0 - beta - gamma;
-
-                alpha * n0 + beta * n1 + gamma * n2
-            }
-        }
-        .normalize();
+        let normal = self.normal_at(mesh, u * inv_det, v * inv_det);
 
         Some(Intersection::new(point, normal, ray.direction, t))
     }
@@ -315,6 +313,8 @@ pub struct Mesh {
     faces: Vec<Face>,
     shading_mode: ShadingMode,
     bvh: Tree,
+    /// The distribution of the faces by their area
+    areas: PiecewiseConstant1D,
 }
 
 impl Mesh {
@@ -330,6 +330,7 @@ impl Mesh {
             faces,
             shading_mode,
             bvh: Default::default(),
+            areas: Default::default(),
         }
     }
 
@@ -417,45 +418,134 @@ impl Mesh {
             log::info!(target: "Mesh", "computed normals!");
         }
 
+        let areas: Vec<Float> = self
+            .faces
+            .iter()
+            .map(|f| 0.5 * f.face_normal(&self.vertices).magnitude())
+            .collect();
+        if !areas.is_empty() {
+            self.areas = PiecewiseConstant1D::new(&areas);
+        }
+
         log::info!(target: "Mesh", "computing BVH (might take a while)...");
         let values: Vec<u32> = (0..self.faces.len() as u32).collect();
         self.bvh = Tree::new(&values, |i| self.faces[i as usize].bounds(&self.vertices));
         log::info!(target: "Mesh", "computed BVH!");
         self
     }
-}
 
-#[typetag::serde]
-impl Geometry for Mesh {
-    #[inline(always)]
-    fn contains(&self, _point: Vec3) -> Option<bool> {
-        None
+    /// Returns the positions of the vertices.
+    #[inline]
+    pub fn vertices(&self) -> &[Vec3] {
+        &self.vertices
     }
 
+    /// Returns the surface area of all faces.
     #[inline]
-    fn bounds(&self) -> Aabb {
-        self.bvh.bounds()
+    pub fn surface_area(&self) -> Float {
+        self.areas.integral() * self.faces.len() as Float
     }
 
-    fn intersect(&self, mut ray: Ray) -> Option<Intersection> {
+    /// Intersects the mesh with a ray.
+    ///
+    /// # Returns
+    /// * The closest intersection
+    /// * The face it lies on
+    pub fn intersect_face(&self, mut ray: Ray) -> Option<(Intersection, &Face)> {
         let mut intersection = None;
 
         for face_hit in self.bvh.intersect(ray) {
-            if let Some(i) = self.faces[face_hit as usize].intersect(self, ray) {
+            let face = &self.faces[face_hit as usize];
+            if let Some(i) = face.intersect(self, ray) {
                 ray.t_end = i.t;
-                intersection = Some(i);
+                intersection = Some((i, face));
             }
         }
 
         intersection
     }
 
+    /// Chooses a face proportional to its area.
+    ///
+    /// # Constraints
+    /// * `sample` - Should be within `[0, 1)`.
+    ///
+    /// # Returns
+    /// * The face
+    /// * The sample remapped to `[0, 1)` within the face, so that it can be reused
+    pub fn choose_face(&self, sample: Float) -> Option<(&Face, Float)> {
+        if self.areas.is_empty() {
+            return None;
+        }
+
+        let (index, _, remapped) = self.areas.sample_discrete(sample);
+
+        Some((&self.faces[index], remapped))
+    }
+
+    /// Returns the probability of [Self::choose_face] choosing a face.
+    #[inline]
+    pub fn face_probability(&self, face: &Face) -> Float {
+        0.5 * face.face_normal(&self.vertices).magnitude() / self.surface_area()
+    }
+
+    /// Samples a point uniformly on the surface.
+    ///
+    /// # Constraints
+    /// * `sample` - All values should be within `[0, 1)`.
+    ///
+    /// # Returns
+    /// * The point
+    /// * The face it lies on
+    /// * The barycentric coordinates of the second and third vertex of the face
+    pub fn sample_face(&self, sample: Vec2) -> Option<(Vec3, &Face, Vec2)> {
+        let (face, remapped) = self.choose_face(sample.x)?;
+        let barycentric = sample_unit_triangle(Vec2::new(remapped, sample.y));
+
+        let (v0, v1, v2) = face.get_vertices(&self.vertices);
+        let point = v0 + barycentric.x * (v1 - v0) + barycentric.y * (v2 - v0);
+
+        Some((point, face, barycentric))
+    }
+}
+
+#[typetag::serde]
+impl Geometry for Mesh {
+    #[inline(always)]
+    fn contains(&self, _point: Vec3) -> Option<bool> {
+        None
+    }
+
+    #[inline]
+    fn bounds(&self) -> Aabb {
+        self.bvh.bounds()
+    }
+
+    #[inline]
+    fn intersect(&self, ray: Ray) -> Option<Intersection> {
+        self.intersect_face(ray).map(|(i, _)| i)
+    }
+
     fn intersects(&self, ray: Ray) -> bool {
         self.bvh
             .intersect(ray)
             .iter()
             .any(|&i| self.faces[i as usize].intersects(self, ray))
     }
+
+    /// A triangle per face, facing like the flat normal.
+    fn patches(&self, _bounds: Aabb) -> Option<Vec<Patch>> {
+        let patches = self
+            .faces
+            .iter()
+            .map(|f| {
+                let (v0, v1, v2) = f.get_vertices(&self.vertices);
+                Patch::triangle(v0, v1 - v0, v2 - v0)
+            })
+            .collect();
+
+        Some(patches)
+    }
 }
 
 impl Serialize for Mesh {
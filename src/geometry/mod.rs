@@ -1,11 +1,16 @@
 pub use aabb::*;
 pub use bubble::*;
 pub use disk::*;
+pub use environment::*;
 pub use mesh::*;
+pub use patch::*;
 pub use plane::*;
 pub use point::*;
 pub use ray::*;
+pub use sky::*;
 pub use sphere::*;
+pub use spot::*;
+pub use sun::*;
 
 use crate::util::floats::BIG_EPSILON;
 use crate::{Float, Vec3};
@@ -15,11 +20,16 @@ pub mod aabb;
 pub mod bubble;
 pub mod bvh;
 pub mod disk;
+pub mod environment;
 pub mod mesh;
+pub mod patch;
 pub mod plane;
 pub mod point;
 pub mod ray;
+pub mod sky;
 pub mod sphere;
+pub mod spot;
+pub mod sun;
 
 /// The unit vectors in all directions.
 #[rustfmt::skip]
@@ -79,9 +89,9 @@ pub fn abs(v: Vec3) -> Vec3 {
 /// # Constraints
 /// * `point`: ALl values should be finite (neither infinite nor `NaN`).
 /// * `normal`: All values should be finite.
-///              Should be normalized.
+///   Should be normalized.
 /// * `direction`: Should be finite.
-///                 (Does not need to be normalized.)
+///   (Does not need to be normalized.)
 ///
 /// # Arguments
 /// * `point`: The starting point
@@ -110,9 +120,9 @@ pub fn offset_point(point: Vec3, normal: Vec3, direction: Vec3) -> Vec3 {
 /// # Constraints
 /// * `point`: ALl values should be finite (neither infinite nor `NaN`).
 /// * `normal`: All values should be finite.
-///              Should be normalized.
+///   Should be normalized.
 /// * `direction`: Should be finite.
-///                 Should be normalized.
+///   Should be normalized.
 ///
 /// # Arguments
 /// * `point`: The starting point
@@ -138,7 +148,7 @@ pub fn offset_ray_towards(point: Vec3, normal: Vec3, direction: Vec3) -> Ray {
 /// # Constraints
 /// * `point`: ALl values should be finite (neither infinite nor `NaN`).
 /// * `normal`: All values should be finite.
-///              Should be normalized.
+///   Should be normalized.
 /// * `target`: Should be finite.
 ///
 /// # Arguments
@@ -299,11 +309,11 @@ impl CoordinateSystem {
     ///
     /// # Constraints
     /// * `x`: All values must be finite (neither infinite nor `NaN`).
-    ///          Should be normalized.
+    ///   Should be normalized.
     /// * `y`: All values must be finite.
-    ///          Should be normalized.
+    ///   Should be normalized.
     /// * `z`: All values must be finite.
-    ///          Should be normalized.
+    ///   Should be normalized.
     ///
     /// # Arguments
     /// * `x`: The first vector
@@ -321,7 +331,7 @@ impl CoordinateSystem {
     ///
     /// # Constraints
     /// * `x_axis`: All values must be finite (neither infinite nor `NaN`).
-    ///              Should be normalized.
+    ///   Should be normalized.
     ///
     /// # Arguments
     /// * `x_axis`: The x direction vector
@@ -341,7 +351,7 @@ impl CoordinateSystem {
     ///
     /// # Constraints
     /// * `y_axis`: All values must be finite (neither infinite nor `NaN`).
-    ///              Should be normalized.
+    ///   Should be normalized.
     ///
     /// # Arguments
     /// * `y_axis`: The y direction vector
@@ -361,7 +371,7 @@ impl CoordinateSystem {
     ///
     /// # Constraints
     /// * `z_axis`: All values must be finite (neither infinite nor `NaN`).
-    ///              Should be normalized.
+    ///   Should be normalized.
     ///
     /// # Arguments
     /// * `z_axis`: The z direction vector
@@ -439,4 +449,28 @@ pub trait Geometry: Send + Sync {
     fn intersects(&self, ray: Ray) -> bool {
         self.intersect(ray).is_some()
     }
+
+    /// Discretizes the surface into planar patches.
+    ///
+    /// # Arguments
+    /// - `bounds`: the bounds to clip infinite surfaces to
+    ///
+    /// # Returns
+    /// - `None`: no implementation for this discretization
+    /// - `Some`: the (coarse) patches covering the surface
+    fn patches(&self, _bounds: Aabb) -> Option<Vec<Patch>> {
+        None
+    }
+
+    /// Projects a point onto the closest point of the surface.
+    ///
+    /// # Arguments
+    /// - `point`: a point near the surface
+    ///
+    /// # Returns
+    /// - `None`: no implementation for this projection
+    /// - `Some`: the projected point and the surface normal there
+    fn project(&self, _point: Vec3) -> Option<(Vec3, Vec3)> {
+        None
+    }
 }
@@ -0,0 +1,238 @@
+use crate::geometry::{max3, min3, Aabb, Ray};
+use crate::util::floats;
+use crate::{Float, Vec2, Vec3};
+use cgmath::InnerSpace;
+
+/// A planar patch of a surface, spanned by two edges from an origin.
+///
+/// Patches are either parallelograms (`origin + a * u + b * v` with `a, b` within `[0, 1]`) or
+/// triangles (additionally `a + b <= 1`).
+/// They are one-sided, the front side being in the direction of `u × v`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Patch {
+    pub origin: Vec3,
+    pub u: Vec3,
+    pub v: Vec3,
+    pub triangle: bool,
+}
+
+impl Patch {
+    #[inline]
+    pub const fn quad(origin: Vec3, u: Vec3, v: Vec3) -> Self {
+        Self {
+            origin,
+            u,
+            v,
+            triangle: false,
+        }
+    }
+
+    #[inline]
+    pub const fn triangle(origin: Vec3, u: Vec3, v: Vec3) -> Self {
+        Self {
+            origin,
+            u,
+            v,
+            triangle: true,
+        }
+    }
+
+    /// Returns the normal of the front side.
+    #[inline]
+    pub fn normal(&self) -> Vec3 {
+        self.u.cross(self.v).normalize()
+    }
+
+    #[inline]
+    pub fn area(&self) -> Float {
+        let area = self.u.cross(self.v).magnitude();
+
+        if self.triangle {
+            area / 2.0
+        } else {
+            area
+        }
+    }
+
+    #[inline]
+    pub fn center(&self) -> Vec3 {
+        if self.triangle {
+            self.origin + (self.u + self.v) / 3.0
+        } else {
+            self.origin + (self.u + self.v) / 2.0
+        }
+    }
+
+    pub fn bounds(&self) -> Aabb {
+        let a = self.origin + self.u;
+        let b = self.origin + self.v;
+
+        if self.triangle {
+            Aabb::new(min3(self.origin, a, b), max3(self.origin, a, b))
+        } else {
+            let c = a + self.v;
+            Aabb::new(min3(self.origin, a, b), max3(self.origin, a, b)).join2(c)
+        }
+    }
+
+    /// Samples a point on the patch uniformly.
+    ///
+    /// # Constraints
+    /// * `sample`: All values should be within `[0, 1)`.
+    pub fn sample(&self, sample: Vec2) -> Vec3 {
+        let (a, b) = if self.triangle && sample.x + sample.y > 1.0 {
+            (1.0 - sample.x, 1.0 - sample.y)
+        } else {
+            (sample.x, sample.y)
+        };
+
+        self.origin + a * self.u + b * self.v
+    }
+
+    /// Intersects a ray with this patch from both sides.
+    ///
+    /// # Returns
+    /// * The ray parameter and the coordinates `(a, b)` of the intersection along the edges
+    pub fn intersect(&self, ray: Ray) -> Option<(Float, Vec2)> {
+        let h = ray.direction.cross(self.v);
+        let det = self.u.dot(h);
+
+        // ray is parallel to the patch
+        if floats::approx_eq(det, 0.0) {
+            return None;
+        }
+
+        let f = 1.0 / det;
+        let s = ray.origin - self.origin;
+        let a = f * s.dot(h);
+        if !(0.0..=1.0).contains(&a) {
+            return None;
+        }
+
+        let q = s.cross(self.u);
+        let b = f * ray.direction.dot(q);
+        let limit = if self.triangle { 1.0 - a } else { 1.0 };
+        if !(0.0..=limit).contains(&b) {
+            return None;
+        }
+
+        let t = f * self.v.dot(q);
+        if !ray.contains(t) {
+            return None;
+        }
+
+        Some((t, Vec2::new(a, b)))
+    }
+
+    /// Computes the resolution of [Self::subdivide].
+    ///
+    /// # Arguments
+    /// * `size`: The maximum edge length of the smaller patches
+    ///
+    /// # Returns
+    /// * The number of cells along `u` and `v`
+    pub fn resolution(&self, size: Float) -> (usize, usize) {
+        let cells = |edge: Vec3| ((edge.magnitude() / size).ceil() as usize).max(1);
+
+        if self.triangle {
+            let n = cells(self.u).max(cells(self.v)).max(cells(self.v - self.u));
+            (n, n)
+        } else {
+            (cells(self.u), cells(self.v))
+        }
+    }
+
+    /// Subdivides this patch into a grid of smaller patches.
+    ///
+    /// Parallelograms are split into `nu × nv` cells, ordered by rows along `v`.
+    /// Triangles are split into `n²` triangles, ordered by rows along `v` and alternating
+    /// between upright and inverted triangles within a row.
+    ///
+    /// # Arguments
+    /// * `size`: The maximum edge length of the smaller patches
+    pub fn subdivide(&self, size: Float) -> Vec<Patch> {
+        let (nu, nv) = self.resolution(size);
+        let u = self.u / nu as Float;
+        let v = self.v / nv as Float;
+        let at = |i: usize, j: usize| self.origin + i as Float * u + j as Float * v;
+
+        let mut patches = Vec::new();
+        for j in 0..nv {
+            if self.triangle {
+                for i in 0..nu - j {
+                    patches.push(Patch::triangle(at(i, j), u, v));
+                    if i + j + 1 < nu {
+                        patches.push(Patch::triangle(at(i + 1, j + 1), -u, -v));
+                    }
+                }
+            } else {
+                for i in 0..nu {
+                    patches.push(Patch::quad(at(i, j), u, v));
+                }
+            }
+        }
+
+        patches
+    }
+
+    /// Computes the index of the cell of [Self::subdivide] containing the given coordinates.
+    ///
+    /// # Arguments
+    /// * `coordinates`: The coordinates `(a, b)` along the edges of this patch
+    /// * `resolution`: The resolution of the subdivision (see [Self::resolution])
+    pub fn cell(&self, coordinates: Vec2, resolution: (usize, usize)) -> usize {
+        let (nu, nv) = resolution;
+        let x = (coordinates.x * nu as Float).max(0.0);
+        let y = (coordinates.y * nv as Float).max(0.0);
+        let j = (y as usize).min(nv - 1);
+
+        if self.triangle {
+            let n = nu;
+            let i = (x as usize).min(n - 1 - j);
+            let inverted = i + j + 1 < n && (x - i as Float) + (y - j as Float) > 1.0;
+            let row = j * (2 * n - j);
+
+            row + 2 * i + inverted as usize
+        } else {
+            let i = (x as usize).min(nu - 1);
+
+            j * nu + i
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cells_match_subdivision() {
+        let patches = [
+            Patch::quad(
+                Vec3::new(0.0, 0.0, 0.0),
+                Vec3::unit_x() * 3.0,
+                Vec3::unit_z(),
+            ),
+            Patch::triangle(
+                Vec3::new(1.0, 2.0, 3.0),
+                Vec3::unit_x() * 2.0,
+                Vec3::unit_y(),
+            ),
+        ];
+
+        for patch in patches {
+            let resolution = patch.resolution(0.4);
+            let cells = patch.subdivide(0.4);
+
+            let area = cells.iter().map(Patch::area).sum::<Float>();
+            assert!((area - patch.area()).abs() < 1e-4);
+
+            for (index, cell) in cells.iter().enumerate() {
+                let ray = Ray::new(cell.center() + cell.normal(), -cell.normal());
+                let (_, coordinates) = patch.intersect(ray).unwrap();
+
+                assert_eq!(index, patch.cell(coordinates, resolution));
+            }
+        }
+    }
+}
@@ -1,6 +1,6 @@
-use crate::geometry::{Aabb, Geometry, Intersection, Ray};
+use crate::geometry::{Aabb, Geometry, Intersection, Patch, Ray};
 use crate::util::floats;
-use crate::Vec3;
+use crate::{Float, Vec3};
 use cgmath::InnerSpace;
 use serde::{Deserialize, Serialize};
 
@@ -54,4 +54,54 @@ impl Geometry for Plane {
         let t = delta.dot(self.normal) / denom;
         ray.contains(t)
     }
+
+    /// A single parallelogram covering the plane within the bounds.
+    fn patches(&self, bounds: Aabb) -> Option<Vec<Patch>> {
+        let size = bounds.size();
+        if !(size.x >= 0.0 && size.magnitude().is_finite()) {
+            return None;
+        }
+
+        let normal = self.normal.normalize();
+        let helper = if normal.x.abs() < 0.9 {
+            Vec3::unit_x()
+        } else {
+            Vec3::unit_y()
+        };
+        let tangent = normal.cross(helper).normalize();
+        let bitangent = normal.cross(tangent);
+
+        let mut min = (Float::INFINITY, Float::INFINITY);
+        let mut max = (Float::NEG_INFINITY, Float::NEG_INFINITY);
+        for i in 0..8 {
+            let corner = Vec3::new(
+                if i & 1 == 0 {
+                    bounds.min.x
+                } else {
+                    bounds.max.x
+                },
+                if i & 2 == 0 {
+                    bounds.min.y
+                } else {
+                    bounds.max.y
+                },
+                if i & 4 == 0 {
+                    bounds.min.z
+                } else {
+                    bounds.max.z
+                },
+            );
+            let delta = corner - self.point;
+            let (t, b) = (delta.dot(tangent), delta.dot(bitangent));
+
+            min = (min.0.min(t), min.1.min(b));
+            max = (max.0.max(t), max.1.max(b));
+        }
+
+        let origin = self.point + tangent * min.0 + bitangent * min.1;
+        let u = tangent * (max.0 - min.0);
+        let v = bitangent * (max.1 - min.1);
+
+        Some(vec![Patch::quad(origin, u, v)])
+    }
 }
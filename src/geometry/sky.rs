@@ -0,0 +1,320 @@
+//! # Summary
+//! The daylight sky by the analytic model of Preetham et al. (1999), at an infinite distance.
+//!
+//! The luminance and chromaticity of the sky follow the Perez distribution fitted to the sun
+//! position and turbidity.
+//! The spectral radiance is then reconstructed from the chromaticity with the CIE daylight series
+//! on every wavelength of a [Spectrum](color::Spectrum).
+//!
+//! # Units
+//! The sky emits its physical radiance, i.e. a luminance in `cd/m²` of several thousands.
+//! The sun itself is not part of the sky and can be added as a [Sun](crate::geometry::Sun).
+//!
+//! # Ground
+//! Below the horizon, the sky is replaced by a diffuse ground of some albedo, which reflects the
+//! sky (but not the sun).
+
+use crate::color;
+use crate::color::cie::{CIE_Y_INTEGRAL, MAX_LUMINOUS_EFFICACY};
+use crate::color::color_data::LAMBDA_RANGE;
+use crate::color::illuminant::{daylight_basis, daylight_weights};
+use crate::color::Xyz;
+use crate::geometry::{
+    equirectangular_solid_angle, equirectangular_to_direction, Aabb, Geometry, Intersection, Ray,
+};
+use crate::util::distribution::PiecewiseConstant2D;
+use crate::{Float, Vec2, Vec3};
+use cgmath::InnerSpace;
+use serde::{Deserialize, Serialize, Serializer};
+#[cfg(not(feature = "f64"))]
+use std::f32::consts::{FRAC_1_PI, PI};
+#[cfg(feature = "f64")]
+use std::f64::consts::{FRAC_1_PI, PI};
+
+/// The number of texels per row of the equirectangular grid the sky is sampled with.
+const WIDTH: usize = 128;
+/// The number of rows of the equirectangular grid the sky is sampled with.
+const HEIGHT: usize = 64;
+
+/// The luminance in `cd/m²` of a [Spectrum](color::Spectrum) per unit of its `Y` component.
+const LUMINANCE_PER_Y: Float = MAX_LUMINOUS_EFFICACY * CIE_Y_INTEGRAL / LAMBDA_RANGE;
+
+/// The range of turbidities the model is fitted for.
+const TURBIDITY: (Float, Float) = (1.7, 10.0);
+
+/// The coefficients `A` to `E` of the Perez distribution.
+type Perez = [Float; 5];
+
+/// An analytic daylight sky surrounding the scene, with a diffuse ground below the horizon.
+#[derive(Deserialize)]
+#[serde(from = "SkyConfig")]
+pub struct Sky {
+    /// The direction towards the sun
+    sun: Vec3,
+    turbidity: Float,
+    albedo: Float,
+    /// The Perez distributions of the luminance and both chromaticity coordinates
+    perez: [Perez; 3],
+    /// The luminance (in `cd/m²`) and chromaticity at the zenith, divided by their Perez
+    /// distribution there
+    zenith: [Float; 3],
+    /// The CIE daylight series and their `Y` components
+    basis: [color::Spectrum; 3],
+    basis_y: [Float; 3],
+    /// The radiance of the ground
+    ground: color::Spectrum,
+    /// The distribution over the equirectangular grid, with `x` to the right and `y` downwards
+    pub(crate) distribution: PiecewiseConstant2D,
+    /// The average radiance integrated over all directions
+    irradiance: Float,
+}
+
+impl Sky {
+    /// Creates a new sky.
+    ///
+    /// # Constraints
+    /// * `sun` - Should not be zero.
+    ///   The model only holds for the sun above the horizon, so it is clamped to it.
+    /// * `turbidity` - Is clamped to `[1.7, 10]`, from a clear to a hazy sky.
+    /// * `albedo` - Should be within `[0, 1]`.
+    ///
+    /// # Arguments
+    /// * `sun` - The direction towards the sun
+    /// * `turbidity` - The turbidity of the atmosphere
+    /// * `albedo` - The albedo of the ground
+    pub fn new(sun: Vec3, turbidity: Float, albedo: Float) -> Self {
+        let sun = sun.normalize();
+        if sun.y < 0.0 {
+            log::warn!(target: "Sky", "the sun is below the horizon, clamping it to the horizon");
+        }
+        let theta_sun = sun.y.clamp(0.0, 1.0).acos();
+
+        let t = turbidity.clamp(TURBIDITY.0, TURBIDITY.1);
+        if t != turbidity {
+            log::warn!(target: "Sky", "clamping the turbidity {} to {}", turbidity, t);
+        }
+
+        let perez = [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+
+        let zenith = zenith(t, theta_sun);
+        let zenith = [0, 1, 2].map(|i| zenith[i] / perez_distribution(perez[i], 0.0, theta_sun));
+
+        let basis = daylight_basis();
+        let mut sky = Self {
+            sun,
+            turbidity,
+            albedo,
+            perez,
+            zenith,
+            basis,
+            basis_y: basis.map(|s| Xyz::from(s)[1]),
+            ground: color::Spectrum::splat(0.0),
+            distribution: PiecewiseConstant2D::default(),
+            irradiance: 0.0,
+        };
+
+        // tabulate the sky to integrate and sample it
+        let mut texels = Vec::with_capacity(WIDTH * HEIGHT);
+        let mut horizontal = color::Spectrum::splat(0.0);
+        for row in 0..HEIGHT {
+            let solid_angle = equirectangular_solid_angle(row, WIDTH, HEIGHT);
+
+            for column in 0..WIDTH {
+                let point = Vec2::new(
+                    (column as Float + 0.5) / WIDTH as Float,
+                    (row as Float + 0.5) / HEIGHT as Float,
+                );
+                let (direction, _) = equirectangular_to_direction(point);
+                let radiance = sky.sky(direction);
+
+                if direction.y > 0.0 {
+                    horizontal += radiance * (direction.y * solid_angle);
+                }
+                texels.push(radiance);
+            }
+        }
+
+        sky.ground = horizontal * (albedo.max(0.0) * FRAC_1_PI);
+
+        let mut weights = Vec::with_capacity(texels.len());
+        for (row, texels) in texels.chunks_exact_mut(WIDTH).enumerate() {
+            let sin_theta = (PI * (row as Float + 0.5) / HEIGHT as Float).sin();
+            let solid_angle = equirectangular_solid_angle(row, WIDTH, HEIGHT);
+            let below = row >= HEIGHT / 2;
+
+            for texel in texels {
+                if below {
+                    *texel = sky.ground;
+                }
+
+                weights.push(Xyz::from(*texel)[1] * sin_theta);
+                sky.irradiance +=
+                    texel.sum_values() / color::Spectrum::size() as Float * solid_angle;
+            }
+        }
+        sky.distribution = PiecewiseConstant2D::new(&weights, WIDTH, HEIGHT);
+
+        sky
+    }
+
+    /// Computes the spectral radiance arriving from a direction.
+    ///
+    /// # Constraints
+    /// * `direction` - Should be normalized.
+    pub fn radiance(&self, direction: Vec3) -> color::Spectrum {
+        if direction.y > 0.0 {
+            self.sky(direction)
+        } else {
+            self.ground
+        }
+    }
+
+    /// Computes the spectral radiance of the sky (above the horizon) arriving from a direction.
+    fn sky(&self, direction: Vec3) -> color::Spectrum {
+        let theta = direction.y.clamp(0.0, 1.0).acos();
+        let gamma = direction.dot(self.sun).clamp(-1.0, 1.0).acos();
+
+        let [luminance, x, y] =
+            [0, 1, 2].map(|i| self.zenith[i] * perez_distribution(self.perez[i], theta, gamma));
+
+        let (m1, m2) = daylight_weights(x, y);
+        let [s0, s1, s2] = self.basis;
+        let spectrum_y = self.basis_y[0] + m1 * self.basis_y[1] + m2 * self.basis_y[2];
+        if !(luminance > 0.0 && spectrum_y > 0.0) {
+            return color::Spectrum::splat(0.0);
+        }
+
+        (s0 + s1 * m1 + s2 * m2) * (luminance / (LUMINANCE_PER_Y * spectrum_y))
+    }
+
+    /// Returns the radiance integrated over all directions, averaged over the wavelengths.
+    #[inline]
+    pub fn irradiance(&self) -> Float {
+        self.irradiance
+    }
+}
+
+/// Evaluates the Perez distribution of the sky.
+///
+/// # Arguments
+/// * `coefficients` - The coefficients `A` to `E`
+/// * `theta` - The angle between the direction and the zenith
+/// * `gamma` - The angle between the direction and the sun
+fn perez_distribution(coefficients: Perez, theta: Float, gamma: Float) -> Float {
+    let [a, b, c, d, e] = coefficients;
+    let cos_gamma = gamma.cos();
+
+    (1.0 + a * (b / theta.cos().max(1e-3)).exp())
+        * (1.0 + c * (d * gamma).exp() + e * cos_gamma * cos_gamma)
+}
+
+/// Computes the luminance (in `cd/m²`) and chromaticity of the sky at the zenith.
+///
+/// # Arguments
+/// * `t` - The turbidity
+/// * `theta_sun` - The angle between the sun and the zenith
+fn zenith(t: Float, theta_sun: Float) -> [Float; 3] {
+    let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_sun);
+    let luminance = 1000.0 * ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192);
+
+    let theta = [theta_sun.powi(3), theta_sun.powi(2), theta_sun, 1.0];
+    let polynomial = |coefficients: [[Float; 4]; 3]| {
+        let [t2, t1, t0] = coefficients.map(|c| {
+            c.iter()
+                .zip(theta)
+                .map(|(c, theta)| c * theta)
+                .sum::<Float>()
+        });
+        t * t * t2 + t * t1 + t0
+    };
+
+    let x = polynomial([
+        [0.00166, -0.00375, 0.00209, 0.0],
+        [-0.02903, 0.06377, -0.03202, 0.00394],
+        [0.11693, -0.21196, 0.06052, 0.25886],
+    ]);
+    let y = polynomial([
+        [0.00275, -0.00610, 0.00317, 0.0],
+        [-0.04214, 0.08970, -0.04153, 0.00516],
+        [0.15346, -0.26756, 0.06670, 0.26688],
+    ]);
+
+    [luminance.max(0.0), x, y]
+}
+
+/// The sky is infinitely far away, so it cannot be intersected.
+/// Rays escaping the scene see it instead (see [Sampleable](crate::scene::Sampleable)).
+#[typetag::serde]
+impl Geometry for Sky {
+    #[inline(always)]
+    fn contains(&self, _point: Vec3) -> Option<bool> {
+        None
+    }
+
+    #[inline]
+    fn bounds(&self) -> Aabb {
+        Aabb::max()
+    }
+
+    #[inline(always)]
+    fn intersect(&self, _ray: Ray) -> Option<Intersection> {
+        None
+    }
+
+    #[inline(always)]
+    fn intersects(&self, _ray: Ray) -> bool {
+        false
+    }
+}
+
+impl Serialize for Sky {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        SkyConfig {
+            sun: self.sun,
+            turbidity: self.turbidity,
+            albedo: self.albedo,
+        }
+        .serialize(serializer)
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct SkyConfig {
+    /// The direction towards the sun
+    sun: Vec3,
+    /// The turbidity of the atmosphere within `[1.7, 10]`
+    turbidity: Float,
+    /// The albedo of the ground
+    #[serde(default)]
+    albedo: Float,
+}
+impl From<SkyConfig> for Sky {
+    fn from(c: SkyConfig) -> Self {
+        Sky::new(c.sun, c.turbidity, c.albedo)
+    }
+}
@@ -99,4 +99,14 @@ impl Geometry for Sphere {
             false
         }
     }
+
+    fn project(&self, point: Vec3) -> Option<(Vec3, Vec3)> {
+        let mut normal = (point - self.center).normalize();
+        let projected = self.center + normal * self.radius;
+        if self.inverse {
+            normal = -normal;
+        }
+
+        Some((projected, normal))
+    }
 }
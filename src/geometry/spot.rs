@@ -0,0 +1,58 @@
+use crate::geometry::{Aabb, Geometry, Intersection, Ray};
+use crate::{Float, Vec3};
+use serde::{Deserialize, Serialize};
+
+/// A point emitting into a cone, smoothly falling off from an inner to an outer angle.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Spot {
+    pub position: Vec3,
+    /// The axis of the cone
+    pub direction: Vec3,
+    /// The angle in degrees between the axis and the end of the full intensity
+    pub inner: Float,
+    /// The angle in degrees between the axis and the edge of the cone
+    pub outer: Float,
+}
+
+impl Spot {
+    pub const fn new(position: Vec3, direction: Vec3, inner: Float, outer: Float) -> Self {
+        Self {
+            position,
+            direction,
+            inner,
+            outer,
+        }
+    }
+
+    /// Returns the cosines of the inner and the outer angle.
+    #[inline]
+    pub fn cos_angles(&self) -> (Float, Float) {
+        let outer = self.outer.to_radians().cos();
+        let inner = self.inner.to_radians().cos().max(outer);
+
+        (inner, outer)
+    }
+}
+
+#[typetag::serde]
+impl Geometry for Spot {
+    #[inline(always)]
+    fn contains(&self, _point: Vec3) -> Option<bool> {
+        None
+    }
+
+    #[inline]
+    fn bounds(&self) -> Aabb {
+        Aabb::new(self.position, self.position)
+    }
+
+    #[inline(always)]
+    fn intersect(&self, _ray: Ray) -> Option<Intersection> {
+        None
+    }
+
+    #[inline(always)]
+    fn intersects(&self, _ray: Ray) -> bool {
+        false
+    }
+}
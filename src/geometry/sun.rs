@@ -0,0 +1,56 @@
+use crate::geometry::{Aabb, Geometry, Intersection, Ray};
+use crate::{Float, Vec3};
+use serde::{Deserialize, Serialize};
+
+/// A disk at an infinite distance (e.g. the sun), emitting parallel light into the scene.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Sun {
+    /// The direction towards the center of the disk
+    pub direction: Vec3,
+    /// The angular diameter of the disk in degrees (`0` for purely directional light)
+    #[serde(default)]
+    pub angle: Float,
+}
+
+impl Sun {
+    pub const fn new(direction: Vec3, angle: Float) -> Self {
+        Self { direction, angle }
+    }
+
+    /// Returns the cosine of the angular radius of the disk.
+    #[inline]
+    pub fn cos_radius(&self) -> Float {
+        (0.5 * self.angle.clamp(0.0, 180.0)).to_radians().cos()
+    }
+
+    /// Returns whether the disk covers no solid angle, so that it only emits into its direction.
+    #[inline]
+    pub fn is_directional(&self) -> bool {
+        self.angle <= 0.0
+    }
+}
+
+/// The disk is infinitely far away, so it cannot be intersected.
+/// Rays escaping the scene see it instead (see [Sampleable](crate::scene::Sampleable)).
+#[typetag::serde]
+impl Geometry for Sun {
+    #[inline(always)]
+    fn contains(&self, _point: Vec3) -> Option<bool> {
+        None
+    }
+
+    #[inline]
+    fn bounds(&self) -> Aabb {
+        Aabb::max()
+    }
+
+    #[inline(always)]
+    fn intersect(&self, _ray: Ray) -> Option<Intersection> {
+        None
+    }
+
+    #[inline(always)]
+    fn intersects(&self, _ray: Ray) -> bool {
+        false
+    }
+}
@@ -0,0 +1,978 @@
+use crate::bxdf::{BxDFFlag, BSDF};
+use crate::camera::sensor::{Pixel, SplatBuffer};
+use crate::camera::Camera;
+use crate::geometry::{offset_ray_towards, Ray};
+use crate::integrator::lpe::{Event, LightPathTracker, LightPaths};
+use crate::integrator::{sample_bsdf, Heuristic, Integrator};
+use crate::sampler::{FloatSampler, SpectralSampler};
+use crate::scene::{Emitter, OcclusionTester, Scene, SceneIntersection, SceneObject};
+use crate::util::PacketOps;
+use crate::{Float, Vec3, PACKET_SIZE};
+use cgmath::{InnerSpace, Zero};
+use serde::{Deserialize, Serialize};
+
+/// A bidirectional path tracer.
+///
+/// For every pixel sample, a camera subpath and a light subpath are traced.
+/// Every vertex of the camera subpath is then connected to every vertex of the light subpath and
+/// all resulting strategies are weighted by multiple importance sampling.
+///
+/// Light subpaths connected to the camera directly (`t = 1`) are splatted into the pixel they
+/// are seen in.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Bdpt {
+    max_depth: u32,
+    sampler: FloatSampler,
+    spectral_sampler: SpectralSampler,
+    #[serde(default)]
+    heuristic: Heuristic,
+    /// The expressions to separate the contributions of every strategy into
+    #[serde(default)]
+    #[serde(skip_serializing_if = "LightPaths::is_empty")]
+    light_paths: LightPaths,
+}
+
+#[derive(Clone)]
+enum VertexKind<'a> {
+    Camera,
+    Emitter(&'a Emitter),
+    Surface(SceneIntersection<'a>),
+}
+
+/// A vertex of a subpath.
+///
+/// All pdfs are stored with respect to the surface area of the vertex.
+#[derive(Clone)]
+struct Vertex<'a> {
+    kind: VertexKind<'a>,
+    point: Vec3,
+    /// The surface normal (zero for cameras and delta emitters)
+    normal: Vec3,
+    /// The direction towards the previous vertex of the subpath
+    outgoing: Vec3,
+    /// The throughput of the subpath up to this vertex
+    beta: [Float; PACKET_SIZE],
+    /// Whether the vertex was scattered specularly
+    delta: bool,
+    /// The event of scattering towards the next vertex of the subpath, if any
+    event: Option<Event>,
+    /// The pdf of generating this vertex by the subpath it belongs to
+    pdf_fwd: Float,
+    /// The pdf of generating this vertex by the opposite subpath
+    pdf_rev: Float,
+}
+
+impl<'a> Vertex<'a> {
+    fn camera(point: Vec3, beta: [Float; PACKET_SIZE]) -> Self {
+        Self {
+            kind: VertexKind::Camera,
+            point,
+            normal: Vec3::zero(),
+            outgoing: Vec3::zero(),
+            beta,
+            delta: false,
+            event: None,
+            pdf_fwd: 0.0,
+            pdf_rev: 0.0,
+        }
+    }
+
+    fn emitter(
+        emitter: &'a Emitter,
+        point: Vec3,
+        normal: Vec3,
+        beta: [Float; PACKET_SIZE],
+        pdf_fwd: Float,
+    ) -> Self {
+        Self {
+            kind: VertexKind::Emitter(emitter),
+            point,
+            normal,
+            outgoing: Vec3::zero(),
+            beta,
+            delta: false,
+            event: None,
+            pdf_fwd,
+            pdf_rev: 0.0,
+        }
+    }
+
+    fn surface(hit: SceneIntersection<'a>, beta: [Float; PACKET_SIZE], pdf_fwd: Float) -> Self {
+        Self {
+            point: hit.i.point,
+            normal: hit.i.normal,
+            outgoing: -hit.i.incoming,
+            kind: VertexKind::Surface(hit),
+            beta,
+            delta: false,
+            event: None,
+            pdf_fwd,
+            pdf_rev: 0.0,
+        }
+    }
+
+    #[inline]
+    fn on_surface(&self) -> bool {
+        !self.normal.is_zero()
+    }
+
+    /// Returns the emitter this vertex lies on, if any.
+    fn emitter_ref(&self) -> Option<&'a Emitter> {
+        match &self.kind {
+            VertexKind::Emitter(e) => Some(e),
+            VertexKind::Surface(hit) => match hit.object {
+                SceneObject::Emitter(e) => Some(e),
+                SceneObject::Receiver(_) => None,
+            },
+            VertexKind::Camera => None,
+        }
+    }
+
+    #[inline]
+    fn is_delta_emitter(&self) -> bool {
+        self.emitter_ref().is_some_and(Emitter::is_delta)
+    }
+
+    /// Returns whether this vertex lies on an infinite emitter.
+    /// Its point then only describes the direction from the previous vertex and its normal is the
+    /// direction of the emitted light.
+    #[inline]
+    fn is_infinite_emitter(&self) -> bool {
+        matches!(self.kind, VertexKind::Emitter(e) if e.is_infinite())
+    }
+
+    /// Creates an occlusion tester from this vertex to the next one.
+    fn occlusion(&self, next: &Vertex) -> OcclusionTester {
+        if next.is_infinite_emitter() {
+            OcclusionTester::towards(self.point, (next.point - self.point).normalize())
+        } else {
+            OcclusionTester::between(self.point, next.point)
+        }
+    }
+
+    fn bsdf(&self) -> Option<&'a BSDF> {
+        match &self.kind {
+            VertexKind::Surface(hit) => Some(hit.object.bsdf()),
+            _ => None,
+        }
+    }
+
+    /// Evaluates the BSDF of this vertex scattering light from the next vertex.
+    ///
+    /// # Returns
+    /// * The evaluated spectrum and the flag of the evaluated BxDF
+    fn f(
+        &self,
+        next: &Vertex,
+        sample: Float,
+        indices: &[usize; PACKET_SIZE],
+    ) -> ([Float; PACKET_SIZE], BxDFFlag) {
+        match self.bsdf() {
+            Some(bsdf) => {
+                let incident = (next.point - self.point).normalize();
+
+                bsdf.evaluate_with_flag(
+                    self.normal,
+                    incident,
+                    self.outgoing,
+                    sample,
+                    BxDFFlag::empty(),
+                    indices,
+                )
+            }
+            None => ([0.0; PACKET_SIZE], BxDFFlag::empty()),
+        }
+    }
+
+    /// Converts a pdf with respect to the solid angle of this vertex to a pdf with respect to the
+    /// area of the next vertex.
+    /// Infinite emitters have no area, so their pdfs stay with respect to the solid angle.
+    fn convert_density(&self, pdf: Float, next: &Vertex) -> Float {
+        if next.is_infinite_emitter() {
+            return pdf;
+        }
+
+        let direction = next.point - self.point;
+        let distance2 = direction.magnitude2();
+        if distance2 == 0.0 {
+            return 0.0;
+        }
+
+        let pdf = if next.on_surface() {
+            pdf * next.normal.dot(direction / distance2.sqrt()).abs()
+        } else {
+            pdf
+        };
+
+        pdf / distance2
+    }
+
+    /// Computes the pdf (with respect to the area) of this vertex sampling the next vertex.
+    fn pdf(&self, camera: &dyn Camera, prev: Option<&Vertex>, next: &Vertex) -> Float {
+        if let VertexKind::Emitter(_) = self.kind {
+            return self.pdf_light(next);
+        }
+
+        let incident = (next.point - self.point).normalize();
+
+        let pdf = match &self.kind {
+            VertexKind::Camera => camera.pdf(Ray::new(self.point, incident)).1,
+            VertexKind::Surface(hit) => match prev {
+                Some(prev) => {
+                    let outgoing = (prev.point - self.point).normalize();
+                    hit.object
+                        .bsdf()
+                        .pdf(self.normal, incident, outgoing, BxDFFlag::empty())
+                }
+                None => 0.0,
+            },
+            VertexKind::Emitter(_) => unreachable!(),
+        };
+
+        self.convert_density(pdf, next)
+    }
+
+    /// Computes the pdf (with respect to the area) of this emitter vertex emitting a ray towards
+    /// the next vertex.
+    fn pdf_light(&self, next: &Vertex) -> Float {
+        let emitter = match self.emitter_ref() {
+            Some(e) => e,
+            None => return 0.0,
+        };
+
+        let direction = (next.point - self.point).normalize();
+        let (pdf_point, pdf_direction) = emitter.pdf_ray(self.point, self.normal, direction);
+
+        if emitter.is_infinite() {
+            // the ray origin on the disk covering the scene determines the next vertex
+            let cos = if next.on_surface() {
+                next.normal.dot(direction).abs()
+            } else {
+                1.0
+            };
+            return pdf_point * cos;
+        }
+
+        self.convert_density(pdf_direction, next)
+    }
+
+    /// Computes the pdf (with respect to the area) of this emitter vertex being chosen as the
+    /// origin of a light subpath.
+    fn pdf_light_origin(&self, emitter_pdf: Float) -> Float {
+        match self.emitter_ref() {
+            Some(e) if e.is_infinite() => emitter_pdf * e.geometry.pdf_direction(self.normal),
+            Some(e) => emitter_pdf * e.geometry.pdf_area(self.point),
+            None => 0.0,
+        }
+    }
+}
+
+impl Bdpt {
+    /// Maps a pdf for the MIS weight according to the heuristic.
+    /// Delta distributions (with a pdf of `0`) are mapped to `1`.
+    #[inline]
+    fn remap(&self, pdf: Float) -> Float {
+        let pdf = if pdf == 0.0 { 1.0 } else { pdf };
+
+        match self.heuristic {
+            Heuristic::Balance => pdf,
+            Heuristic::Power => pdf * pdf,
+        }
+    }
+
+    /// Continues a subpath by tracing the ray and scattering on surfaces.
+    ///
+    /// # Constraints
+    /// * `path` - Should contain the starting vertex.
+    ///
+    /// # Arguments
+    /// * `scene` - The scene
+    /// * `ray` - The ray leaving the last vertex of the path
+    /// * `beta` - The throughput of the path, including the sampled ray
+    /// * `pdf` - The pdf (with respect to the solid angle) of the sampled ray
+    /// * `max_vertices` - The maximum number of vertices to add
+    /// * `indices` - The spectral indices
+    /// * `path` - The path to append to
+    ///
+    /// # Returns
+    /// * The ray escaping the scene with its throughput and pdf, if any
+    #[allow(clippy::too_many_arguments)]
+    fn random_walk<'a>(
+        &self,
+        scene: &'a Scene,
+        mut ray: Ray,
+        mut beta: [Float; PACKET_SIZE],
+        mut pdf: Float,
+        max_vertices: u32,
+        indices: &[usize; PACKET_SIZE],
+        path: &mut Vec<Vertex<'a>>,
+    ) -> Option<(Ray, [Float; PACKET_SIZE], Float)> {
+        for _ in 0..max_vertices {
+            if beta.is_black() {
+                break;
+            }
+
+            let hit = match scene.intersect(ray) {
+                Some(hit) => hit,
+                None => return Some((ray, beta, pdf)),
+            };
+
+            let prev = path.len() - 1;
+            let bsdf = hit.object.bsdf();
+            let mut vertex = Vertex::surface(hit, beta, 0.0);
+            vertex.pdf_fwd = path[prev].convert_density(pdf, &vertex);
+
+            let sample = match sample_bsdf(
+                bsdf,
+                vertex.normal,
+                vertex.outgoing,
+                self.sampler,
+                indices,
+                &mut beta,
+            ) {
+                Some(s) if s.pdf > 0.0 && !s.spectrum.is_black() => s,
+                _ => {
+                    path.push(vertex);
+                    break;
+                }
+            };
+
+            vertex.event = Some(Event::from(sample.flag));
+            let pdf_rev = if sample.flag.specular() {
+                vertex.delta = true;
+                pdf = 0.0;
+                0.0
+            } else {
+                pdf = bsdf.pdf(
+                    vertex.normal,
+                    sample.incident,
+                    vertex.outgoing,
+                    BxDFFlag::empty(),
+                );
+                bsdf.pdf(
+                    vertex.normal,
+                    vertex.outgoing,
+                    sample.incident,
+                    BxDFFlag::empty(),
+                )
+            };
+
+            let cos_abs = if sample.flag.specular() {
+                // division of cosine omitted in specular bxdfs
+                1.0
+            } else {
+                sample.incident.dot(vertex.normal).abs()
+            };
+
+            beta.mul_assign(sample.spectrum.mul_t(cos_abs / sample.pdf));
+
+            path[prev].pdf_rev = vertex.convert_density(pdf_rev, &path[prev]);
+            ray = offset_ray_towards(vertex.point, vertex.normal, sample.incident);
+            path.push(vertex);
+        }
+
+        None
+    }
+
+    /// Traces the camera subpath.
+    ///
+    /// # Returns
+    /// * The subpath
+    /// * The ray escaping the scene with its throughput and pdf, if any
+    #[allow(clippy::type_complexity)]
+    fn camera_subpath<'a>(
+        &self,
+        scene: &'a Scene,
+        camera: &dyn Camera,
+        primary_ray: Ray,
+        indices: &[usize; PACKET_SIZE],
+    ) -> (Vec<Vertex<'a>>, Option<(Ray, [Float; PACKET_SIZE], Float)>) {
+        let mut path = Vec::with_capacity(self.max_depth as usize + 2);
+
+        let (_, pdf) = camera.pdf(primary_ray);
+        let beta = [1.0; PACKET_SIZE];
+        path.push(Vertex::camera(primary_ray.origin, beta));
+
+        let escaped = self.random_walk(
+            scene,
+            primary_ray,
+            beta,
+            pdf,
+            self.max_depth + 1,
+            indices,
+            &mut path,
+        );
+
+        (path, escaped)
+    }
+
+    fn light_subpath<'a>(
+        &self,
+        scene: &'a Scene,
+        emitter_pdf: Float,
+        indices: &[usize; PACKET_SIZE],
+    ) -> Vec<Vertex<'a>> {
+        let mut path = Vec::with_capacity(self.max_depth as usize + 1);
+
+        let emitter = match self.choose_emitter(scene) {
+            Some(e) => e,
+            None => return path,
+        };
+
+        let emitter_ray = emitter.sample_ray(self.sampler.vec2(), self.sampler.vec2());
+        if emitter_ray.pdf_point == 0.0 || emitter_ray.pdf_direction == 0.0 {
+            return path;
+        }
+
+        let radiance =
+            emitter.radiance_towards(indices, emitter_ray.normal, emitter_ray.ray.direction);
+        if radiance.is_black() {
+            return path;
+        }
+
+        let pdf_fwd = emitter_pdf * emitter_ray.pdf_point;
+        let vertex = Vertex::emitter(
+            emitter,
+            emitter_ray.ray.origin,
+            emitter_ray.normal,
+            radiance.div_t(pdf_fwd),
+            pdf_fwd,
+        );
+
+        let cos_abs = if vertex.on_surface() {
+            vertex.normal.dot(emitter_ray.ray.direction).abs()
+        } else {
+            1.0
+        };
+        let beta = vertex.beta.mul_t(cos_abs / emitter_ray.pdf_direction);
+        let ray = offset_ray_towards(vertex.point, vertex.normal, emitter_ray.ray.direction);
+        path.push(vertex);
+
+        self.random_walk(
+            scene,
+            ray,
+            beta,
+            emitter_ray.pdf_direction,
+            self.max_depth,
+            indices,
+            &mut path,
+        );
+
+        if emitter.is_infinite() {
+            // infinite emitters choose the direction first and the position on the disk second
+            path[0].pdf_fwd = emitter_pdf * emitter_ray.pdf_direction;
+            if let [origin, first, ..] = path.as_mut_slice() {
+                first.pdf_fwd = origin.pdf_light(first);
+            }
+        }
+
+        path
+    }
+
+    fn choose_emitter<'a>(&self, scene: &'a Scene) -> Option<&'a Emitter> {
+        let num = scene.num_emitters();
+        if num == 0 {
+            return None;
+        }
+
+        let chosen = ((self.sampler.float() * num as Float) as usize).min(num - 1);
+        scene.get_emitter(scene.emitters()[chosen] as usize)
+    }
+
+    /// Samples a point on a random emitter as seen from the given vertex (`s = 1`).
+    fn sample_emitter<'a>(
+        &self,
+        scene: &'a Scene,
+        emitter_pdf: Float,
+        pt: &Vertex,
+        indices: &[usize; PACKET_SIZE],
+    ) -> Option<Vertex<'a>> {
+        let emitter = self.choose_emitter(scene)?;
+
+        let surface_sample = emitter
+            .geometry
+            .sample_surface(pt.point, self.sampler.vec2());
+        if surface_sample.pdf == 0.0 {
+            return None;
+        }
+
+        let (point, towards) = if emitter.is_infinite() {
+            // the vertex only describes the direction towards the emitter
+            (pt.point - surface_sample.normal, surface_sample.normal)
+        } else {
+            let towards = (pt.point - surface_sample.point).normalize();
+            (surface_sample.point, towards)
+        };
+
+        let mut radiance = emitter
+            .radiance_towards(indices, surface_sample.normal, towards)
+            .div_t(surface_sample.pdf * emitter_pdf);
+        if emitter.is_delta() && emitter.decay && !emitter.is_infinite() {
+            radiance.mul_assign_t(1.0 / (surface_sample.point - pt.point).magnitude2());
+        }
+
+        let normal = if emitter.is_delta() && !emitter.is_infinite() {
+            Vec3::zero()
+        } else {
+            surface_sample.normal
+        };
+
+        let mut vertex = Vertex::emitter(emitter, point, normal, radiance, 0.0);
+        vertex.pdf_fwd = vertex.pdf_light_origin(emitter_pdf);
+
+        Some(vertex)
+    }
+
+    /// Tracks the events of a strategy through the light paths.
+    ///
+    /// # Arguments
+    /// * `tracker` - The tracker of the pixel sample
+    /// * `camera_vertices` - The camera subpath between the camera and the connection
+    /// * `connection` - The events at the connected vertices, from the camera towards the light
+    /// * `light_vertices` - The light subpath between the emitter and the connection
+    /// * `contribution` - The weighted contribution of the strategy
+    ///
+    /// # Returns
+    /// * The tracker of the strategy
+    fn track<'a>(
+        tracker: &LightPathTracker<'a, [usize; PACKET_SIZE]>,
+        camera_vertices: &[Vertex],
+        connection: &[Option<Event>],
+        light_vertices: &[Vertex],
+        contribution: [Float; PACKET_SIZE],
+    ) -> LightPathTracker<'a, [usize; PACKET_SIZE]> {
+        let mut strategy = tracker.branch(*tracker.indices());
+
+        let events = camera_vertices
+            .iter()
+            .filter_map(|v| v.event)
+            .chain(connection.iter().flatten().copied())
+            .chain(light_vertices.iter().rev().filter_map(|v| v.event));
+        for event in events {
+            strategy.scatter(event);
+        }
+        strategy.add_emitted(contribution);
+
+        strategy
+    }
+
+    /// Connects the first `s` vertices of the light subpath with the first `t` vertices of the
+    /// camera subpath.
+    ///
+    /// Connections to the camera (`t = 1`) are splatted, as they belong to another pixel.
+    ///
+    /// # Returns
+    /// * The weighted contribution of the strategy to the pixel
+    #[allow(clippy::too_many_arguments)]
+    fn connect(
+        &self,
+        scene: &Scene,
+        camera: &dyn Camera,
+        light_path: &[Vertex],
+        camera_path: &[Vertex],
+        s: usize,
+        t: usize,
+        emitter_pdf: Float,
+        indices: &[usize; PACKET_SIZE],
+        splats: &SplatBuffer,
+        tracker: &mut LightPathTracker<[usize; PACKET_SIZE]>,
+    ) -> [Float; PACKET_SIZE] {
+        if t == 1 {
+            self.connect_camera(
+                scene,
+                camera,
+                light_path,
+                s,
+                emitter_pdf,
+                indices,
+                splats,
+                tracker,
+            );
+            return [0.0; PACKET_SIZE];
+        }
+
+        let pt = &camera_path[t - 1];
+        let mut sampled = None;
+        let mut connection = [None; 2];
+
+        let contribution = if s == 0 {
+            match pt.emitter_ref() {
+                Some(e) => pt
+                    .beta
+                    .mul(e.radiance_towards(indices, pt.normal, pt.outgoing)),
+                None => return [0.0; PACKET_SIZE],
+            }
+        } else if s == 1 {
+            if pt.delta {
+                return [0.0; PACKET_SIZE];
+            }
+
+            let qs = match self.sample_emitter(scene, emitter_pdf, pt, indices) {
+                Some(v) => v,
+                None => return [0.0; PACKET_SIZE],
+            };
+
+            let (f, flag) = pt.f(&qs, self.sampler.float(), indices);
+            if f.is_black() {
+                return [0.0; PACKET_SIZE];
+            }
+            connection[0] = Some(Event::from(flag));
+
+            let incident = (qs.point - pt.point).normalize();
+            let cos_abs = incident.dot(pt.normal).abs();
+            let contribution = pt.beta.mul(f).mul(qs.beta).mul_t(cos_abs);
+
+            if contribution.is_black() {
+                return [0.0; PACKET_SIZE];
+            }
+
+            let transmittance = pt.occlusion(&qs).unoccluded(scene, self.sampler, indices);
+            if transmittance.is_black() {
+                return [0.0; PACKET_SIZE];
+            }
+
+            sampled = Some(qs);
+            contribution.mul(transmittance)
+        } else {
+            let qs = &light_path[s - 1];
+            if pt.delta || qs.delta {
+                return [0.0; PACKET_SIZE];
+            }
+
+            let (f_pt, flag_pt) = pt.f(qs, self.sampler.float(), indices);
+            let (f_qs, flag_qs) = qs.f(pt, self.sampler.float(), indices);
+            if f_pt.is_black() || f_qs.is_black() {
+                return [0.0; PACKET_SIZE];
+            }
+            connection = [Some(Event::from(flag_pt)), Some(Event::from(flag_qs))];
+
+            let direction = qs.point - pt.point;
+            let distance2 = direction.magnitude2();
+            let direction = direction / distance2.sqrt();
+            let g = direction.dot(pt.normal).abs() * direction.dot(qs.normal).abs() / distance2;
+
+            let contribution = pt.beta.mul(f_pt).mul(f_qs).mul(qs.beta).mul_t(g);
+
+            if contribution.is_black() {
+                return [0.0; PACKET_SIZE];
+            }
+
+            let transmittance = OcclusionTester::between(pt.point, qs.point).unoccluded(
+                scene,
+                self.sampler,
+                indices,
+            );
+            if transmittance.is_black() {
+                return [0.0; PACKET_SIZE];
+            }
+
+            contribution.mul(transmittance)
+        };
+
+        let weight = self.mis_weight(
+            camera,
+            light_path,
+            camera_path,
+            sampled.as_ref(),
+            s,
+            t,
+            emitter_pdf,
+        );
+
+        let contribution = contribution.mul_t(weight);
+        let light_vertices = if s > 1 { &light_path[1..s - 1] } else { &[] };
+        tracker.join(Self::track(
+            tracker,
+            &camera_path[1..t - 1],
+            &connection,
+            light_vertices,
+            contribution,
+        ));
+
+        contribution
+    }
+
+    /// Connects the first `s` vertices of the light subpath to a point sampled on the camera
+    /// (`t = 1`) and splats the weighted contribution.
+    #[allow(clippy::too_many_arguments)]
+    fn connect_camera(
+        &self,
+        scene: &Scene,
+        camera: &dyn Camera,
+        light_path: &[Vertex],
+        s: usize,
+        emitter_pdf: Float,
+        indices: &[usize; PACKET_SIZE],
+        splats: &SplatBuffer,
+        tracker: &LightPathTracker<[usize; PACKET_SIZE]>,
+    ) {
+        let qs = &light_path[s - 1];
+        if qs.delta || !qs.on_surface() {
+            return;
+        }
+
+        let camera_sample = match camera.sample_importance(qs.point) {
+            Some(c) if c.importance > 0.0 && c.pdf > 0.0 => c,
+            _ => return,
+        };
+
+        let beta = camera_sample.importance / camera_sample.pdf;
+        let pt = Vertex::camera(camera_sample.point, [beta; PACKET_SIZE]);
+
+        let (f, flag) = qs.f(&pt, self.sampler.float(), indices);
+        if f.is_black() {
+            return;
+        }
+
+        let incident = (pt.point - qs.point).normalize();
+        let cos_abs = incident.dot(qs.normal).abs();
+        let contribution = qs.beta.mul(f).mul(pt.beta).mul_t(cos_abs);
+
+        if contribution.is_black() {
+            return;
+        }
+
+        let transmittance =
+            OcclusionTester::between(qs.point, pt.point).unoccluded(scene, self.sampler, indices);
+        let contribution = contribution.mul(transmittance);
+        if contribution.is_black() {
+            return;
+        }
+
+        let weight = self.mis_weight(camera, light_path, &[], Some(&pt), s, 1, emitter_pdf);
+        let contribution = contribution.mul_t(weight);
+        splats.add_packet(camera_sample.raster, &contribution, indices);
+
+        let strategy = Self::track(
+            tracker,
+            &[],
+            &[Some(Event::from(flag))],
+            &light_path[1..s - 1],
+            contribution,
+        );
+        self.light_paths
+            .splat(strategy, camera.resolution(), camera_sample.raster);
+    }
+
+    /// Computes the MIS weight of the strategy with `s` light and `t` camera vertices by comparing
+    /// it to all other strategies that could have created the same path.
+    ///
+    /// The `sampled` vertex replaces the last light vertex for `s = 1` and the camera vertex for
+    /// `t = 1`.
+    #[allow(clippy::too_many_arguments)]
+    fn mis_weight(
+        &self,
+        camera: &dyn Camera,
+        light_path: &[Vertex],
+        camera_path: &[Vertex],
+        sampled: Option<&Vertex>,
+        s: usize,
+        t: usize,
+        emitter_pdf: Float,
+    ) -> Float {
+        if s + t == 2 {
+            return 1.0;
+        }
+
+        let light_vertex = |i: usize| match sampled {
+            Some(v) if s == 1 && i == 0 => v,
+            _ => &light_path[i],
+        };
+
+        let qs = if s > 0 {
+            Some(light_vertex(s - 1))
+        } else {
+            None
+        };
+        let qs_minus = if s > 1 {
+            Some(light_vertex(s - 2))
+        } else {
+            None
+        };
+        let pt = match sampled {
+            Some(v) if t == 1 => v,
+            _ => &camera_path[t - 1],
+        };
+        let pt_minus = if t > 1 {
+            Some(&camera_path[t - 2])
+        } else {
+            None
+        };
+
+        // the reverse pdfs of the vertices around the connection differ from the stored ones
+        let pt_pdf_rev = match qs {
+            Some(qs) => qs.pdf(camera, qs_minus, pt),
+            None => pt.pdf_light_origin(emitter_pdf),
+        };
+        let pt_minus_pdf_rev = match (qs, pt_minus) {
+            (Some(qs), Some(pt_minus)) => pt.pdf(camera, Some(qs), pt_minus),
+            (None, Some(pt_minus)) => pt.pdf_light(pt_minus),
+            (_, None) => 0.0,
+        };
+        let qs_pdf_rev = qs.map_or(0.0, |qs| pt.pdf(camera, pt_minus, qs));
+        let qs_minus_pdf_rev = match (qs, qs_minus) {
+            (Some(qs), Some(qs_minus)) => qs.pdf(camera, Some(pt), qs_minus),
+            _ => 0.0,
+        };
+
+        let mut sum = 0.0;
+
+        let mut ratio = 1.0;
+        for i in (1..t).rev() {
+            let vertex = &camera_path[i];
+            let (pdf_rev, delta) = if i == t - 1 {
+                (pt_pdf_rev, false)
+            } else if i == t - 2 {
+                (pt_minus_pdf_rev, vertex.delta)
+            } else {
+                (vertex.pdf_rev, vertex.delta)
+            };
+
+            ratio *= self.remap(pdf_rev) / self.remap(vertex.pdf_fwd);
+
+            if !delta && !camera_path[i - 1].delta {
+                sum += ratio;
+            }
+        }
+
+        let mut ratio = 1.0;
+        for i in (0..s).rev() {
+            let vertex = light_vertex(i);
+            let (pdf_rev, delta) = if i == s - 1 {
+                (qs_pdf_rev, false)
+            } else if i == s - 2 {
+                (qs_minus_pdf_rev, vertex.delta)
+            } else {
+                (vertex.pdf_rev, vertex.delta)
+            };
+
+            ratio *= self.remap(pdf_rev) / self.remap(vertex.pdf_fwd);
+
+            let prev_delta = if i > 0 {
+                light_vertex(i - 1).delta
+            } else {
+                vertex.is_delta_emitter()
+            };
+
+            if !delta && !prev_delta {
+                sum += ratio;
+            }
+        }
+
+        1.0 / (1.0 + sum)
+    }
+}
+
+#[typetag::serde]
+impl Integrator for Bdpt {
+    fn integrate(
+        &self,
+        scene: &Scene,
+        camera: &dyn Camera,
+        primary_ray: Ray,
+        pixel: &mut Pixel,
+        splats: &SplatBuffer,
+    ) {
+        let indices = self.spectral_sampler.create();
+        splats.count_packet(&indices);
+        self.light_paths.count_splats(&indices, camera.resolution());
+        let mut tracker = self.light_paths.tracker(indices);
+
+        let (mut camera_path, escaped) = self.camera_subpath(scene, camera, primary_ray, &indices);
+
+        let emitter_pdf = if scene.num_emitters() > 0 {
+            1.0 / scene.num_emitters() as Float
+        } else {
+            0.0
+        };
+        let light_path = self.light_subpath(scene, emitter_pdf, &indices);
+
+        let mut illumination = [0.0; PACKET_SIZE];
+
+        for t in 1..=camera_path.len() {
+            // sampling an emitter (s = 1) does not require a light subpath
+            for s in 0..=light_path.len().max(1) {
+                // the camera can neither be hit nor see an emitter sampled independently
+                if t == 1 && s < 2 {
+                    continue;
+                }
+                if s + t - 2 > self.max_depth as usize {
+                    continue;
+                }
+
+                let contribution = self.connect(
+                    scene,
+                    camera,
+                    &light_path,
+                    &camera_path,
+                    s,
+                    t,
+                    emitter_pdf,
+                    &indices,
+                    splats,
+                    &mut tracker,
+                );
+                illumination.add_assign(contribution);
+            }
+        }
+
+        // the escaped camera subpath sees every infinite emitter (s = 0)
+        if let Some((ray, beta, pdf)) = escaped {
+            let t = camera_path.len() + 1;
+
+            for &index in scene.infinite_emitters() {
+                let emitter = match scene.get_emitter(index as usize) {
+                    Some(e) if !e.is_delta() && t - 2 <= self.max_depth as usize => e,
+                    _ => continue,
+                };
+
+                let mut vertex = Vertex::emitter(
+                    emitter,
+                    ray.origin + ray.direction,
+                    -ray.direction,
+                    beta,
+                    pdf,
+                );
+                vertex.outgoing = -ray.direction;
+                camera_path.push(vertex);
+
+                let contribution = self.connect(
+                    scene,
+                    camera,
+                    &light_path,
+                    &camera_path,
+                    0,
+                    t,
+                    emitter_pdf,
+                    &indices,
+                    splats,
+                    &mut tracker,
+                );
+                illumination.add_assign(contribution);
+                camera_path.pop();
+            }
+        }
+
+        pixel.add_packet(&illumination, &indices);
+        self.light_paths
+            .add(tracker, camera.resolution(), pixel.position);
+    }
+
+    fn light_paths(&self) -> Option<&LightPaths> {
+        Some(&self.light_paths)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::integrator::testing::assert_converges;
+
+    #[test]
+    fn converges_to_path() {
+        assert_converges(
+            r#"{ "Bdpt": (max_depth: 4, sampler: Random, spectral_sampler: Random) }"#,
+            256,
+            0.05,
+        );
+    }
+}
@@ -1,4 +1,5 @@
-use crate::camera::sensor::Pixel;
+use crate::camera::sensor::{Pixel, SplatBuffer};
+use crate::camera::Camera;
 use crate::geometry::Ray;
 use crate::integrator::Integrator;
 use crate::scene::Scene;
@@ -9,7 +10,7 @@ pub struct DummyIntegrator;
 
 #[typetag::serde]
 impl Integrator for DummyIntegrator {
-    fn integrate(&self, _: &Scene, _: Ray, _: &mut Pixel) {
+    fn integrate(&self, _: &Scene, _: &dyn Camera, _: Ray, _: &mut Pixel, _: &SplatBuffer) {
         unimplemented!()
     }
 }
@@ -0,0 +1,554 @@
+//! Learned path guiding in the style of "Practical Path Guiding for Efficient Light-Transport
+//! Simulation" (Müller et al. 2017).
+//!
+//! The incident radiance is learned in a spatial-directional tree (SD-tree): a binary tree
+//! subdividing the scene bounds, whose leaves each hold a quadtree over the sphere of directions.
+//! Directions are mapped to the unit square with the equal-area cylindrical mapping of
+//! [sample_unit_sphere], so the quadtree cells have equal solid angles at every depth.
+//!
+//! Every leaf holds two quadtrees: one to sample from, learned in the previous pass, and one
+//! collecting the radiance of the current pass. Between passes, the collected radiance replaces
+//! the sampled one and both trees are refined.
+
+use crate::geometry::{Aabb, Geometry};
+use crate::scene::Scene;
+use crate::util::mc::sample_unit_sphere;
+use crate::{Float, Vec2, Vec3};
+use cgmath::{ElementWise, InnerSpace};
+use core::sync::atomic::{AtomicU64, Ordering};
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+#[cfg(not(feature = "f64"))]
+use std::f32::consts::{PI, TAU};
+#[cfg(feature = "f64")]
+use std::f64::consts::{PI, TAU};
+
+/// The maximum depth of a directional quadtree.
+const MAX_DIRECTIONAL_DEPTH: usize = 20;
+
+/// The maximum depth of the spatial tree.
+const MAX_SPATIAL_DEPTH: usize = 48;
+
+/// The configuration and learned distribution of path guiding.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(default)]
+pub struct PathGuiding {
+    /// The probability of sampling the BSDF instead of the learned distribution
+    pub bsdf_fraction: Float,
+    /// The number of samples recorded in a spatial leaf within a pass before it is split
+    pub spatial_threshold: u64,
+    /// The fraction of the energy of a directional quadtree above which its cells are subdivided
+    pub directional_threshold: Float,
+    /// The learned distribution, preserved in checkpoints
+    #[serde(skip_serializing_if = "SdTree::is_empty")]
+    state: RwLock<SdTree>,
+}
+
+impl Default for PathGuiding {
+    fn default() -> Self {
+        Self {
+            bsdf_fraction: 0.5,
+            spatial_threshold: 12000,
+            directional_threshold: 0.01,
+            state: RwLock::new(SdTree::default()),
+        }
+    }
+}
+
+impl PathGuiding {
+    /// Prepares the distribution for a new pass.
+    ///
+    /// The SD-tree is created for the scene if it is missing.
+    /// Otherwise the radiance collected in the last pass becomes the distribution to sample and
+    /// the trees are refined accordingly.
+    pub fn begin_pass(&self, scene: &Scene) {
+        let mut state = self.state.write();
+
+        if state.nodes.is_empty() {
+            *state = SdTree::new(scene);
+        } else {
+            state.refine(self.spatial_threshold, self.directional_threshold);
+        }
+    }
+
+    /// Returns the learned distribution at the point, if there is one yet.
+    pub fn distribution(&self, point: Vec3) -> Option<Distribution<'_>> {
+        let state = self.state.read();
+        let index = state.leaf(point)?;
+
+        if state.nodes[index].sampling.total() > 0.0 {
+            Some(Distribution {
+                tree: state,
+                index,
+                bsdf_fraction: self.bsdf_fraction,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Records the radiance arriving at a point from a direction.
+    ///
+    /// # Arguments
+    /// * `point` - The point receiving the radiance
+    /// * `incident` - The (normalized) direction the radiance arrives from
+    /// * `radiance` - The estimated incident radiance
+    /// * `pdf` - The pdf the direction was sampled with
+    pub fn record(&self, point: Vec3, incident: Vec3, radiance: Float, pdf: Float) {
+        if !(pdf > 0.0 && radiance.is_finite()) {
+            return;
+        }
+
+        let state = self.state.read();
+        if let Some(index) = state.leaf(point) {
+            let node = &state.nodes[index];
+
+            node.building
+                .record(direction_to_square(incident), widen(radiance / pdf));
+            node.samples.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// The learned distribution of a spatial leaf, locked for reading.
+pub struct Distribution<'a> {
+    tree: parking_lot::RwLockReadGuard<'a, SdTree>,
+    index: usize,
+    bsdf_fraction: Float,
+}
+
+impl Distribution<'_> {
+    /// Returns the probability of sampling the BSDF instead of this distribution.
+    #[inline]
+    pub const fn bsdf_fraction(&self) -> Float {
+        self.bsdf_fraction
+    }
+
+    /// Samples a direction proportional to the learned radiance.
+    ///
+    /// # Constraints
+    /// * `sample`: All values should be within `[0, 1)`.
+    pub fn sample(&self, sample: Vec2) -> Vec3 {
+        let square = self.tree.nodes[self.index].sampling.sample(sample);
+
+        sample_unit_sphere(square)
+    }
+
+    /// Computes the solid angle pdf of sampling a direction.
+    ///
+    /// # Constraints
+    /// * `direction`: Should be normalized.
+    pub fn pdf(&self, direction: Vec3) -> Float {
+        let pdf = self.tree.nodes[self.index]
+            .sampling
+            .pdf(direction_to_square(direction));
+
+        pdf as Float / (4.0 * PI)
+    }
+}
+
+/// Maps a direction to the unit square, inverting [sample_unit_sphere].
+fn direction_to_square(direction: Vec3) -> Vec2 {
+    let x = (1.0 - direction.z) / 2.0;
+    let phi = direction.y.atan2(direction.x);
+    let y = if phi < 0.0 { phi + TAU } else { phi } / TAU;
+
+    Vec2::new(x.clamp(0.0, 1.0), y.clamp(0.0, 1.0))
+}
+
+/// Converts a [Float] to the `f64` the directional quadtrees accumulate their energy in.
+#[cfg(not(feature = "f64"))]
+#[inline]
+fn widen(value: Float) -> f64 {
+    f64::from(value)
+}
+
+/// Converts a [Float] to the `f64` the directional quadtrees accumulate their energy in.
+#[cfg(feature = "f64")]
+#[inline]
+fn widen(value: Float) -> f64 {
+    value
+}
+
+/// A float that can be added to atomically.
+#[derive(Debug, Default, Deserialize, Serialize)]
+#[serde(from = "f64", into = "f64")]
+struct AtomicFloat(AtomicU64);
+
+impl AtomicFloat {
+    #[inline]
+    fn load(&self) -> f64 {
+        f64::from_bits(self.0.load(Ordering::Relaxed))
+    }
+
+    #[inline]
+    fn add(&self, value: f64) {
+        let _ = self
+            .0
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+                Some((f64::from_bits(bits) + value).to_bits())
+            });
+    }
+}
+
+impl Clone for AtomicFloat {
+    fn clone(&self) -> Self {
+        Self::from(self.load())
+    }
+}
+
+impl From<f64> for AtomicFloat {
+    fn from(value: f64) -> Self {
+        Self(AtomicU64::new(value.to_bits()))
+    }
+}
+
+impl From<AtomicFloat> for f64 {
+    fn from(value: AtomicFloat) -> Self {
+        value.load()
+    }
+}
+
+/// A node of a directional quadtree.
+///
+/// The quadrants are ordered `(0, 0), (1, 0), (0, 1), (1, 1)`.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+struct DNode {
+    sums: [AtomicFloat; 4],
+    /// The indices of the child nodes, `0` for leaves
+    children: [u32; 4],
+}
+
+impl DNode {
+    #[inline]
+    fn sums(&self) -> [f64; 4] {
+        [
+            self.sums[0].load(),
+            self.sums[1].load(),
+            self.sums[2].load(),
+            self.sums[3].load(),
+        ]
+    }
+}
+
+/// Returns the quadrant containing the point and moves the point into it.
+#[inline]
+fn quadrant(point: &mut Vec2) -> usize {
+    let x = (point.x >= 0.5) as usize;
+    let y = (point.y >= 0.5) as usize;
+    *point = (*point * 2.0).sub_element_wise(Vec2::new(x as Float, y as Float));
+
+    x + 2 * y
+}
+
+/// A quadtree over the unit square, with the energy of each quadrant.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct DTree {
+    nodes: Vec<DNode>,
+}
+
+impl Default for DTree {
+    fn default() -> Self {
+        Self {
+            nodes: vec![DNode::default()],
+        }
+    }
+}
+
+impl DTree {
+    fn total(&self) -> f64 {
+        self.nodes[0].sums().iter().sum()
+    }
+
+    fn record(&self, mut point: Vec2, value: f64) {
+        let mut index = 0;
+
+        loop {
+            let node = &self.nodes[index];
+            let q = quadrant(&mut point);
+            node.sums[q].add(value);
+
+            match node.children[q] {
+                0 => break,
+                c => index = c as usize,
+            }
+        }
+    }
+
+    fn pdf(&self, mut point: Vec2) -> f64 {
+        let mut index = 0;
+        let mut pdf = 1.0;
+
+        loop {
+            let node = &self.nodes[index];
+            let sums = node.sums();
+            let total = sums.iter().sum::<f64>();
+            if total <= 0.0 {
+                return 0.0;
+            }
+
+            let q = quadrant(&mut point);
+            pdf *= 4.0 * sums[q] / total;
+
+            match node.children[q] {
+                0 => return pdf,
+                c => index = c as usize,
+            }
+        }
+    }
+
+    /// Samples a point proportional to the energy, choosing the column of every node by the
+    /// first dimension of the sample and the row by the second dimension.
+    ///
+    /// # Constraints
+    /// * The total energy should be positive.
+    fn sample(&self, mut sample: Vec2) -> Vec2 {
+        let mut index = 0;
+        let mut origin = Vec2::new(0.0, 0.0);
+        let mut size = 1.0;
+
+        let choose = |s: &mut Float, p: f64| {
+            let p = p as Float;
+            if *s < p {
+                *s = (*s / p).min(1.0 - Float::EPSILON);
+                0
+            } else {
+                *s = ((*s - p) / (1.0 - p)).clamp(0.0, 1.0 - Float::EPSILON);
+                1
+            }
+        };
+
+        loop {
+            let node = &self.nodes[index];
+            let sums = node.sums();
+            let total = sums.iter().sum::<f64>();
+
+            let x = choose(&mut sample.x, (sums[0] + sums[2]) / total);
+            let column = sums[x] + sums[x + 2];
+            let y = choose(&mut sample.y, sums[x] / column);
+            let q = x + 2 * y;
+
+            size /= 2.0;
+            origin += Vec2::new(x as Float, y as Float) * size;
+
+            match node.children[q] {
+                0 => return origin + sample * size,
+                c => index = c as usize,
+            }
+        }
+    }
+
+    /// Returns a copy of the structure without any energy.
+    fn cleared(&self) -> Self {
+        let nodes = self
+            .nodes
+            .iter()
+            .map(|n| DNode {
+                sums: Default::default(),
+                children: n.children,
+            })
+            .collect();
+
+        Self { nodes }
+    }
+
+    /// Creates an empty quadtree whose cells contain at most the given fraction of the energy of
+    /// this tree.
+    ///
+    /// The energy of leaves is assumed to be uniformly distributed.
+    fn refined(&self, threshold: Float) -> Self {
+        let total = self.total();
+        if total <= 0.0 {
+            return self.cleared();
+        }
+
+        let mut tree = Self::default();
+        let mut stack = vec![(Some(0), self.nodes[0].sums(), 0, 1)];
+
+        while let Some((old, sums, new, depth)) = stack.pop() {
+            for (q, &sum) in sums.iter().enumerate() {
+                if depth >= MAX_DIRECTIONAL_DEPTH || sum / total <= widen(threshold) {
+                    continue;
+                }
+
+                let child = old
+                    .map(|o: usize| self.nodes[o].children[q] as usize)
+                    .filter(|&c| c != 0);
+                let child_sums = child.map_or([sum / 4.0; 4], |c| self.nodes[c].sums());
+
+                let index = tree.nodes.len();
+                tree.nodes.push(DNode::default());
+                tree.nodes[new].children[q] = index as u32;
+                stack.push((child, child_sums, index, depth + 1));
+            }
+        }
+
+        tree
+    }
+}
+
+/// A node of the spatial binary tree.
+///
+/// Only leaves use their directional quadtrees.
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct SNode {
+    /// The axis the node is split along
+    axis: usize,
+    /// The indices of the child nodes, `0` for leaves
+    children: [u32; 2],
+    sampling: DTree,
+    building: DTree,
+    /// The number of samples recorded in the current pass
+    #[serde(skip)]
+    samples: AtomicU64,
+}
+
+impl Clone for SNode {
+    fn clone(&self) -> Self {
+        Self {
+            axis: self.axis,
+            children: self.children,
+            sampling: self.sampling.clone(),
+            building: self.building.clone(),
+            samples: AtomicU64::new(self.samples.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+/// A binary tree over cubic bounds, split at the middle along alternating axes.
+#[derive(Debug, Deserialize, Serialize)]
+struct SdTree {
+    bounds: Aabb,
+    nodes: Vec<SNode>,
+}
+
+impl Default for SdTree {
+    fn default() -> Self {
+        Self {
+            bounds: Aabb::empty(),
+            nodes: Vec::new(),
+        }
+    }
+}
+
+impl SdTree {
+    #[inline]
+    fn is_empty(state: &RwLock<Self>) -> bool {
+        state.read().nodes.is_empty()
+    }
+
+    /// Creates a single leaf covering all finite objects of the scene.
+    fn new(scene: &Scene) -> Self {
+        let bounds = (0..scene.num_objects())
+            .map(|i| scene.get_object(i).bounds())
+            .filter(|b| b.size().magnitude().is_finite())
+            .fold(Aabb::empty(), |acc, b| acc.join(b));
+
+        let size = bounds.size();
+        let extent = size.x.max(size.y).max(size.z).max(Float::EPSILON);
+        let min = if extent.is_finite() {
+            bounds.min
+        } else {
+            Vec3::new(0.0, 0.0, 0.0)
+        };
+
+        Self {
+            bounds: Aabb::new(min, min + Vec3::new(extent, extent, extent)),
+            nodes: vec![SNode::default()],
+        }
+    }
+
+    /// Returns the index of the leaf containing the point, clamped into the bounds.
+    fn leaf(&self, point: Vec3) -> Option<usize> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+
+        let relative = (point - self.bounds.min).div_element_wise(self.bounds.size());
+        let mut p = [relative.x, relative.y, relative.z].map(|c| c.clamp(0.0, 1.0));
+        let mut index = 0;
+
+        loop {
+            let node = &self.nodes[index];
+            if node.children[0] == 0 {
+                return Some(index);
+            }
+
+            let c = &mut p[node.axis];
+            let child = (*c >= 0.5) as usize;
+            *c = *c * 2.0 - child as Float;
+            index = node.children[child] as usize;
+        }
+    }
+
+    /// Splits the leaves with enough samples and refines the directional quadtrees of all leaves.
+    fn refine(&mut self, spatial_threshold: u64, directional_threshold: Float) {
+        let mut stack = vec![(0, 0)];
+
+        while let Some((index, depth)) = stack.pop() {
+            let node = &mut self.nodes[index];
+            if node.children[0] != 0 {
+                stack.push((node.children[0] as usize, depth + 1));
+                stack.push((node.children[1] as usize, depth + 1));
+                continue;
+            }
+
+            let samples = *node.samples.get_mut();
+            if depth < MAX_SPATIAL_DEPTH && samples > spatial_threshold {
+                let mut child = node.clone();
+                child.axis = (node.axis + 1) % 3;
+                *child.samples.get_mut() = samples / 2;
+                node.sampling = DTree::default();
+                node.building = DTree::default();
+
+                let first = self.nodes.len();
+                self.nodes[index].children = [first as u32, first as u32 + 1];
+                self.nodes.push(child.clone());
+                self.nodes.push(child);
+
+                stack.push((first, depth + 1));
+                stack.push((first + 1, depth + 1));
+                continue;
+            }
+
+            node.sampling = core::mem::take(&mut node.building);
+            node.building = node.sampling.refined(directional_threshold);
+            *node.samples.get_mut() = 0;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sampling_matches_pdf() {
+        let tree = DTree::default();
+        tree.record(Vec2::new(0.1, 0.2), 1.0);
+        tree.record(Vec2::new(0.7, 0.3), 3.0);
+        let tree = tree.refined(0.1);
+        tree.record(Vec2::new(0.1, 0.2), 1.0);
+        tree.record(Vec2::new(0.7, 0.3), 3.0);
+        tree.record(Vec2::new(0.9, 0.9), 0.5);
+
+        let n = 64;
+        let mut integral = 0.0;
+        for i in 0..n {
+            for j in 0..n {
+                let point = Vec2::new(i as Float + 0.5, j as Float + 0.5) / n as Float;
+                integral += tree.pdf(point) / f64::from(n * n);
+
+                let sampled = tree.sample(point);
+                assert!(tree.pdf(sampled) > 0.0);
+            }
+        }
+        assert!((integral - 1.0).abs() < 1e-6);
+
+        for direction in [Vec3::unit_x(), -Vec3::unit_y(), Vec3::new(0.3, -0.4, 0.5)] {
+            let direction = direction.normalize();
+            let mapped = sample_unit_sphere(direction_to_square(direction));
+            assert!((mapped - direction).magnitude() < 1e-4);
+        }
+    }
+}
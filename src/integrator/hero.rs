@@ -1,190 +1,64 @@
-use crate::bxdf::{BxDFFlag, BxDFSamplePacket};
-use crate::camera::sensor::Pixel;
-use crate::geometry::{offset_ray_towards, Ray};
-use crate::integrator::{DirectIllumination, Integrator};
+use crate::camera::sensor::{Pixel, SplatBuffer};
+use crate::camera::Camera;
+use crate::geometry::Ray;
+use crate::integrator::lpe::LightPaths;
+use crate::integrator::tracer::PathTracer;
+use crate::integrator::{DirectIllumination, Heuristic, Integrator, RussianRoulette};
 use crate::sampler::{FloatSampler, SpectralSampler};
-use crate::scene::{Scene, SceneIntersection, SceneObject};
-use crate::util::PacketOps;
-use crate::{Float, PACKET_SIZE};
-use cgmath::InnerSpace;
+use crate::scene::Scene;
 use serde::{Deserialize, Serialize};
 
-#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct PureHero {
     max_depth: u32,
     sampler: FloatSampler,
     direct_illum: DirectIllumination,
+    #[serde(default)]
+    heuristic: Heuristic,
+    #[serde(default)]
+    roulette: RussianRoulette,
+    /// The expressions to separate the contributions into
+    #[serde(default)]
+    #[serde(skip_serializing_if = "LightPaths::is_empty")]
+    light_paths: LightPaths,
 }
 
 impl PureHero {
-    fn trace_single<'a>(
-        &self,
-        scene: &'a Scene,
-        mut hit: SceneIntersection<'a>,
-        index: usize,
-        illumination: &mut Float,
-        throughput: &mut Float,
-        curr_depth: u32,
-    ) {
-        for curr_depth in curr_depth..self.max_depth {
-            let outgoing = -hit.i.incoming;
-            let point = hit.i.point;
-            let normal = hit.i.normal;
-            let bsdf = hit.object.bsdf();
-
-            if let SceneObject::Emitter(e) = hit.object {
-                if curr_depth != 1 || self.direct_illum != DirectIllumination::Indirect {
-                    *illumination += *throughput * e.radiance_lambda(index);
-                }
-            }
-
-            *illumination += *throughput
-                * self
-                    .direct_illum
-                    .sample_lambda(scene, &hit, &self.sampler, index);
-
-            if let Some(bxdf_sample) = bsdf.sample_lambda(
-                normal,
-                outgoing,
-                self.sampler.sample(),
-                BxDFFlag::empty(),
-                index,
-            ) {
-                if bxdf_sample.pdf == 0.0 || bxdf_sample.spectrum == 0.0 {
-                    break;
-                }
-
-                let cos_abs = if bxdf_sample.flag.specular() {
-                    // division of cosine omitted in specular bxdfs
-                    1.0
-                } else {
-                    bxdf_sample.incident.dot(normal).abs()
-                };
-
-                *throughput *= bxdf_sample.spectrum * (cos_abs / bxdf_sample.pdf);
-
-                let ray = offset_ray_towards(point, normal, bxdf_sample.incident);
-                match scene.intersect(ray) {
-                    Some(i) => hit = i,
-                    None => break,
-                }
-            } else {
-                break;
-            }
+    fn tracer(&self) -> PathTracer<'_> {
+        PathTracer {
+            max_depth: self.max_depth,
+            sampler: self.sampler,
+            direct_illum: self.direct_illum,
+            heuristic: self.heuristic,
+            roulette: self.roulette,
+            guiding: None,
+            light_paths: &self.light_paths,
         }
     }
+}
 
-    fn trace_bundle<'a>(
+#[typetag::serde]
+impl Integrator for PureHero {
+    fn integrate(
         &self,
-        scene: &'a Scene,
-        mut hit: SceneIntersection<'a>,
-        indices: &[usize; PACKET_SIZE],
-        illumination: &mut [Float; PACKET_SIZE],
-        throughput: &mut [Float; PACKET_SIZE],
+        scene: &Scene,
+        camera: &dyn Camera,
+        primary_ray: Ray,
+        pixel: &mut Pixel,
+        _: &SplatBuffer,
     ) {
-        for curr_depth in 0..self.max_depth {
-            let outgoing = -hit.i.incoming;
-            let point = hit.i.point;
-            let normal = hit.i.normal;
-            let bsdf = hit.object.bsdf();
-
-            if let SceneObject::Emitter(e) = hit.object {
-                if curr_depth != 1 || self.direct_illum != DirectIllumination::Indirect {
-                    illumination.add_assign(throughput.mul(e.radiance_packet(indices)));
-                }
-            }
-
-            illumination.add_assign(throughput.mul(self.direct_illum.sample_packet(
-                scene,
-                &hit,
-                &self.sampler,
-                indices,
-            )));
-
-            match bsdf.sample_packet(
-                normal,
-                outgoing,
-                self.sampler.sample(),
-                BxDFFlag::empty(),
-                indices,
-            ) {
-                BxDFSamplePacket::Bundle(Some(bxdf_sample)) => {
-                    if bxdf_sample.pdf == 0.0 || bxdf_sample.spectrum.is_black() {
-                        break;
-                    }
-
-                    let cos_abs = if bxdf_sample.flag.specular() {
-                        // division of cosine omitted in specular bxdfs
-                        1.0
-                    } else {
-                        bxdf_sample.incident.dot(normal).abs()
-                    };
-
-                    throughput.mul_assign(bxdf_sample.spectrum.mul_t(cos_abs / bxdf_sample.pdf));
-
-                    let ray = offset_ray_towards(point, normal, bxdf_sample.incident);
-                    match scene.intersect(ray) {
-                        Some(i) => hit = i,
-                        None => break,
-                    }
-                }
-                BxDFSamplePacket::Split(split) => {
-                    for i in 0..PACKET_SIZE {
-                        if let Some(bxdf_sample) = split[0] {
-                            if bxdf_sample.pdf == 0.0 || bxdf_sample.spectrum == 0.0 {
-                                continue;
-                            }
-
-                            let cos_abs = if bxdf_sample.flag.specular() {
-                                // division of cosine omitted in specular bxdfs
-                                1.0
-                            } else {
-                                bxdf_sample.incident.dot(normal).abs()
-                            };
-
-                            throughput[i] *= bxdf_sample.spectrum * (cos_abs / bxdf_sample.pdf);
-
-                            let ray = offset_ray_towards(point, normal, bxdf_sample.incident);
-
-                            if i == 0 {
-                                match scene.intersect(ray) {
-                                    Some(new_hit) => {
-                                        self.trace_single(
-                                            scene,
-                                            new_hit,
-                                            indices[i],
-                                            &mut illumination[i],
-                                            &mut throughput[i],
-                                            curr_depth,
-                                        );
-                                    }
-                                    None => continue,
-                                }
-                            }
-                        }
-                    }
-
-                    break;
-                }
-                _ => break,
-            }
+        let indices = SpectralSampler::Hero.create();
+
+        match self
+            .tracer()
+            .trace_pixel(scene, camera, primary_ray, &indices, pixel.position)
+        {
+            Some(illumination) => pixel.add_packet(&illumination, &indices),
+            None => pixel.add_none(),
         }
     }
-}
 
-#[typetag::serde]
-impl Integrator for PureHero {
-    fn integrate(&self, scene: &Scene, primary_ray: Ray, pixel: &mut Pixel) {
-        if let Some(hit) = scene.intersect(primary_ray) {
-            let indices = SpectralSampler::Hero.create();
-            let mut illumination = [0.0; PACKET_SIZE];
-            let mut throughput = [1.0; PACKET_SIZE];
-
-            self.trace_bundle(scene, hit, &indices, &mut illumination, &mut throughput);
-
-            pixel.add_packet(&illumination, &indices);
-        } else {
-            pixel.add_none();
-        }
+    fn light_paths(&self) -> Option<&LightPaths> {
+        Some(&self.light_paths)
     }
 }
@@ -0,0 +1,209 @@
+use crate::bxdf::BxDFFlag;
+use crate::camera::sensor::{Pixel, SplatBuffer};
+use crate::camera::Camera;
+use crate::geometry::{offset_ray_towards, Ray};
+use crate::integrator::{sample_bsdf, Integrator};
+use crate::sampler::{FloatSampler, SpectralSampler};
+use crate::scene::{OcclusionTester, Scene};
+use crate::util::PacketOps;
+use crate::{Float, Vec3, PACKET_SIZE};
+use cgmath::InnerSpace;
+use serde::{Deserialize, Serialize};
+
+/// A light (particle) tracer.
+///
+/// For every pixel sample, a path is traced from a random emitter instead of the camera.
+/// Every vertex of that path is connected to the camera and splatted into the pixel it is seen
+/// in, which makes caustics cheap to render.
+///
+/// Delta emitters are not visible to the camera, just like in the other integrators.
+/// Paths reaching the camera only by specular scattering cannot be connected and are missing.
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+pub struct LightTracer {
+    max_depth: u32,
+    sampler: FloatSampler,
+    spectral_sampler: SpectralSampler,
+}
+
+impl LightTracer {
+    /// Connects a point to the camera and splats the contribution.
+    ///
+    /// # Arguments
+    /// * `scene` - The scene
+    /// * `camera` - The camera to connect to
+    /// * `splats` - The buffer to splat into
+    /// * `point` - The point to connect
+    /// * `f` - Evaluates the scattering towards the camera, given the direction towards it
+    /// * `indices` - The spectral indices
+    fn connect<F>(
+        &self,
+        scene: &Scene,
+        camera: &dyn Camera,
+        splats: &SplatBuffer,
+        point: Vec3,
+        f: F,
+        indices: &[usize; PACKET_SIZE],
+    ) where
+        F: FnOnce(Vec3) -> [Float; PACKET_SIZE],
+    {
+        let camera_sample = match camera.sample_importance(point) {
+            Some(s) if s.importance > 0.0 && s.pdf > 0.0 => s,
+            _ => return,
+        };
+
+        let to_camera = (camera_sample.point - point).normalize();
+        let contribution = f(to_camera).mul_t(camera_sample.importance / camera_sample.pdf);
+
+        if !contribution.is_black() {
+            let transmittance = OcclusionTester::between(point, camera_sample.point).unoccluded(
+                scene,
+                self.sampler,
+                indices,
+            );
+            let contribution = contribution.mul(transmittance);
+
+            if !contribution.is_black() {
+                splats.add_packet(camera_sample.raster, &contribution, indices);
+            }
+        }
+    }
+}
+
+#[typetag::serde]
+impl Integrator for LightTracer {
+    fn integrate(
+        &self,
+        scene: &Scene,
+        camera: &dyn Camera,
+        _: Ray,
+        _: &mut Pixel,
+        splats: &SplatBuffer,
+    ) {
+        let indices = self.spectral_sampler.create();
+        splats.count_packet(&indices);
+
+        let num_emitters = scene.num_emitters();
+        if num_emitters == 0 {
+            return;
+        }
+
+        let chosen =
+            ((self.sampler.float() * num_emitters as Float) as usize).min(num_emitters - 1);
+        let emitter = match scene.get_emitter(scene.emitters()[chosen] as usize) {
+            Some(e) => e,
+            None => return,
+        };
+
+        let emitter_ray = emitter.sample_ray(self.sampler.vec2(), self.sampler.vec2());
+        if emitter_ray.pdf_point == 0.0 || emitter_ray.pdf_direction == 0.0 {
+            return;
+        }
+
+        let radiance = emitter
+            .radiance(&indices)
+            .mul_t(num_emitters as Float / emitter_ray.pdf_point);
+        if radiance.is_black() {
+            return;
+        }
+
+        let origin = emitter_ray.ray.origin;
+        let normal = emitter_ray.normal;
+
+        let direction = emitter_ray.ray.direction;
+        let cos = if emitter.is_delta() {
+            1.0
+        } else {
+            // the rays of infinite emitters start on a virtual disk, which the camera cannot see
+            if !emitter.is_infinite() {
+                self.connect(
+                    scene,
+                    camera,
+                    splats,
+                    origin,
+                    |to_camera| {
+                        let fraction = emitter.emission_fraction(normal, to_camera);
+                        radiance.mul_t(fraction * to_camera.dot(normal).abs())
+                    },
+                    &indices,
+                );
+            }
+
+            direction.dot(normal).abs()
+        };
+
+        let mut beta = emitter.radiance_towards(&indices, normal, direction).mul_t(
+            num_emitters as Float * cos / (emitter_ray.pdf_point * emitter_ray.pdf_direction),
+        );
+        let mut ray = offset_ray_towards(origin, normal, emitter_ray.ray.direction);
+
+        for _ in 0..self.max_depth {
+            if beta.is_black() {
+                break;
+            }
+
+            let hit = match scene.intersect(ray) {
+                Some(hit) => hit,
+                None => break,
+            };
+
+            let point = hit.i.point;
+            let normal = hit.i.normal;
+            let outgoing = -hit.i.incoming;
+            let bsdf = hit.object.bsdf();
+
+            self.connect(
+                scene,
+                camera,
+                splats,
+                point,
+                |to_camera| {
+                    let f = bsdf.evaluate(
+                        normal,
+                        to_camera,
+                        outgoing,
+                        self.sampler.float(),
+                        BxDFFlag::empty(),
+                        &indices,
+                    );
+
+                    beta.mul(f).mul_t(to_camera.dot(normal).abs())
+                },
+                &indices,
+            );
+
+            let sample =
+                match sample_bsdf(bsdf, normal, outgoing, self.sampler, &indices, &mut beta) {
+                    Some(s) if s.pdf > 0.0 && !s.spectrum.is_black() => s,
+                    _ => break,
+                };
+
+            let cos_abs = if sample.flag.specular() {
+                // division of cosine omitted in specular bxdfs
+                1.0
+            } else {
+                sample.incident.dot(normal).abs()
+            };
+
+            beta.mul_assign(sample.spectrum.mul_t(cos_abs / sample.pdf));
+            ray = offset_ray_towards(point, normal, sample.incident);
+        }
+    }
+
+    fn splats_only(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::integrator::testing::assert_converges;
+
+    #[test]
+    fn converges_to_path() {
+        assert_converges(
+            r#"{ "LightTracer": (max_depth: 4, sampler: Random, spectral_sampler: Random) }"#,
+            1024,
+            0.05,
+        );
+    }
+}
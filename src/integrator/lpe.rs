@@ -0,0 +1,794 @@
+//! # Summary
+//! Light path expressions (LPEs) separate the contributions of an integrator by the kind of path
+//! that carried them, using the notation of the [integrator module](crate::integrator).
+//!
+//! # Syntax
+//! * `E`, `L`, `D`, `G`, `S` - The eye, the light and diffuse, glossy or specular scattering
+//! * `V` - Scattering inside a medium
+//! * `.` - Any scattering event (`D`, `G`, `S` or `V`)
+//! * `xy` - `x` followed by `y`
+//! * `x|y` - Either `x` or `y`
+//! * `x*`, `x+`, `x?` - Any number, at least one or at most one `x`
+//! * `[x]` - At most one `x` (like `x?`)
+//! * `(x)` - Groups `x`
+//!
+//! Whitespace is ignored and expressions always have to match the whole path, e.g. `E S+ D L`
+//! only matches caustics and `E D L` only the direct illumination of diffuse surfaces.
+//!
+//! # Classification
+//! Scattering events are classified by the [BxDFFlag] of the BxDF that was sampled or evaluated
+//! at a vertex (specular before glossy before diffuse).
+//! Vertices connected to emitters directly are therefore classified by the BxDF evaluated for the
+//! connection, e.g. a connection evaluating the glossy BxDF of a diffuse and glossy BSDF is
+//! glossy.
+
+use crate::bxdf::BxDFFlag;
+use crate::camera::sensor::{Pixel, SplatBuffer};
+use crate::color::{SampledSpectrum, SpectralIndices};
+use crate::{Image, UVec2, Vec2};
+use core::convert::TryFrom;
+use image::{ImageBuffer, Rgb};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize, Serializer};
+use std::iter::Peekable;
+use std::sync::OnceLock;
+use std::vec::IntoIter;
+
+/// The maximum number of states an expression may compile to.
+const MAX_STATES: usize = u128::BITS as usize;
+
+/// An event along a path.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    Eye,
+    Light,
+    Diffuse,
+    Glossy,
+    Specular,
+    Volume,
+}
+
+impl Event {
+    #[inline]
+    const fn bit(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+impl From<BxDFFlag> for Event {
+    fn from(flag: BxDFFlag) -> Self {
+        if flag.specular() {
+            Event::Specular
+        } else if flag.glossy() {
+            Event::Glossy
+        } else {
+            Event::Diffuse
+        }
+    }
+}
+
+/// The syntax tree of an expression.
+#[derive(Debug)]
+enum Node {
+    /// Matches a single event of the set.
+    Events(u8),
+    Concat(Vec<Node>),
+    Alternate(Vec<Node>),
+    Star(Box<Node>),
+    Plus(Box<Node>),
+    Optional(Box<Node>),
+}
+
+/// A recursive descent parser for expressions.
+struct Parser {
+    chars: Peekable<IntoIter<char>>,
+}
+
+impl Parser {
+    fn new(expression: &str) -> Self {
+        let chars: Vec<char> = expression.chars().filter(|c| !c.is_whitespace()).collect();
+
+        Self {
+            chars: chars.into_iter().peekable(),
+        }
+    }
+
+    fn parse(mut self) -> Result<Node, String> {
+        let node = self.alternate()?;
+
+        match self.chars.next() {
+            None => Ok(node),
+            Some(c) => Err(format!("unexpected '{}'", c)),
+        }
+    }
+
+    fn alternate(&mut self) -> Result<Node, String> {
+        let mut nodes = vec![self.concat()?];
+        while self.chars.next_if_eq(&'|').is_some() {
+            nodes.push(self.concat()?);
+        }
+
+        Ok(if nodes.len() == 1 {
+            nodes.remove(0)
+        } else {
+            Node::Alternate(nodes)
+        })
+    }
+
+    fn concat(&mut self) -> Result<Node, String> {
+        let mut nodes = Vec::new();
+        while let Some(&c) = self.chars.peek() {
+            if c == '|' || c == ')' || c == ']' {
+                break;
+            }
+            nodes.push(self.repeat()?);
+        }
+
+        if nodes.is_empty() {
+            Err("empty expression".to_string())
+        } else if nodes.len() == 1 {
+            Ok(nodes.remove(0))
+        } else {
+            Ok(Node::Concat(nodes))
+        }
+    }
+
+    fn repeat(&mut self) -> Result<Node, String> {
+        let mut node = self.atom()?;
+
+        while let Some(&c) = self.chars.peek() {
+            node = match c {
+                '*' => Node::Star(Box::new(node)),
+                '+' => Node::Plus(Box::new(node)),
+                '?' => Node::Optional(Box::new(node)),
+                _ => break,
+            };
+            self.chars.next();
+        }
+
+        Ok(node)
+    }
+
+    fn atom(&mut self) -> Result<Node, String> {
+        let c = self.chars.next().ok_or("unexpected end of expression")?;
+
+        let events = match c {
+            'E' => Event::Eye.bit(),
+            'L' => Event::Light.bit(),
+            'D' => Event::Diffuse.bit(),
+            'G' => Event::Glossy.bit(),
+            'S' => Event::Specular.bit(),
+            'V' => Event::Volume.bit(),
+            '.' => {
+                Event::Diffuse.bit()
+                    | Event::Glossy.bit()
+                    | Event::Specular.bit()
+                    | Event::Volume.bit()
+            }
+            '(' | '[' => {
+                let close = if c == '(' { ')' } else { ']' };
+                let node = self.alternate()?;
+
+                if self.chars.next() != Some(close) {
+                    return Err(format!("missing '{}'", close));
+                }
+
+                return Ok(if c == '[' {
+                    Node::Optional(Box::new(node))
+                } else {
+                    node
+                });
+            }
+            _ => return Err(format!("unexpected '{}'", c)),
+        };
+
+        Ok(Node::Events(events))
+    }
+}
+
+/// A non-deterministic automaton, whose states are the bits of a set.
+#[derive(Clone, Debug, Default)]
+struct Automaton {
+    /// The events and target of every state, if it is not an epsilon state.
+    transitions: Vec<Option<(u8, usize)>>,
+    /// The epsilon targets of every state.
+    epsilon: Vec<Vec<usize>>,
+}
+
+impl Automaton {
+    fn add_state(&mut self) -> usize {
+        self.transitions.push(None);
+        self.epsilon.push(Vec::new());
+        self.transitions.len() - 1
+    }
+
+    /// Builds the fragment of a node (Thompson's construction).
+    ///
+    /// # Returns
+    /// * The start and end state of the fragment
+    fn build(&mut self, node: &Node) -> (usize, usize) {
+        match node {
+            Node::Events(events) => {
+                let start = self.add_state();
+                let end = self.add_state();
+                self.transitions[start] = Some((*events, end));
+                (start, end)
+            }
+            Node::Concat(nodes) => {
+                let (start, mut end) = self.build(&nodes[0]);
+                for node in &nodes[1..] {
+                    let (s, e) = self.build(node);
+                    self.epsilon[end].push(s);
+                    end = e;
+                }
+                (start, end)
+            }
+            Node::Alternate(nodes) => {
+                let start = self.add_state();
+                let end = self.add_state();
+                for node in nodes {
+                    let (s, e) = self.build(node);
+                    self.epsilon[start].push(s);
+                    self.epsilon[e].push(end);
+                }
+                (start, end)
+            }
+            Node::Star(node) => self.build_repeat(node, true, true),
+            Node::Plus(node) => self.build_repeat(node, false, true),
+            Node::Optional(node) => self.build_repeat(node, true, false),
+        }
+    }
+
+    /// Builds the fragment of a repeated node.
+    ///
+    /// # Arguments
+    /// * `node` - The repeated node
+    /// * `skip` - Whether the node may be skipped
+    /// * `repeat` - Whether the node may be repeated
+    fn build_repeat(&mut self, node: &Node, skip: bool, repeat: bool) -> (usize, usize) {
+        let start = self.add_state();
+        let end = self.add_state();
+        let (s, e) = self.build(node);
+
+        self.epsilon[start].push(s);
+        self.epsilon[e].push(end);
+        if skip {
+            self.epsilon[start].push(end);
+        }
+        if repeat {
+            self.epsilon[e].push(s);
+        }
+
+        (start, end)
+    }
+}
+
+/// A compiled light path expression.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Expression {
+    source: String,
+    /// The set of states reachable from every state without consuming an event.
+    closures: Vec<u128>,
+    transitions: Vec<Option<(u8, usize)>>,
+    start: u128,
+    accept: u128,
+}
+
+impl Expression {
+    /// Compiles an expression.
+    ///
+    /// # Arguments
+    /// * `source` - The expression (see the [module](self) for its syntax)
+    ///
+    /// # Returns
+    /// * The compiled expression or an error
+    pub fn new(source: &str) -> Result<Self, String> {
+        let node = Parser::new(source)
+            .parse()
+            .map_err(|e| format!("invalid light path expression '{}': {}", source, e))?;
+
+        let mut automaton = Automaton::default();
+        let (start, end) = automaton.build(&node);
+
+        let num_states = automaton.transitions.len();
+        if num_states > MAX_STATES {
+            return Err(format!(
+                "light path expression '{}' is too long ({} states, at most {})",
+                source, num_states, MAX_STATES
+            ));
+        }
+
+        let closures = (0..num_states)
+            .map(|state| {
+                let mut closure = 1u128 << state;
+                let mut stack = vec![state];
+                while let Some(s) = stack.pop() {
+                    for &t in &automaton.epsilon[s] {
+                        if closure & (1 << t) == 0 {
+                            closure |= 1 << t;
+                            stack.push(t);
+                        }
+                    }
+                }
+                closure
+            })
+            .collect::<Vec<_>>();
+
+        Ok(Self {
+            source: source.to_string(),
+            start: closures[start],
+            closures,
+            transitions: automaton.transitions,
+            accept: 1 << end,
+        })
+    }
+
+    /// Returns the states before any event.
+    #[inline]
+    pub const fn start(&self) -> u128 {
+        self.start
+    }
+
+    /// Advances the states by an event.
+    ///
+    /// # Returns
+    /// * The new states (empty if the path can never match)
+    pub fn step(&self, states: u128, event: Event) -> u128 {
+        let mut next = 0;
+        let mut remaining = states;
+
+        while remaining != 0 {
+            let state = remaining.trailing_zeros() as usize;
+            remaining &= remaining - 1;
+
+            if let Some((events, target)) = self.transitions[state] {
+                if events & event.bit() != 0 {
+                    next |= self.closures[target];
+                }
+            }
+        }
+
+        next
+    }
+
+    /// Returns whether the states accept the path.
+    #[inline]
+    pub const fn accepts(&self, states: u128) -> bool {
+        states & self.accept != 0
+    }
+
+    /// Returns whether a whole path matches.
+    pub fn matches(&self, events: &[Event]) -> bool {
+        let states = events
+            .iter()
+            .fold(self.start, |states, &e| self.step(states, e));
+        self.accepts(states)
+    }
+}
+
+impl TryFrom<String> for Expression {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::new(&value)
+    }
+}
+
+impl From<Expression> for String {
+    fn from(e: Expression) -> Self {
+        e.source
+    }
+}
+
+/// A named light path expression, which is saved as its own image.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct LightPath {
+    pub name: String,
+    pub expression: Expression,
+}
+
+/// The light path expressions of an integrator and the buffers they are rendered into.
+///
+/// Like the sensor, every expression averages the contributions of the pixel samples per
+/// wavelength and sums up the contributions splatted into other pixels (see [SplatBuffer]).
+/// The buffers are allocated with the first sample and not preserved in checkpoints.
+#[derive(Debug, Default, Deserialize)]
+#[serde(from = "Vec<LightPath>")]
+pub struct LightPaths {
+    paths: Vec<LightPath>,
+    resolution: OnceLock<UVec2>,
+    /// The averages of every expression, per pixel
+    pixels: OnceLock<Vec<Mutex<Vec<Pixel>>>>,
+    /// The splats of every expression
+    splats: OnceLock<Vec<SplatBuffer>>,
+}
+
+impl From<Vec<LightPath>> for LightPaths {
+    fn from(paths: Vec<LightPath>) -> Self {
+        Self {
+            paths,
+            resolution: OnceLock::new(),
+            pixels: OnceLock::new(),
+            splats: OnceLock::new(),
+        }
+    }
+}
+
+impl Serialize for LightPaths {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.paths.serialize(serializer)
+    }
+}
+
+impl LightPaths {
+    /// Returns whether there are no expressions.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    /// Returns the expressions.
+    pub fn paths(&self) -> &[LightPath] {
+        &self.paths
+    }
+
+    /// Starts tracking a new path carrying the given wavelengths, which has already left the eye.
+    pub fn tracker<I: SpectralIndices>(&self, indices: I) -> LightPathTracker<'_, I> {
+        LightPathTracker {
+            states: self
+                .paths
+                .iter()
+                .map(|p| p.expression.step(p.expression.start(), Event::Eye))
+                .collect(),
+            contributions: vec![I::Sampled::splat(0.0); self.paths.len()],
+            indices,
+            paths: self,
+        }
+    }
+
+    /// Returns the buffers, allocating them for the resolution of the sensor if necessary.
+    fn buffers(&self, resolution: UVec2) -> (&[Mutex<Vec<Pixel>>], &[SplatBuffer]) {
+        let resolution = *self.resolution.get_or_init(|| resolution);
+        let pixels = self.pixels.get_or_init(|| {
+            (0..resolution.y)
+                .flat_map(|y| (0..resolution.x).map(move |x| UVec2::new(x, y)))
+                .map(|position| Mutex::new(vec![Pixel::new(position); self.paths.len()]))
+                .collect()
+        });
+        let splats = self.splats.get_or_init(|| {
+            self.paths
+                .iter()
+                .map(|_| SplatBuffer::new(resolution))
+                .collect()
+        });
+
+        (pixels, splats)
+    }
+
+    /// Adds the contributions of a finished path to its pixel.
+    ///
+    /// # Arguments
+    /// * `tracker` - The tracker of the path
+    /// * `resolution` - The resolution of the sensor
+    /// * `position` - The pixel the path was started from
+    pub fn add<I: SpectralIndices>(
+        &self,
+        tracker: LightPathTracker<I>,
+        resolution: UVec2,
+        position: UVec2,
+    ) {
+        if self.is_empty() {
+            return;
+        }
+
+        let (pixels, _) = self.buffers(resolution);
+        let wavelengths = tracker.indices.wavelengths();
+
+        let mut pixel = pixels[(position.y * resolution.x + position.x) as usize].lock();
+        for (pixel, contribution) in pixel.iter_mut().zip(&tracker.contributions) {
+            for (i, &value) in contribution.as_slice().iter().enumerate() {
+                pixel.add_lambda(value, wavelengths.index(i));
+            }
+        }
+    }
+
+    /// Adds a sample without contributions (e.g. a primary ray escaping the scene) to a pixel.
+    ///
+    /// # Arguments
+    /// * `resolution` - The resolution of the sensor
+    /// * `position` - The pixel of the sample
+    pub fn add_none(&self, resolution: UVec2, position: UVec2) {
+        if self.is_empty() {
+            return;
+        }
+
+        let (pixels, _) = self.buffers(resolution);
+        let mut pixel = pixels[(position.y * resolution.x + position.x) as usize].lock();
+        pixel.iter_mut().for_each(Pixel::add_none);
+    }
+
+    /// Splats the contributions of a finished path into the pixel it is seen in.
+    ///
+    /// # Arguments
+    /// * `tracker` - The tracker of the path
+    /// * `resolution` - The resolution of the sensor
+    /// * `raster` - The raster position the path is seen at
+    pub fn splat<I: SpectralIndices>(
+        &self,
+        tracker: LightPathTracker<I>,
+        resolution: UVec2,
+        raster: Vec2,
+    ) {
+        if self.is_empty() {
+            return;
+        }
+
+        let (_, splats) = self.buffers(resolution);
+        let wavelengths = tracker.indices.wavelengths();
+
+        for (splats, contribution) in splats.iter().zip(&tracker.contributions) {
+            if contribution.is_black() {
+                continue;
+            }
+
+            for (i, &value) in contribution.as_slice().iter().enumerate() {
+                splats.add_lambda(raster, value, wavelengths.index(i));
+            }
+        }
+    }
+
+    /// Counts a splatting path carrying the given wavelengths (see [SplatBuffer::count]).
+    ///
+    /// # Arguments
+    /// * `indices` - The spectral indices of the path
+    /// * `resolution` - The resolution of the sensor
+    pub fn count_splats<I: SpectralIndices>(&self, indices: &I, resolution: UVec2) {
+        if self.is_empty() {
+            return;
+        }
+
+        let (_, splats) = self.buffers(resolution);
+        let wavelengths = indices.wavelengths();
+
+        for splats in splats {
+            (0..wavelengths.len()).for_each(|i| splats.count_lambda(wavelengths.index(i)));
+        }
+    }
+
+    pub fn reset(&self) {
+        for p in self.pixels.get().into_iter().flatten() {
+            p.lock().iter_mut().for_each(Pixel::reset);
+        }
+        for s in self.splats.get().into_iter().flatten() {
+            s.reset();
+        }
+    }
+
+    /// Returns the image of an expression.
+    ///
+    /// # Arguments
+    /// * `index` - The index of the expression
+    pub fn get_image(&self, index: usize) -> Image<f32> {
+        let resolution = self.resolution.get().copied().unwrap_or(UVec2::new(0, 0));
+        let mut image = ImageBuffer::new(resolution.x, resolution.y);
+
+        let splats = self.splats.get().map(|s| &s[index]);
+        for p in self.pixels.get().into_iter().flatten() {
+            let pixel = p.lock()[index];
+            let mut value = pixel.average;
+            if let Some(splats) = splats {
+                value += splats.get(pixel.position);
+            }
+
+            image.put_pixel(pixel.position.x, pixel.position.y, Rgb::from(value));
+        }
+
+        image
+    }
+}
+
+/// Tracks a single path carrying some wavelengths through the expressions of [LightPaths].
+pub struct LightPathTracker<'a, I: SpectralIndices> {
+    paths: &'a LightPaths,
+    indices: I,
+    states: Vec<u128>,
+    contributions: Vec<I::Sampled>,
+}
+
+impl<'a, I: SpectralIndices> LightPathTracker<'a, I> {
+    /// Returns the spectral indices carried by the path.
+    #[inline]
+    pub const fn indices(&self) -> &I {
+        &self.indices
+    }
+
+    /// Advances the path by a scattering event.
+    pub fn scatter(&mut self, event: Event) {
+        for (states, path) in self.states.iter_mut().zip(&self.paths.paths) {
+            *states = path.expression.step(*states, event);
+        }
+    }
+
+    /// Adds the contribution of an emitter hit by the path.
+    pub fn add_emitted(&mut self, spectrum: I::Sampled) {
+        self.add_direct(&[], spectrum);
+    }
+
+    /// Adds the contribution of an emitter connected to the current vertex of the path.
+    ///
+    /// # Arguments
+    /// * `events` - The scattering events from the current vertex to the emitter
+    /// * `spectrum` - The contribution
+    pub fn add_direct(&mut self, events: &[Event], spectrum: I::Sampled) {
+        if self.states.is_empty() || spectrum.is_black() {
+            return;
+        }
+
+        let paths = &self.paths.paths;
+        for ((states, contribution), path) in
+            self.states.iter().zip(&mut self.contributions).zip(paths)
+        {
+            let states = events
+                .iter()
+                .chain(&[Event::Light])
+                .fold(*states, |s, &e| path.expression.step(s, e));
+
+            if path.expression.accepts(states) {
+                contribution.add_assign(spectrum);
+            }
+        }
+    }
+
+    /// Branches off a path sharing the events of this one so far, without its contributions
+    /// (e.g. a strategy of a bidirectional path tracer or a single wavelength continuing alone).
+    ///
+    /// # Arguments
+    /// * `indices` - The spectral indices carried by the branch
+    pub fn branch<J: SpectralIndices>(&self, indices: J) -> LightPathTracker<'a, J> {
+        LightPathTracker {
+            paths: self.paths,
+            indices,
+            states: self.states.clone(),
+            contributions: vec![J::Sampled::splat(0.0); self.contributions.len()],
+        }
+    }
+
+    /// Adds the contributions of a branch carrying the same wavelengths.
+    pub fn join(&mut self, branch: Self) {
+        for (c, b) in self.contributions.iter_mut().zip(branch.contributions) {
+            c.add_assign(b);
+        }
+    }
+
+    /// Adds the contributions of a branch carrying only the `i`-th wavelength of this path.
+    pub fn join_lane(&mut self, i: usize, branch: LightPathTracker<'a, usize>) {
+        for (c, b) in self.contributions.iter_mut().zip(branch.contributions) {
+            c.as_mut_slice()[i] += b;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Event::*;
+
+    #[test]
+    fn matches_paths() {
+        let caustics = Expression::new("E S+ D L").unwrap();
+        assert!(caustics.matches(&[Eye, Specular, Diffuse, Light]));
+        assert!(caustics.matches(&[Eye, Specular, Specular, Diffuse, Light]));
+        assert!(!caustics.matches(&[Eye, Diffuse, Light]));
+        assert!(!caustics.matches(&[Eye, Specular, Diffuse, Diffuse, Light]));
+
+        let whitted = Expression::new("E[S*](D|G)L").unwrap();
+        assert!(whitted.matches(&[Eye, Glossy, Light]));
+        assert!(whitted.matches(&[Eye, Specular, Specular, Diffuse, Light]));
+        assert!(!whitted.matches(&[Eye, Light]));
+
+        let any = Expression::new("E.*L").unwrap();
+        assert!(any.matches(&[Eye, Light]));
+        assert!(any.matches(&[Eye, Glossy, Diffuse, Specular, Light]));
+    }
+
+    #[test]
+    fn classifies_evaluated_bxdf() {
+        use crate::bxdf::{
+            FresnelType, LambertianReflection, MicrofacetDistribution, MicrofacetReflection,
+            Roughness, BSDF,
+        };
+        use crate::{Spectrum, Vec3};
+
+        let bsdf = BSDF::new(vec![
+            Box::new(LambertianReflection::new(Spectrum::splat(0.5))),
+            Box::new(MicrofacetReflection::new(
+                Spectrum::splat(0.5),
+                MicrofacetDistribution::Ggx(Roughness::Isotropic(0.3)),
+                FresnelType::Noop,
+            )),
+        ]);
+        let normal = Vec3::unit_z();
+        let direction = Vec3::new(0.0, 0.3, 1.0);
+
+        // connections classify by the bxdf they evaluate, not by the bsdf containing a diffuse one
+        let evaluate = |sample| {
+            let (spectrum, flag) = bsdf.evaluate_with_flag(
+                normal,
+                direction,
+                direction,
+                sample,
+                BxDFFlag::empty(),
+                &(),
+            );
+            assert!(!spectrum.is_black());
+            Event::from(flag)
+        };
+        assert_eq!(Diffuse, evaluate(0.25));
+        assert_eq!(Glossy, evaluate(0.75));
+    }
+
+    #[test]
+    fn spectral_integrators_separate_light_paths() {
+        use crate::integrator::Integrator;
+
+        let integrators = [
+            "\"SpectralPath\": (max_depth: 4, sampler: Random, spectral_sampler: Random, direct_illum: All",
+            "\"PureHero\": (max_depth: 4, sampler: Random, direct_illum: All",
+            "\"SpectralSingle\": (max_depth: 4, sampler: Random, spectral_sampler: Random, direct_illum: All",
+            "\"VolumetricPath\": (max_depth: 4, sampler: Random, spectral_sampler: Random, direct_illum: All",
+            "\"Bdpt\": (max_depth: 4, sampler: Random, spectral_sampler: Random",
+        ];
+
+        for integrator in integrators.iter() {
+            let ron = format!(
+                "{{ {}, light_paths: [(name: \"caustics\", expression: \"E S+ D L\")]) }}",
+                integrator
+            );
+            let integrator: Box<dyn Integrator> = ron::from_str(&ron).unwrap();
+            let light_paths = integrator.light_paths().unwrap();
+
+            assert_eq!(1, light_paths.paths().len());
+            assert_eq!("caustics", light_paths.paths()[0].name);
+        }
+    }
+
+    #[test]
+    fn lanes_join_their_wavelength() {
+        use crate::PACKET_SIZE;
+
+        let paths = LightPaths::from(vec![LightPath {
+            name: "refraction".to_string(),
+            expression: Expression::new("E S L").unwrap(),
+        }]);
+        let mut indices = [0; PACKET_SIZE];
+        indices
+            .iter_mut()
+            .enumerate()
+            .for_each(|(i, index)| *index = 2 * i);
+        let mut tracker = paths.tracker(indices);
+
+        // the last wavelength refracts on its own and hits an emitter
+        let lane = PACKET_SIZE - 1;
+        let mut branch = tracker.branch(indices[lane]);
+        assert_eq!(indices[lane], *branch.indices());
+        branch.scatter(Specular);
+        branch.add_emitted(2.0);
+        tracker.join_lane(lane, branch);
+
+        let mut expected = [0.0; PACKET_SIZE];
+        expected[lane] = 2.0;
+        assert_eq!(expected, tracker.contributions[0]);
+    }
+
+    #[test]
+    fn invalid_expressions() {
+        assert!(Expression::new("").is_err());
+        assert!(Expression::new("E(D L").is_err());
+        assert!(Expression::new("E D X L").is_err());
+        assert!(Expression::new("E D L)").is_err());
+    }
+}
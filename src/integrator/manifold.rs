@@ -0,0 +1,629 @@
+//! Manifold next event estimation (MNEE) in the style of "Manifold Next Event Estimation"
+//! (Hanika et al. 2015).
+//!
+//! Emitters behind refractive surfaces (e.g. glass spheres) cannot be connected to by emitter
+//! sampling, leaving their caustics to be found by chance.
+//! MNEE connects such emitters anyway: the chain of refractions between a point and a sampled
+//! emitter point is seeded along the straight line between them and then moved onto the specular
+//! manifold (where every vertex fulfills Snell's law) with Newton iterations.
+//!
+//! Chains pass through one or two interfaces of a single object, whose geometry has to be
+//! [projectable](Geometry::project) and whose BSDF has to be a single
+//! [refraction](crate::bxdf::BSDF::refraction).
+//! The indices of refraction are evaluated per wavelength, so caustics disperse.
+//! Chains that are not reachable from their seed are missed, which makes the estimate slightly
+//! biased.
+
+use crate::bxdf::{fresnel_dielectric, refraction::RefractiveType, BxDFFlag, FresnelDielectric};
+use crate::color::{SampledSpectrum, SpectralIndices, Wavelengths};
+use crate::geometry::{offset_ray_to, CoordinateSystem, Geometry, Ray};
+use crate::integrator::lpe::Event;
+use crate::sampler::FloatSampler;
+use crate::scene::{Emitter, OcclusionTester, Scene, SceneIntersection, SceneObject};
+use crate::util::floats;
+use crate::{Float, Spectrum, Vec3};
+use cgmath::InnerSpace;
+
+/// The maximum number of refractions of a chain.
+pub const MAX_REFRACTIONS: usize = 2;
+
+/// The maximum number of Newton iterations to solve a chain.
+const MAX_ITERATIONS: usize = 32;
+
+/// The residual of the constraints at which a chain is solved.
+const TOLERANCE: Float = 1e-6;
+
+/// The residual at which a chain is accepted if the iterations stall due to floating point
+/// precision.
+const ACCEPTANCE: Float = 1e-4;
+
+/// The step of the finite differences, relative to the size of the geometry.
+const STEP: Float = 1e-3;
+
+/// The step of the finite differences of the emitter point, relative to its distance.
+const EMITTER_STEP: Float = 1e-2;
+
+/// A refractive surface that chains can be solved through.
+#[derive(Copy, Clone)]
+pub struct Refraction<'a> {
+    geometry: &'a dyn Geometry,
+    fresnel: FresnelDielectric,
+    transmission: Spectrum,
+}
+
+impl<'a> Refraction<'a> {
+    /// Returns the refraction of an object at a point on its surface, if chains can be solved
+    /// through it.
+    pub fn of(object: &'a SceneObject, point: Vec3) -> Option<Self> {
+        match object {
+            SceneObject::Emitter(_) => None,
+            SceneObject::Receiver(r) => {
+                let (fresnel, transmission) = r.bsdf.refraction()?;
+                r.geometry.project(point)?;
+
+                Some(Self {
+                    geometry: r.geometry.as_ref(),
+                    fresnel,
+                    transmission,
+                })
+            }
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Vertex {
+    point: Vec3,
+    normal: Vec3,
+}
+
+/// The indices of refraction on the side of the normal (`outside`) and the opposite side.
+#[derive(Copy, Clone, Debug)]
+struct Eta {
+    outside: Float,
+    inside: Float,
+}
+
+impl Eta {
+    #[inline]
+    fn towards(&self, direction: Vec3, normal: Vec3) -> Float {
+        if direction.dot(normal) > 0.0 {
+            self.outside
+        } else {
+            self.inside
+        }
+    }
+}
+
+/// A chain of one or two refractions through the same surface.
+pub struct Chain<'a> {
+    refraction: Refraction<'a>,
+    vertices: [Vertex; 2],
+    len: usize,
+}
+
+/// A chain solved for a wavelength.
+pub struct Connection {
+    /// The direction from the point towards the first vertex
+    pub incident: Vec3,
+    /// The fraction of light transmitted by the Fresnel term of all vertices
+    pub fresnel: Float,
+    /// The change of the solid angle at the point with respect to the area at the emitter
+    pub jacobian: Float,
+    points: [Vec3; 4],
+    len: usize,
+}
+
+impl Connection {
+    /// Returns the occlusion testers of the segments between the point, the vertices and the
+    /// emitter.
+    pub fn segments(&self) -> impl Iterator<Item = OcclusionTester> + '_ {
+        self.points[..self.len]
+            .windows(2)
+            .map(|w| OcclusionTester::between(w[0], w[1]))
+    }
+
+    /// Returns the direction from the emitter towards the last vertex.
+    pub fn exitant(&self) -> Vec3 {
+        (self.points[self.len - 2] - self.points[self.len - 1]).normalize()
+    }
+}
+
+impl<'a> Chain<'a> {
+    /// Seeds a chain with the intersections of the straight line towards a target with the first
+    /// surface on it, if it is refractive.
+    ///
+    /// # Arguments
+    /// * `scene` - The scene to intersect against
+    /// * `point` - The start of the chain
+    /// * `normal` - The surface normal at the start
+    /// * `target` - The end of the chain
+    pub fn seed(scene: &'a Scene, point: Vec3, normal: Vec3, target: Vec3) -> Option<Self> {
+        let ray = offset_ray_to(point, normal, target);
+        let hit = scene.intersect(ray)?;
+        let refraction = Refraction::of(hit.object, hit.i.point)?;
+
+        let first = Vertex {
+            point: hit.i.point,
+            normal: hit.i.normal,
+        };
+        let mut chain = Self {
+            refraction,
+            vertices: [first; 2],
+            len: 1,
+        };
+
+        let direction = target - first.point;
+        let distance = direction.magnitude();
+        let ray = Ray::new2(
+            first.point,
+            direction / distance,
+            floats::BIG_EPSILON,
+            distance - floats::BIG_EPSILON,
+        );
+        if let Some(i) = refraction.geometry.intersect(ray) {
+            chain.vertices[1] = Vertex {
+                point: i.point,
+                normal: i.normal,
+            };
+            chain.len = 2;
+        }
+
+        Some(chain)
+    }
+
+    /// Returns the number of refractions.
+    #[inline]
+    pub const fn refractions(&self) -> usize {
+        self.len
+    }
+
+    /// Returns the transmission color of the refractive surface.
+    #[inline]
+    pub const fn transmission(&self) -> Spectrum {
+        self.refraction.transmission
+    }
+
+    /// Solves the chain between a point and an emitter point for a wavelength.
+    ///
+    /// # Arguments
+    /// * `point` - The start of the chain
+    /// * `target` - The point on the emitter
+    /// * `target_normal` - The surface normal of the emitter
+    /// * `n` - Evaluates the index of refraction of the wavelength
+    pub fn connect(
+        &self,
+        point: Vec3,
+        target: Vec3,
+        target_normal: Vec3,
+        n: impl Fn(RefractiveType) -> Float,
+    ) -> Option<Connection> {
+        let eta = Eta {
+            outside: n(self.refraction.fresnel.eta_i),
+            inside: n(self.refraction.fresnel.eta_t),
+        };
+
+        let mut vertices = self.vertices;
+        let len = self.len;
+        self.solve(point, target, &mut vertices[..len], eta)?;
+
+        let incident = (vertices[0].point - point).normalize();
+
+        // the change of the direction at the point for perturbations of the emitter point
+        let frame = CoordinateSystem::from_y(target_normal);
+        let step = EMITTER_STEP * (target - vertices[len - 1].point).magnitude();
+        let mut differences = [Vec3::new(0.0, 0.0, 0.0); 2];
+        for (difference, tangent) in differences.iter_mut().zip([frame.x_axis, frame.z_axis]) {
+            let mut directions = [incident; 2];
+            for (direction, sign) in directions.iter_mut().zip([1.0, -1.0]) {
+                let mut perturbed = vertices;
+                let moved = target + tangent * (sign * step);
+                self.solve(point, moved, &mut perturbed[..len], eta)?;
+                *direction = (perturbed[0].point - point).normalize();
+            }
+            *difference = (directions[0] - directions[1]) / (2.0 * step);
+        }
+        let jacobian = differences[0].cross(differences[1]).dot(incident).abs();
+
+        let mut fresnel = 1.0;
+        let mut points = [point; 4];
+        for (i, vertex) in vertices[..len].iter().enumerate() {
+            let previous = if i == 0 { point } else { vertices[i - 1].point };
+            let cos = (previous - vertex.point).normalize().dot(vertex.normal);
+
+            fresnel *= 1.0 - fresnel_dielectric(cos, eta.outside, eta.inside);
+            points[i + 1] = vertex.point;
+        }
+        points[len + 1] = target;
+
+        if jacobian.is_finite() && jacobian > 0.0 {
+            Some(Connection {
+                incident,
+                fresnel,
+                jacobian,
+                points,
+                len: len + 2,
+            })
+        } else {
+            None
+        }
+    }
+
+    /// Moves the vertices onto the specular manifold with Newton iterations.
+    ///
+    /// # Returns
+    /// * `Some` if the chain was solved
+    fn solve(&self, start: Vec3, end: Vec3, vertices: &mut [Vertex], eta: Eta) -> Option<()> {
+        let step = STEP * self.refraction.geometry.bounds().size().magnitude();
+        let dimension = 2 * vertices.len();
+
+        for _ in 0..MAX_ITERATIONS {
+            let tangents = frames(vertices);
+            let residuals = constraints(start, end, vertices, &tangents, eta)?;
+            let residual = norm(&residuals[..dimension]);
+            if residual < TOLERANCE {
+                return Some(());
+            }
+
+            // the jacobian of the constraints with respect to the tangential offsets
+            let mut jacobian = [[0.0; 4]; 4];
+            for column in 0..dimension {
+                let mut perturbed = [vertices[0]; 2];
+                perturbed[..vertices.len()].copy_from_slice(vertices);
+                let perturbed = &mut perturbed[..vertices.len()];
+
+                let mut offset = [0.0; 4];
+                offset[column] = step;
+                self.offset(perturbed, &tangents, &offset)?;
+
+                let c = constraints(start, end, perturbed, &tangents, eta)?;
+                for row in 0..dimension {
+                    jacobian[row][column] = (c[row] - residuals[row]) / step;
+                }
+            }
+
+            let mut delta = residuals.map(|c| -c);
+            solve_linear(&mut jacobian, &mut delta, dimension)?;
+
+            // halve the step until the residual decreases
+            let mut scale = 1.0;
+            loop {
+                let mut candidate = [vertices[0]; 2];
+                candidate[..vertices.len()].copy_from_slice(vertices);
+                let candidate = &mut candidate[..vertices.len()];
+
+                let offset = delta.map(|d| d * scale);
+                let improved = self.offset(candidate, &tangents, &offset).and_then(|_| {
+                    let c = constraints(start, end, candidate, &frames(candidate), eta)?;
+                    Some(norm(&c[..dimension]) < residual)
+                });
+
+                if improved == Some(true) {
+                    vertices.copy_from_slice(candidate);
+                    break;
+                }
+
+                scale *= 0.5;
+                if scale < 1e-3 {
+                    return if residual < ACCEPTANCE {
+                        Some(())
+                    } else {
+                        None
+                    };
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Moves the vertices along their tangents and projects them back onto the surface.
+    fn offset(
+        &self,
+        vertices: &mut [Vertex],
+        frames: &[CoordinateSystem; 2],
+        offset: &[Float; 4],
+    ) -> Option<()> {
+        for (i, vertex) in vertices.iter_mut().enumerate() {
+            let frame = frames[i];
+            let moved =
+                vertex.point + frame.x_axis * offset[2 * i] + frame.z_axis * offset[2 * i + 1];
+            let (point, normal) = self.refraction.geometry.project(moved)?;
+
+            *vertex = Vertex { point, normal };
+        }
+
+        Some(())
+    }
+}
+
+/// Returns the tangent frames of the vertices.
+fn frames(vertices: &[Vertex]) -> [CoordinateSystem; 2] {
+    let mut frames = [CoordinateSystem::default(); 2];
+    for (frame, vertex) in frames.iter_mut().zip(vertices) {
+        *frame = CoordinateSystem::from_y(vertex.normal);
+    }
+
+    frames
+}
+
+/// Computes the specular constraints of the vertices, which are the tangential components of the
+/// generalized half vectors projected onto the given frames.
+///
+/// # Returns
+/// * `None` if a vertex does not refract
+fn constraints(
+    start: Vec3,
+    end: Vec3,
+    vertices: &[Vertex],
+    frames: &[CoordinateSystem; 2],
+    eta: Eta,
+) -> Option<[Float; 4]> {
+    let mut constraints = [0.0; 4];
+
+    for (i, vertex) in vertices.iter().enumerate() {
+        let previous = if i == 0 { start } else { vertices[i - 1].point };
+        let next = vertices.get(i + 1).map_or(end, |v| v.point);
+
+        let incoming = (previous - vertex.point).normalize();
+        let outgoing = (next - vertex.point).normalize();
+        let normal = vertex.normal;
+        if incoming.dot(normal) * outgoing.dot(normal) >= 0.0 {
+            return None;
+        }
+
+        let half =
+            incoming * eta.towards(incoming, normal) + outgoing * eta.towards(outgoing, normal);
+        let tangential = half - normal * half.dot(normal);
+
+        constraints[2 * i] = tangential.dot(frames[i].x_axis);
+        constraints[2 * i + 1] = tangential.dot(frames[i].z_axis);
+    }
+
+    Some(constraints)
+}
+
+#[inline]
+fn norm(values: &[Float]) -> Float {
+    values.iter().map(|v| v * v).sum::<Float>().sqrt()
+}
+
+/// Solves the linear system `a * x = b` in place with Gaussian elimination.
+///
+/// # Returns
+/// * `None` if the system is singular
+fn solve_linear(a: &mut [[Float; 4]; 4], b: &mut [Float; 4], n: usize) -> Option<()> {
+    for column in 0..n {
+        let pivot =
+            (column..n).max_by(|&i, &j| a[i][column].abs().total_cmp(&a[j][column].abs()))?;
+        if a[pivot][column].abs() < Float::EPSILON {
+            return None;
+        }
+        a.swap(column, pivot);
+        b.swap(column, pivot);
+
+        for row in column + 1..n {
+            let factor = a[row][column] / a[column][column];
+            let pivot_row = a[column];
+            for (value, pivot_value) in a[row][column..n].iter_mut().zip(&pivot_row[column..n]) {
+                *value -= factor * pivot_value;
+            }
+            b[row] -= factor * b[column];
+        }
+    }
+
+    for row in (0..n).rev() {
+        let sum = (row + 1..n).map(|k| a[row][k] * b[k]).sum::<Float>();
+        b[row] = (b[row] - sum) / a[row][row];
+    }
+
+    Some(())
+}
+
+/// Samples a point on the emitter and seeds a chain towards it.
+fn seed<'a>(
+    scene: &'a Scene,
+    hit: &SceneIntersection,
+    emitter: &Emitter,
+    sampler: &FloatSampler,
+) -> Option<(Chain<'a>, Vec3, Vec3, Float)> {
+    if emitter.is_delta() || !hit.object.bsdf().is_non_specular() {
+        return None;
+    }
+
+    let sample = emitter.geometry.sample_area(sampler.vec2());
+    if sample.pdf <= 0.0 {
+        return None;
+    }
+
+    let chain = Chain::seed(scene, hit.i.point, hit.i.normal, sample.point)?;
+
+    Some((chain, sample.point, sample.normal, sample.pdf))
+}
+
+/// Connects an emitter through a refractive surface.
+///
+/// The chain is solved once if all wavelengths are sampled at once, and for every wavelength on
+/// its own otherwise, so that caustics disperse.
+///
+/// # Arguments
+/// * `scene` - The scene
+/// * `hit` - The intersection to connect from
+/// * `emitter` - The emitter to connect to
+/// * `sampler` - The sampler
+/// * `indices` - The spectral indices to sample
+/// * `observe` - Observes the contribution of every solved chain with its scattering events
+pub fn sample<I: SpectralIndices>(
+    scene: &Scene,
+    hit: &SceneIntersection,
+    emitter: &Emitter,
+    sampler: &FloatSampler,
+    indices: &I,
+    observe: &mut impl FnMut(&[Event], I::Sampled),
+) -> I::Sampled {
+    let mut illum = I::Sampled::splat(0.0);
+    let (chain, target, target_normal, pdf) = match seed(scene, hit, emitter, sampler) {
+        Some(s) => s,
+        None => return illum,
+    };
+
+    // the intersection is followed by a specular event for every refraction
+    let mut events = [Event::Specular; MAX_REFRACTIONS + 1];
+    let events = &mut events[..chain.refractions() + 1];
+
+    match indices.wavelengths() {
+        Wavelengths::All => {
+            let (value, flag) = connect(
+                scene,
+                hit,
+                emitter,
+                sampler,
+                indices,
+                None,
+                &chain,
+                target,
+                target_normal,
+            );
+
+            illum = value.mul_t(1.0 / pdf);
+            events[0] = Event::from(flag);
+            observe(events, illum);
+        }
+        Wavelengths::Indices(indices) => {
+            for (i, index) in indices.iter().enumerate() {
+                let (value, flag) = connect(
+                    scene,
+                    hit,
+                    emitter,
+                    sampler,
+                    index,
+                    Some(Spectrum::lambda(*index)),
+                    &chain,
+                    target,
+                    target_normal,
+                );
+
+                let mut lane = I::Sampled::splat(0.0);
+                lane.as_mut_slice()[i] = value / pdf;
+                illum.as_mut_slice()[i] = value / pdf;
+                events[0] = Event::from(flag);
+                observe(events, lane);
+            }
+        }
+    }
+
+    illum
+}
+
+/// Solves the chain for a wavelength and computes its contribution with respect to the area of
+/// the emitter.
+///
+/// # Arguments
+/// * `indices` - The spectral indices to evaluate the contribution at
+/// * `lambda` - The wavelength to solve the chain for (`None` for the uniform indices of
+///   refraction)
+///
+/// # Returns
+/// * The contribution and the flag of the BxDF evaluated at the intersection
+#[allow(clippy::too_many_arguments)]
+fn connect<I: SpectralIndices>(
+    scene: &Scene,
+    hit: &SceneIntersection,
+    emitter: &Emitter,
+    sampler: &FloatSampler,
+    indices: &I,
+    lambda: Option<Float>,
+    chain: &Chain,
+    target: Vec3,
+    target_normal: Vec3,
+) -> (I::Sampled, BxDFFlag) {
+    let black = (I::Sampled::splat(0.0), BxDFFlag::empty());
+    let c = match chain.connect(hit.i.point, target, target_normal, |n| n.n_at(lambda)) {
+        Some(c) => c,
+        None => return black,
+    };
+
+    let (spectrum, flag) = hit.object.bsdf().evaluate_with_flag(
+        hit.i.normal,
+        c.incident,
+        -hit.i.incoming,
+        sampler.float(),
+        BxDFFlag::empty(),
+        indices,
+    );
+    if spectrum.is_black() {
+        return black;
+    }
+
+    let mut transmittance = I::Sampled::splat(1.0);
+    for segment in c.segments() {
+        transmittance = transmittance.mul(segment.unoccluded(scene, *sampler, indices));
+    }
+
+    let mut transmission = I::Sampled::splat(1.0);
+    for _ in 0..chain.refractions() {
+        transmission = transmission.mul(indices.select(&chain.transmission()));
+    }
+
+    let cos = c.incident.dot(hit.i.normal).abs();
+    let contribution = spectrum
+        .mul(transmission)
+        .mul(emitter.radiance_towards(indices, target_normal, c.exitant()))
+        .mul(transmittance)
+        .mul_t(cos * c.fresnel * c.jacobian);
+
+    (contribution, flag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::Sphere;
+
+    #[test]
+    fn solved_chain_refracts() {
+        let sphere = Sphere::new(Vec3::new(0.0, 0.0, 0.0), 1.0);
+        let fresnel = FresnelDielectric::new(RefractiveType::Air, RefractiveType::Glass);
+        let eta = Eta {
+            outside: fresnel.eta_i.n_uniform(),
+            inside: fresnel.eta_t.n_uniform(),
+        };
+
+        let start = Vec3::new(0.3, 0.1, 3.0);
+        let end = Vec3::new(-0.2, 0.4, -3.0);
+        let seed = |z: Float| {
+            let (point, normal) = sphere.project(Vec3::new(0.0, 0.2, z)).unwrap();
+            Vertex { point, normal }
+        };
+        let chain = Chain {
+            refraction: Refraction {
+                geometry: &sphere,
+                fresnel,
+                transmission: Spectrum::splat(1.0),
+            },
+            vertices: [seed(1.0), seed(-1.0)],
+            len: 2,
+        };
+
+        let mut vertices = chain.vertices;
+        assert!(chain.solve(start, end, &mut vertices, eta).is_some());
+
+        // snell's law holds at both vertices
+        let path = [start, vertices[0].point, vertices[1].point, end];
+        for (i, vertex) in vertices.iter().enumerate() {
+            let incoming = (path[i] - vertex.point).normalize();
+            let outgoing = (path[i + 2] - vertex.point).normalize();
+
+            let sin_in = incoming.cross(vertex.normal).magnitude();
+            let sin_out = outgoing.cross(vertex.normal).magnitude();
+            let (eta_in, eta_out) = if i == 0 {
+                (eta.outside, eta.inside)
+            } else {
+                (eta.inside, eta.outside)
+            };
+
+            assert!((eta_in * sin_in - eta_out * sin_out).abs() < 1e-3);
+            assert!(incoming.dot(vertex.normal) * outgoing.dot(vertex.normal) < 0.0);
+        }
+    }
+}
@@ -16,28 +16,47 @@
 //! * **Kajiya** path tracing: `E[(D|G|S)+(D|G)]L`
 //! * **Goral** radiosity: `ED*L`
 
+pub mod bdpt;
 pub mod dummy;
+pub mod guiding;
 pub mod hero;
+pub mod light;
+pub mod lpe;
+pub mod manifold;
 pub mod path;
+pub mod pssmlt;
+pub mod radiosity;
 pub mod spectral;
 pub mod spectral_single;
+pub mod sppm;
+pub mod tracer;
+pub mod volumetric;
 pub mod whitted;
 
+pub use bdpt::*;
 pub use hero::*;
+pub use light::*;
 pub use path::*;
+pub use pssmlt::*;
+pub use radiosity::*;
 pub use spectral::*;
 pub use spectral_single::*;
+pub use sppm::*;
+pub use volumetric::*;
 pub use whitted::*;
 
-use crate::camera::sensor::Pixel;
+use crate::camera::sensor::{Pixel, SplatBuffer};
+use crate::camera::Camera;
+use crate::color::{SampledSpectrum, SpectralIndices};
 use crate::geometry::Ray;
+use crate::integrator::lpe::{Event, LightPaths};
 use crate::sampler::FloatSampler;
-use crate::scene::{Scene, SceneIntersection};
-use crate::{Float, Spectrum, PACKET_SIZE};
+use crate::scene::{Emitter, EmitterSample, Scene, SceneIntersection};
+use crate::{Float, Vec3};
 use cgmath::InnerSpace;
 
-use crate::bxdf::BxDFFlag;
-use crate::util::PacketOps;
+use crate::bxdf::{BxDFFlag, BxDFSamples, BSDF};
+use crate::util::mc::{balance_heuristic, power_heuristic};
 use serde::{Deserialize, Serialize};
 
 #[typetag::serde]
@@ -46,12 +65,199 @@ pub trait Integrator: Send + Sync {
     ///
     /// # Arguments
     /// * `scene` - The scene to integrate
+    /// * `camera` - The camera that shot the primary ray
     /// * `primary_ray` - The primary ray shot into the scene
     /// * `pixel` - The pixel to integrate into
+    /// * `splats` - The buffer to splat contributions to other pixels into
     ///
     /// # Returns
     /// * The color spectrum of the given ray
-    fn integrate(&self, scene: &Scene, primary_ray: Ray, pixel: &mut Pixel);
+    fn integrate(
+        &self,
+        scene: &Scene,
+        camera: &dyn Camera,
+        primary_ray: Ray,
+        pixel: &mut Pixel,
+        splats: &SplatBuffer,
+    );
+
+    /// Returns the phases of this integrator, if its passes have to be synchronized.
+    fn progressive(&self) -> Option<&dyn Progressive> {
+        None
+    }
+
+    /// Returns the light path expressions this integrator separates its contributions into, if
+    /// it supports any.
+    fn light_paths(&self) -> Option<&LightPaths> {
+        None
+    }
+
+    /// Returns whether all contributions are splatted instead of added to the pixel of the
+    /// primary ray (e.g. light tracing).
+    fn splats_only(&self) -> bool {
+        false
+    }
+}
+
+/// An integrator that shares state between all pixels of a pass (e.g. a photon map).
+///
+/// The [Runtime](crate::runtime::Runtime) runs every pass in synchronized phases:
+/// 1. [Self::begin_pass] once
+/// 2. [Self::run_job] for all [Self::num_jobs] jobs in parallel
+/// 3. [Self::end_jobs] once
+/// 4. [Integrator::integrate] for every pixel in parallel
+pub trait Progressive: Send + Sync {
+    /// Prepares a new pass.
+    ///
+    /// # Arguments
+    /// * `scene` - The scene to integrate
+    /// * `camera` - The camera of the sensor
+    /// * `pass` - The index of the pass
+    fn begin_pass(&self, scene: &Scene, camera: &dyn Camera, pass: usize);
+
+    /// Returns the number of jobs of the current pass.
+    fn num_jobs(&self) -> usize;
+
+    /// Runs a single job of the current pass.
+    ///
+    /// # Arguments
+    /// * `scene` - The scene to integrate
+    /// * `camera` - The camera of the sensor
+    /// * `splats` - The buffer to splat contributions into
+    /// * `job` - The index of the job within `[0, `[Self::num_jobs]`)`
+    fn run_job(&self, scene: &Scene, camera: &dyn Camera, splats: &SplatBuffer, job: usize);
+
+    /// Finishes the jobs of the current pass, before its pixels are integrated.
+    fn end_jobs(&self);
+}
+
+/// The multiple importance sampling heuristic used to combine emitter sampling with BSDF sampling.
+#[derive(Copy, Clone, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+pub enum Heuristic {
+    /// Weights samples proportional to their pdf.
+    Balance,
+    /// Weights samples proportional to their squared pdf.
+    #[default]
+    Power,
+}
+
+impl Heuristic {
+    /// Computes the weight of a sample of strategy `f` when combined with strategy `g`.
+    ///
+    /// # Arguments
+    /// * `f_pdf` - The pdf of the strategy that generated the sample
+    /// * `g_pdf` - The pdf of the other strategy
+    #[inline]
+    pub fn weight(self, f_pdf: Float, g_pdf: Float) -> Float {
+        match self {
+            Heuristic::Balance => balance_heuristic(1, f_pdf, 1, g_pdf),
+            Heuristic::Power => power_heuristic(1, f_pdf, 1, g_pdf),
+        }
+    }
+}
+
+/// The Russian roulette policy, which terminates paths of low throughput early without bias.
+#[derive(Copy, Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
+pub enum RussianRoulette {
+    /// Paths only terminate at the maximum depth.
+    #[default]
+    Off,
+    /// Paths survive with a probability equal to their throughput.
+    Throughput {
+        /// The depth from which on paths may be terminated.
+        start_depth: u32,
+        /// The minimum probability of a path to survive.
+        min_survival: Float,
+    },
+}
+
+impl RussianRoulette {
+    /// Decides whether a path survives.
+    ///
+    /// # Arguments
+    /// * `depth` - The current depth of the path
+    /// * `throughput` - The maximum throughput over all wavelengths of the path
+    /// * `sampler` - The sampler of the decision
+    ///
+    /// # Returns
+    /// * The factor to scale the throughput by if the path survives (`None` to terminate)
+    pub fn survive(self, depth: u32, throughput: Float, sampler: FloatSampler) -> Option<Float> {
+        match self {
+            RussianRoulette::Throughput {
+                start_depth,
+                min_survival,
+            } if depth >= start_depth => {
+                let probability = throughput.max(min_survival).min(1.0);
+
+                if sampler.float() < probability {
+                    Some(1.0 / probability)
+                } else {
+                    None
+                }
+            }
+            _ => Some(1.0),
+        }
+    }
+}
+
+/// Describes the last scattering event along a path, which is needed to weight emitters hit by
+/// BSDF-sampled rays.
+#[derive(Copy, Clone, Debug)]
+pub struct Bounce {
+    /// The point the ray was scattered from.
+    pub origin: Vec3,
+    /// The pdf (with respect to the solid angle) of the scattered direction.
+    pub pdf: Float,
+    /// Whether the scattering was specular (emitter sampling cannot handle it).
+    pub specular: bool,
+    /// The number of specular refractions since the last non-specular scattering, if all of them
+    /// can be connected by manifold next event estimation.
+    pub refractions: Option<u8>,
+}
+
+impl Bounce {
+    /// Creates a bounce that manifold next event estimation does not connect from (e.g. in
+    /// media).
+    pub const fn new(origin: Vec3, pdf: Float, specular: bool) -> Self {
+        Self {
+            origin,
+            pdf,
+            specular,
+            refractions: None,
+        }
+    }
+
+    /// The bounce of a BSDF sample at an intersection following this bounce.
+    ///
+    /// # Arguments
+    /// * `hit` - The intersection the sample was scattered at
+    /// * `pdf` - The pdf of the sampled direction
+    /// * `flag` - The flag of the sampled BxDF
+    pub fn scatter(self, hit: &SceneIntersection, pdf: Float, flag: BxDFFlag) -> Self {
+        let refractions = if !flag.specular() {
+            Some(0)
+        } else if flag.contains(BxDFFlag::TRANSMISSION)
+            && manifold::Refraction::of(hit.object, hit.i.point).is_some()
+        {
+            self.refractions
+                .filter(|&n| (n as usize) < manifold::MAX_REFRACTIONS)
+                .map(|n| n + 1)
+        } else {
+            None
+        };
+
+        Self {
+            origin: hit.i.point,
+            pdf,
+            specular: flag.specular(),
+            refractions,
+        }
+    }
+
+    /// The bounce of a primary ray, which behaves like a specular bounce.
+    pub const fn primary(origin: Vec3) -> Self {
+        Self::new(origin, 1.0, true)
+    }
 }
 
 /// The direct illumination strategy.
@@ -65,93 +271,251 @@ pub enum DirectIllumination {
     None,
     /// Only count indirect illumination.
     Indirect,
+    /// Get direct illumination for all emitters, also through refractive surfaces with
+    /// [manifold next event estimation](manifold).
+    Manifold,
+    /// Get direct illumination for an emitter chosen proportional to its power.
+    Power,
+    /// Get direct illumination for an emitter chosen by its estimated contribution with a
+    /// [light tree](crate::scene::LightTree).
+    LightTree,
 }
 
 impl DirectIllumination {
-    pub fn emitter_indices(self, scene: &Scene, sample: Float) -> &[u32] {
+    /// Chooses the emitters to sample for direct illumination.
+    ///
+    /// # Arguments
+    /// * `scene` - The scene
+    /// * `point` - The point to illuminate
+    /// * `sample` - The random sample to choose a single emitter
+    ///
+    /// # Returns
+    /// * The object indices of the chosen emitters
+    pub fn emitter_indices(self, scene: &Scene, point: Vec3, sample: Float) -> &[u32] {
+        let slot = match self {
+            DirectIllumination::All | DirectIllumination::Manifold => return scene.emitters(),
+            DirectIllumination::Random if scene.num_emitters() > 0 => {
+                let num = scene.num_emitters();
+                Some(((sample * num as Float) as usize).min(num - 1))
+            }
+            DirectIllumination::Power => scene.power_distribution().sample(sample),
+            DirectIllumination::LightTree => scene.light_tree().sample(point, sample),
+            _ => None,
+        };
+
+        slot.map_or(&[], |s| &scene.emitters()[s..=s])
+    }
+
+    /// Returns whether this strategy samples emitters.
+    #[inline]
+    pub fn samples_emitters(self) -> bool {
+        matches!(
+            self,
+            DirectIllumination::All
+                | DirectIllumination::Random
+                | DirectIllumination::Manifold
+                | DirectIllumination::Power
+                | DirectIllumination::LightTree
+        )
+    }
+
+    /// Returns the probability with which an emitter is chosen to be sampled (see
+    /// [Self::emitter_indices]).
+    ///
+    /// # Arguments
+    /// * `scene` - The scene
+    /// * `point` - The point to illuminate
+    /// * `index` - The object index of the emitter
+    pub fn emitter_pdf(self, scene: &Scene, point: Vec3, index: u32) -> Float {
         match self {
-            DirectIllumination::All => scene.emitters(),
-            DirectIllumination::Random => {
-                let chosen = (sample * scene.num_emitters() as Float) as usize;
-                &scene.emitters()[chosen..chosen]
+            DirectIllumination::All | DirectIllumination::Manifold => 1.0,
+            DirectIllumination::Random if scene.num_emitters() > 0 => {
+                1.0 / scene.num_emitters() as Float
             }
-            _ => &[],
+            DirectIllumination::Power => scene
+                .emitter_slot(index)
+                .map_or(0.0, |s| scene.power_distribution().pdf(s)),
+            DirectIllumination::LightTree => scene
+                .emitter_slot(index)
+                .map_or(0.0, |s| scene.light_tree().pdf(point, s)),
+            _ => 0.0,
         }
     }
 
-    pub fn sample(
+    /// Computes the weight of an emitter that was hit by a BSDF-sampled ray.
+    ///
+    /// # Arguments
+    /// * `scene` - The scene
+    /// * `emitter` - The hit emitter
+    /// * `index` - The object index of the hit emitter
+    /// * `bounce` - The bounce that lead to the emitter
+    /// * `incident` - The direction of the ray that hit the emitter
+    /// * `heuristic` - The MIS heuristic (`None` to not weight at all)
+    pub fn emitter_weight(
         self,
         scene: &Scene,
-        hit: &SceneIntersection,
-        sampler: &FloatSampler,
-    ) -> Spectrum {
-        let mut illum = Spectrum::splat(0.0);
-        let bsdf = hit.object.bsdf();
-        if bsdf.is_empty() {
-            return illum;
+        emitter: &Emitter,
+        index: u32,
+        bounce: &Bounce,
+        incident: Vec3,
+        heuristic: Option<Heuristic>,
+    ) -> Float {
+        if self == DirectIllumination::Manifold && bounce.specular && bounce.refractions > Some(0) {
+            // already connected through the refractions by manifold next event estimation
+            return 0.0;
         }
 
-        let outgoing_world = -hit.i.incoming;
+        match heuristic {
+            Some(h) if !bounce.specular && self.samples_emitters() => {
+                let emitter_pdf = self.emitter_pdf(scene, bounce.origin, index)
+                    * emitter.pdf(bounce.origin, incident);
+                h.weight(bounce.pdf, emitter_pdf)
+            }
+            _ => 1.0,
+        }
+    }
 
-        let emitter_indices = self.emitter_indices(scene, sampler.float());
-        for emitter in emitter_indices
-            .iter()
-            .filter_map(|&i| scene.get_emitter(i as usize))
-        {
-            let emitter_sample = emitter.sample(hit.i.point, sampler.vec2());
+    /// Computes the radiance of the infinite emitters arriving along a ray that escaped the
+    /// scene, weighted like [Self::emitter_weight].
+    ///
+    /// # Arguments
+    /// * `scene` - The scene
+    /// * `bounce` - The bounce that lead to the escaped ray
+    /// * `direction` - The direction of the escaped ray
+    /// * `indices` - The spectral indices
+    /// * `heuristic` - The MIS heuristic (`None` to not weight at all)
+    pub fn escaped<I: SpectralIndices>(
+        self,
+        scene: &Scene,
+        bounce: &Bounce,
+        direction: Vec3,
+        indices: &I,
+        heuristic: Option<Heuristic>,
+    ) -> I::Sampled {
+        let mut radiance = I::Sampled::splat(0.0);
 
-            if !emitter_sample.radiance.is_black() && emitter_sample.occlusion.unoccluded(scene) {
-                let spectrum = bsdf.evaluate(
-                    hit.i.normal,
-                    emitter_sample.incident,
-                    outgoing_world,
-                    sampler.float(),
-                    BxDFFlag::empty(),
-                );
+        for &index in scene.infinite_emitters() {
+            let emitter = match scene.get_emitter(index as usize) {
+                Some(e) if !e.is_delta() => e,
+                _ => continue,
+            };
 
-                if !spectrum.is_black() {
-                    let cos = emitter_sample.incident.dot(hit.i.normal);
+            let weight = self.emitter_weight(scene, emitter, index, bounce, direction, heuristic);
+            if weight > 0.0 {
+                let emitted = emitter.radiance_towards(indices, -direction, -direction);
+                radiance.add_assign(emitted.mul_t(weight));
+            }
+        }
 
-                    if cos != 0.0 {
-                        let decay_inv = if emitter.decay {
-                            emitter_sample.decay_inv()
-                        } else {
-                            1.
-                        };
+        radiance
+    }
 
-                        illum += spectrum * emitter_sample.radiance * cos.abs() * decay_inv;
-                    }
-                }
-            }
+    /// Computes the factor of an emitter sample, consisting of the inverse pdf (including the
+    /// emitter choice) and the MIS weight.
+    ///
+    /// # Arguments
+    /// * `emitter` - The sampled emitter
+    /// * `emitter_pdf` - The probability of choosing the emitter (see [Self::emitter_pdf])
+    /// * `emitter_sample` - The emitter sample
+    /// * `bsdf_pdf` - Computes the pdf of the BSDF sampling the emitter sample direction
+    /// * `heuristic` - The MIS heuristic (`None` to not weight at all)
+    fn sample_factor<T>(
+        emitter: &Emitter,
+        emitter_pdf: Float,
+        emitter_sample: &EmitterSample<T>,
+        bsdf_pdf: impl FnOnce() -> Float,
+        heuristic: Option<Heuristic>,
+    ) -> Float {
+        let pdf = emitter_pdf * emitter_sample.pdf;
+
+        if emitter.is_delta() {
+            let decay_inv = if emitter.decay {
+                emitter_sample.decay_inv()
+            } else {
+                1.0
+            };
+
+            decay_inv / pdf
+        } else {
+            let weight = heuristic.map_or(1.0, |h| h.weight(pdf, bsdf_pdf()));
+
+            weight / pdf
         }
+    }
 
-        illum
+    /// Samples the emitters for direct illumination, weighting the samples against BSDF sampling.
+    ///
+    /// # Arguments
+    /// * `scene` - The scene
+    /// * `hit` - The intersection to illuminate
+    /// * `sampler` - The sampler
+    /// * `indices` - The spectral indices to sample
+    /// * `heuristic` - The MIS heuristic (`None` to not weight at all)
+    pub fn sample<I: SpectralIndices>(
+        self,
+        scene: &Scene,
+        hit: &SceneIntersection,
+        sampler: &FloatSampler,
+        indices: &I,
+        heuristic: Option<Heuristic>,
+    ) -> I::Sampled {
+        let bsdf = hit.object.bsdf();
+        let outgoing_world = -hit.i.incoming;
+        let bsdf_pdf =
+            |incident| bsdf.pdf(hit.i.normal, incident, outgoing_world, BxDFFlag::empty());
+
+        self.sample_with(scene, hit, sampler, indices, heuristic, bsdf_pdf, |_, _| {})
     }
 
-    pub fn sample_packet(
+    /// Samples the emitters like [Self::sample], but weights the samples against a custom
+    /// strategy of sampling directions (e.g. a mixture of BSDF sampling and path guiding).
+    ///
+    /// # Arguments
+    /// * `direction_pdf` - The solid angle pdf of the other strategy sampling an incident
+    ///   direction
+    /// * `observe` - Observes every contribution with the scattering events from the intersection
+    ///   to the emitter (see [lpe])
+    #[allow(clippy::too_many_arguments)]
+    pub fn sample_with<I: SpectralIndices>(
         self,
         scene: &Scene,
         hit: &SceneIntersection,
         sampler: &FloatSampler,
-        indices: &[usize; PACKET_SIZE],
-    ) -> [Float; PACKET_SIZE] {
-        let mut illum = [0.0; PACKET_SIZE];
+        indices: &I,
+        heuristic: Option<Heuristic>,
+        direction_pdf: impl Fn(Vec3) -> Float,
+        mut observe: impl FnMut(&[Event], I::Sampled),
+    ) -> I::Sampled {
+        let mut illum = I::Sampled::splat(0.0);
         let bsdf = hit.object.bsdf();
         if bsdf.is_empty() {
             return illum;
         }
 
         let outgoing_world = -hit.i.incoming;
+        let point = hit.i.point;
 
-        let emitter_indices = self.emitter_indices(scene, sampler.float());
-        for emitter in emitter_indices
-            .iter()
-            .filter_map(|&i| scene.get_emitter(i as usize))
-        {
-            let emitter_sample = emitter.sample_packet(hit.i.point, sampler.vec2(), indices);
+        for &index in self.emitter_indices(scene, point, sampler.float()) {
+            let emitter = match scene.get_emitter(index as usize) {
+                Some(e) => e,
+                None => continue,
+            };
 
-            if !emitter_sample.radiance.is_black() && emitter_sample.occlusion.unoccluded(scene) {
-                let spectrum = bsdf.evaluate_packet(
+            if self == DirectIllumination::Manifold {
+                illum.add_assign(manifold::sample(
+                    scene,
+                    hit,
+                    emitter,
+                    sampler,
+                    indices,
+                    &mut observe,
+                ));
+            }
+
+            let emitter_sample = emitter.sample(point, sampler.vec2(), indices);
+
+            if emitter_sample.pdf > 0.0 && !emitter_sample.radiance.is_black() {
+                let (spectrum, flag) = bsdf.evaluate_with_flag(
                     hit.i.normal,
                     emitter_sample.incident,
                     outgoing_world,
@@ -164,14 +528,24 @@ impl DirectIllumination {
                     let cos = emitter_sample.incident.dot(hit.i.normal);
 
                     if cos != 0.0 {
-                        let decay_inv = if emitter.decay {
-                            emitter_sample.decay_inv()
-                        } else {
-                            1.
-                        };
+                        let bsdf_pdf = || direction_pdf(emitter_sample.incident);
+                        let factor = Self::sample_factor(
+                            emitter,
+                            self.emitter_pdf(scene, point, index),
+                            &emitter_sample,
+                            bsdf_pdf,
+                            heuristic,
+                        );
+
+                        let transmittance = emitter_sample
+                            .occlusion
+                            .unoccluded(scene, *sampler, indices);
+
                         let rhs = spectrum
                             .mul(emitter_sample.radiance)
-                            .mul_t(cos.abs() * decay_inv);
+                            .mul(transmittance)
+                            .mul_t(cos.abs() * factor);
+                        observe(&[Event::from(flag)], rhs);
                         illum.add_assign(rhs)
                     }
                 }
@@ -180,55 +554,235 @@ impl DirectIllumination {
 
         illum
     }
+}
 
-    pub fn sample_lambda(
-        self,
-        scene: &Scene,
-        hit: &SceneIntersection,
-        sampler: &FloatSampler,
-        index: usize,
-    ) -> Float {
-        let mut illum = 0.0;
-        let bsdf = hit.object.bsdf();
-        if bsdf.is_empty() {
-            return illum;
+/// A BSDF sample for all wavelengths of a path.
+pub struct PathSample<S> {
+    /// The BSDF of every wavelength (`0` for the wavelengths that do not continue)
+    pub spectrum: S,
+    pub incident: Vec3,
+    pub pdf: Float,
+    pub flag: BxDFFlag,
+    /// The wavelength continuing the path alone, if the wavelengths were split
+    pub lane: Option<usize>,
+}
+
+/// Chooses the wavelength that continues a path alone once its wavelengths scatter into different
+/// directions (e.g. by dispersion).
+///
+/// Every integrator splits paths this way: a random wavelength still alive is chosen uniformly,
+/// so its throughput has to be multiplied by the number of alive wavelengths to stay unbiased.
+///
+/// # Arguments
+/// * `alive` - Whether every wavelength is still alive
+/// * `sample` - A uniform sample in `[0, 1)`
+///
+/// # Returns
+/// * The index of the chosen wavelength and its weight, or `None` if no wavelength is alive
+pub fn choose_split_lane(alive: &[bool], sample: Float) -> Option<(usize, Float)> {
+    let num_alive = alive.iter().filter(|&&a| a).count();
+    if num_alive == 0 {
+        return None;
+    }
+
+    let chosen = ((sample * num_alive as Float) as usize).min(num_alive - 1);
+    let index = (0..alive.len()).filter(|&i| alive[i]).nth(chosen)?;
+
+    Some((index, num_alive as Float))
+}
+
+/// Samples the BSDF for all wavelengths of a path.
+///
+/// If the wavelengths scatter into different directions (e.g. by dispersion), a single wavelength
+/// continues the path alone (see [choose_split_lane]), unless all of them still share one
+/// direction.
+///
+/// # Arguments
+/// * `bsdf` - The BSDF to sample
+/// * `normal` - The surface normal
+/// * `outgoing` - The outgoing direction in world space
+/// * `sampler` - The sampler for the BSDF and the wavelength choice
+/// * `indices` - The spectral indices
+/// * `beta` - The throughput of the path, adjusted if a single wavelength continues
+pub fn sample_bsdf<I: SpectralIndices>(
+    bsdf: &BSDF,
+    normal: Vec3,
+    outgoing: Vec3,
+    sampler: FloatSampler,
+    indices: &I,
+    beta: &mut I::Sampled,
+) -> Option<PathSample<I::Sampled>> {
+    let split = match bsdf.sample(
+        normal,
+        outgoing,
+        sampler.sample(),
+        BxDFFlag::empty(),
+        indices,
+    )? {
+        BxDFSamples::Bundle(s) => {
+            return Some(PathSample {
+                spectrum: s.spectrum,
+                incident: s.incident,
+                pdf: s.pdf,
+                flag: s.flag,
+                lane: None,
+            })
         }
+        BxDFSamples::Split(split) => split,
+    };
 
-        let outgoing_world = -hit.i.incoming;
+    let alive = split
+        .iter()
+        .zip(beta.as_slice())
+        .map(|(s, &b)| b != 0.0 && s.is_some_and(|s| s.pdf > 0.0 && s.spectrum != 0.0))
+        .collect::<Vec<_>>();
 
-        let emitter_indices = self.emitter_indices(scene, sampler.float());
-        for emitter in emitter_indices
-            .iter()
-            .filter_map(|&i| scene.get_emitter(i as usize))
-        {
-            let emitter_sample = emitter.sample_lambda(hit.i.point, sampler.vec2(), index);
+    let first = (0..alive.len()).find(|&i| alive[i])?;
+    let first_sample = split[first]?;
 
-            if emitter_sample.radiance != 0.0 && emitter_sample.occlusion.unoccluded(scene) {
-                let spectrum = bsdf.evaluate_lambda(
-                    hit.i.normal,
-                    emitter_sample.incident,
-                    outgoing_world,
-                    sampler.float(),
-                    BxDFFlag::empty(),
-                    index,
-                );
+    let coherent = (0..alive.len())
+        .filter(|&i| alive[i])
+        .all(|i| split[i].is_some_and(|s| s.incident == first_sample.incident));
 
-                // TODO: Ignore checks for single floats because performance?
-                if spectrum != 0.0 {
-                    let cos = emitter_sample.incident.dot(hit.i.normal);
+    let mut spectrum = I::Sampled::splat(0.0);
+    if coherent {
+        // all wavelengths still share one direction, scale to the pdf of the first one
+        for (i, f) in spectrum.as_mut_slice().iter_mut().enumerate() {
+            if let Some(s) = split[i].filter(|_| alive[i]) {
+                *f = s.spectrum * first_sample.pdf / s.pdf;
+            }
+        }
 
-                    if cos != 0.0 {
-                        let decay_inv = if emitter.decay {
-                            emitter_sample.decay_inv()
-                        } else {
-                            1.
-                        };
-                        illum += spectrum * emitter_sample.radiance * cos.abs() * decay_inv;
-                    }
+        return Some(PathSample {
+            spectrum,
+            incident: first_sample.incident,
+            pdf: first_sample.pdf,
+            flag: first_sample.flag,
+            lane: None,
+        });
+    }
+
+    let (index, weight) = choose_split_lane(&alive, sampler.float())?;
+    let sample = split[index]?;
+
+    spectrum.as_mut_slice()[index] = sample.spectrum;
+    for (i, b) in beta.as_mut_slice().iter_mut().enumerate() {
+        if i == index {
+            *b *= weight;
+        } else {
+            *b = 0.0;
+        }
+    }
+
+    Some(PathSample {
+        spectrum,
+        incident: sample.incident,
+        pdf: sample.pdf,
+        flag: sample.flag,
+        lane: Some(index),
+    })
+}
+
+/// Renders small scenes to compare integrators against each other.
+#[cfg(test)]
+pub(crate) mod testing {
+    use super::*;
+    use crate::camera::sensor::Pixel;
+    use crate::color::SampledSpectrum;
+    use crate::UVec2;
+
+    /// A diffuse quad lit by a disk emitter above it, seen at an angle without the emitter.
+    pub const QUAD_UNDER_LIGHT: &str = r#"(
+        objects: [
+            Receiver((
+                geometry: {
+                    "Aabb": (min: (x: -1, y: -0.1, z: -1), max: (x: 1, y: 0, z: 1)),
+                },
+                bsdf: (bxdfs: [{ "LambertianReflection": (r: Color(White)) }]),
+            )),
+            Emitter((
+                geometry: {
+                    "Disk": (center: (x: 0, y: 1, z: 0), normal: (x: 0, y: -1, z: 0), radius: 0.5),
+                },
+                emission: MulColor(4, White),
+            )),
+        ],
+    )"#;
+
+    /// Creates a camera of 8x8 pixels looking at the quad of [QUAD_UNDER_LIGHT].
+    pub fn camera() -> Box<dyn Camera> {
+        ron::from_str(
+            r#"{ "NaiveCamera": (
+                sampler: Random,
+                eye: (x: 0, y: 0.6, z: 1.8),
+                target: (x: 0, y: 0, z: 0),
+                up: (x: 0, y: 1, z: 0),
+                fov: 40,
+                resolution: (x: 8, y: 8),
+            ) }"#,
+        )
+        .unwrap()
+    }
+
+    /// Renders a scene with an integrator on a single thread like the
+    /// [Runtime](crate::runtime::Runtime).
+    ///
+    /// # Returns
+    /// * The average over all pixels and wavelengths
+    pub fn render(integrator: &str, scene: &str, passes: usize) -> Float {
+        let integrator: Box<dyn Integrator> = ron::from_str(integrator).unwrap();
+        let scene: Scene = ron::from_str(scene).unwrap();
+        let camera = camera();
+        let resolution = camera.resolution();
+        let splats = SplatBuffer::new(resolution);
+
+        let mut pixels: Vec<_> = (0..resolution.y)
+            .flat_map(|y| (0..resolution.x).map(move |x| Pixel::new(UVec2::new(x, y))))
+            .collect();
+
+        for pass in 0..passes {
+            if let Some(progressive) = integrator.progressive() {
+                progressive.begin_pass(&scene, camera.as_ref(), pass);
+                for job in 0..progressive.num_jobs() {
+                    progressive.run_job(&scene, camera.as_ref(), &splats, job);
                 }
+                progressive.end_jobs();
+            }
+
+            for pixel in &mut pixels {
+                let ray = camera.primary_ray(pixel.position);
+                integrator.integrate(&scene, camera.as_ref(), ray, pixel, &splats);
             }
         }
 
-        illum
+        let sum: Float = pixels
+            .iter()
+            .map(|p| (p.average + splats.get(p.position)).average())
+            .sum();
+
+        sum / pixels.len() as Float
+    }
+
+    /// The unbiased reference every integrator is compared against.
+    const REFERENCE: &str = r#"{ "Path": (max_depth: 4, sampler: Random, direct_illum: All) }"#;
+
+    /// Asserts that an integrator converges to the same mean as [REFERENCE] on
+    /// [QUAD_UNDER_LIGHT].
+    ///
+    /// # Arguments
+    /// * `integrator` - The integrator in RON
+    /// * `passes` - The number of passes to render both integrators with
+    /// * `tolerance` - The maximum relative difference of the means
+    pub fn assert_converges(integrator: &str, passes: usize, tolerance: Float) {
+        let expected = render(REFERENCE, QUAD_UNDER_LIGHT, passes);
+        let actual = render(integrator, QUAD_UNDER_LIGHT, passes);
+
+        assert!(expected > 0.0);
+        assert!(
+            (actual - expected).abs() <= tolerance * expected,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
     }
 }
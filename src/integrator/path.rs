@@ -1,70 +1,62 @@
-use crate::bxdf::BxDFFlag;
-use crate::camera::sensor::Pixel;
-use crate::geometry::{offset_ray_towards, Ray};
-use crate::integrator::{DirectIllumination, Integrator};
+use crate::camera::sensor::{Pixel, SplatBuffer};
+use crate::camera::Camera;
+use crate::geometry::Ray;
+use crate::integrator::lpe::LightPaths;
+use crate::integrator::tracer::PathTracer;
+use crate::integrator::{DirectIllumination, Heuristic, Integrator, RussianRoulette};
 use crate::sampler::FloatSampler;
-use crate::scene::{Scene, SceneObject};
-use crate::Spectrum;
-use cgmath::InnerSpace;
+use crate::scene::Scene;
 use serde::{Deserialize, Serialize};
 
-#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct Path {
     max_depth: u32,
     sampler: FloatSampler,
     direct_illum: DirectIllumination,
+    #[serde(default)]
+    heuristic: Heuristic,
+    #[serde(default)]
+    roulette: RussianRoulette,
+    /// The expressions to separate the contributions into
+    #[serde(default)]
+    #[serde(skip_serializing_if = "LightPaths::is_empty")]
+    light_paths: LightPaths,
+}
+
+impl Path {
+    fn tracer(&self) -> PathTracer<'_> {
+        PathTracer {
+            max_depth: self.max_depth,
+            sampler: self.sampler,
+            direct_illum: self.direct_illum,
+            heuristic: self.heuristic,
+            roulette: self.roulette,
+            guiding: None,
+            light_paths: &self.light_paths,
+        }
+    }
 }
 
 #[typetag::serde]
 impl Integrator for Path {
-    fn integrate(&self, scene: &Scene, primary_ray: Ray, pixel: &mut Pixel) {
-        if let Some(mut hit) = scene.intersect(primary_ray) {
-            let mut illumination = Spectrum::splat(0.0);
-            let mut throughput = Spectrum::splat(1.0);
-
-            for curr_depth in 0..self.max_depth {
-                let outgoing = -hit.i.incoming;
-                let point = hit.i.point;
-                let normal = hit.i.normal;
-                let bsdf = hit.object.bsdf();
-
-                if let SceneObject::Emitter(e) = &hit.object {
-                    if curr_depth != 1 || self.direct_illum != DirectIllumination::Indirect {
-                        illumination += throughput * e.radiance();
-                    }
-                }
-
-                illumination += throughput * self.direct_illum.sample(scene, &hit, &self.sampler);
-
-                if let Some(bxdf_sample) =
-                    bsdf.sample(normal, outgoing, self.sampler.sample(), BxDFFlag::empty())
-                {
-                    if bxdf_sample.pdf == 0.0 || bxdf_sample.spectrum.is_black() {
-                        break;
-                    }
-
-                    let cos_abs = if bxdf_sample.flag.specular() {
-                        // division of cosine omitted in specular bxdfs
-                        1.0
-                    } else {
-                        bxdf_sample.incident.dot(normal).abs()
-                    };
-
-                    throughput *= bxdf_sample.spectrum * (cos_abs / bxdf_sample.pdf);
-
-                    let ray = offset_ray_towards(point, normal, bxdf_sample.incident);
-                    match scene.intersect(ray) {
-                        Some(i) => hit = i,
-                        None => break,
-                    }
-                } else {
-                    break;
-                }
-            }
-
-            pixel.add(illumination);
-        } else {
-            pixel.add_none()
+    fn integrate(
+        &self,
+        scene: &Scene,
+        camera: &dyn Camera,
+        primary_ray: Ray,
+        pixel: &mut Pixel,
+        _: &SplatBuffer,
+    ) {
+        match self
+            .tracer()
+            .trace_pixel(scene, camera, primary_ray, &(), pixel.position)
+        {
+            Some(illumination) => pixel.add(illumination),
+            None => pixel.add_none(),
         }
     }
+
+    fn light_paths(&self) -> Option<&LightPaths> {
+        Some(&self.light_paths)
+    }
 }
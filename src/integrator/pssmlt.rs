@@ -0,0 +1,299 @@
+use crate::camera::sensor::{Pixel, SplatBuffer};
+use crate::camera::Camera;
+use crate::geometry::Ray;
+use crate::integrator::{Integrator, Progressive};
+use crate::sampler::{next_float, SampleStream};
+use crate::scene::Scene;
+use crate::{Float, Spectrum, UVec2, Vec2};
+use core::convert::TryFrom;
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+
+/// Primary sample space Metropolis light transport.
+///
+/// Instead of sampling every pixel independently, Markov chains mutate the random numbers
+/// consumed by another integrator, which allows exploring difficult paths (e.g. light entering a
+/// room through glass) once they are found.
+/// The pixel of a path is chosen by the chain as well, so that all contributions are splatted.
+///
+/// Before the first pass, a bootstrap phase estimates the normalization of the image and chooses
+/// the initial states of the chains.
+/// Every pass mutates as many samples as there are pixels, spread over all chains.
+///
+/// Only the pixel estimate of the wrapped integrator is used, contributions it splats itself
+/// (e.g. the `t = 1` strategy of [Bdpt](crate::integrator::Bdpt)) are discarded.
+/// Progressive integrators and integrators that only splat are therefore rejected.
+/// Spectral integrators have to sample their wavelengths uniformly.
+///
+/// The algorithm is described in
+/// - authors: C. Kelemen & L. Szirmay-Kalos & G. Antal & F. Csonka
+/// - paper: A Simple and Robust Mutation Strategy for the Metropolis Light Transport Algorithm
+/// - year: 2002
+#[derive(Deserialize, Serialize)]
+#[serde(try_from = "PssmltSerde")]
+pub struct Pssmlt {
+    /// The integrator whose random numbers are mutated
+    integrator: Box<dyn Integrator>,
+    /// The number of samples estimating the normalization
+    bootstrap: usize,
+    /// The number of Markov chains
+    chains: usize,
+    /// The probability of replacing all random numbers instead of perturbing them
+    #[serde(default = "default_large_step")]
+    large_step: Float,
+    /// The standard deviation of the perturbations
+    #[serde(default = "default_sigma")]
+    sigma: Float,
+    /// The chains of a running rendering, bootstrapped anew on resumption
+    #[serde(skip)]
+    state: RwLock<MltState>,
+}
+
+/// The configuration of [Pssmlt], whose wrapped integrator has to estimate the pixel of a primary
+/// ray on its own.
+#[derive(Deserialize)]
+struct PssmltSerde {
+    integrator: Box<dyn Integrator>,
+    bootstrap: usize,
+    chains: usize,
+    #[serde(default = "default_large_step")]
+    large_step: Float,
+    #[serde(default = "default_sigma")]
+    sigma: Float,
+}
+
+impl TryFrom<PssmltSerde> for Pssmlt {
+    type Error = String;
+
+    fn try_from(serde: PssmltSerde) -> Result<Self, Self::Error> {
+        if serde.integrator.progressive().is_some() {
+            return Err(String::from(
+                "Pssmlt cannot wrap a progressive integrator, as its passes would not be run",
+            ));
+        }
+        if serde.integrator.splats_only() {
+            return Err(String::from(
+                "Pssmlt cannot wrap an integrator that only splats, as splats are discarded",
+            ));
+        }
+
+        Ok(Self {
+            integrator: serde.integrator,
+            bootstrap: serde.bootstrap,
+            chains: serde.chains,
+            large_step: serde.large_step,
+            sigma: serde.sigma,
+            state: RwLock::default(),
+        })
+    }
+}
+
+const fn default_large_step() -> Float {
+    0.3
+}
+
+const fn default_sigma() -> Float {
+    0.01
+}
+
+/// The outcome of a single evaluation of the wrapped integrator.
+#[derive(Copy, Clone, Debug)]
+struct MltSample {
+    raster: Vec2,
+    spectrum: Spectrum,
+    /// The scalar contribution the chains are distributed proportional to
+    target: Float,
+}
+
+struct Chain {
+    stream: SampleStream,
+    current: MltSample,
+}
+
+#[derive(Default)]
+struct MltState {
+    /// The average target function over the primary sample space
+    normalization: Float,
+    chains: Vec<Mutex<Chain>>,
+    /// The number of mutations per chain and pass
+    mutations: usize,
+}
+
+impl Pssmlt {
+    /// Evaluates the wrapped integrator with the random numbers of the stream.
+    ///
+    /// # Arguments
+    /// * `scene` - The scene to integrate
+    /// * `camera` - The camera of the sensor
+    /// * `stream` - The stream providing the random numbers
+    /// * `discard` - An empty buffer for the splats of the wrapped integrator
+    ///
+    /// # Returns
+    /// * The contribution of the evaluated path
+    fn evaluate(
+        &self,
+        scene: &Scene,
+        camera: &dyn Camera,
+        stream: &mut SampleStream,
+        discard: &SplatBuffer,
+    ) -> MltSample {
+        stream.run(|| {
+            let resolution = camera.resolution();
+            let x = ((next_float() * resolution.x as Float) as u32).min(resolution.x - 1);
+            let y = ((next_float() * resolution.y as Float) as u32).min(resolution.y - 1);
+            let position = UVec2::new(x, y);
+
+            let mut pixel = Pixel::new(position);
+            let ray = camera.primary_ray(position);
+            self.integrator
+                .integrate(scene, camera, ray, &mut pixel, discard);
+
+            // wavelengths not sampled are estimated by the sampled ones
+            let sampled = pixel.sampled();
+            let spectrum = if sampled > 0 {
+                pixel.average * (Spectrum::size() as Float / sampled as Float)
+            } else {
+                Spectrum::splat(0.0)
+            };
+
+            MltSample {
+                raster: position.cast::<Float>().unwrap() + Vec2::new(0.5, 0.5),
+                spectrum,
+                target: spectrum.sum_values().max(0.0) / Spectrum::size() as Float,
+            }
+        })
+    }
+
+    /// Estimates the normalization and chooses the initial states of the chains proportional to
+    /// their contribution.
+    fn bootstrap(&self, scene: &Scene, camera: &dyn Camera, state: &mut MltState) {
+        let discard = SplatBuffer::default();
+        let seed = fastrand::u64(..);
+        let stream =
+            |i: usize| SampleStream::new(seed.wrapping_add(i as u64), self.large_step, self.sigma);
+
+        let mut cdf = Vec::with_capacity(self.bootstrap);
+        let mut sum = 0.0;
+        for i in 0..self.bootstrap {
+            sum += self
+                .evaluate(scene, camera, &mut stream(i), &discard)
+                .target;
+            cdf.push(sum);
+        }
+
+        state.normalization = sum / self.bootstrap as Float;
+        if sum == 0.0 {
+            log::warn!(target: "Pssmlt", "no contribution found during bootstrap");
+            return;
+        }
+
+        state.chains = (0..self.chains)
+            .map(|_| {
+                let u = next_float() * sum;
+                let i = cdf.partition_point(|&c| c <= u).min(self.bootstrap - 1);
+
+                // the same seed replays the bootstrap sample
+                let mut stream = stream(i);
+                let current = self.evaluate(scene, camera, &mut stream, &discard);
+
+                Mutex::new(Chain { stream, current })
+            })
+            .collect();
+    }
+}
+
+#[typetag::serde]
+impl Integrator for Pssmlt {
+    /// All contributions are splatted by the chains.
+    fn integrate(&self, _: &Scene, _: &dyn Camera, _: Ray, _: &mut Pixel, _: &SplatBuffer) {}
+
+    fn progressive(&self) -> Option<&dyn Progressive> {
+        Some(self)
+    }
+}
+
+impl Progressive for Pssmlt {
+    fn begin_pass(&self, scene: &Scene, camera: &dyn Camera, _: usize) {
+        let mut state = self.state.write();
+
+        if state.chains.is_empty() {
+            self.bootstrap(scene, camera, &mut state);
+        }
+
+        let resolution = camera.resolution();
+        let num_pixels = (resolution.x * resolution.y) as usize;
+        state.mutations = num_pixels.div_ceil(self.chains.max(1));
+    }
+
+    fn num_jobs(&self) -> usize {
+        self.state.read().chains.len()
+    }
+
+    fn run_job(&self, scene: &Scene, camera: &dyn Camera, splats: &SplatBuffer, job: usize) {
+        let state = self.state.read();
+        let normalization = state.normalization;
+        let mut chain = state.chains[job].lock();
+        let discard = SplatBuffer::default();
+
+        for _ in 0..state.mutations {
+            chain.stream.start_iteration();
+            let proposed = self.evaluate(scene, camera, &mut chain.stream, &discard);
+            let current = chain.current;
+
+            let accept = if current.target > 0.0 {
+                (proposed.target / current.target).min(1.0)
+            } else {
+                1.0
+            };
+
+            // both states contribute according to their probability (expected values)
+            if proposed.target > 0.0 {
+                let weight = normalization * accept / proposed.target;
+                splats.add(proposed.raster, proposed.spectrum * weight);
+            }
+            if current.target > 0.0 {
+                let weight = normalization * (1.0 - accept) / current.target;
+                splats.add(current.raster, current.spectrum * weight);
+            }
+            splats.count();
+
+            if next_float() < accept {
+                chain.current = proposed;
+                chain.stream.accept();
+            } else {
+                chain.stream.reject();
+            }
+        }
+    }
+
+    fn end_jobs(&self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn load(integrator: &str) -> Result<Box<dyn Integrator>, ron::error::SpannedError> {
+        ron::from_str(&format!(
+            "{{ \"Pssmlt\": (integrator: {}, bootstrap: 16, chains: 2) }}",
+            integrator
+        ))
+    }
+
+    fn rejected(result: Result<Box<dyn Integrator>, ron::error::SpannedError>) -> bool {
+        matches!(result, Err(e) if e.to_string().contains("Pssmlt cannot wrap"))
+    }
+
+    #[test]
+    fn rejects_integrators_without_pixel_estimates() {
+        let path = "{ \"SpectralPath\": (max_depth: 4, sampler: Random, spectral_sampler: Random, direct_illum: All) }";
+        assert!(load(path).is_ok());
+
+        let light =
+            "{ \"LightTracer\": (max_depth: 4, sampler: Random, spectral_sampler: Random) }";
+        assert!(rejected(load(light)));
+
+        let sppm = "{ \"Sppm\": (max_depth: 4, photons: 16, radius: 0.1, sampler: Random) }";
+        assert!(rejected(load(sppm)));
+    }
+}
@@ -0,0 +1,467 @@
+use crate::bxdf::{bxdf_to_world, BxDFFlag, BxDFSamples, BSDF};
+use crate::camera::sensor::{Pixel, SplatBuffer};
+use crate::camera::Camera;
+use crate::geometry::bvh::Tree;
+use crate::geometry::{offset_point, offset_ray_towards, Aabb, Geometry, Patch, Ray};
+use crate::integrator::{Bounce, DirectIllumination, Integrator, Progressive};
+use crate::sampler::FloatSampler;
+use crate::scene::{EmissionProfile, Scene, SceneObject};
+use crate::util::floats::BIG_EPSILON;
+use crate::util::mc::sample_unit_hemisphere;
+use crate::{Float, Spectrum, Vec3};
+use cgmath::InnerSpace;
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+#[cfg(not(feature = "f64"))]
+use std::f32::consts::FRAC_1_PI;
+#[cfg(feature = "f64")]
+use std::f64::consts::FRAC_1_PI;
+
+/// The number of patches whose form factors are estimated by a single job.
+const PATCHES_PER_JOB: usize = 64;
+/// The number of samples estimating the reflectance of a BSDF.
+const REFLECTANCE_SAMPLES: usize = 256;
+
+/// Radiosity solved by progressive refinement (Goral, `ED*L`).
+///
+/// The surfaces of the scene are discretized into patches, whose form factors are estimated by
+/// casting cosine-distributed rays through the scene.
+/// The patch with the most unshot power then repeatedly distributes it to all patches it sees,
+/// for every spectral bin at once.
+/// The solution is computed in the first pass and rendered noise-free by looking up the patch
+/// seen through every pixel.
+///
+/// All surfaces are treated as one-sided and diffuse, with the directional-hemispherical
+/// reflectance of their BSDF.
+/// Only geometries providing [patches](Geometry::patches) take part, i.e. cubes, planes
+/// (clipped to the bounds of all finite objects) and meshes.
+/// Emitters without patches (e.g. spheres) do not reflect, but their light is gathered by every
+/// patch through emitter sampling before the first iteration.
+#[derive(Deserialize, Serialize)]
+pub struct Radiosity {
+    /// The maximum edge length of a patch
+    patch_size: Float,
+    /// The number of rays cast from every patch to estimate its form factors
+    rays: usize,
+    /// The maximum number of patches shooting their power
+    iterations: usize,
+    sampler: FloatSampler,
+    #[serde(skip)]
+    state: RwLock<RadiosityState>,
+}
+
+/// A patch and its share of the solution.
+struct RadiosityPatch {
+    patch: Patch,
+    normal: Vec3,
+    area: Float,
+    reflectance: Spectrum,
+    radiance: Spectrum,
+    /// The radiance not yet distributed to other patches
+    unshot: Spectrum,
+}
+
+/// A coarse patch of an object, which is subdivided into a range of patches.
+struct Surface {
+    patch: Patch,
+    resolution: (usize, usize),
+    first: usize,
+}
+
+/// The surfaces of a single scene object.
+#[derive(Default)]
+struct ObjectSurfaces {
+    surfaces: Vec<Surface>,
+    tree: Tree,
+}
+
+#[derive(Default)]
+struct RadiosityState {
+    patches: Vec<RadiosityPatch>,
+    objects: Vec<ObjectSurfaces>,
+    /// The form factors from every patch to the patches it sees (sparse)
+    form_factors: Vec<Mutex<Vec<(u32, Float)>>>,
+    /// The radiance every patch reflects from emitters without patches
+    direct: Vec<Mutex<Spectrum>>,
+    solved: bool,
+}
+
+impl RadiosityState {
+    /// Finds the patch hit by a ray.
+    ///
+    /// # Arguments
+    /// * `object` - The index of the hit object
+    /// * `ray` - The ray, ending at the hit
+    ///
+    /// # Returns
+    /// * The index of the patch and whether its front side was hit
+    fn locate(&self, object: usize, ray: Ray) -> Option<(usize, bool)> {
+        let object = self.objects.get(object)?;
+
+        // allow for rounding differences between the object and its patches
+        let ray = Ray::new2(
+            ray.origin,
+            ray.direction,
+            ray.t_start,
+            ray.t_end + BIG_EPSILON,
+        );
+
+        let (_, surface, coordinates) = object
+            .tree
+            .intersect(ray)
+            .into_iter()
+            .filter_map(|i| {
+                let surface = &object.surfaces[i as usize];
+                surface
+                    .patch
+                    .intersect(ray)
+                    .map(|(t, coordinates)| (t, surface, coordinates))
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0))?;
+
+        let index = surface.first + surface.patch.cell(coordinates, surface.resolution);
+        let front = ray.direction.dot(self.patches[index].normal) < 0.0;
+
+        Some((index, front))
+    }
+}
+
+impl Radiosity {
+    /// Estimates the directional-hemispherical reflectance of a BSDF (normal incidence).
+    fn reflectance(&self, bsdf: &BSDF) -> Spectrum {
+        let normal = Vec3::unit_y();
+        let mut reflectance = Spectrum::splat(0.0);
+
+        for _ in 0..REFLECTANCE_SAMPLES {
+            if let Some(s) = bsdf
+                .sample(
+                    normal,
+                    normal,
+                    self.sampler.sample(),
+                    BxDFFlag::empty(),
+                    &(),
+                )
+                .and_then(BxDFSamples::bundle)
+            {
+                if s.pdf > 0.0 && s.incident.dot(normal) > 0.0 {
+                    let cos = if s.flag.specular() {
+                        // division of cosine omitted in specular bxdfs
+                        1.0
+                    } else {
+                        s.incident.dot(normal)
+                    };
+
+                    reflectance += s.spectrum * (cos / s.pdf);
+                }
+            }
+        }
+
+        reflectance / REFLECTANCE_SAMPLES as Float
+    }
+
+    /// Discretizes the scene into patches.
+    fn discretize(&self, scene: &Scene) -> RadiosityState {
+        let bounds = (0..scene.num_objects())
+            .map(|i| scene.get_object(i).bounds())
+            .filter(|b| b.size().magnitude().is_finite())
+            .fold(Aabb::empty(), |acc, b| acc.join(b));
+
+        let mut state = RadiosityState::default();
+        for index in 0..scene.num_objects() {
+            let object = scene.get_object(index);
+
+            let coarse = match object {
+                SceneObject::Emitter(e) => e.geometry.patches(bounds),
+                SceneObject::Receiver(r) => r.geometry.patches(bounds),
+            };
+            let coarse = match coarse {
+                Some(c) => c,
+                None => {
+                    log::warn!(target: "Radiosity", "ignoring object {} without patches", index);
+                    state.objects.push(ObjectSurfaces::default());
+                    continue;
+                }
+            };
+
+            let bsdf = object.bsdf();
+            if bsdf.matches(BxDFFlag::GLOSSY) || bsdf.matches(BxDFFlag::SPECULAR) {
+                log::warn!(target: "Radiosity", "treating object {} as diffuse", index);
+            }
+
+            let reflectance = self.reflectance(bsdf);
+            let emission = match object {
+                SceneObject::Emitter(e) => {
+                    if let EmissionProfile::CosinePower(_) = e.profile {
+                        log::warn!(target: "Radiosity", "treating emitter {} as diffuse", index);
+                    }
+                    e.radiance(&())
+                }
+                SceneObject::Receiver(_) => Spectrum::splat(0.0),
+            };
+
+            let mut surfaces = Vec::with_capacity(coarse.len());
+            for patch in coarse {
+                surfaces.push(Surface {
+                    patch,
+                    resolution: patch.resolution(self.patch_size),
+                    first: state.patches.len(),
+                });
+
+                state
+                    .patches
+                    .extend(
+                        patch
+                            .subdivide(self.patch_size)
+                            .into_iter()
+                            .map(|p| RadiosityPatch {
+                                patch: p,
+                                normal: p.normal(),
+                                area: p.area(),
+                                reflectance,
+                                radiance: emission,
+                                unshot: emission,
+                            }),
+                    );
+            }
+
+            let indices = (0..surfaces.len() as u32).collect::<Vec<_>>();
+            let tree = Tree::new(&indices, |i| surfaces[i as usize].patch.bounds());
+            state.objects.push(ObjectSurfaces { surfaces, tree });
+        }
+
+        state.form_factors = (0..state.patches.len())
+            .map(|_| Mutex::new(Vec::new()))
+            .collect();
+        state.direct = (0..state.patches.len())
+            .map(|_| Mutex::new(Spectrum::splat(0.0)))
+            .collect();
+        log::info!(target: "Radiosity", "discretized scene into {} patches", state.patches.len());
+
+        state
+    }
+
+    /// Estimates the form factors from a patch to all other patches.
+    fn form_factors(
+        &self,
+        scene: &Scene,
+        state: &RadiosityState,
+        from: usize,
+    ) -> Vec<(u32, Float)> {
+        let patch = &state.patches[from];
+        let rotation = bxdf_to_world(patch.normal);
+
+        let mut hits = Vec::with_capacity(self.rays);
+        for _ in 0..self.rays {
+            let point = patch.patch.sample(self.sampler.vec2());
+            let direction = rotation.rotate_vector(sample_unit_hemisphere(self.sampler.vec2()));
+            let ray = offset_ray_towards(point, patch.normal, direction);
+
+            if let (Some((object, hit)), _) = scene.intersect_traced(ray) {
+                let ray = Ray::new2(ray.origin, ray.direction, ray.t_start, hit.i.t);
+
+                if let Some((to, true)) = state.locate(object, ray) {
+                    hits.push(to as u32);
+                }
+            }
+        }
+
+        hits.sort_unstable();
+        let weight = 1.0 / self.rays as Float;
+
+        let mut form_factors: Vec<(u32, Float)> = Vec::new();
+        for to in hits {
+            match form_factors.last_mut() {
+                Some((last, f)) if *last == to => *f += weight,
+                _ => form_factors.push((to, weight)),
+            }
+        }
+
+        form_factors
+    }
+
+    /// Estimates the radiance a patch reflects from emitters without patches (e.g. spheres or
+    /// points), which cannot shoot their power themselves.
+    fn direct(&self, scene: &Scene, state: &RadiosityState, to: usize) -> Spectrum {
+        let patch = &state.patches[to];
+        let mut irradiance = Spectrum::splat(0.0);
+
+        let emitters = scene
+            .emitters()
+            .iter()
+            .filter(|&&i| state.objects[i as usize].surfaces.is_empty())
+            .filter_map(|&i| scene.get_emitter(i as usize));
+        for emitter in emitters {
+            for _ in 0..self.rays {
+                let point = patch.patch.sample(self.sampler.vec2());
+                let point = offset_point(point, patch.normal, patch.normal);
+                let sample = emitter.sample(point, self.sampler.vec2(), &());
+
+                let cos = sample.incident.dot(patch.normal);
+                if sample.pdf > 0.0 && cos > 0.0 {
+                    let factor =
+                        DirectIllumination::sample_factor(emitter, 1.0, &sample, || 0.0, None);
+                    let transmittance = sample.occlusion.unoccluded(scene, self.sampler, &());
+
+                    irradiance += sample.radiance * transmittance * (cos * factor);
+                }
+            }
+        }
+
+        patch.reflectance * irradiance * (FRAC_1_PI / self.rays as Float)
+    }
+
+    /// Solves the radiosity equation by progressive refinement.
+    fn solve(&self, state: &mut RadiosityState) {
+        let form_factors = state
+            .form_factors
+            .iter_mut()
+            .map(|f| std::mem::take(f.get_mut()))
+            .collect::<Vec<_>>();
+
+        for (patch, direct) in state.patches.iter_mut().zip(&mut state.direct) {
+            let direct = *direct.get_mut();
+            patch.radiance += direct;
+            patch.unshot += direct;
+        }
+
+        let mut iterations = 0;
+        while iterations < self.iterations {
+            let (from, power) = state
+                .patches
+                .iter()
+                .enumerate()
+                .map(|(i, p)| (i, p.unshot.max_value() * p.area))
+                .max_by(|a, b| a.1.total_cmp(&b.1))
+                .unwrap_or((0, 0.0));
+
+            if power <= 0.0 {
+                break;
+            }
+
+            let unshot = state.patches[from].unshot;
+            let area = state.patches[from].area;
+            state.patches[from].unshot = Spectrum::splat(0.0);
+
+            for &(to, f) in &form_factors[from] {
+                let to = &mut state.patches[to as usize];
+                // reciprocity: A_to * F_to,from = A_from * F_from,to
+                let delta = to.reflectance * unshot * (f * area / to.area);
+
+                to.radiance += delta;
+                to.unshot += delta;
+            }
+
+            iterations += 1;
+        }
+
+        let remaining = state
+            .patches
+            .iter()
+            .map(|p| p.unshot.max_value() * p.area)
+            .sum::<Float>();
+        log::info!(
+            target: "Radiosity",
+            "solved after {} iterations, remaining unshot power: {}",
+            iterations,
+            remaining
+        );
+
+        state.form_factors.clear();
+        state.direct.clear();
+        state.solved = true;
+    }
+}
+
+#[typetag::serde]
+impl Integrator for Radiosity {
+    fn integrate(
+        &self,
+        scene: &Scene,
+        _: &dyn Camera,
+        primary_ray: Ray,
+        pixel: &mut Pixel,
+        _: &SplatBuffer,
+    ) {
+        let (object, hit) = match scene.intersect_traced(primary_ray) {
+            (Some(hit), _) => hit,
+            _ if !scene.infinite_emitters().is_empty() => {
+                let bounce = Bounce::primary(primary_ray.origin);
+                let radiance = DirectIllumination::All.escaped(
+                    scene,
+                    &bounce,
+                    primary_ray.direction,
+                    &(),
+                    None,
+                );
+                pixel.add(radiance);
+                return;
+            }
+            _ => {
+                pixel.add_none();
+                return;
+            }
+        };
+
+        let state = self.state.read();
+        let ray = Ray::new2(
+            primary_ray.origin,
+            primary_ray.direction,
+            primary_ray.t_start,
+            hit.i.t,
+        );
+
+        match state.locate(object, ray) {
+            Some((index, true)) => pixel.add(state.patches[index].radiance),
+            Some((_, false)) => pixel.add(Spectrum::splat(0.0)),
+            // objects without patches are only visible as emitters
+            None => match hit.object {
+                SceneObject::Emitter(e) => {
+                    pixel.add(e.radiance_towards(&(), hit.i.normal, -hit.i.incoming))
+                }
+                SceneObject::Receiver(_) => pixel.add(Spectrum::splat(0.0)),
+            },
+        }
+    }
+
+    fn progressive(&self) -> Option<&dyn Progressive> {
+        Some(self)
+    }
+}
+
+impl Progressive for Radiosity {
+    fn begin_pass(&self, scene: &Scene, _: &dyn Camera, _: usize) {
+        if !self.state.read().solved {
+            *self.state.write() = self.discretize(scene);
+        }
+    }
+
+    fn num_jobs(&self) -> usize {
+        let state = self.state.read();
+
+        if state.solved {
+            0
+        } else {
+            state.patches.len().div_ceil(PATCHES_PER_JOB)
+        }
+    }
+
+    fn run_job(&self, scene: &Scene, _: &dyn Camera, _: &SplatBuffer, job: usize) {
+        let state = self.state.read();
+
+        let start = job * PATCHES_PER_JOB;
+        let end = (start + PATCHES_PER_JOB).min(state.patches.len());
+        for from in start..end {
+            let form_factors = self.form_factors(scene, &state, from);
+            *state.form_factors[from].lock() = form_factors;
+            *state.direct[from].lock() = self.direct(scene, &state, from);
+        }
+    }
+
+    fn end_jobs(&self) {
+        let mut state = self.state.write();
+
+        if !state.solved {
+            self.solve(&mut state);
+        }
+    }
+}
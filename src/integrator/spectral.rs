@@ -1,186 +1,91 @@
-use crate::bxdf::{BxDFFlag, BxDFSamplePacket};
-use crate::camera::sensor::Pixel;
-use crate::geometry::{offset_ray_towards, Ray};
-use crate::integrator::{DirectIllumination, Integrator};
+use crate::camera::sensor::{Pixel, SplatBuffer};
+use crate::camera::Camera;
+use crate::geometry::Ray;
+use crate::integrator::guiding::PathGuiding;
+use crate::integrator::lpe::LightPaths;
+use crate::integrator::tracer::PathTracer;
+use crate::integrator::{DirectIllumination, Heuristic, Integrator, Progressive, RussianRoulette};
 use crate::sampler::{FloatSampler, SpectralSampler};
-use crate::scene::{Scene, SceneIntersection, SceneObject};
-use crate::util::PacketOps;
-use crate::{Float, PACKET_SIZE};
-use cgmath::InnerSpace;
+use crate::scene::Scene;
 use serde::{Deserialize, Serialize};
 
-#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct SpectralPath {
     max_depth: u32,
     sampler: FloatSampler,
     spectral_sampler: SpectralSampler,
     direct_illum: DirectIllumination,
+    #[serde(default)]
+    heuristic: Heuristic,
+    #[serde(default)]
+    roulette: RussianRoulette,
+    /// Learns the incident radiance over the passes to sample directions at non-specular vertices
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    guiding: Option<PathGuiding>,
+    /// The expressions to separate the contributions into
+    #[serde(default)]
+    #[serde(skip_serializing_if = "LightPaths::is_empty")]
+    light_paths: LightPaths,
 }
 
 impl SpectralPath {
-    fn trace_single<'a>(
-        &self,
-        scene: &'a Scene,
-        mut hit: SceneIntersection<'a>,
-        index: usize,
-        illumination: &mut Float,
-        throughput: &mut Float,
-        curr_depth: u32,
-    ) {
-        for i in curr_depth..self.max_depth {
-            let outgoing = -hit.i.incoming;
-            let point = hit.i.point;
-            let normal = hit.i.normal;
-            let bsdf = hit.object.bsdf();
-
-            if let SceneObject::Emitter(e) = hit.object {
-                if i != 1 || self.direct_illum != DirectIllumination::Indirect {
-                    *illumination += *throughput * e.radiance_lambda(index);
-                }
-            }
-
-            *illumination += *throughput
-                * self
-                    .direct_illum
-                    .sample_lambda(scene, &hit, &self.sampler, index);
-
-            if let Some(bxdf_sample) = bsdf.sample_lambda(
-                normal,
-                outgoing,
-                self.sampler.sample(),
-                BxDFFlag::empty(),
-                index,
-            ) {
-                if bxdf_sample.pdf == 0.0 || bxdf_sample.spectrum == 0.0 {
-                    break;
-                }
-
-                let cos_abs = if bxdf_sample.flag.specular() {
-                    // division of cosine omitted in specular bxdfs
-                    1.0
-                } else {
-                    bxdf_sample.incident.dot(normal).abs()
-                };
-
-                *throughput *= bxdf_sample.spectrum * (cos_abs / bxdf_sample.pdf);
-
-                let ray = offset_ray_towards(point, normal, bxdf_sample.incident);
-                match scene.intersect(ray) {
-                    Some(i) => hit = i,
-                    None => break,
-                }
-            } else {
-                break;
-            }
+    fn tracer(&self) -> PathTracer<'_> {
+        PathTracer {
+            max_depth: self.max_depth,
+            sampler: self.sampler,
+            direct_illum: self.direct_illum,
+            heuristic: self.heuristic,
+            roulette: self.roulette,
+            guiding: self.guiding.as_ref(),
+            light_paths: &self.light_paths,
         }
     }
+}
 
-    fn trace_bundle<'a>(
+#[typetag::serde]
+impl Integrator for SpectralPath {
+    fn integrate(
         &self,
-        scene: &'a Scene,
-        mut hit: SceneIntersection<'a>,
-        indices: &[usize; PACKET_SIZE],
-        illumination: &mut [Float; PACKET_SIZE],
-        throughput: &mut [Float; PACKET_SIZE],
+        scene: &Scene,
+        camera: &dyn Camera,
+        primary_ray: Ray,
+        pixel: &mut Pixel,
+        _: &SplatBuffer,
     ) {
-        for curr_depth in 0..self.max_depth {
-            let outgoing = -hit.i.incoming;
-            let point = hit.i.point;
-            let normal = hit.i.normal;
-            let bsdf = hit.object.bsdf();
-
-            if let SceneObject::Emitter(e) = hit.object {
-                if curr_depth != 1 || self.direct_illum != DirectIllumination::Indirect {
-                    illumination.add_assign(throughput.mul(e.radiance_packet(indices)));
-                }
-            }
-
-            let direct_illumination =
-                self.direct_illum
-                    .sample_packet(scene, &hit, &self.sampler, indices);
-            illumination.add_assign(throughput.mul(direct_illumination));
-
-            match bsdf.sample_packet(
-                normal,
-                outgoing,
-                self.sampler.sample(),
-                BxDFFlag::empty(),
-                indices,
-            ) {
-                BxDFSamplePacket::Bundle(Some(bxdf_sample)) => {
-                    if bxdf_sample.pdf == 0.0 || bxdf_sample.spectrum.is_black() {
-                        break;
-                    }
-
-                    let cos_abs = if bxdf_sample.flag.specular() {
-                        // division of cosine omitted in specular bxdfs
-                        1.0
-                    } else {
-                        bxdf_sample.incident.dot(normal).abs()
-                    };
-
-                    throughput.mul_assign(bxdf_sample.spectrum.mul_t(cos_abs / bxdf_sample.pdf));
-
-                    let ray = offset_ray_towards(point, normal, bxdf_sample.incident);
-                    match scene.intersect(ray) {
-                        Some(i) => hit = i,
-                        None => break,
-                    }
-                }
-                BxDFSamplePacket::Split(split) => {
-                    for i in 0..PACKET_SIZE {
-                        if let Some(bxdf_sample) = split[i] {
-                            if bxdf_sample.pdf == 0.0 || bxdf_sample.spectrum == 0.0 {
-                                continue;
-                            }
-
-                            let cos_abs = if bxdf_sample.flag.specular() {
-                                // division of cosine omitted in specular bxdfs
-                                1.0
-                            } else {
-                                bxdf_sample.incident.dot(normal).abs()
-                            };
+        let indices = self.spectral_sampler.create();
+
+        match self
+            .tracer()
+            .trace_pixel(scene, camera, primary_ray, &indices, pixel.position)
+        {
+            Some(illumination) => pixel.add_packet(&illumination, &indices),
+            None => pixel.add_none(),
+        }
+    }
 
-                            throughput[i] *= bxdf_sample.spectrum * (cos_abs / bxdf_sample.pdf);
+    fn progressive(&self) -> Option<&dyn Progressive> {
+        self.guiding.as_ref().map(|_| self as &dyn Progressive)
+    }
 
-                            let ray = offset_ray_towards(point, normal, bxdf_sample.incident);
-                            match scene.intersect(ray) {
-                                Some(new_hit) => {
-                                    self.trace_single(
-                                        scene,
-                                        new_hit,
-                                        indices[i],
-                                        &mut illumination[i],
-                                        &mut throughput[i],
-                                        curr_depth,
-                                    );
-                                }
-                                None => continue,
-                            }
-                        }
-                    }
+    fn light_paths(&self) -> Option<&LightPaths> {
+        Some(&self.light_paths)
+    }
+}
 
-                    break;
-                }
-                _ => break,
-            }
+/// The passes train the path guiding distribution.
+impl Progressive for SpectralPath {
+    fn begin_pass(&self, scene: &Scene, _: &dyn Camera, _: usize) {
+        if let Some(guiding) = &self.guiding {
+            guiding.begin_pass(scene);
         }
     }
-}
 
-#[typetag::serde]
-impl Integrator for SpectralPath {
-    fn integrate(&self, scene: &Scene, primary_ray: Ray, pixel: &mut Pixel) {
-        if let Some(hit) = scene.intersect(primary_ray) {
-            let indices = self.spectral_sampler.create();
-            let mut illumination = [0.0; PACKET_SIZE];
-            let mut throughput = [1.0; PACKET_SIZE];
+    fn num_jobs(&self) -> usize {
+        0
+    }
 
-            self.trace_bundle(scene, hit, &indices, &mut illumination, &mut throughput);
+    fn run_job(&self, _: &Scene, _: &dyn Camera, _: &SplatBuffer, _: usize) {}
 
-            pixel.add_packet(&illumination, &indices);
-        } else {
-            pixel.add_none();
-        }
-    }
+    fn end_jobs(&self) {}
 }
@@ -1,77 +1,87 @@
-use crate::bxdf::BxDFFlag;
-use crate::camera::sensor::Pixel;
-use crate::geometry::{offset_ray_towards, Ray};
-use crate::integrator::{DirectIllumination, Integrator};
+use crate::camera::sensor::{Pixel, SplatBuffer};
+use crate::camera::Camera;
+use crate::geometry::Ray;
+use crate::integrator::lpe::LightPaths;
+use crate::integrator::tracer::PathTracer;
+use crate::integrator::{Bounce, DirectIllumination, Heuristic, Integrator, RussianRoulette};
 use crate::sampler::{FloatSampler, SpectralSampler};
-use crate::scene::{Scene, SceneObject};
-use cgmath::InnerSpace;
+use crate::scene::Scene;
 use serde::{Deserialize, Serialize};
 
-#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+#[derive(Debug, Deserialize, Serialize)]
 pub struct SpectralSingle {
     max_depth: u32,
     sampler: FloatSampler,
     spectral_sampler: SpectralSampler,
     direct_illum: DirectIllumination,
+    #[serde(default)]
+    heuristic: Heuristic,
+    #[serde(default)]
+    roulette: RussianRoulette,
+    /// The expressions to separate the contributions into
+    #[serde(default)]
+    #[serde(skip_serializing_if = "LightPaths::is_empty")]
+    light_paths: LightPaths,
+}
+
+impl SpectralSingle {
+    fn tracer(&self) -> PathTracer<'_> {
+        PathTracer {
+            max_depth: self.max_depth,
+            sampler: self.sampler,
+            direct_illum: self.direct_illum,
+            heuristic: self.heuristic,
+            roulette: self.roulette,
+            guiding: None,
+            light_paths: &self.light_paths,
+        }
+    }
 }
 
 #[typetag::serde]
 impl Integrator for SpectralSingle {
-    fn integrate(&self, scene: &Scene, primary_ray: Ray, pixel: &mut Pixel) {
-        if let Some(mut hit) = scene.intersect(primary_ray) {
-            for index in self.spectral_sampler.create() {
-                let mut illumination = 0.0;
-                let mut throughput = 1.0;
-
-                for curr_depth in 0..self.max_depth {
-                    let outgoing = -hit.i.incoming;
-                    let point = hit.i.point;
-                    let normal = hit.i.normal;
-                    let bsdf = hit.object.bsdf();
-
-                    if let SceneObject::Emitter(e) = hit.object {
-                        if curr_depth != 1 || self.direct_illum != DirectIllumination::Indirect {
-                            illumination += throughput * e.radiance_lambda(index);
-                        }
-                    }
-
-                    illumination += throughput
-                        * self
-                            .direct_illum
-                            .sample_lambda(scene, &hit, &self.sampler, index);
-
-                    if let Some(bxdf_sample) = bsdf.sample_lambda(
-                        normal,
-                        outgoing,
-                        self.sampler.sample(),
-                        BxDFFlag::empty(),
-                        index,
-                    ) {
-                        if bxdf_sample.pdf == 0.0 || bxdf_sample.spectrum == 0.0 {
-                            break;
-                        }
+    fn integrate(
+        &self,
+        scene: &Scene,
+        camera: &dyn Camera,
+        primary_ray: Ray,
+        pixel: &mut Pixel,
+        _: &SplatBuffer,
+    ) {
+        if let Some(hit) = scene.intersect(primary_ray) {
+            let tracer = self.tracer();
 
-                        let cos_abs = if bxdf_sample.flag.specular() {
-                            // division of cosine omitted in specular bxdfs
-                            1.0
-                        } else {
-                            bxdf_sample.incident.dot(normal).abs()
-                        };
+            // every wavelength traces its own path from the primary intersection
+            for index in self.spectral_sampler.create() {
+                let bounce = Bounce::primary(primary_ray.origin);
+                let illumination = tracer.trace_pixel_from(
+                    scene,
+                    camera,
+                    hit.clone(),
+                    &index,
+                    bounce,
+                    pixel.position,
+                );
 
-                        throughput *= bxdf_sample.spectrum * (cos_abs / bxdf_sample.pdf);
+                pixel.add_lambda(illumination, index);
+            }
+        } else if !scene.infinite_emitters().is_empty() {
+            let tracer = self.tracer();
 
-                        let ray = offset_ray_towards(point, normal, bxdf_sample.incident);
-                        match scene.intersect(ray) {
-                            Some(i) => hit = i,
-                            None => break,
-                        }
-                    }
+            for index in self.spectral_sampler.create() {
+                match tracer.trace_pixel(scene, camera, primary_ray, &index, pixel.position) {
+                    Some(illumination) => pixel.add_lambda(illumination, index),
+                    None => pixel.add_none_lambda(index),
                 }
-
-                pixel.add_lambda(illumination, index);
             }
         } else {
             pixel.add_none();
+            self.light_paths
+                .add_none(camera.resolution(), pixel.position);
         }
     }
+
+    fn light_paths(&self) -> Option<&LightPaths> {
+        Some(&self.light_paths)
+    }
 }
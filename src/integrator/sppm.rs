@@ -0,0 +1,442 @@
+use crate::bxdf::{BxDFFlag, BxDFSamples};
+use crate::camera::sensor::{Pixel, SplatBuffer};
+use crate::camera::Camera;
+use crate::geometry::{offset_ray_towards, Ray};
+use crate::integrator::{Bounce, DirectIllumination, Integrator, Progressive};
+use crate::sampler::FloatSampler;
+use crate::scene::{Scene, SceneObject};
+use crate::{Float, Spectrum, UVec2, Vec3};
+use cgmath::InnerSpace;
+use parking_lot::{Mutex, RwLock};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+#[cfg(not(feature = "f64"))]
+use std::f32::consts::PI;
+#[cfg(feature = "f64")]
+use std::f64::consts::PI;
+
+/// The number of photons traced by a single job of the photon phase.
+const PHOTONS_PER_JOB: usize = 4096;
+
+/// Stochastic progressive photon mapping.
+///
+/// Every pass traces photons of a single wavelength from the emitters (photon phase) and gathers
+/// them at the first non-specular point seen through every pixel (gather phase).
+/// The gather radius of every pixel shrinks with the number of photons found, making the
+/// estimate consistent.
+///
+/// The wavelength cycles through the spectrum with every pass, so dispersion by specular BxDFs
+/// is captured for every wavelength.
+/// Direct illumination at the gather points is sampled from the emitters instead.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Sppm {
+    max_depth: u32,
+    /// The number of photons traced per pass
+    photons: usize,
+    /// The initial gather radius
+    radius: Float,
+    sampler: FloatSampler,
+    /// The statistics of a running rendering, preserved in checkpoints
+    #[serde(default)]
+    #[serde(skip_serializing_if = "SppmState::is_empty")]
+    state: RwLock<SppmState>,
+}
+
+/// A photon deposited on a non-specular surface.
+#[derive(Copy, Clone, Debug)]
+struct Photon {
+    point: Vec3,
+    /// The direction the photon came from
+    incident: Vec3,
+    /// The flux of the photon for the wavelength of the pass
+    flux: Float,
+}
+
+/// A uniform grid over the photons of a pass.
+#[derive(Debug, Default)]
+struct PhotonGrid {
+    cell_size: Float,
+    cells: HashMap<[i32; 3], Vec<Photon>>,
+}
+
+impl PhotonGrid {
+    fn new(photons: Vec<Photon>, cell_size: Float) -> Self {
+        let mut grid = Self {
+            cell_size,
+            cells: HashMap::new(),
+        };
+
+        for photon in photons {
+            let cell = grid.cell(photon.point);
+            grid.cells.entry(cell).or_default().push(photon);
+        }
+
+        grid
+    }
+
+    #[inline]
+    fn cell(&self, point: Vec3) -> [i32; 3] {
+        let p = point / self.cell_size;
+        [p.x.floor() as i32, p.y.floor() as i32, p.z.floor() as i32]
+    }
+
+    /// Calls `f` for all photons within the radius around the point.
+    fn for_each_within<F>(&self, point: Vec3, radius: Float, mut f: F)
+    where
+        F: FnMut(&Photon),
+    {
+        let offset = Vec3::new(radius, radius, radius);
+        let min = self.cell(point - offset);
+        let max = self.cell(point + offset);
+        let radius2 = radius * radius;
+
+        for x in min[0]..=max[0] {
+            for y in min[1]..=max[1] {
+                for z in min[2]..=max[2] {
+                    let photons = match self.cells.get(&[x, y, z]) {
+                        Some(p) => p,
+                        None => continue,
+                    };
+
+                    photons
+                        .iter()
+                        .filter(|p| (p.point - point).magnitude2() <= radius2)
+                        .for_each(&mut f);
+                }
+            }
+        }
+    }
+}
+
+/// The statistics of a single pixel, for every wavelength separately.
+#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+struct SppmPixel {
+    radius: Spectrum,
+    /// The (reduced) number of photons gathered
+    photons: Spectrum,
+    /// The (reduced) flux gathered
+    flux: Spectrum,
+    /// The sum of the directly received radiance
+    direct: Spectrum,
+    passes: Spectrum,
+}
+
+impl SppmPixel {
+    fn new(radius: Float) -> Self {
+        Self {
+            radius: Spectrum::splat(radius),
+            photons: Spectrum::splat(0.0),
+            flux: Spectrum::splat(0.0),
+            direct: Spectrum::splat(0.0),
+            passes: Spectrum::splat(0.0),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct SppmState {
+    resolution: UVec2,
+    pixels: Vec<Mutex<SppmPixel>>,
+    /// The number of photons emitted per wavelength
+    emitted: Spectrum,
+    /// The wavelength of the current pass
+    index: usize,
+    #[serde(skip)]
+    photons: Mutex<Vec<Photon>>,
+    #[serde(skip)]
+    grid: PhotonGrid,
+}
+
+impl Default for SppmState {
+    fn default() -> Self {
+        Self {
+            resolution: UVec2::new(0, 0),
+            pixels: Vec::new(),
+            emitted: Spectrum::splat(0.0),
+            index: 0,
+            photons: Mutex::new(Vec::new()),
+            grid: PhotonGrid::default(),
+        }
+    }
+}
+
+impl SppmState {
+    #[inline]
+    fn is_empty(state: &RwLock<Self>) -> bool {
+        state.read().pixels.is_empty()
+    }
+}
+
+impl Sppm {
+    /// Traces photons through the scene.
+    ///
+    /// Photons are deposited on every non-specular surface except the first one, as direct
+    /// illumination is sampled at the gather points.
+    fn trace_photons(&self, scene: &Scene, index: usize, num: usize, photons: &mut Vec<Photon>) {
+        let num_emitters = scene.num_emitters();
+        if num_emitters == 0 {
+            return;
+        }
+
+        for _ in 0..num {
+            let chosen =
+                ((self.sampler.float() * num_emitters as Float) as usize).min(num_emitters - 1);
+            let emitter = match scene.get_emitter(scene.emitters()[chosen] as usize) {
+                Some(e) => e,
+                None => continue,
+            };
+
+            let emitter_ray = emitter.sample_ray(self.sampler.vec2(), self.sampler.vec2());
+            if emitter_ray.pdf_point == 0.0 || emitter_ray.pdf_direction == 0.0 {
+                continue;
+            }
+
+            let cos_abs = if emitter.is_delta() {
+                1.0
+            } else {
+                emitter_ray.ray.direction.dot(emitter_ray.normal).abs()
+            };
+
+            let radiance =
+                emitter.radiance_towards(&index, emitter_ray.normal, emitter_ray.ray.direction);
+            let mut flux = radiance * num_emitters as Float * cos_abs
+                / (emitter_ray.pdf_point * emitter_ray.pdf_direction);
+            let mut ray = offset_ray_towards(
+                emitter_ray.ray.origin,
+                emitter_ray.normal,
+                emitter_ray.ray.direction,
+            );
+
+            for depth in 0..self.max_depth {
+                if flux == 0.0 {
+                    break;
+                }
+
+                let hit = match scene.intersect(ray) {
+                    Some(hit) => hit,
+                    None => break,
+                };
+
+                let bsdf = hit.object.bsdf();
+                let outgoing = -hit.i.incoming;
+
+                if depth > 0 && bsdf.is_non_specular() {
+                    photons.push(Photon {
+                        point: hit.i.point,
+                        incident: outgoing,
+                        flux,
+                    });
+                }
+
+                let sample = match bsdf
+                    .sample(
+                        hit.i.normal,
+                        outgoing,
+                        self.sampler.sample(),
+                        BxDFFlag::empty(),
+                        &index,
+                    )
+                    .and_then(BxDFSamples::bundle)
+                {
+                    Some(s) if s.pdf > 0.0 && s.spectrum != 0.0 => s,
+                    _ => break,
+                };
+
+                let cos_abs = if sample.flag.specular() {
+                    // division of cosine omitted in specular bxdfs
+                    1.0
+                } else {
+                    sample.incident.dot(hit.i.normal).abs()
+                };
+
+                flux *= sample.spectrum * cos_abs / sample.pdf;
+                ray = offset_ray_towards(hit.i.point, hit.i.normal, sample.incident);
+            }
+        }
+    }
+}
+
+#[typetag::serde]
+impl Integrator for Sppm {
+    fn integrate(
+        &self,
+        scene: &Scene,
+        _: &dyn Camera,
+        primary_ray: Ray,
+        pixel: &mut Pixel,
+        _: &SplatBuffer,
+    ) {
+        let state = self.state.read();
+        let index = state.index;
+
+        let mut direct = 0.0;
+        let mut flux = 0.0;
+        let mut found = 0.0;
+
+        let mut beta = 1.0;
+        let mut ray = primary_ray;
+        let mut gather_point = None;
+
+        for _ in 0..self.max_depth {
+            let hit = match scene.intersect(ray) {
+                Some(hit) => hit,
+                None => {
+                    // only specular bounces lead here, which emitter sampling cannot handle
+                    let bounce = Bounce::primary(ray.origin);
+                    direct += beta
+                        * DirectIllumination::All.escaped(
+                            scene,
+                            &bounce,
+                            ray.direction,
+                            &index,
+                            None,
+                        );
+                    break;
+                }
+            };
+
+            // emitters are only hit directly or through specular bxdfs
+            if let SceneObject::Emitter(e) = hit.object {
+                direct += beta * e.radiance_towards(&index, hit.i.normal, -hit.i.incoming);
+            }
+
+            let bsdf = hit.object.bsdf();
+            let outgoing = -hit.i.incoming;
+
+            if bsdf.is_non_specular() {
+                direct +=
+                    beta * DirectIllumination::All.sample(scene, &hit, &self.sampler, &index, None);
+                gather_point = Some((hit, beta));
+                break;
+            }
+
+            let sample = match bsdf
+                .sample(
+                    hit.i.normal,
+                    outgoing,
+                    self.sampler.sample(),
+                    BxDFFlag::empty(),
+                    &index,
+                )
+                .and_then(BxDFSamples::bundle)
+            {
+                Some(s) if s.pdf > 0.0 && s.spectrum != 0.0 => s,
+                _ => break,
+            };
+
+            beta *= sample.spectrum / sample.pdf;
+            ray = offset_ray_towards(hit.i.point, hit.i.normal, sample.incident);
+        }
+
+        let position = pixel.position;
+        let mut stats =
+            state.pixels[(position.y * state.resolution.x + position.x) as usize].lock();
+
+        if let Some((hit, beta)) = gather_point {
+            let bsdf = hit.object.bsdf();
+            let outgoing = -hit.i.incoming;
+
+            state
+                .grid
+                .for_each_within(hit.i.point, stats.radius[index], |photon| {
+                    let f = bsdf.evaluate(
+                        hit.i.normal,
+                        photon.incident,
+                        outgoing,
+                        self.sampler.float(),
+                        BxDFFlag::empty(),
+                        &index,
+                    );
+
+                    flux += beta * f * photon.flux;
+                    found += 1.0;
+                });
+        }
+
+        // progressive radius reduction (alpha = 2/3)
+        if found > 0.0 {
+            let photons = stats.photons[index] + 2.0 / 3.0 * found;
+            let radius = stats.radius[index] * (photons / (stats.photons[index] + found)).sqrt();
+            let scale = radius / stats.radius[index];
+
+            stats.flux[index] = (stats.flux[index] + flux) * scale * scale;
+            stats.photons[index] = photons;
+            stats.radius[index] = radius;
+        }
+
+        stats.direct[index] += direct;
+        stats.passes[index] += 1.0;
+
+        let radius = stats.radius[index];
+        let indirect = if state.emitted[index] > 0.0 {
+            stats.flux[index] / (state.emitted[index] * PI * radius * radius)
+        } else {
+            0.0
+        };
+
+        pixel.set_lambda(stats.direct[index] / stats.passes[index] + indirect, index);
+    }
+
+    fn progressive(&self) -> Option<&dyn Progressive> {
+        Some(self)
+    }
+}
+
+impl Progressive for Sppm {
+    fn begin_pass(&self, _: &Scene, camera: &dyn Camera, pass: usize) {
+        let mut state = self.state.write();
+
+        let resolution = camera.resolution();
+        if state.resolution != resolution || state.pixels.is_empty() {
+            let num_pixels = (resolution.x * resolution.y) as usize;
+
+            state.resolution = resolution;
+            state.pixels = (0..num_pixels)
+                .map(|_| Mutex::new(SppmPixel::new(self.radius)))
+                .collect();
+            state.emitted = Spectrum::splat(0.0);
+        }
+
+        let index = pass % Spectrum::size();
+        state.index = index;
+        state.emitted[index] += self.photons as Float;
+        state.photons.get_mut().clear();
+    }
+
+    fn num_jobs(&self) -> usize {
+        self.photons.div_ceil(PHOTONS_PER_JOB)
+    }
+
+    fn run_job(&self, scene: &Scene, _: &dyn Camera, _: &SplatBuffer, job: usize) {
+        let state = self.state.read();
+
+        let start = job * PHOTONS_PER_JOB;
+        let num = self.photons.saturating_sub(start).min(PHOTONS_PER_JOB);
+
+        let mut photons = Vec::with_capacity(num);
+        self.trace_photons(scene, state.index, num, &mut photons);
+
+        state.photons.lock().append(&mut photons);
+    }
+
+    fn end_jobs(&self) {
+        let mut state = self.state.write();
+
+        let photons = std::mem::take(state.photons.get_mut());
+        state.grid = PhotonGrid::new(photons, self.radius);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::integrator::testing::assert_converges;
+
+    #[test]
+    fn converges_to_path() {
+        assert_converges(
+            r#"{ "Sppm": (max_depth: 4, photons: 4096, radius: 0.1, sampler: Random) }"#,
+            64,
+            0.05,
+        );
+    }
+}
@@ -0,0 +1,514 @@
+//! The path tracing core shared by the path tracers.
+//!
+//! [PathTracer] traces paths generically over the [SpectralIndices] of the sampled wavelengths,
+//! so that tracing all wavelengths at once, a packet of wavelengths or a single wavelength takes
+//! the same code path.
+//! If the wavelengths of a path scatter into different directions (e.g. by dispersion), a single
+//! wavelength continues the path alone (see [sample_bsdf]).
+
+use crate::bxdf::{BxDFFlag, BxDFSamples};
+use crate::camera::Camera;
+use crate::color::{SampledSpectrum, SpectralIndices};
+use crate::geometry::{offset_ray_towards, Ray};
+use crate::integrator::guiding::PathGuiding;
+use crate::integrator::lpe::{Event, LightPathTracker, LightPaths};
+use crate::integrator::{sample_bsdf, Bounce, DirectIllumination, Heuristic, RussianRoulette};
+use crate::sampler::FloatSampler;
+use crate::scene::{Scene, SceneIntersection, SceneObject};
+use crate::{Float, UVec2, Vec3};
+use cgmath::InnerSpace;
+
+/// Observes the contributions and scattering events along a path.
+pub trait PathObserver<S> {
+    /// Observes a single wavelength continuing the path alone.
+    type Lane: PathObserver<Float>;
+
+    /// Starts observing the `i`-th wavelength continuing the path alone once the wavelengths
+    /// scatter into different directions.
+    fn lane(&self, i: usize) -> Self::Lane;
+
+    /// Adds the observations of the `i`-th wavelength after it continued the path alone.
+    fn join_lane(&mut self, i: usize, lane: Self::Lane);
+
+    /// Observes the contribution of an emitter hit by the path.
+    fn emitted(&mut self, _spectrum: S) {}
+
+    /// Observes the contribution of an emitter connected to the current vertex.
+    ///
+    /// # Arguments
+    /// * `events` - The scattering events from the current vertex to the emitter
+    /// * `spectrum` - The contribution
+    fn direct(&mut self, _events: &[Event], _spectrum: S) {}
+
+    /// Observes the path scattering at the current vertex.
+    fn scatter(&mut self, _event: Event) {}
+}
+
+impl<S> PathObserver<S> for () {
+    type Lane = ();
+
+    fn lane(&self, _: usize) -> Self::Lane {}
+
+    fn join_lane(&mut self, _: usize, _: Self::Lane) {}
+}
+
+impl<'a, I: SpectralIndices> PathObserver<I::Sampled> for LightPathTracker<'a, I> {
+    type Lane = LightPathTracker<'a, usize>;
+
+    fn lane(&self, i: usize) -> Self::Lane {
+        self.branch(self.indices().wavelengths().index(i))
+    }
+
+    fn join_lane(&mut self, i: usize, lane: Self::Lane) {
+        LightPathTracker::join_lane(self, i, lane);
+    }
+
+    fn emitted(&mut self, spectrum: I::Sampled) {
+        self.add_emitted(spectrum);
+    }
+
+    fn direct(&mut self, events: &[Event], spectrum: I::Sampled) {
+        self.add_direct(events, spectrum);
+    }
+
+    fn scatter(&mut self, event: Event) {
+        LightPathTracker::scatter(self, event);
+    }
+}
+
+/// A non-specular vertex of a path, whose incident radiance is recorded for path guiding.
+struct GuidingVertex<S> {
+    point: Vec3,
+    incident: Vec3,
+    pdf: Float,
+    /// The throughput after scattering at the vertex
+    throughput: S,
+    /// The illumination gathered up to the vertex
+    illumination: S,
+}
+
+/// Traces paths from intersections with emitter and BSDF sampling.
+#[derive(Copy, Clone)]
+pub struct PathTracer<'a> {
+    pub max_depth: u32,
+    pub sampler: FloatSampler,
+    pub direct_illum: DirectIllumination,
+    pub heuristic: Heuristic,
+    pub roulette: RussianRoulette,
+    /// Samples directions at non-specular vertices from the learned incident radiance
+    pub guiding: Option<&'a PathGuiding>,
+    /// The expressions to separate the contributions of pixel samples into
+    pub light_paths: &'a LightPaths,
+}
+
+impl PathTracer<'_> {
+    /// Traces the path of a pixel sample starting with a primary ray and separates its
+    /// contributions into the light paths.
+    ///
+    /// # Arguments
+    /// * `scene` - The scene
+    /// * `camera` - The camera
+    /// * `ray` - The primary ray
+    /// * `indices` - The spectral indices to trace
+    /// * `position` - The pixel of the sample
+    ///
+    /// # Returns
+    /// * The illumination arriving along the path, or `None` if the ray escaped a scene without
+    ///   infinite emitters
+    pub fn trace_pixel<I: SpectralIndices>(
+        &self,
+        scene: &Scene,
+        camera: &dyn Camera,
+        ray: Ray,
+        indices: &I,
+        position: UVec2,
+    ) -> Option<I::Sampled> {
+        let mut light_path = self.light_paths.tracker(*indices);
+        let illumination = self.trace_primary(scene, ray, indices, &mut light_path);
+
+        match illumination {
+            Some(_) => self
+                .light_paths
+                .add(light_path, camera.resolution(), position),
+            None => self.light_paths.add_none(camera.resolution(), position),
+        }
+
+        illumination
+    }
+
+    /// Traces the path of a pixel sample starting at its primary intersection like
+    /// [Self::trace_pixel].
+    ///
+    /// # Arguments
+    /// * `scene` - The scene
+    /// * `camera` - The camera
+    /// * `hit` - The primary intersection
+    /// * `indices` - The spectral indices to trace
+    /// * `bounce` - The primary bounce
+    /// * `position` - The pixel of the sample
+    ///
+    /// # Returns
+    /// * The illumination arriving along the path
+    pub fn trace_pixel_from<'s, I: SpectralIndices>(
+        &self,
+        scene: &'s Scene,
+        camera: &dyn Camera,
+        hit: SceneIntersection<'s>,
+        indices: &I,
+        bounce: Bounce,
+        position: UVec2,
+    ) -> I::Sampled {
+        let mut light_path = self.light_paths.tracker(*indices);
+        let illumination = self.trace(scene, hit, indices, bounce, &mut light_path);
+        self.light_paths
+            .add(light_path, camera.resolution(), position);
+
+        illumination
+    }
+
+    /// Traces a path starting at an intersection.
+    ///
+    /// # Arguments
+    /// * `scene` - The scene
+    /// * `hit` - The first intersection of the path
+    /// * `indices` - The spectral indices to trace
+    /// * `bounce` - The bounce that lead to the intersection
+    /// * `observer` - Observes the path
+    ///
+    /// # Returns
+    /// * The illumination arriving along the path
+    fn trace<'s, I: SpectralIndices>(
+        &self,
+        scene: &'s Scene,
+        hit: SceneIntersection<'s>,
+        indices: &I,
+        bounce: Bounce,
+        observer: &mut impl PathObserver<I::Sampled>,
+    ) -> I::Sampled {
+        let throughput = I::Sampled::splat(1.0);
+
+        self.trace_from(scene, hit, indices, throughput, bounce, 0, observer)
+    }
+
+    /// Traces a path starting with a primary ray.
+    ///
+    /// # Arguments
+    /// * `scene` - The scene
+    /// * `ray` - The primary ray
+    /// * `indices` - The spectral indices to trace
+    /// * `observer` - Observes the path
+    ///
+    /// # Returns
+    /// * The illumination arriving along the path, or `None` if the ray escaped a scene without
+    ///   infinite emitters
+    fn trace_primary<I: SpectralIndices>(
+        &self,
+        scene: &Scene,
+        ray: Ray,
+        indices: &I,
+        observer: &mut impl PathObserver<I::Sampled>,
+    ) -> Option<I::Sampled> {
+        let bounce = Bounce::primary(ray.origin);
+
+        match scene.intersect(ray) {
+            Some(hit) => Some(self.trace(scene, hit, indices, bounce, observer)),
+            None if !scene.infinite_emitters().is_empty() => {
+                let throughput = I::Sampled::splat(1.0);
+                Some(self.escaped(scene, ray, indices, throughput, &bounce, 0, observer))
+            }
+            None => None,
+        }
+    }
+
+    /// Traces a path from an intersection at a depth with a throughput.
+    #[allow(clippy::too_many_arguments)]
+    fn trace_from<'s, I: SpectralIndices>(
+        &self,
+        scene: &'s Scene,
+        mut hit: SceneIntersection<'s>,
+        indices: &I,
+        mut throughput: I::Sampled,
+        mut bounce: Bounce,
+        depth: u32,
+        observer: &mut impl PathObserver<I::Sampled>,
+    ) -> I::Sampled {
+        let mut illumination = I::Sampled::splat(0.0);
+        let mut vertices = Vec::new();
+
+        for curr_depth in depth..self.max_depth {
+            let outgoing = -hit.i.incoming;
+            let point = hit.i.point;
+            let normal = hit.i.normal;
+            let bsdf = hit.object.bsdf();
+
+            if let SceneObject::Emitter(e) = hit.object {
+                if curr_depth != 1 || self.direct_illum != DirectIllumination::Indirect {
+                    let weight = self.direct_illum.emitter_weight(
+                        scene,
+                        e,
+                        hit.index,
+                        &bounce,
+                        hit.i.incoming,
+                        Some(self.heuristic),
+                    );
+                    let emitted = throughput
+                        .mul(e.radiance_towards(indices, normal, outgoing))
+                        .mul_t(weight);
+                    illumination.add_assign(emitted);
+                    observer.emitted(emitted);
+                }
+            }
+
+            // bxdfs only sample the hemisphere of the normal, so guiding is restricted to it
+            let guiding = self.guiding.filter(|_| {
+                bsdf.is_non_specular()
+                    && !bsdf.matches(BxDFFlag::SPECULAR)
+                    && outgoing.dot(normal) > 0.0
+            });
+            let distribution = guiding.and_then(|g| g.distribution(point));
+            let mixture_pdf = |incident: Vec3| {
+                let bsdf_pdf = bsdf.pdf(normal, incident, outgoing, BxDFFlag::empty());
+
+                distribution.as_ref().map_or(bsdf_pdf, |d| {
+                    d.bsdf_fraction() * bsdf_pdf + (1.0 - d.bsdf_fraction()) * d.pdf(incident)
+                })
+            };
+
+            let direct = throughput.mul(self.direct_illum.sample_with(
+                scene,
+                &hit,
+                &self.sampler,
+                indices,
+                Some(self.heuristic),
+                mixture_pdf,
+                |events, spectrum| observer.direct(events, throughput.mul(spectrum)),
+            ));
+            illumination.add_assign(direct);
+
+            if guiding.is_some() {
+                // sample either the bsdf or the learned distribution, weighting by both pdfs
+                let incident = match &distribution {
+                    Some(d) if self.sampler.float() >= d.bsdf_fraction() => {
+                        d.sample(self.sampler.vec2())
+                    }
+                    _ => match bsdf
+                        .sample(
+                            normal,
+                            outgoing,
+                            self.sampler.sample(),
+                            BxDFFlag::empty(),
+                            indices,
+                        )
+                        .and_then(BxDFSamples::bundle)
+                    {
+                        Some(bxdf_sample) => bxdf_sample.incident,
+                        None => break,
+                    },
+                };
+
+                let (spectrum, flag) = bsdf.evaluate_with_flag(
+                    normal,
+                    incident,
+                    outgoing,
+                    self.sampler.float(),
+                    BxDFFlag::empty(),
+                    indices,
+                );
+                let pdf = mixture_pdf(incident);
+                if pdf == 0.0 || spectrum.is_black() {
+                    break;
+                }
+
+                throughput = throughput.mul(spectrum.mul_t(incident.dot(normal).abs() / pdf));
+                bounce = bounce.scatter(&hit, pdf, BxDFFlag::empty());
+                observer.scatter(Event::from(flag));
+
+                match self
+                    .roulette
+                    .survive(curr_depth, throughput.max_value(), self.sampler)
+                {
+                    Some(factor) => throughput = throughput.mul_t(factor),
+                    None => break,
+                }
+
+                vertices.push(GuidingVertex {
+                    point,
+                    incident,
+                    pdf,
+                    throughput,
+                    illumination,
+                });
+
+                let ray = offset_ray_towards(point, normal, incident);
+                match scene.intersect(ray) {
+                    Some(i) => hit = i,
+                    None => {
+                        let escaped = self.escaped(
+                            scene,
+                            ray,
+                            indices,
+                            throughput,
+                            &bounce,
+                            curr_depth + 1,
+                            observer,
+                        );
+                        illumination.add_assign(escaped);
+                        break;
+                    }
+                }
+
+                continue;
+            }
+
+            let bxdf_sample = match sample_bsdf(
+                bsdf,
+                normal,
+                outgoing,
+                self.sampler,
+                indices,
+                &mut throughput,
+            ) {
+                Some(s) if s.pdf > 0.0 && !s.spectrum.is_black() => s,
+                _ => break,
+            };
+
+            let cos_abs = if bxdf_sample.flag.specular() {
+                // division of cosine omitted in specular bxdfs
+                1.0
+            } else {
+                bxdf_sample.incident.dot(normal).abs()
+            };
+
+            if let Some(i) = bxdf_sample.lane {
+                let throughput = throughput.as_slice()[i]
+                    * bxdf_sample.spectrum.as_slice()[i]
+                    * (cos_abs / bxdf_sample.pdf);
+                let throughput = match self.roulette.survive(curr_depth, throughput, self.sampler) {
+                    Some(factor) => throughput * factor,
+                    None => break,
+                };
+                let bounce = bounce.scatter(&hit, bxdf_sample.pdf, bxdf_sample.flag);
+                let index = indices.wavelengths().index(i);
+                let mut lane = observer.lane(i);
+                lane.scatter(Event::from(bxdf_sample.flag));
+
+                let ray = offset_ray_towards(point, normal, bxdf_sample.incident);
+                illumination.as_mut_slice()[i] += match scene.intersect(ray) {
+                    Some(new_hit) => self.trace_from(
+                        scene,
+                        new_hit,
+                        &index,
+                        throughput,
+                        bounce,
+                        curr_depth + 1,
+                        &mut lane,
+                    ),
+                    None => self.escaped(
+                        scene,
+                        ray,
+                        &index,
+                        throughput,
+                        &bounce,
+                        curr_depth + 1,
+                        &mut lane,
+                    ),
+                };
+                observer.join_lane(i, lane);
+
+                break;
+            }
+
+            throughput = throughput.mul(bxdf_sample.spectrum.mul_t(cos_abs / bxdf_sample.pdf));
+            bounce = bounce.scatter(&hit, bxdf_sample.pdf, bxdf_sample.flag);
+            observer.scatter(Event::from(bxdf_sample.flag));
+
+            match self
+                .roulette
+                .survive(curr_depth, throughput.max_value(), self.sampler)
+            {
+                Some(factor) => throughput = throughput.mul_t(factor),
+                None => break,
+            }
+
+            let ray = offset_ray_towards(point, normal, bxdf_sample.incident);
+            match scene.intersect(ray) {
+                Some(i) => hit = i,
+                None => {
+                    let escaped = self.escaped(
+                        scene,
+                        ray,
+                        indices,
+                        throughput,
+                        &bounce,
+                        curr_depth + 1,
+                        observer,
+                    );
+                    illumination.add_assign(escaped);
+                    break;
+                }
+            }
+        }
+
+        if let Some(guiding) = self.guiding {
+            for vertex in vertices {
+                // the radiance arriving at the vertex, averaged over the wavelengths
+                let mut radiance = illumination;
+                for ((r, t), i) in radiance
+                    .as_mut_slice()
+                    .iter_mut()
+                    .zip(vertex.throughput.as_slice())
+                    .zip(vertex.illumination.as_slice())
+                {
+                    *r = if *t > 0.0 { (*r - i) / t } else { 0.0 };
+                }
+
+                guiding.record(
+                    vertex.point,
+                    vertex.incident,
+                    radiance.average(),
+                    vertex.pdf,
+                );
+            }
+        }
+
+        illumination
+    }
+
+    /// Computes the illumination of the infinite emitters seen by a ray escaping the scene.
+    ///
+    /// # Arguments
+    /// * `scene` - The scene
+    /// * `ray` - The escaped ray
+    /// * `indices` - The spectral indices to trace
+    /// * `throughput` - The throughput of the path up to the ray
+    /// * `bounce` - The bounce that lead to the ray
+    /// * `depth` - The depth the ray would have hit an object at
+    /// * `observer` - Observes the path
+    #[allow(clippy::too_many_arguments)]
+    fn escaped<I: SpectralIndices>(
+        &self,
+        scene: &Scene,
+        ray: Ray,
+        indices: &I,
+        throughput: I::Sampled,
+        bounce: &Bounce,
+        depth: u32,
+        observer: &mut impl PathObserver<I::Sampled>,
+    ) -> I::Sampled {
+        if depth >= self.max_depth
+            || (depth == 1 && self.direct_illum == DirectIllumination::Indirect)
+        {
+            return I::Sampled::splat(0.0);
+        }
+
+        let radiance =
+            self.direct_illum
+                .escaped(scene, bounce, ray.direction, indices, Some(self.heuristic));
+        let emitted = throughput.mul(radiance);
+        if !emitted.is_black() {
+            observer.emitted(emitted);
+        }
+
+        emitted
+    }
+}
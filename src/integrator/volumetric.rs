@@ -0,0 +1,290 @@
+use crate::bxdf::BxDFFlag;
+use crate::camera::sensor::{Pixel, SplatBuffer};
+use crate::camera::Camera;
+use crate::geometry::{offset_ray_towards, Ray};
+use crate::integrator::lpe::{Event, LightPaths};
+use crate::integrator::{
+    sample_bsdf, Bounce, DirectIllumination, Heuristic, Integrator, RussianRoulette,
+};
+use crate::medium::{Medium, MediumEvent};
+use crate::sampler::{FloatSampler, SpectralSampler};
+use crate::scene::{Scene, SceneObject};
+use crate::util::PacketOps;
+use crate::{Float, Vec3, PACKET_SIZE};
+use cgmath::InnerSpace;
+use serde::{Deserialize, Serialize};
+
+/// A path tracer for scenes with participating media.
+///
+/// Collisions inside media are sampled with delta tracking, while shadow rays estimate the
+/// transmittance with ratio tracking.
+/// Receivers without a BSDF only bound their medium and are passed through.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct VolumetricPath {
+    max_depth: u32,
+    sampler: FloatSampler,
+    spectral_sampler: SpectralSampler,
+    direct_illum: DirectIllumination,
+    #[serde(default)]
+    heuristic: Heuristic,
+    #[serde(default)]
+    roulette: RussianRoulette,
+    /// The expressions to separate the contributions into
+    #[serde(default)]
+    #[serde(skip_serializing_if = "LightPaths::is_empty")]
+    light_paths: LightPaths,
+}
+
+impl VolumetricPath {
+    /// Samples the direct illumination at a scattering point inside a medium.
+    ///
+    /// # Arguments
+    /// * `scene` - The scene
+    /// * `medium` - The medium containing the point
+    /// * `point` - The scattering point
+    /// * `outgoing` - The direction towards the previous vertex of the path
+    /// * `indices` - The spectral indices
+    fn sample_medium(
+        &self,
+        scene: &Scene,
+        medium: &dyn Medium,
+        point: Vec3,
+        outgoing: Vec3,
+        indices: &[usize; PACKET_SIZE],
+    ) -> [Float; PACKET_SIZE] {
+        let mut illum = [0.0; PACKET_SIZE];
+        let phase = medium.phase();
+
+        let emitter_indices = self
+            .direct_illum
+            .emitter_indices(scene, point, self.sampler.float());
+        for &index in emitter_indices {
+            let emitter = match scene.get_emitter(index as usize) {
+                Some(e) => e,
+                None => continue,
+            };
+
+            let emitter_sample = emitter.sample(point, self.sampler.vec2(), indices);
+
+            if emitter_sample.pdf > 0.0 && !emitter_sample.radiance.is_black() {
+                let p = phase.evaluate(outgoing, emitter_sample.incident);
+
+                if p > 0.0 {
+                    let factor = DirectIllumination::sample_factor(
+                        emitter,
+                        self.direct_illum.emitter_pdf(scene, point, index),
+                        &emitter_sample,
+                        || p,
+                        Some(self.heuristic),
+                    );
+                    let transmittance =
+                        emitter_sample
+                            .occlusion
+                            .unoccluded(scene, self.sampler, indices);
+
+                    let rhs = emitter_sample.radiance.mul(transmittance).mul_t(p * factor);
+                    illum.add_assign(rhs);
+                }
+            }
+        }
+
+        illum
+    }
+}
+
+#[typetag::serde]
+impl Integrator for VolumetricPath {
+    fn integrate(
+        &self,
+        scene: &Scene,
+        camera: &dyn Camera,
+        primary_ray: Ray,
+        pixel: &mut Pixel,
+        _: &SplatBuffer,
+    ) {
+        let indices = self.spectral_sampler.create();
+        let mut light_path = self.light_paths.tracker(indices);
+        let mut illumination = [0.0; PACKET_SIZE];
+        let mut throughput = [1.0; PACKET_SIZE];
+        let mut bounce = Bounce::primary(primary_ray.origin);
+
+        let mut ray = primary_ray;
+        let mut curr_depth = 0;
+        while curr_depth < self.max_depth && !throughput.is_black() {
+            let hit = scene.intersect(ray);
+
+            let t_end = hit.as_ref().map_or(ray.t_end, |h| h.i.t);
+            let segment = Ray::new2(ray.origin, ray.direction, ray.t_start, t_end);
+            if let Some(medium) = scene.medium_along(segment) {
+                match medium.sample_packet(segment, self.sampler, &indices, &mut throughput) {
+                    MediumEvent::Absorb => break,
+                    MediumEvent::Scatter(point) => {
+                        let outgoing = -ray.direction;
+
+                        let direct = self.sample_medium(scene, medium, point, outgoing, &indices);
+                        illumination.add_assign(throughput.mul(direct));
+                        light_path.add_direct(&[Event::Volume], throughput.mul(direct));
+                        light_path.scatter(Event::Volume);
+
+                        // the phase function is perfectly importance sampled
+                        let (incident, pdf) = medium.phase().sample(outgoing, self.sampler.vec2());
+
+                        bounce = Bounce::new(point, pdf, false);
+
+                        match self.roulette.survive(
+                            curr_depth,
+                            throughput.max_value(),
+                            self.sampler,
+                        ) {
+                            Some(factor) => throughput.mul_assign_t(factor),
+                            None => break,
+                        }
+                        ray = Ray::new(point, incident);
+                        curr_depth += 1;
+                        continue;
+                    }
+                    MediumEvent::Pass => {}
+                }
+            }
+
+            let hit = match hit {
+                Some(hit) => hit,
+                None => {
+                    if curr_depth != 1 || self.direct_illum != DirectIllumination::Indirect {
+                        let radiance = self.direct_illum.escaped(
+                            scene,
+                            &bounce,
+                            ray.direction,
+                            &indices,
+                            Some(self.heuristic),
+                        );
+                        illumination.add_assign(throughput.mul(radiance));
+                        light_path.add_emitted(throughput.mul(radiance));
+                    }
+                    break;
+                }
+            };
+
+            let outgoing = -hit.i.incoming;
+            let point = hit.i.point;
+            let normal = hit.i.normal;
+            let bsdf = hit.object.bsdf();
+
+            match hit.object {
+                SceneObject::Receiver(r) if r.is_interface() => {
+                    ray = offset_ray_towards(point, normal, ray.direction);
+                    continue;
+                }
+                SceneObject::Emitter(e)
+                    if curr_depth != 1 || self.direct_illum != DirectIllumination::Indirect =>
+                {
+                    let weight = self.direct_illum.emitter_weight(
+                        scene,
+                        e,
+                        hit.index,
+                        &bounce,
+                        hit.i.incoming,
+                        Some(self.heuristic),
+                    );
+                    let radiance = e
+                        .radiance_towards(&indices, hit.i.normal, -hit.i.incoming)
+                        .mul_t(weight);
+                    illumination.add_assign(throughput.mul(radiance));
+                    light_path.add_emitted(throughput.mul(radiance));
+                }
+                _ => {}
+            }
+
+            let bsdf_pdf = |incident| bsdf.pdf(normal, incident, outgoing, BxDFFlag::empty());
+            illumination.add_assign(throughput.mul(self.direct_illum.sample_with(
+                scene,
+                &hit,
+                &self.sampler,
+                &indices,
+                Some(self.heuristic),
+                bsdf_pdf,
+                |events, spectrum| light_path.add_direct(events, throughput.mul(spectrum)),
+            )));
+
+            let sample = match sample_bsdf(
+                bsdf,
+                normal,
+                outgoing,
+                self.sampler,
+                &indices,
+                &mut throughput,
+            ) {
+                Some(s) if s.pdf > 0.0 && !s.spectrum.is_black() => s,
+                _ => break,
+            };
+
+            let cos_abs = if sample.flag.specular() {
+                // division of cosine omitted in specular bxdfs
+                1.0
+            } else {
+                sample.incident.dot(normal).abs()
+            };
+
+            throughput.mul_assign(sample.spectrum.mul_t(cos_abs / sample.pdf));
+            bounce = bounce.scatter(&hit, sample.pdf, sample.flag);
+            light_path.scatter(Event::from(sample.flag));
+
+            match self
+                .roulette
+                .survive(curr_depth, throughput.max_value(), self.sampler)
+            {
+                Some(factor) => throughput.mul_assign_t(factor),
+                None => break,
+            }
+
+            ray = offset_ray_towards(point, normal, sample.incident);
+            curr_depth += 1;
+        }
+
+        pixel.add_packet(&illumination, &indices);
+        self.light_paths
+            .add(light_path, camera.resolution(), pixel.position);
+    }
+
+    fn light_paths(&self) -> Option<&LightPaths> {
+        Some(&self.light_paths)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::integrator::testing::{assert_converges, render};
+    use crate::Float;
+
+    const INTEGRATOR: &str = r#"{ "VolumetricPath": (max_depth: 4, sampler: Random, spectral_sampler: Random, direct_illum: All) }"#;
+
+    #[test]
+    fn converges_to_path() {
+        assert_converges(INTEGRATOR, 256, 0.05);
+    }
+
+    #[test]
+    fn attenuates_by_absorption() {
+        // every camera ray travels the radius of the emitting sphere around the eye
+        let scene = r#"(
+            objects: [
+                Emitter((
+                    geometry: { "Sphere": (center: (x: 0, y: 0.6, z: 1.8), radius: 1) },
+                    emission: Constant(2),
+                )),
+            ],
+            medium: Some({
+                "Homogeneous": (sigma_a: Constant(0.5), sigma_s: Constant(0)),
+            }),
+        )"#;
+
+        let expected = 2.0 * Float::exp(-0.5);
+        let actual = render(INTEGRATOR, scene, 512);
+        assert!(
+            (actual - expected).abs() <= 0.03 * expected,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+}
@@ -1,10 +1,11 @@
-use crate::bxdf::BxDFFlag;
-use crate::camera::sensor::Pixel;
+use crate::bxdf::{BxDFFlag, BxDFSamples};
+use crate::camera::sensor::{Pixel, SplatBuffer};
+use crate::camera::Camera;
 use crate::geometry::{offset_ray_towards, Ray};
-use crate::integrator::{DirectIllumination, Integrator};
+use crate::integrator::{Bounce, DirectIllumination, Integrator, RussianRoulette};
 use crate::sampler::FloatSampler;
 use crate::scene::{Scene, SceneIntersection, SceneObject};
-use crate::Spectrum;
+use crate::{Float, Spectrum};
 use cgmath::InnerSpace;
 use serde::{Deserialize, Serialize};
 
@@ -13,6 +14,8 @@ pub struct Whitted {
     max_depth: u32,
     sampler: FloatSampler,
     direct_illum: DirectIllumination,
+    #[serde(default)]
+    roulette: RussianRoulette,
 }
 
 impl Whitted {
@@ -20,11 +23,13 @@ impl Whitted {
         max_depth: u32,
         sampler: FloatSampler,
         direct_illum: DirectIllumination,
+        roulette: RussianRoulette,
     ) -> Self {
         Self {
             max_depth,
             sampler,
             direct_illum,
+            roulette,
         }
     }
 
@@ -33,13 +38,17 @@ impl Whitted {
         scene: &Scene,
         intersection: &SceneIntersection,
         depth: u32,
+        throughput: Float,
         flag: BxDFFlag,
     ) -> Spectrum {
         let outgoing = -intersection.i.incoming;
         let bsdf = intersection.object.bsdf();
         let normal = intersection.i.normal;
 
-        if let Some(bxdf_sample) = bsdf.sample(normal, outgoing, self.sampler.sample(), flag) {
+        if let Some(bxdf_sample) = bsdf
+            .sample(normal, outgoing, self.sampler.sample(), flag, &())
+            .and_then(BxDFSamples::bundle)
+        {
             if bxdf_sample.pdf > 0.0 && !bxdf_sample.spectrum.is_black() {
                 let cos_abs = if bxdf_sample.flag.specular() {
                     // division of cosine omitted in specular bxdfs
@@ -49,13 +58,29 @@ impl Whitted {
                 };
 
                 if cos_abs != 0.0 {
+                    let factor = bxdf_sample.spectrum * (cos_abs / bxdf_sample.pdf);
+                    let throughput = throughput * factor.max_value();
+
+                    let survival = match self.roulette.survive(depth, throughput, self.sampler) {
+                        Some(survival) => survival,
+                        None => return Spectrum::splat(0.0),
+                    };
+
                     let refl_ray =
                         offset_ray_towards(intersection.i.point, normal, bxdf_sample.incident);
 
-                    if let Some(si) = scene.intersect(refl_ray) {
-                        let illum = self.illumination(scene, &si, depth);
-                        return illum * bxdf_sample.spectrum * (cos_abs / bxdf_sample.pdf);
-                    }
+                    let illum = match scene.intersect(refl_ray) {
+                        Some(si) => self.illumination(scene, &si, depth, throughput * survival),
+                        None => {
+                            let bounce = Bounce::new(
+                                intersection.i.point,
+                                bxdf_sample.pdf,
+                                bxdf_sample.flag.specular(),
+                            );
+                            self.escaped(scene, &bounce, refl_ray, depth)
+                        }
+                    };
+                    return illum * factor * survival;
                 }
             }
         }
@@ -63,30 +88,46 @@ impl Whitted {
         Spectrum::splat(0.0)
     }
 
+    /// Computes the illumination of the infinite emitters seen by a ray escaping the scene.
+    fn escaped(&self, scene: &Scene, bounce: &Bounce, ray: Ray, depth: u32) -> Spectrum {
+        if depth != 1 || self.direct_illum != DirectIllumination::Indirect {
+            self.direct_illum
+                .escaped(scene, bounce, ray.direction, &(), None)
+        } else {
+            Spectrum::splat(0.0)
+        }
+    }
+
     fn illumination(
         &self,
         scene: &Scene,
         intersection: &SceneIntersection,
         depth: u32,
+        throughput: Float,
     ) -> Spectrum {
         let mut illumination = Spectrum::splat(0.0);
 
         if let SceneObject::Emitter(e) = intersection.object {
             if depth != 1 || self.direct_illum != DirectIllumination::Indirect {
-                illumination += e.radiance();
+                illumination +=
+                    e.radiance_towards(&(), intersection.i.normal, -intersection.i.incoming);
             }
         }
 
-        illumination += self.direct_illum.sample(scene, intersection, &self.sampler);
+        illumination += self
+            .direct_illum
+            .sample(scene, intersection, &self.sampler, &(), None);
 
         let new_depth = depth + 1;
         if new_depth < self.max_depth {
             let reflection = BxDFFlag::SPECULAR | BxDFFlag::REFLECTION;
             let transmission = BxDFFlag::SPECULAR | BxDFFlag::TRANSMISSION;
             let both = reflection | transmission;
-            illumination += self.integrate_flag(scene, intersection, new_depth, reflection);
-            illumination += self.integrate_flag(scene, intersection, new_depth, transmission);
-            illumination += self.integrate_flag(scene, intersection, new_depth, both);
+            illumination +=
+                self.integrate_flag(scene, intersection, new_depth, throughput, reflection);
+            illumination +=
+                self.integrate_flag(scene, intersection, new_depth, throughput, transmission);
+            illumination += self.integrate_flag(scene, intersection, new_depth, throughput, both);
         }
 
         illumination
@@ -95,9 +136,21 @@ impl Whitted {
 
 #[typetag::serde]
 impl Integrator for Whitted {
-    fn integrate(&self, scene: &Scene, primary_ray: Ray, pixel: &mut Pixel) {
+    fn integrate(
+        &self,
+        scene: &Scene,
+        _: &dyn Camera,
+        primary_ray: Ray,
+        pixel: &mut Pixel,
+        _: &SplatBuffer,
+    ) {
         if let Some(i) = scene.intersect(primary_ray) {
-            let illumination = self.illumination(scene, &i, 0);
+            let illumination = self.illumination(scene, &i, 0, 1.0);
+
+            pixel.add(illumination);
+        } else if !scene.infinite_emitters().is_empty() {
+            let bounce = Bounce::primary(primary_ray.origin);
+            let illumination = self.escaped(scene, &bounce, primary_ray, 0);
 
             pixel.add(illumination);
         } else {
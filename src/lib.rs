@@ -3,12 +3,14 @@ extern crate image;
 use cgmath::{Basis3, Matrix4, Vector2, Vector3};
 use image::{ImageBuffer, Pixel, Rgb};
 
+pub mod aov;
 pub mod bxdf;
 pub mod camera;
 pub mod color;
 pub mod config;
 pub mod geometry;
 pub mod integrator;
+pub mod medium;
 pub mod renderer;
 pub mod runtime;
 pub mod sampler;
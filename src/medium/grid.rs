@@ -0,0 +1,524 @@
+//! # Summary
+//! A heterogeneous medium, whose density is given by a dense voxel grid.
+//!
+//! # File format
+//! Grid files contain the following little-endian data:
+//! 1. The magic bytes `GRID`
+//! 2. The resolution `(x, y, z)` as three `u32`
+//! 3. The `x * y * z` densities as `f32`, with `x` varying fastest and `z` slowest
+//!
+//! Files ending in `.grid` are read as is, while files ending in `.lz4` are decompressed with a
+//! prepended size first.
+//!
+//! # Placement
+//! The grid fills the unit cube `[0, 1]³`, which is then scaled, rotated and translated (in this
+//! order) into the scene.
+
+use crate::geometry::Ray;
+use crate::medium::{HenyeyGreenstein, Medium};
+use crate::{Float, Rot3, Spectrum, Vec3};
+use cgmath::{ElementWise, One, Rotation, Vector3};
+use core::convert::TryFrom;
+use lz4_flex::decompress_size_prepended;
+use serde::{Deserialize, Serialize, Serializer};
+use std::fs;
+
+const MAGIC: &[u8; 4] = b"GRID";
+const HEADER_SIZE: usize = 16;
+/// The maximal resolution of the majorant grid per axis.
+const MAJORANT_RESOLUTION: usize = 16;
+
+/// A dense grid of densities inside the unit cube.
+///
+/// The densities are located at the voxel centers and interpolated trilinearly in between.
+#[derive(Clone, Debug, PartialEq)]
+pub struct DensityGrid {
+    resolution: [usize; 3],
+    densities: Vec<Float>,
+}
+
+impl DensityGrid {
+    /// Creates a new density grid.
+    ///
+    /// # Arguments
+    /// * `resolution` - The number of voxels per axis
+    /// * `densities` - The densities, with `x` varying fastest and `z` slowest
+    pub fn new(resolution: [usize; 3], densities: Vec<Float>) -> Result<Self, String> {
+        let [x, y, z] = resolution;
+        let len = match x.checked_mul(y).and_then(|xy| xy.checked_mul(z)) {
+            Some(len) if len > 0 => len,
+            _ => return Err(format!("Invalid grid resolution: {resolution:?}")),
+        };
+        if densities.len() != len {
+            return Err(format!(
+                "Expected {} densities for a grid of {resolution:?}, got {}",
+                len,
+                densities.len()
+            ));
+        }
+        if densities.iter().any(|&d| !(d >= 0.0 && d.is_finite())) {
+            return Err("Grid densities must be finite and non-negative".into());
+        }
+
+        Ok(Self {
+            resolution,
+            densities,
+        })
+    }
+
+    /// Loads a density grid from a `.grid` or `.lz4` file.
+    ///
+    /// # Arguments
+    /// * `path` - The path of the grid file
+    pub fn load(path: &str) -> Result<Self, String> {
+        let bytes = match path.rsplit_once('.') {
+            Some((_, "grid")) => fs::read(path).map_err(|e| e.to_string())?,
+            Some((_, "lz4")) => {
+                let binary = fs::read(path).map_err(|e| e.to_string())?;
+                decompress_size_prepended(&binary).map_err(|e| e.to_string())?
+            }
+            Some((_, ending)) => return Err(format!("Unknown file ending: {ending}")),
+            None => return Err(format!("Unknown file type: {path}")),
+        };
+
+        Self::from_bytes(&bytes)
+    }
+
+    /// Parses a density grid from the (uncompressed) grid file format.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, String> {
+        if bytes.len() < HEADER_SIZE || &bytes[0..4] != MAGIC {
+            return Err("Missing grid header".into());
+        }
+
+        let read_u32 = |i: usize| {
+            let mut le = [0; 4];
+            le.copy_from_slice(&bytes[i..i + 4]);
+            u32::from_le_bytes(le) as usize
+        };
+        let resolution = [read_u32(4), read_u32(8), read_u32(12)];
+
+        let body = &bytes[HEADER_SIZE..];
+        if !body.len().is_multiple_of(4) {
+            return Err(format!("Truncated grid data of {} bytes", body.len()));
+        }
+
+        let densities = body
+            .chunks_exact(4)
+            .map(|chunk| {
+                let mut le = [0; 4];
+                le.copy_from_slice(chunk);
+                f32::from_le_bytes(le) as Float
+            })
+            .collect();
+
+        Self::new(resolution, densities)
+    }
+
+    /// Encodes this density grid into the (uncompressed) grid file format.
+    #[allow(clippy::unnecessary_cast)]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_SIZE + 4 * self.densities.len());
+        bytes.extend_from_slice(MAGIC);
+        for r in self.resolution {
+            bytes.extend_from_slice(&(r as u32).to_le_bytes());
+        }
+        for &d in &self.densities {
+            bytes.extend_from_slice(&(d as f32).to_le_bytes());
+        }
+
+        bytes
+    }
+
+    #[inline]
+    fn voxel(&self, x: usize, y: usize, z: usize) -> Float {
+        let [rx, ry, _] = self.resolution;
+        self.densities[x + rx * (y + ry * z)]
+    }
+
+    /// Looks up the trilinearly interpolated density.
+    ///
+    /// # Arguments
+    /// * `point` - The point in the unit cube (density is zero outside)
+    pub fn lookup(&self, point: Vec3) -> Float {
+        if !(0.0..=1.0).contains(&point.x)
+            || !(0.0..=1.0).contains(&point.y)
+            || !(0.0..=1.0).contains(&point.z)
+        {
+            return 0.0;
+        }
+
+        // (lower voxel index, upper voxel index, weight of upper voxel) per axis
+        let axis = |p: Float, res: usize| {
+            let continuous = (p * res as Float - 0.5).max(0.0);
+            let lower = (continuous as usize).min(res - 1);
+            let upper = (lower + 1).min(res - 1);
+
+            (lower, upper, (continuous - lower as Float).min(1.0))
+        };
+        let (x0, x1, wx) = axis(point.x, self.resolution[0]);
+        let (y0, y1, wy) = axis(point.y, self.resolution[1]);
+        let (z0, z1, wz) = axis(point.z, self.resolution[2]);
+
+        let lerp = |a: Float, b: Float, w: Float| a + w * (b - a);
+
+        let d00 = lerp(self.voxel(x0, y0, z0), self.voxel(x1, y0, z0), wx);
+        let d10 = lerp(self.voxel(x0, y1, z0), self.voxel(x1, y1, z0), wx);
+        let d01 = lerp(self.voxel(x0, y0, z1), self.voxel(x1, y0, z1), wx);
+        let d11 = lerp(self.voxel(x0, y1, z1), self.voxel(x1, y1, z1), wx);
+
+        lerp(lerp(d00, d10, wy), lerp(d01, d11, wy), wz)
+    }
+}
+
+/// A coarse grid storing the maximal density of the voxels influencing each of its cells.
+#[derive(Clone, Debug)]
+struct MajorantGrid {
+    resolution: [usize; 3],
+    majorants: Vec<Float>,
+}
+
+impl MajorantGrid {
+    fn new(density: &DensityGrid) -> Self {
+        let resolution = density.resolution.map(|r| r.min(MAJORANT_RESOLUTION));
+        let [mx, my, mz] = resolution;
+        let mut majorants: Vec<Float> = vec![0.0; mx * my * mz];
+
+        // a voxel influences the interpolation up to one voxel around its center
+        let range = |i: usize, res: usize, m_res: usize| {
+            let lo = (i as Float - 0.5) / res as Float;
+            let hi = (i as Float + 1.5) / res as Float;
+            let to_cell = |p: Float| ((p.max(0.0) * m_res as Float) as usize).min(m_res - 1);
+
+            to_cell(lo)..=to_cell(hi)
+        };
+
+        let [rx, ry, rz] = density.resolution;
+        for z in 0..rz {
+            for y in 0..ry {
+                for x in 0..rx {
+                    let d = density.voxel(x, y, z);
+
+                    for cz in range(z, rz, mz) {
+                        for cy in range(y, ry, my) {
+                            for cx in range(x, rx, mx) {
+                                let m = &mut majorants[cx + mx * (cy + my * cz)];
+                                *m = m.max(d);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        Self {
+            resolution,
+            majorants,
+        }
+    }
+
+    /// Returns the maximal density of all cells overlapping the bounds of the line between both
+    /// points in the unit cube.
+    fn max_between(&self, a: Vec3, b: Vec3) -> Float {
+        let cell = |p: Float, res: usize| ((p * res as Float) as usize).min(res - 1);
+        let range = |a: Float, b: Float, res: usize| cell(a.min(b), res)..=cell(a.max(b), res);
+        let [mx, my, _] = self.resolution;
+
+        let mut max: Float = 0.0;
+        for z in range(a.z, b.z, self.resolution[2]) {
+            for y in range(a.y, b.y, my) {
+                for x in range(a.x, b.x, mx) {
+                    max = max.max(self.majorants[x + mx * (y + my * z)]);
+                }
+            }
+        }
+
+        max
+    }
+}
+
+/// A heterogeneous medium, whose coefficients are scaled by the density of a voxel grid.
+#[derive(Deserialize)]
+#[serde(try_from = "GridSerde")]
+pub struct Grid {
+    sigma_a: Spectrum,
+    sigma_s: Spectrum,
+    phase: HenyeyGreenstein,
+    density: DensityGrid,
+    majorants: MajorantGrid,
+    scale: Vec3,
+    rotation: Rot3,
+    rotation_inv: Rot3,
+    translation: Vec3,
+    /// The maximal extinction coefficient `σ_t` for a density of `1`.
+    sigma_t_max: Float,
+}
+
+impl Grid {
+    /// Creates a new grid medium.
+    ///
+    /// # Arguments
+    /// * `sigma_a` - The absorption coefficient for a density of `1`
+    /// * `sigma_s` - The scattering coefficient for a density of `1`
+    /// * `phase` - The phase function
+    /// * `density` - The density grid
+    /// * `scale` - The scaling of the unit cube (1st application)
+    /// * `rotation` - The rotation of the unit cube (2nd application)
+    /// * `translation` - The translation of the unit cube (3rd application)
+    pub fn new(
+        sigma_a: Spectrum,
+        sigma_s: Spectrum,
+        phase: HenyeyGreenstein,
+        density: DensityGrid,
+        scale: Vec3,
+        rotation: Rot3,
+        translation: Vec3,
+    ) -> Self {
+        let majorants = MajorantGrid::new(&density);
+
+        Self {
+            sigma_a,
+            sigma_s,
+            phase,
+            density,
+            majorants,
+            scale,
+            rotation,
+            rotation_inv: rotation.invert(),
+            translation,
+            sigma_t_max: (sigma_a + sigma_s).max_value(),
+        }
+    }
+
+    /// Transforms a point from the scene into the unit cube of the grid.
+    #[inline]
+    fn to_local(&self, point: Vec3) -> Vec3 {
+        self.rotation_inv
+            .rotate_vector(point - self.translation)
+            .div_element_wise(self.scale)
+    }
+}
+
+#[typetag::serde]
+impl Medium for Grid {
+    #[inline]
+    fn coefficients(&self, point: Vec3, index: usize) -> (Float, Float) {
+        let density = self.density.lookup(self.to_local(point));
+
+        (density * self.sigma_a[index], density * self.sigma_s[index])
+    }
+
+    fn majorant(&self, ray: Ray) -> (Float, Float, Float) {
+        // the transformation is affine, so the ray parameter stays the same
+        let origin = self.to_local(ray.origin);
+        let direction = self
+            .rotation_inv
+            .rotate_vector(ray.direction)
+            .div_element_wise(self.scale);
+
+        // clip the segment to the unit cube
+        let t1 = (-origin).div_element_wise(direction);
+        let t2 = (Vector3::new(1.0, 1.0, 1.0) - origin).div_element_wise(direction);
+        let t_min = t1.zip(t2, |a, b| a.min(b));
+        let t_max = t1.zip(t2, |a, b| a.max(b));
+
+        let t_start = ray.t_start.max(t_min.x.max(t_min.y).max(t_min.z));
+        let t_end = ray.t_end.min(t_max.x.min(t_max.y).min(t_max.z));
+        if t_start >= t_end || t_start.is_nan() || t_end.is_nan() {
+            return (ray.t_start, ray.t_start, 0.0);
+        }
+
+        let clamp = |p: Vec3| p.map(|c| c.clamp(0.0, 1.0));
+        let a = clamp(origin + t_start * direction);
+        let b = clamp(origin + t_end * direction);
+
+        let majorant = self.majorants.max_between(a, b) * self.sigma_t_max;
+
+        (t_start, t_end, majorant)
+    }
+
+    #[inline]
+    fn phase(&self) -> &HenyeyGreenstein {
+        &self.phase
+    }
+}
+
+impl Serialize for Grid {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        GridSerde::Checkpoint(GridCheckpoint {
+            sigma_a: self.sigma_a,
+            sigma_s: self.sigma_s,
+            phase: self.phase,
+            resolution: self.density.resolution,
+            densities: self.density.densities.clone(),
+            scale: self.scale,
+            rotation: self.rotation,
+            translation: self.translation,
+        })
+        .serialize(serializer)
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+enum GridSerde {
+    Config(GridConfig),
+    Checkpoint(GridCheckpoint),
+}
+impl TryFrom<GridSerde> for Grid {
+    type Error = String;
+
+    fn try_from(serde: GridSerde) -> Result<Self, Self::Error> {
+        match serde {
+            GridSerde::Config(c) => {
+                let density = DensityGrid::load(&c.path)?;
+
+                Ok(Grid::new(
+                    c.sigma_a,
+                    c.sigma_s,
+                    c.phase,
+                    density,
+                    c.scale.unwrap_or_else(|| Vec3::new(1.0, 1.0, 1.0)),
+                    c.rotation.unwrap_or_else(Rot3::one),
+                    c.translation.unwrap_or_else(|| Vec3::new(0.0, 0.0, 0.0)),
+                ))
+            }
+            GridSerde::Checkpoint(c) => {
+                let density = DensityGrid::new(c.resolution, c.densities)?;
+
+                Ok(Grid::new(
+                    c.sigma_a,
+                    c.sigma_s,
+                    c.phase,
+                    density,
+                    c.scale,
+                    c.rotation,
+                    c.translation,
+                ))
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct GridConfig {
+    /// The path of the grid file
+    path: String,
+    /// The absorption coefficient for a density of `1`
+    sigma_a: Spectrum,
+    /// The scattering coefficient for a density of `1`
+    sigma_s: Spectrum,
+    #[serde(default)]
+    phase: HenyeyGreenstein,
+    /// Optional scaling (1st application)
+    #[serde(default)]
+    scale: Option<Vec3>,
+    /// Optional rotation (2nd application)
+    /// - params: (axis, angle)
+    #[serde(default)]
+    rotation: Option<Rot3>,
+    /// Optional translation (3rd application)
+    #[serde(default)]
+    translation: Option<Vec3>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct GridCheckpoint {
+    sigma_a: Spectrum,
+    sigma_s: Spectrum,
+    phase: HenyeyGreenstein,
+    resolution: [usize; 3],
+    densities: Vec<Float>,
+    scale: Vec3,
+    rotation: Rot3,
+    translation: Vec3,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Wavelengths;
+    use crate::sampler::FloatSampler;
+    use crate::PACKET_SIZE;
+
+    fn gradient() -> DensityGrid {
+        let densities = (0..4 * 2 * 2).map(|i| (i % 4) as Float).collect();
+        DensityGrid::new([4, 2, 2], densities).unwrap()
+    }
+
+    #[test]
+    fn bytes_roundtrip() {
+        let grid = gradient();
+        let parsed = DensityGrid::from_bytes(&grid.to_bytes()).unwrap();
+
+        assert_eq!(grid, parsed);
+        assert!(DensityGrid::from_bytes(&grid.to_bytes()[..20]).is_err());
+    }
+
+    #[test]
+    fn rejects_overflowing_resolution() {
+        let mut bytes = MAGIC.to_vec();
+        for _ in 0..3 {
+            bytes.extend_from_slice(&u32::MAX.to_le_bytes());
+        }
+
+        assert!(DensityGrid::from_bytes(&bytes).is_err());
+    }
+
+    #[test]
+    fn escaping_rays_are_clipped() {
+        let medium = Grid::new(
+            Spectrum::splat(1.0),
+            Spectrum::splat(1.0),
+            HenyeyGreenstein::default(),
+            gradient(),
+            Vec3::new(2.0, 2.0, 2.0),
+            Rot3::one(),
+            Vec3::new(0.0, 0.0, 0.0),
+        );
+
+        let ray = Ray::new(Vec3::new(1.0, 1.0, 1.0), Vec3::unit_x());
+        let (t_min, t_max, majorant) = medium.majorant(ray);
+        assert_eq!(ray.t_start, t_min);
+        assert!((t_max - 1.0).abs() < 1e-5);
+        assert!(majorant > 0.0);
+
+        let mut transmittance = [0.0; 2];
+        let indices = [0, 1];
+        medium.transmittance(
+            ray,
+            FloatSampler::Random,
+            Wavelengths::Indices(&indices),
+            &mut transmittance,
+        );
+        assert!(transmittance.iter().all(|&tr| (0.0..=1.0).contains(&tr)));
+
+        let mut beta = [1.0; PACKET_SIZE];
+        medium.sample_packet(ray, FloatSampler::Random, &[0; PACKET_SIZE], &mut beta);
+    }
+
+    #[test]
+    fn trilinear_lookup() {
+        let grid = gradient();
+
+        // voxel centers
+        assert_eq!(0.0, grid.lookup(Vec3::new(0.125, 0.5, 0.5)));
+        assert_eq!(3.0, grid.lookup(Vec3::new(0.875, 0.5, 0.5)));
+        // between the centers of the 2nd and 3rd voxel
+        assert!((grid.lookup(Vec3::new(0.5, 0.5, 0.5)) - 1.5).abs() < 1e-5);
+        // outside
+        assert_eq!(0.0, grid.lookup(Vec3::new(1.5, 0.5, 0.5)));
+    }
+
+    #[test]
+    fn majorant_bounds_density() {
+        let grid = gradient();
+        let majorants = MajorantGrid::new(&grid);
+
+        for i in 0..=20 {
+            let p = Vec3::new(i as Float / 20.0, 0.3, 0.7);
+            assert!(grid.lookup(p) <= majorants.max_between(p, p));
+        }
+    }
+}
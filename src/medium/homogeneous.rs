@@ -0,0 +1,74 @@
+use crate::color::Wavelengths;
+use crate::geometry::Ray;
+use crate::medium::{HenyeyGreenstein, Medium};
+use crate::sampler::FloatSampler;
+use crate::{Float, Spectrum, Vec3};
+use serde::{Deserialize, Serialize};
+
+/// A homogeneous medium has the same coefficients at every point.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Homogeneous {
+    sigma_a: Spectrum,
+    sigma_s: Spectrum,
+    #[serde(default)]
+    phase: HenyeyGreenstein,
+}
+
+impl Homogeneous {
+    /// Creates a new homogeneous medium.
+    ///
+    /// # Arguments
+    /// * `sigma_a` - The absorption coefficient
+    /// * `sigma_s` - The scattering coefficient
+    /// * `phase` - The phase function
+    pub const fn new(sigma_a: Spectrum, sigma_s: Spectrum, phase: HenyeyGreenstein) -> Self {
+        Self {
+            sigma_a,
+            sigma_s,
+            phase,
+        }
+    }
+}
+
+#[typetag::serde]
+impl Medium for Homogeneous {
+    #[inline]
+    fn coefficients(&self, _: Vec3, index: usize) -> (Float, Float) {
+        (self.sigma_a[index], self.sigma_s[index])
+    }
+
+    #[inline]
+    fn majorant(&self, ray: Ray) -> (Float, Float, Float) {
+        (
+            ray.t_start,
+            ray.t_end,
+            (self.sigma_a + self.sigma_s).max_value(),
+        )
+    }
+
+    #[inline]
+    fn phase(&self) -> &HenyeyGreenstein {
+        &self.phase
+    }
+
+    /// Computes the transmittance analytically with Beer's law.
+    fn transmittance(
+        &self,
+        ray: Ray,
+        _: FloatSampler,
+        wavelengths: Wavelengths,
+        transmittance: &mut [Float],
+    ) {
+        let distance = ray.t_end - ray.t_start;
+        for (i, tr) in transmittance.iter_mut().enumerate() {
+            let index = wavelengths.index(i);
+            let sigma_t = self.sigma_a[index] + self.sigma_s[index];
+            // wavelengths without extinction pass even infinite segments
+            *tr = if sigma_t > 0.0 {
+                (-sigma_t * distance).exp()
+            } else {
+                1.0
+            };
+        }
+    }
+}
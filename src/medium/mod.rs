@@ -0,0 +1,249 @@
+//! # Summary
+//! Participating media scatter and absorb light between surfaces (e.g. fog, smoke or murky
+//! water).
+//!
+//! Media are described by their absorption coefficient `σ_a`, their scattering coefficient `σ_s`
+//! and a phase function.
+//! Their sum `σ_t = σ_a + σ_s` is the extinction coefficient.
+//!
+//! All media are traced with null collisions against a majorant, which bounds `σ_t` for all
+//! wavelengths of the spectrum, so that a single distance serves all wavelengths of a packet.
+
+pub mod grid;
+pub mod homogeneous;
+pub mod phase;
+
+pub use grid::*;
+pub use homogeneous::*;
+pub use phase::*;
+
+use crate::color::Wavelengths;
+use crate::geometry::Ray;
+use crate::sampler::FloatSampler;
+use crate::util::PacketOps;
+use crate::{Float, Vec3, PACKET_SIZE};
+
+/// The outcome of tracking a ray through a medium.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MediumEvent {
+    /// The ray was absorbed.
+    Absorb,
+    /// The ray was scattered at the given point.
+    Scatter(Vec3),
+    /// The ray reached the end of its segment.
+    Pass,
+}
+
+#[typetag::serde]
+pub trait Medium: Send + Sync {
+    /// Returns the absorption coefficient `σ_a` and the scattering coefficient `σ_s`.
+    ///
+    /// # Constraints
+    /// * `index` - Should be within `[0, `[Spectrum::size](crate::Spectrum::size)`)`.
+    ///
+    /// # Arguments
+    /// * `point` - The point inside the medium
+    /// * `index` - The spectral index
+    fn coefficients(&self, point: Vec3, index: usize) -> (Float, Float);
+
+    /// Clips the ray segment `[t_start, t_end]` to the extent of the medium and bounds `σ_t`
+    /// along it for all wavelengths.
+    ///
+    /// # Arguments
+    /// * `ray` - The ray segment
+    ///
+    /// # Returns
+    /// * The start of the clipped segment
+    /// * The end of the clipped segment
+    /// * The majorant along the clipped segment (`0` if it is empty)
+    fn majorant(&self, ray: Ray) -> (Float, Float, Float);
+
+    /// Returns the phase function.
+    fn phase(&self) -> &HenyeyGreenstein;
+
+    /// Computes the transmittance along the ray segment `[t_start, t_end]` with ratio tracking.
+    ///
+    /// Only the segment clipped to the medium (see [Self::majorant]) is tracked.
+    /// An infinite segment (e.g. towards an infinite emitter) only transmits the wavelengths
+    /// without extinction.
+    ///
+    /// # Constraints
+    /// * `transmittance` - Should have the length of `wavelengths`.
+    ///
+    /// # Arguments
+    /// * `ray` - The ray segment
+    /// * `sampler` - The sampler of the tentative collisions
+    /// * `wavelengths` - The wavelengths to compute for
+    /// * `transmittance` - The transmittance to write, one value per wavelength
+    fn transmittance(
+        &self,
+        ray: Ray,
+        sampler: FloatSampler,
+        wavelengths: Wavelengths,
+        transmittance: &mut [Float],
+    ) {
+        ratio_tracking(self, ray, sampler, wavelengths, transmittance)
+    }
+
+    /// Samples a collision along the ray segment `[t_start, t_end]` with delta tracking.
+    ///
+    /// The collision type is chosen by the average coefficients of the packet and the throughput
+    /// of every wavelength is weighted accordingly, so that a single path serves all of them.
+    /// Along an infinite segment, the wavelengths without extinction escape and all others collide
+    /// eventually, so the path randomly continues with one of both groups.
+    ///
+    /// # Constraints
+    /// * `indices` - All values should be within `[0, `[Spectrum::size](crate::Spectrum::size)`)`.
+    ///
+    /// # Arguments
+    /// * `ray` - The ray segment
+    /// * `sampler` - The sampler of the collisions
+    /// * `indices` - The spectral indices
+    /// * `beta` - The throughput of the path, weighted in place
+    ///
+    /// # Returns
+    /// * The sampled event
+    fn sample_packet(
+        &self,
+        ray: Ray,
+        sampler: FloatSampler,
+        indices: &[usize; PACKET_SIZE],
+        beta: &mut [Float; PACKET_SIZE],
+    ) -> MediumEvent {
+        let (t_min, t_max, majorant) = self.majorant(ray);
+        if majorant <= 0.0 || t_min >= t_max {
+            return MediumEvent::Pass;
+        }
+
+        if t_max.is_infinite() {
+            let clear = clear_wavelengths(self, ray.at(t_min), indices);
+            let alive = beta.iter().filter(|&&b| b != 0.0).count();
+            let escaping = (0..PACKET_SIZE)
+                .filter(|&i| clear[i] && beta[i] != 0.0)
+                .count();
+
+            if escaping > 0 {
+                let probability = escaping as Float / alive as Float;
+                let escape = sampler.float() < probability;
+                let weight = if escape {
+                    1.0 / probability
+                } else {
+                    1.0 / (1.0 - probability)
+                };
+
+                for (b, &c) in beta.iter_mut().zip(&clear) {
+                    *b = if c == escape { *b * weight } else { 0.0 };
+                }
+                if escape {
+                    return MediumEvent::Pass;
+                }
+            }
+        }
+
+        let mut t = t_min;
+        loop {
+            t -= (1.0 - sampler.float()).ln() / majorant;
+            if t >= t_max {
+                return MediumEvent::Pass;
+            }
+
+            let point = ray.at(t);
+            let mut sigma_a = [0.0; PACKET_SIZE];
+            let mut sigma_s = [0.0; PACKET_SIZE];
+            let mut sigma_n = [0.0; PACKET_SIZE];
+            for i in 0..PACKET_SIZE {
+                let (a, s) = self.coefficients(point, indices[i]);
+                sigma_a[i] = a;
+                sigma_s[i] = s;
+                sigma_n[i] = (majorant - a - s).max(0.0);
+            }
+
+            let mean =
+                |sigma: &[Float; PACKET_SIZE]| sigma.iter().sum::<Float>() / PACKET_SIZE as Float;
+            let (mean_a, mean_s, mean_n) = (mean(&sigma_a), mean(&sigma_s), mean(&sigma_n));
+
+            // without extinction, only null collisions would follow along an infinite segment
+            if mean_a + mean_s == 0.0 && t_max.is_infinite() {
+                return MediumEvent::Pass;
+            }
+
+            let u = sampler.float() * (mean_a + mean_s + mean_n);
+            if u < mean_a {
+                return MediumEvent::Absorb;
+            } else if u < mean_a + mean_s {
+                beta.mul_assign(sigma_s.div_t(mean_s));
+                return MediumEvent::Scatter(point);
+            }
+
+            beta.mul_assign(sigma_n.div_t(mean_n));
+            if beta.is_black() {
+                return MediumEvent::Absorb;
+            }
+        }
+    }
+}
+
+/// Computes the transmittance of the given wavelengths along the ray segment `[t_start, t_end]`
+/// with ratio tracking.
+fn ratio_tracking<M: Medium + ?Sized>(
+    medium: &M,
+    ray: Ray,
+    sampler: FloatSampler,
+    wavelengths: Wavelengths,
+    transmittance: &mut [Float],
+) {
+    transmittance.fill(1.0);
+
+    let (t_min, t_max, majorant) = medium.majorant(ray);
+    if majorant <= 0.0 || t_min >= t_max {
+        return;
+    }
+
+    // wavelengths without extinction would never end the tracking
+    if t_max.is_infinite() {
+        let point = ray.at(t_min);
+        for (i, tr) in transmittance.iter_mut().enumerate() {
+            let (sigma_a, sigma_s) = medium.coefficients(point, wavelengths.index(i));
+            if sigma_a + sigma_s > 0.0 {
+                *tr = 0.0;
+            }
+        }
+        return;
+    }
+
+    let mut t = t_min;
+    loop {
+        t -= (1.0 - sampler.float()).ln() / majorant;
+        if t >= t_max {
+            return;
+        }
+
+        let point = ray.at(t);
+        for (i, tr) in transmittance.iter_mut().enumerate() {
+            let (sigma_a, sigma_s) = medium.coefficients(point, wavelengths.index(i));
+            *tr *= (1.0 - (sigma_a + sigma_s) / majorant).max(0.0);
+        }
+
+        if transmittance.iter().all(|&tr| tr == 0.0) {
+            return;
+        }
+    }
+}
+
+/// Returns which wavelengths have no extinction at a point.
+///
+/// Only unbounded media (i.e. homogeneous ones) have infinite segments, so the coefficients at
+/// any point along them hold for the whole segment.
+fn clear_wavelengths<M: Medium + ?Sized>(
+    medium: &M,
+    point: Vec3,
+    indices: &[usize; PACKET_SIZE],
+) -> [bool; PACKET_SIZE] {
+    let mut clear = [false; PACKET_SIZE];
+    for (c, &index) in clear.iter_mut().zip(indices) {
+        let (sigma_a, sigma_s) = medium.coefficients(point, index);
+        *c = sigma_a + sigma_s == 0.0;
+    }
+
+    clear
+}
@@ -0,0 +1,89 @@
+use crate::bxdf::bxdf_to_world;
+use crate::{Float, Vec2, Vec3};
+use cgmath::InnerSpace;
+use serde::{Deserialize, Serialize};
+#[cfg(not(feature = "f64"))]
+use std::f32::consts::{FRAC_1_PI, TAU};
+#[cfg(feature = "f64")]
+use std::f64::consts::{FRAC_1_PI, TAU};
+
+/// The Henyey-Greenstein phase function describes the angular distribution of light scattered
+/// inside a medium with a single asymmetry parameter `g`.
+/// * `g < 0` - Back scattering
+/// * `g = 0` - Isotropic scattering
+/// * `g > 0` - Forward scattering
+#[derive(Copy, Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct HenyeyGreenstein {
+    g: Float,
+}
+
+impl HenyeyGreenstein {
+    /// Creates a new Henyey-Greenstein phase function.
+    ///
+    /// # Constraints
+    /// * `g` - Should be within `(-1, 1)`.
+    ///
+    /// # Arguments
+    /// * `g` - The asymmetry parameter
+    pub const fn new(g: Float) -> Self {
+        Self { g }
+    }
+
+    /// Evaluates the phase function for the given cosine between the direction of propagation
+    /// and the scattered direction.
+    #[inline]
+    fn evaluate_cos(&self, cos_theta: Float) -> Float {
+        let g2 = self.g * self.g;
+        let denom = 1.0 + g2 - 2.0 * self.g * cos_theta;
+
+        0.25 * FRAC_1_PI * (1.0 - g2) / (denom * denom.max(0.0).sqrt())
+    }
+
+    /// Evaluates the phase function.
+    /// As it is perfectly importance sampled, this is also the pdf (with respect to the solid
+    /// angle) of [Self::sample].
+    ///
+    /// # Constraints
+    /// * `outgoing` - Should be normalized.
+    /// * `incident` - Should be normalized.
+    ///
+    /// # Arguments
+    /// * `outgoing` - The direction towards the previous vertex of the path
+    /// * `incident` - The direction towards the next vertex of the path
+    #[inline]
+    pub fn evaluate(&self, outgoing: Vec3, incident: Vec3) -> Float {
+        self.evaluate_cos(-outgoing.dot(incident))
+    }
+
+    /// Samples an incident direction.
+    ///
+    /// # Constraints
+    /// * `outgoing` - Should be normalized.
+    /// * `sample` - All values should be within `[0, 1)`.
+    ///
+    /// # Arguments
+    /// * `outgoing` - The direction towards the previous vertex of the path
+    /// * `sample` - A random sample
+    ///
+    /// # Returns
+    /// * The incident direction
+    /// * The pdf (with respect to the solid angle), which equals the phase function
+    pub fn sample(&self, outgoing: Vec3, sample: Vec2) -> (Vec3, Float) {
+        let cos_theta = if self.g.abs() < 1e-3 {
+            1.0 - 2.0 * sample.x
+        } else {
+            let g2 = self.g * self.g;
+            let sqr = (1.0 - g2) / (1.0 - self.g + 2.0 * self.g * sample.x);
+
+            ((1.0 + g2 - sqr * sqr) / (2.0 * self.g)).clamp(-1.0, 1.0)
+        };
+
+        let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+        let (sin_phi, cos_phi) = Float::sin_cos(sample.y * TAU);
+        let local = Vec3::new(sin_theta * cos_phi, cos_theta, sin_theta * sin_phi);
+
+        let incident = bxdf_to_world(-outgoing).rotate_vector(local);
+
+        (incident, self.evaluate_cos(cos_theta))
+    }
+}
@@ -1,7 +1,9 @@
+use crate::aov::AovBuffer;
 use crate::camera::sensor::Sensor;
 use crate::camera::Camera;
 use crate::config::Config;
 use crate::integrator::Integrator;
+use crate::sampler::FloatSampler;
 use crate::scene::Scene;
 use crate::Image;
 use image::{ImageBuffer, ImageFormat, Pixel, Rgb};
@@ -16,6 +18,8 @@ pub struct Renderer {
     pub integrator: Box<dyn Integrator>,
     #[serde(skip_serializing_if = "Scene::is_empty")]
     pub scene: Scene,
+    #[serde(skip)]
+    pub aovs: AovBuffer,
 }
 
 impl Renderer {
@@ -26,17 +30,24 @@ impl Renderer {
         integrator: Box<dyn Integrator>,
         scene: Scene,
     ) -> Self {
+        let aovs = AovBuffer::new(config.aovs.clone(), sensor.resolution);
+
         Self {
             config,
             camera,
             sensor,
             integrator,
             scene,
+            aovs,
         }
     }
 
     pub fn reset(&mut self) {
         self.sensor.reset();
+        self.aovs.reset();
+        if let Some(light_paths) = self.integrator.light_paths() {
+            light_paths.reset();
+        }
     }
 
     pub fn integrate(&self, index: usize) {
@@ -44,8 +55,16 @@ impl Renderer {
 
         for px in &mut tile.pixels {
             let ray = self.camera.primary_ray(px.position);
-
-            self.integrator.integrate(&self.scene, ray, px);
+            self.aovs
+                .add(&self.scene, ray, px.position, FloatSampler::Random);
+
+            self.integrator.integrate(
+                &self.scene,
+                self.camera.as_ref(),
+                ray,
+                px,
+                &self.sensor.splats,
+            );
         }
     }
 
@@ -56,8 +75,9 @@ impl Renderer {
 
         for sensor_tile in &self.sensor.tiles {
             for px in &sensor_tile.lock().pixels {
-                exr.put_pixel(px.position.x, px.position.y, Rgb::from(px.average));
-                png.put_pixel(px.position.x, px.position.y, Rgb::from(px.average));
+                let value = px.average + self.sensor.splats.get(px.position);
+                exr.put_pixel(px.position.x, px.position.y, Rgb::from(value));
+                png.put_pixel(px.position.x, px.position.y, Rgb::from(value));
             }
         }
 
@@ -73,7 +93,8 @@ impl Renderer {
 
         for sensor_tile in &self.sensor.tiles {
             for px in &sensor_tile.lock().pixels {
-                image.put_pixel(px.position.x, px.position.y, Rgb::from(px.average));
+                let value = px.average + self.sensor.splats.get(px.position);
+                image.put_pixel(px.position.x, px.position.y, Rgb::from(value));
             }
         }
 
@@ -106,6 +127,42 @@ impl Renderer {
             Ok(_) => log::info!(target: "Renderer", "saved image! (PNG)"),
             Err(e) => log::error!(target: "Renderer", "unable to save image (PNG): {}", e),
         }
+
+        if let Some(light_paths) = self.integrator.light_paths() {
+            for (index, light_path) in light_paths.paths().iter().enumerate() {
+                let image = light_paths.get_image(index);
+                let lpe_path = format!("{}-{}.exr", &path, light_path.name);
+
+                match image.save_with_format(lpe_path, ImageFormat::OpenExr) {
+                    Ok(_) => log::info!(
+                        target: "Renderer",
+                        "saved light path {}! (OpenEXR)",
+                        light_path.name
+                    ),
+                    Err(e) => log::error!(
+                        target: "Renderer",
+                        "unable to save light path {} (OpenEXR): {}",
+                        light_path.name,
+                        e
+                    ),
+                }
+            }
+        }
+
+        for &aov in self.aovs.aovs() {
+            let image = self.aovs.get_image(aov);
+            let aov_path = format!("{}-{}.exr", &path, aov.name());
+
+            match image.save_with_format(aov_path, ImageFormat::OpenExr) {
+                Ok(_) => log::info!(target: "Renderer", "saved AOV {}! (OpenEXR)", aov.name()),
+                Err(e) => log::error!(
+                    target: "Renderer",
+                    "unable to save AOV {} (OpenEXR): {}",
+                    aov.name(),
+                    e
+                ),
+            }
+        }
     }
 }
 
@@ -156,6 +213,7 @@ impl<'de> Deserialize<'de> for Renderer {
 //             output: "".to_string(),
 //             passes: 0,
 //             threads: None,
+//             aovs: vec![],
 //         };
 //         let camera = Box::new(DummyCamera);
 //         let sensor = Sensor::new(camera.resolution());
@@ -5,7 +5,7 @@ use lz4_flex::{compress_prepend_size, decompress_size_prepended};
 use serde::{Deserialize, Serialize};
 use signal_hook::consts as signals;
 use std::path::Path;
-use std::sync::Arc;
+use std::sync::{Arc, Barrier};
 use std::time::{Duration, Instant};
 use std::{fs, thread};
 
@@ -214,6 +214,11 @@ impl Runtime {
             return;
         }
 
+        if self.renderer.integrator.progressive().is_some() {
+            self.run_progressive(Some(frames));
+            return;
+        }
+
         let total_tiles = self.total_tiles;
         if self.tile_progress.load(Ordering::SeqCst) >= total_tiles {
             return;
@@ -243,6 +248,11 @@ impl Runtime {
     }
 
     pub fn run(&self) {
+        if self.renderer.integrator.progressive().is_some() {
+            self.run_progressive(None);
+            return;
+        }
+
         for _ in 0..self.threadpool.workers() {
             let c = self.cancel.clone();
             let r = self.renderer.clone();
@@ -266,6 +276,86 @@ impl Runtime {
         }
     }
 
+    /// Runs the passes of a progressive integrator.
+    ///
+    /// All workers run the phases of a pass together and wait for each other in between.
+    /// The leading worker decides whether to continue, so that a cancellation stops all workers
+    /// at the same pass.
+    ///
+    /// # Arguments
+    /// * `frames` - The number of passes to run (`None` for all remaining ones)
+    fn run_progressive(&self, frames: Option<usize>) {
+        let workers = self.threadpool.workers();
+        let barrier = Arc::new(Barrier::new(workers));
+        let jobs = Arc::new(AtomicUsize::new(0));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let tiles = self.tiles_per_frame;
+        let passes = self.passes;
+        let frames = frames.unwrap_or(passes);
+
+        for _ in 0..workers {
+            let c = self.cancel.clone();
+            let r = self.renderer.clone();
+            let p = self.tile_progress.clone();
+            let barrier = barrier.clone();
+            let jobs = jobs.clone();
+            let stop = stop.clone();
+
+            self.threadpool.execute(move || {
+                let progressive = match r.integrator.progressive() {
+                    Some(p) => p,
+                    None => return,
+                };
+
+                for frame in 0.. {
+                    if barrier.wait().is_leader() {
+                        // previous calls may still have been running when this one was queued
+                        let pass = p.load(Ordering::SeqCst) / tiles;
+                        let done = frame >= frames || pass >= passes || c.load(Ordering::Relaxed);
+                        if !done {
+                            progressive.begin_pass(&r.scene, r.camera.as_ref(), pass);
+                            p.store(pass * tiles, Ordering::SeqCst);
+                        }
+
+                        stop.store(done, Ordering::SeqCst);
+                        jobs.store(0, Ordering::SeqCst);
+                    }
+                    barrier.wait();
+                    if stop.load(Ordering::SeqCst) {
+                        break;
+                    }
+
+                    let num_jobs = progressive.num_jobs();
+                    loop {
+                        let job = jobs.fetch_add(1, Ordering::SeqCst);
+                        if job >= num_jobs {
+                            break;
+                        }
+
+                        progressive.run_job(&r.scene, r.camera.as_ref(), &r.sensor.splats, job);
+                    }
+
+                    if barrier.wait().is_leader() {
+                        progressive.end_jobs();
+                        jobs.store(0, Ordering::SeqCst);
+                    }
+                    barrier.wait();
+
+                    loop {
+                        let tile = jobs.fetch_add(1, Ordering::SeqCst);
+                        if tile >= tiles {
+                            break;
+                        }
+
+                        r.integrate(tile);
+                        p.fetch_add(1, Ordering::SeqCst);
+                    }
+                }
+            })
+        }
+    }
+
     pub fn done(&self) -> bool {
         self.tile_progress.load(Ordering::Relaxed) >= self.total_tiles
     }
@@ -1,15 +1,7 @@
+use crate::sampler::next_float;
 use crate::{Float, Vec2};
 use serde::{Deserialize, Serialize};
 
-#[cfg(not(feature = "f64"))]
-fn rand() -> f32 {
-    fastrand::f32()
-}
-#[cfg(feature = "f64")]
-fn rand() -> f64 {
-    fastrand::f64()
-}
-
 #[derive(Copy, Clone, Debug, Deserialize, Serialize)]
 pub enum CameraSampler {
     Const(Float, Float),
@@ -21,7 +13,7 @@ impl CameraSampler {
     pub fn sample(self) -> Vec2 {
         match self {
             CameraSampler::Const(x, y) => Vec2::new(x, y),
-            CameraSampler::Random => Vec2::new(rand(), rand()),
+            CameraSampler::Random => Vec2::new(next_float(), next_float()),
         }
     }
 }
@@ -1,15 +1,7 @@
+use crate::sampler::next_float;
 use crate::{Float, Vec2};
 use serde::{Deserialize, Serialize};
 
-#[cfg(not(feature = "f64"))]
-fn rand() -> f32 {
-    fastrand::f32()
-}
-#[cfg(feature = "f64")]
-fn rand() -> f64 {
-    fastrand::f64()
-}
-
 #[derive(Copy, Clone, Debug)]
 pub struct Sample {
     pub float: Float,
@@ -25,6 +17,8 @@ impl Sample {
 #[derive(Copy, Clone, Debug, Serialize, Deserialize)]
 pub enum FloatSampler {
     Constant(Float),
+    /// The random numbers of the current thread, replayable by a
+    /// [SampleStream](crate::sampler::SampleStream)
     Random,
 }
 
@@ -34,7 +28,7 @@ impl FloatSampler {
     pub fn float(self) -> Float {
         match self {
             FloatSampler::Constant(c) => c,
-            FloatSampler::Random => rand(),
+            FloatSampler::Random => next_float(),
         }
     }
 
@@ -43,7 +37,7 @@ impl FloatSampler {
     pub fn vec2(self) -> Vec2 {
         match self {
             FloatSampler::Constant(c) => Vec2::new(c, c),
-            FloatSampler::Random => Vec2::new(rand(), rand()),
+            FloatSampler::Random => Vec2::new(next_float(), next_float()),
         }
     }
 
@@ -52,7 +46,9 @@ impl FloatSampler {
     pub fn sample(self) -> Sample {
         match self {
             FloatSampler::Constant(c) => Sample::new(c, Vec2::new(c, c)),
-            FloatSampler::Random => Sample::new(rand(), Vec2::new(rand(), rand())),
+            FloatSampler::Random => {
+                Sample::new(next_float(), Vec2::new(next_float(), next_float()))
+            }
         }
     }
 }
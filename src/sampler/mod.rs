@@ -1,7 +1,9 @@
 pub mod camera;
 pub mod float;
 pub mod spectral;
+pub mod stream;
 
 pub use camera::*;
 pub use float::*;
 pub use spectral::*;
+pub use stream::*;
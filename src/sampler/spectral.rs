@@ -1,10 +1,12 @@
+use crate::sampler::next_float;
 use crate::util::Index;
-use crate::{Spectrum, PACKET_SIZE};
-use core::ops::RangeBounds;
+use crate::{Float, Spectrum, PACKET_SIZE};
 use serde::{Deserialize, Serialize};
 
-fn rand(range: impl RangeBounds<usize>) -> usize {
-    fastrand::usize(range)
+/// Returns a random index inside `[0, len)`.
+#[inline]
+fn rand(len: usize) -> usize {
+    ((next_float() * len as Float) as usize).min(len - 1)
 }
 
 #[derive(Copy, Clone, Debug, Serialize, Deserialize)]
@@ -23,9 +25,9 @@ impl SpectralSampler {
         }
 
         match self {
-            SpectralSampler::Random => [0; PACKET_SIZE].map(|_| rand(0..Spectrum::size())),
+            SpectralSampler::Random => [0; PACKET_SIZE].map(|_| rand(Spectrum::size())),
             SpectralSampler::Hero => {
-                let hero = rand(0..Spectrum::size());
+                let hero = rand(Spectrum::size());
 
                 let mut i = Index::new();
                 [0; PACKET_SIZE].map(|_| Self::hero(i.get_and_inc(), hero))
@@ -37,12 +39,12 @@ impl SpectralSampler {
         match self {
             SpectralSampler::Random => indices
                 .iter_mut()
-                .for_each(|idx| *idx = rand(0..Spectrum::size())),
+                .for_each(|idx| *idx = rand(Spectrum::size())),
             SpectralSampler::Hero => {
                 if PACKET_SIZE == Spectrum::size() {
                     indices.iter_mut().enumerate().for_each(|(i, idx)| *idx = i);
                 } else {
-                    let hero = rand(0..Spectrum::size());
+                    let hero = rand(Spectrum::size());
 
                     indices
                         .iter_mut()
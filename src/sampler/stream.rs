@@ -0,0 +1,224 @@
+use crate::Float;
+use core::cell::RefCell;
+use core::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(not(feature = "f64"))]
+use std::f32::consts::PI;
+#[cfg(feature = "f64")]
+use std::f64::consts::PI;
+
+thread_local! {
+    static STREAM: RefCell<Option<SampleStream>> = const { RefCell::new(None) };
+}
+
+/// The number of streams running on any thread, so that the thread-local stream is only looked up
+/// while there are any (i.e. by [Pssmlt](crate::integrator::Pssmlt)).
+static RUNNING: AtomicUsize = AtomicUsize::new(0);
+
+#[cfg(not(feature = "f64"))]
+fn rand() -> f32 {
+    fastrand::f32()
+}
+#[cfg(feature = "f64")]
+fn rand() -> f64 {
+    fastrand::f64()
+}
+
+#[cfg(not(feature = "f64"))]
+fn uniform(rng: &mut fastrand::Rng) -> f32 {
+    rng.f32()
+}
+#[cfg(feature = "f64")]
+fn uniform(rng: &mut fastrand::Rng) -> f64 {
+    rng.f64()
+}
+
+/// Returns the next random number inside `[0, 1)` of the current thread.
+///
+/// Usually, this is a fresh random number, but if a [SampleStream] is running on the current
+/// thread, the number is taken from it instead.
+/// All samplers draw their random numbers from here, so that integrators can be replayed and
+/// mutated without knowing about it.
+#[inline]
+pub fn next_float() -> Float {
+    if RUNNING.load(Ordering::Relaxed) == 0 {
+        return rand();
+    }
+
+    STREAM.with(|s| match s.borrow_mut().as_mut() {
+        Some(stream) => stream.next_sample(),
+        None => rand(),
+    })
+}
+
+/// A single value of a [SampleStream], mutated lazily once it is requested.
+#[derive(Copy, Clone, Debug, Default)]
+struct PrimarySample {
+    value: Float,
+    /// The iteration of the last modification
+    modified: u64,
+    backup_value: Float,
+    backup_modified: u64,
+}
+
+impl PrimarySample {
+    #[inline]
+    fn backup(&mut self) {
+        self.backup_value = self.value;
+        self.backup_modified = self.modified;
+    }
+
+    #[inline]
+    fn restore(&mut self) {
+        self.value = self.backup_value;
+        self.modified = self.backup_modified;
+    }
+}
+
+/// A replayable stream of primary samples (random numbers inside `[0, 1)`).
+///
+/// Every iteration of the stream either replaces all of its samples (large step) or perturbs them
+/// slightly (small step). Afterwards, the iteration can be accepted or rejected, restoring the
+/// samples of the previous one.
+///
+/// Samples are mutated lazily, so that the stream grows with the number of samples requested.
+/// The mutations follow
+/// - authors: C. Kelemen & L. Szirmay-Kalos & G. Antal & F. Csonka
+/// - paper: A Simple and Robust Mutation Strategy for the Metropolis Light Transport Algorithm
+/// - year: 2002
+#[derive(Clone, Debug)]
+pub struct SampleStream {
+    rng: fastrand::Rng,
+    samples: Vec<PrimarySample>,
+    /// The index of the next sample
+    index: usize,
+    iteration: u64,
+    last_large_step: u64,
+    large_step: bool,
+    large_step_probability: Float,
+    sigma: Float,
+}
+
+impl Default for SampleStream {
+    fn default() -> Self {
+        Self::new(0, 1.0, 0.01)
+    }
+}
+
+impl SampleStream {
+    /// Creates a new stream.
+    ///
+    /// # Arguments
+    /// * `seed` - The seed of the stream, the same seed results in the same samples
+    /// * `large_step_probability` - The probability of an iteration being a large step
+    /// * `sigma` - The standard deviation of small steps
+    pub fn new(seed: u64, large_step_probability: Float, sigma: Float) -> Self {
+        Self {
+            rng: fastrand::Rng::with_seed(seed),
+            samples: Vec::new(),
+            index: 0,
+            iteration: 0,
+            last_large_step: 0,
+            large_step: true,
+            large_step_probability,
+            sigma,
+        }
+    }
+
+    #[inline]
+    pub const fn is_large_step(&self) -> bool {
+        self.large_step
+    }
+
+    /// Starts a new iteration, deciding whether it is a large or small step.
+    pub fn start_iteration(&mut self) {
+        self.iteration += 1;
+        self.large_step = uniform(&mut self.rng) < self.large_step_probability;
+        self.index = 0;
+    }
+
+    /// Accepts the current iteration.
+    pub fn accept(&mut self) {
+        if self.large_step {
+            self.last_large_step = self.iteration;
+        }
+    }
+
+    /// Rejects the current iteration, restoring the samples of the previous one.
+    pub fn reject(&mut self) {
+        let iteration = self.iteration;
+        self.samples
+            .iter_mut()
+            .filter(|s| s.modified == iteration)
+            .for_each(PrimarySample::restore);
+
+        self.iteration -= 1;
+    }
+
+    /// Returns the next sample of the current iteration.
+    pub fn next_sample(&mut self) -> Float {
+        if self.index >= self.samples.len() {
+            // new samples start uniformly distributed, regardless of the step
+            let sample = PrimarySample {
+                value: uniform(&mut self.rng),
+                modified: self.iteration.max(self.last_large_step),
+                ..Default::default()
+            };
+            self.samples.push(sample);
+        }
+
+        let index = self.index;
+        self.index += 1;
+
+        let rng = &mut self.rng;
+        let sample = &mut self.samples[index];
+
+        // samples not requested since the last large step have to be replaced first
+        if sample.modified < self.last_large_step {
+            sample.value = uniform(rng);
+            sample.modified = self.last_large_step;
+        }
+
+        sample.backup();
+        if self.large_step {
+            sample.value = uniform(rng);
+        } else {
+            let steps = (self.iteration - sample.modified) as Float;
+            let sigma = self.sigma * steps.sqrt();
+
+            // Box-Muller transform
+            let u = 1.0 - uniform(rng);
+            let normal = (-2.0 * u.ln()).sqrt() * (2.0 * PI * uniform(rng)).cos();
+
+            let value = sample.value + normal * sigma;
+            sample.value = value - value.floor();
+        }
+        sample.modified = self.iteration;
+
+        sample.value
+    }
+
+    /// Runs `f` with this stream providing the random numbers of the current thread.
+    ///
+    /// # Arguments
+    /// * `f` - The function to run
+    ///
+    /// # Returns
+    /// * The result of `f`
+    pub fn run<F, R>(&mut self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        let stream = core::mem::take(self);
+        let previous = STREAM.with(|s| s.borrow_mut().replace(stream));
+        RUNNING.fetch_add(1, Ordering::SeqCst);
+
+        let result = f();
+
+        RUNNING.fetch_sub(1, Ordering::SeqCst);
+        *self = STREAM
+            .with(|s| core::mem::replace(&mut *s.borrow_mut(), previous))
+            .expect("the stream is still running");
+
+        result
+    }
+}
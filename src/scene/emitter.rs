@@ -1,10 +1,177 @@
-use crate::bxdf::BSDF;
+use crate::bxdf::{bxdf_to_world, BSDF};
+use crate::color::cie::{CIE_Y_INTEGRAL, MAX_LUMINOUS_EFFICACY};
+use crate::color::color_data::LAMBDA_RANGE;
+#[cfg(not(feature = "xyz"))]
+use crate::color::Xyz;
+use crate::color::{SampledSpectrum, SpectralIndices};
 use crate::geometry::{Aabb, Geometry, Intersection, Point, Ray};
-use crate::scene::{Sampleable, Scene};
+use crate::medium::Medium;
+use crate::sampler::FloatSampler;
+use crate::scene::{IesProfile, Sampleable, Scene, SceneObject};
 use crate::util::floats;
-use crate::{Float, Spectrum, Vec2, Vec3, PACKET_SIZE};
+use crate::util::mc::{
+    hemisphere_power_pdf, sample_unit_disk_concentric, sample_unit_hemisphere,
+    sample_unit_hemisphere_power,
+};
+use crate::{Float, Spectrum, Vec2, Vec3};
 use cgmath::InnerSpace;
 use serde::{Deserialize, Serialize};
+#[cfg(not(feature = "f64"))]
+use std::f32::consts::{FRAC_1_PI, PI, TAU};
+#[cfg(feature = "f64")]
+use std::f64::consts::{FRAC_1_PI, PI, TAU};
+use std::sync::OnceLock;
+
+/// The unit the emission of an [Emitter] is specified in.
+///
+/// Except for [EmissionUnit::Radiance], the emission only describes the spectral distribution,
+/// which is scaled to emit the given amount.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum EmissionUnit {
+    /// The emission is the radiance (`W/(sr m²)`) of surfaces, the intensity (`W/sr`) of delta
+    /// emitters and the irradiance (`W/m²`) perpendicular to infinite emitters.
+    #[default]
+    Radiance,
+    /// The radiant flux (`W`) emitted in total (by infinite emitters through the scene).
+    Watts(Float),
+    /// The luminous flux (`lm`) emitted in total (by infinite emitters through the scene).
+    Lumens(Float),
+    /// The luminous intensity (`cd`) along the normal of surfaces, seen head on.
+    Candela(Float),
+    /// The luminance (`cd/m²`) along the normal of surfaces.
+    /// Delta emitters have no area, so their luminance is their luminous intensity.
+    /// Infinite emitters have no position, so their luminance is their illuminance (`lx`).
+    Nits(Float),
+}
+
+impl EmissionUnit {
+    #[inline]
+    fn is_radiance(&self) -> bool {
+        *self == Self::Radiance
+    }
+
+    /// Computes the factor scaling the emission to radiance.
+    ///
+    /// # Arguments
+    /// * `emission` - The spectral distribution of the emission
+    /// * `flux` - The radiant flux emitted per unit of radiance
+    /// * `intensity` - The radiant intensity along the normal per unit of radiance
+    fn scale(self, emission: &Spectrum, flux: Float, intensity: Float) -> Float {
+        let (amount, per_radiance) = match self {
+            Self::Radiance => return 1.0,
+            Self::Watts(watts) => (watts, flux * emission.average()),
+            Self::Lumens(lumens) => (lumens, flux * luminous(emission)),
+            Self::Candela(candela) => (candela, intensity * luminous(emission)),
+            Self::Nits(nits) => (nits, luminous(emission)),
+        };
+
+        if per_radiance > 0.0 && per_radiance.is_finite() {
+            amount / per_radiance
+        } else {
+            log::warn!(target: "Emitter", "cannot convert {:?} to radiance", self);
+            0.0
+        }
+    }
+}
+
+/// Converts a radiometric quantity of a spectral distribution to the photometric one (e.g. watts
+/// to lumens) by its luminous efficiency.
+fn luminous(spectrum: &Spectrum) -> Float {
+    // a spectrum of ones has the luminance 1
+    luminance(spectrum) * MAX_LUMINOUS_EFFICACY * CIE_Y_INTEGRAL / LAMBDA_RANGE
+}
+
+/// Returns the luminance `Y` of a spectral distribution.
+#[cfg(not(feature = "xyz"))]
+#[inline]
+fn luminance(spectrum: &Spectrum) -> Float {
+    Xyz::from(*spectrum)[1]
+}
+
+/// Returns the luminance `Y` of a spectral distribution.
+#[cfg(feature = "xyz")]
+#[inline]
+fn luminance(spectrum: &Spectrum) -> Float {
+    spectrum[1]
+}
+
+/// The angular distribution of the radiance emitted by surfaces.
+/// Delta emitters always emit uniformly into all directions.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum EmissionProfile {
+    /// The same radiance on both sides of the surface.
+    #[default]
+    TwoSided,
+    /// The same radiance, only on the side of the surface normal.
+    OneSided,
+    /// Only on the side of the surface normal, with the radiance falling off with the power of the
+    /// cosine to the normal.
+    CosinePower(Float),
+}
+
+impl EmissionProfile {
+    #[inline]
+    fn is_two_sided(&self) -> bool {
+        *self == Self::TwoSided
+    }
+
+    /// Returns the fraction of the radiance emitted into a direction.
+    ///
+    /// # Arguments
+    /// * `cos` - The cosine between the surface normal and the direction
+    #[inline]
+    pub fn evaluate(self, cos: Float) -> Float {
+        match self {
+            Self::TwoSided => 1.0,
+            Self::OneSided if cos > 0.0 => 1.0,
+            Self::CosinePower(exponent) if cos > 0.0 => cos.powf(exponent),
+            _ => 0.0,
+        }
+    }
+
+    /// Returns the radiant flux emitted per unit of area and radiance.
+    pub fn flux(self) -> Float {
+        match self {
+            Self::TwoSided => TAU,
+            Self::OneSided => PI,
+            Self::CosinePower(exponent) => TAU / (exponent + 2.0),
+        }
+    }
+
+    /// Samples a direction in the local space of the surface proportional to the emitted radiance
+    /// and the cosine to the normal.
+    ///
+    /// # Constraints
+    /// * `sample` - All values should be within `[0, 1)`.
+    ///
+    /// # Returns
+    /// * The direction
+    /// * The pdf with respect to the solid angle
+    fn sample(self, sample: Vec2) -> (Vec3, Float) {
+        let direction = match self {
+            Self::TwoSided if sample.x < 0.5 => {
+                sample_unit_hemisphere(Vec2::new(2.0 * sample.x, sample.y))
+            }
+            Self::TwoSided => -sample_unit_hemisphere(Vec2::new(2.0 * sample.x - 1.0, sample.y)),
+            Self::OneSided => sample_unit_hemisphere(sample),
+            Self::CosinePower(exponent) => sample_unit_hemisphere_power(sample, exponent + 1.0),
+        };
+
+        (direction, self.pdf(direction.y))
+    }
+
+    /// Computes the pdf (with respect to the solid angle) of [Self::sample].
+    ///
+    /// # Arguments
+    /// * `cos` - The cosine between the surface normal and the direction
+    fn pdf(self, cos: Float) -> Float {
+        match self {
+            Self::TwoSided => 0.5 * cos.abs() * FRAC_1_PI,
+            Self::OneSided => cos.max(0.0) * FRAC_1_PI,
+            Self::CosinePower(exponent) => hemisphere_power_pdf(cos, exponent + 1.0),
+        }
+    }
+}
 
 #[derive(Serialize, Deserialize)]
 pub struct Emitter {
@@ -12,12 +179,33 @@ pub struct Emitter {
     #[serde(default)]
     pub bsdf: BSDF,
     pub emission: Spectrum,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "EmissionUnit::is_radiance")]
+    pub unit: EmissionUnit,
+    #[serde(default)]
+    #[serde(skip_serializing_if = "EmissionProfile::is_two_sided")]
+    pub profile: EmissionProfile,
+    /// An optional photometric profile scaling the emission into each direction, on top of the
+    /// [EmissionProfile] of surfaces (ignored by infinite emitters).
+    #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ies: Option<Box<IesProfile>>,
+    /// Whether the emission of a delta emitter (e.g. a point) decays with the inverse squared
+    /// distance.
+    /// Emitters with a surface always decay by the solid angle they cover.
     #[serde(default = "true_bool")]
     #[serde(skip_serializing_if = "is_true")]
     pub decay: bool,
     #[serde(default)]
     #[serde(skip_serializing_if = "String::is_empty")]
     pub tag: String,
+    /// The factor scaling the emission to radiance (see [Self::unit])
+    #[serde(skip)]
+    scale: OnceLock<Float>,
+    /// The center and radius of the sphere bounding the scene, which infinite emitters emit
+    /// their rays through
+    #[serde(skip)]
+    scene_sphere: OnceLock<(Vec3, Float)>,
 }
 
 #[inline(always)]
@@ -37,82 +225,295 @@ impl Emitter {
             geometry: Box::new(Point(Vec3::new(0., 0., 0.))),
             bsdf: Default::default(),
             emission: Default::default(),
+            unit: Default::default(),
+            profile: Default::default(),
+            ies: None,
             decay: false,
             tag: "dummy".into(),
+            scale: OnceLock::new(),
+            scene_sphere: OnceLock::new(),
+        }
+    }
+
+    /// Sets the bounds of the scene, which infinite emitters emit their rays through.
+    /// Has to be called before the emitter is used and only has an effect once.
+    ///
+    /// # Arguments
+    /// * `bounds` - The bounds of all finite objects in the scene
+    pub fn set_scene_bounds(&self, bounds: Aabb) {
+        let radius = 0.5 * bounds.size().magnitude();
+        let sphere = if radius.is_finite() {
+            (bounds.center(), radius)
+        } else {
+            (Vec3::new(0.0, 0.0, 0.0), 0.0)
+        };
+
+        let _ = self.scene_sphere.set(sphere);
+    }
+
+    /// Returns the center and radius of the sphere bounding the scene.
+    fn scene_sphere(&self) -> (Vec3, Float) {
+        self.scene_sphere
+            .get()
+            .copied()
+            .unwrap_or((Vec3::new(0.0, 0.0, 0.0), 0.0))
+    }
+
+    /// Returns the factor scaling the emission to radiance, converting it from its unit.
+    fn scale(&self) -> Float {
+        *self.scale.get_or_init(|| {
+            let intensity = if self.is_infinite() {
+                0.0
+            } else if self.is_delta() {
+                1.0
+            } else {
+                self.geometry.area()
+            };
+
+            self.unit
+                .scale(&self.emission, self.flux_per_radiance(), intensity)
+        })
+    }
+
+    /// Returns the radiant flux emitted per unit of radiance.
+    fn flux_per_radiance(&self) -> Float {
+        if self.is_infinite() {
+            let (_, radius) = self.scene_sphere();
+            PI * radius * radius * self.geometry.irradiance()
+        } else if self.is_delta() {
+            match &self.ies {
+                Some(ies) => ies.integrate(|direction| self.geometry.intensity(direction)),
+                None => self.geometry.solid_angle(),
+            }
+        } else {
+            match &self.ies {
+                Some(ies) => self.geometry.area() * self.flux_through_normals(ies),
+                None => self.geometry.area() * self.profile.flux(),
+            }
         }
     }
 
-    /// Computes the radiance of this emitter.
+    /// Returns the radiant flux emitted per unit of area and radiance with a photometric profile.
+    /// As it depends on the orientation of the surface, it is averaged over stratified samples of
+    /// its normals.
+    fn flux_through_normals(&self, ies: &IesProfile) -> Float {
+        let n = 8;
+        let normals: Vec<_> = (0..n * n)
+            .map(|i| {
+                let sample = Vec2::new(
+                    ((i % n) as Float + 0.5) / n as Float,
+                    ((i / n) as Float + 0.5) / n as Float,
+                );
+                self.geometry.sample_area(sample).normal
+            })
+            .collect();
+
+        ies.integrate(|direction| {
+            normals
+                .iter()
+                .map(|normal| {
+                    let cos = normal.dot(direction);
+                    self.profile.evaluate(cos) * cos.abs()
+                })
+                .sum::<Float>()
+                / normals.len() as Float
+        })
+    }
+
+    /// Computes the radiance of this emitter along the surface normal (the intensity of delta
+    /// emitters).
+    ///
+    /// # Arguments
+    /// * `indices`: The spectral indices
     #[inline]
-    pub fn radiance(&self) -> Spectrum {
-        self.emission
+    pub fn radiance<I: SpectralIndices>(&self, indices: &I) -> I::Sampled {
+        indices.select(&self.emission).mul_t(self.scale())
     }
 
-    /// Computes the radiance of this emitter.
+    /// Computes the radiance of this emitter into a direction (see [Self::emission_fraction] and
+    /// [Sampleable::color]).
     ///
     /// # Constraints
-    /// * `indices`: All values should be within `[0, `[Spectrum::size]`)`.
+    /// * `normal` - Should be normalized (ignored for delta emitters).
+    /// * `direction` - Should be normalized.
     ///
     /// # Arguments
     /// * `indices`: The spectral indices
+    /// * `normal` - The surface normal at the point of emission
+    /// * `direction` - The direction leaving the emitter
     #[inline]
-    pub fn radiance_packet(&self, indices: &[usize; PACKET_SIZE]) -> [Float; PACKET_SIZE] {
-        indices.map(|i| self.emission[i])
+    pub fn radiance_towards<I: SpectralIndices>(
+        &self,
+        indices: &I,
+        normal: Vec3,
+        direction: Vec3,
+    ) -> I::Sampled {
+        let radiance = self
+            .radiance(indices)
+            .mul_t(self.emission_fraction(normal, direction));
+
+        match self.geometry.color(direction) {
+            Some(color) => radiance.mul(indices.select(&color)),
+            None => radiance,
+        }
     }
 
-    /// Computes the radiance of this emitter.
+    /// Returns the fraction of the radiance emitted into a direction, following the
+    /// [EmissionProfile] of surfaces and the [intensity](Sampleable::intensity) of delta and
+    /// infinite emitters, scaled by the [IesProfile] if any.
     ///
     /// # Constraints
-    /// * `index`: Should be within `[0, `[Spectrum::size]`)`.
+    /// * `normal` - Should be normalized (ignored for delta emitters).
+    /// * `direction` - Should be normalized.
     ///
     /// # Arguments
-    /// * `index`: The spectral index
+    /// * `normal` - The surface normal at the point of emission
+    /// * `direction` - The direction leaving the emitter
     #[inline]
-    pub fn radiance_lambda(&self, index: usize) -> Float {
-        self.emission[index]
+    pub fn emission_fraction(&self, normal: Vec3, direction: Vec3) -> Float {
+        if self.is_infinite() {
+            return self.geometry.intensity(direction);
+        }
+
+        let fraction = if self.is_delta() {
+            self.geometry.intensity(direction)
+        } else {
+            self.profile.evaluate(normal.dot(direction))
+        };
+
+        match &self.ies {
+            Some(ies) => fraction * ies.evaluate(direction),
+            None => fraction,
+        }
     }
 
-    /// Samples the emitter.
+    /// Computes the power (radiant flux) of this emitter, averaged over the wavelengths.
+    ///
+    /// Delta emitters emit their intensity into the whole sphere, surfaces their radiance
+    /// following their [EmissionProfile] and infinite emitters their irradiance through the
+    /// scene.
+    ///
+    /// # Returns
+    /// * The power (infinite for unbounded surfaces)
+    pub fn power(&self) -> Float {
+        self.flux_per_radiance() * self.emission.average() * self.scale()
+    }
+
+    /// Returns whether this emitter is described by a delta distribution (e.g. a point), making it
+    /// impossible to be hit by a ray.
+    #[inline]
+    pub fn is_delta(&self) -> bool {
+        self.geometry.is_delta()
+    }
+
+    /// Returns whether this emitter is infinitely far away (e.g. the sun), so that it can only be
+    /// seen by rays escaping the scene.
+    #[inline]
+    pub fn is_infinite(&self) -> bool {
+        self.geometry.is_infinite()
+    }
+
+    /// Computes the pdf (with respect to the solid angle) of sampling this emitter in the given
+    /// direction.
     ///
     /// # Constraints
-    /// * `point` - ALl values should be finite (neither infinite nor `NaN`).
-    /// * `sample` - All values should be within `[0, 1)`.
+    /// * `point` - All values should be finite (neither infinite nor `NaN`).
+    /// * `incident` - All values should be finite.
+    ///   Should be normalized.
     ///
     /// # Arguments
     /// * `point` - The point from which we sample the emitter
-    /// * `sample` - A random sample
-    pub fn sample(&self, point: Vec3, sample: Vec2) -> EmitterSample<Spectrum> {
-        let surface_sample = self.geometry.sample_surface(point, sample);
-        let occlusion = OcclusionTester::between(point, surface_sample.point);
+    /// * `incident` - The direction from the point towards the emitter
+    #[inline]
+    pub fn pdf(&self, point: Vec3, incident: Vec3) -> Float {
+        self.geometry.pdf(point, incident)
+    }
 
-        EmitterSample::new(self.radiance(), occlusion.ray.direction, occlusion)
+    /// Samples a ray leaving the emitter.
+    ///
+    /// Surfaces emit proportional to their [EmissionProfile] and the cosine to the normal, while
+    /// delta emitters sample their directions themselves (see [Sampleable::sample_direction]).
+    /// Both sample their [IesProfile] instead if they have one.
+    /// Infinite emitters sample their directions as well, with origins on a disk covering the
+    /// scene (see [Self::set_scene_bounds]).
+    ///
+    /// # Constraints
+    /// * `sample_point` - All values should be within `[0, 1)`.
+    /// * `sample_direction` - All values should be within `[0, 1)`.
+    ///
+    /// # Arguments
+    /// * `sample_point` - The random sample for the ray origin
+    /// * `sample_direction` - The random sample for the ray direction
+    pub fn sample_ray(&self, sample_point: Vec2, sample_direction: Vec2) -> EmitterRay {
+        if self.is_infinite() {
+            let (direction, pdf_direction) = self.geometry.sample_direction(sample_direction);
+            let (center, radius) = self.scene_sphere();
+
+            let disk = radius * sample_unit_disk_concentric(sample_point);
+            let offset = bxdf_to_world(direction).rotate_vector(Vec3::new(disk.x, -radius, disk.y));
+
+            return EmitterRay {
+                ray: Ray::new(center + offset, direction),
+                normal: direction,
+                pdf_point: 1.0 / (PI * radius * radius),
+                pdf_direction,
+            };
+        }
+
+        let surface_sample = self.geometry.sample_area(sample_point);
+
+        let (direction, pdf_direction) = if let Some(ies) = &self.ies {
+            ies.sample(sample_direction)
+        } else if self.is_delta() {
+            self.geometry.sample_direction(sample_direction)
+        } else {
+            let (local, pdf) = self.profile.sample(sample_direction);
+            let direction = bxdf_to_world(surface_sample.normal).rotate_vector(local);
+
+            (direction, pdf)
+        };
+
+        EmitterRay {
+            ray: Ray::new(surface_sample.point, direction),
+            normal: surface_sample.normal,
+            pdf_point: surface_sample.pdf,
+            pdf_direction,
+        }
     }
 
-    /// Samples the emitter.
+    /// Computes the pdfs of [Self::sample_ray] sampling the given ray.
     ///
     /// # Constraints
-    /// * `point` - ALl values should be finite (neither infinite nor `NaN`).
-    /// * `sample` - All values should be within `[0, 1)`.
-    /// * `indices`: All values should be within `[0, `[Spectrum::size]`)`.
+    /// * `point` - Should be on the emitter.
+    /// * `normal` - Should be normalized.
+    /// * `direction` - Should be normalized.
     ///
     /// # Arguments
-    /// * `point` - The point from which we sample the emitter
-    /// * `sample` - A random sample
-    /// * `indices`: The spectral indices
-    pub fn sample_packet(
-        &self,
-        point: Vec3,
-        sample: Vec2,
-        indices: &[usize; PACKET_SIZE],
-    ) -> EmitterSample<[Float; PACKET_SIZE]> {
-        let surface_sample = self.geometry.sample_surface(point, sample);
-        let occlusion = OcclusionTester::between(point, surface_sample.point);
+    /// * `point` - The origin of the ray
+    /// * `normal` - The surface normal at the origin
+    /// * `direction` - The direction of the ray
+    ///
+    /// # Returns
+    /// * The pdf with respect to the surface area of the origin
+    /// * The pdf with respect to the solid angle of the direction
+    pub fn pdf_ray(&self, point: Vec3, normal: Vec3, direction: Vec3) -> (Float, Float) {
+        if self.is_infinite() {
+            let (_, radius) = self.scene_sphere();
+            let pdf_direction = self.geometry.pdf_direction(direction);
 
-        EmitterSample::new(
-            self.radiance_packet(indices),
-            occlusion.ray.direction,
-            occlusion,
-        )
+            return (1.0 / (PI * radius * radius), pdf_direction);
+        }
+
+        let pdf_point = self.geometry.pdf_area(point);
+        let pdf_direction = if let Some(ies) = &self.ies {
+            ies.pdf(direction)
+        } else if self.is_delta() {
+            self.geometry.pdf_direction(direction)
+        } else {
+            self.profile.pdf(normal.dot(direction))
+        };
+
+        (pdf_point, pdf_direction)
     }
 
     /// Samples the emitter.
@@ -120,20 +521,29 @@ impl Emitter {
     /// # Constraints
     /// * `point` - ALl values should be finite (neither infinite nor `NaN`).
     /// * `sample` - All values should be within `[0, 1)`.
-    /// * `index`: Should be within `[0, `[Spectrum::size]`)`.
     ///
     /// # Arguments
     /// * `point` - The point from which we sample the emitter
     /// * `sample` - A random sample
-    /// * `index`: The spectral index
-    pub fn sample_lambda(&self, point: Vec3, sample: Vec2, index: usize) -> EmitterSample<Float> {
+    /// * `indices`: The spectral indices
+    pub fn sample<I: SpectralIndices>(
+        &self,
+        point: Vec3,
+        sample: Vec2,
+        indices: &I,
+    ) -> EmitterSample<I::Sampled> {
         let surface_sample = self.geometry.sample_surface(point, sample);
-        let occlusion_tester = OcclusionTester::between(point, surface_sample.point);
+        let occlusion = if self.is_infinite() {
+            OcclusionTester::towards(point, -surface_sample.normal)
+        } else {
+            OcclusionTester::between(point, surface_sample.point)
+        };
 
         EmitterSample::new(
-            self.radiance_lambda(index),
-            occlusion_tester.ray.direction,
-            occlusion_tester,
+            self.radiance_towards(indices, surface_sample.normal, -occlusion.ray.direction),
+            occlusion.ray.direction,
+            surface_sample.pdf,
+            occlusion,
         )
     }
 }
@@ -161,28 +571,52 @@ impl Geometry for Emitter {
     }
 }
 
+/// A ray leaving an emitter.
+#[derive(Copy, Clone, Debug)]
+pub struct EmitterRay {
+    pub ray: Ray,
+    /// The surface normal at the ray origin (zero for delta emitters).
+    pub normal: Vec3,
+    /// The pdf with respect to the surface area of the ray origin.
+    pub pdf_point: Float,
+    /// The pdf with respect to the solid angle of the ray direction.
+    pub pdf_direction: Float,
+}
+
 pub struct EmitterSample<T> {
     pub radiance: T,
     pub incident: Vec3,
+    /// The pdf with respect to the solid angle of the sampling point.
+    pub pdf: Float,
     pub occlusion: OcclusionTester,
 }
 impl<T> EmitterSample<T> {
-    pub const fn new(radiance: T, incident: Vec3, occlusion: OcclusionTester) -> Self {
+    pub const fn new(radiance: T, incident: Vec3, pdf: Float, occlusion: OcclusionTester) -> Self {
         Self {
             radiance,
             incident,
+            pdf,
             occlusion,
         }
     }
 
+    /// Returns the squared distance to the sampled point (`1` for infinite emitters, which do not
+    /// decay).
     #[inline]
     pub fn decay(&self) -> Float {
-        (self.occlusion.ray.t_end - self.occlusion.ray.t_start).powi(2)
+        let distance = self.occlusion.ray.t_end - self.occlusion.ray.t_start;
+
+        if distance.is_finite() {
+            distance.powi(2)
+        } else {
+            1.0
+        }
     }
 
+    /// Returns the inverse of [Self::decay].
     #[inline]
     pub fn decay_inv(&self) -> Float {
-        (self.occlusion.ray.t_end - self.occlusion.ray.t_start).powi(-2)
+        self.decay().recip()
     }
 }
 
@@ -214,11 +648,259 @@ impl OcclusionTester {
         Self { ray }
     }
 
-    /// Tests for unocclusion.
+    /// Creates a new occlusion tester from a point towards infinity, e.g. towards an infinite
+    /// emitter.
+    /// The created ray partition will start at an epsilon like [Self::between].
+    ///
+    /// # Constraints
+    /// * `origin` - All values should be finite (neither infinite nor `NaN`).
+    /// * `direction` - All values should be finite.
+    ///   Should be normalized.
+    ///
+    /// # Arguments
+    /// * `origin` - The origin of the ray
+    /// * `direction` - The direction of the ray
+    pub fn towards(origin: Vec3, direction: Vec3) -> Self {
+        let ray = Ray::new2(origin, direction, floats::BIG_EPSILON, Float::INFINITY);
+        Self { ray }
+    }
+
+    /// Computes the transmittance between both points, which is zero if they are occluded.
     ///
     /// # Arguments
     /// * `scene` - The scene to intersect against
-    pub fn unoccluded(&self, scene: &Scene) -> bool {
-        !scene.intersects(self.ray)
+    /// * `sampler` - The sampler of the medium collisions
+    /// * `indices` - The spectral indices
+    pub fn unoccluded<I: SpectralIndices>(
+        &self,
+        scene: &Scene,
+        sampler: FloatSampler,
+        indices: &I,
+    ) -> I::Sampled {
+        let mut transmittance = I::Sampled::splat(1.0);
+        let unoccluded = self.traverse(scene, |medium, ray| {
+            let mut segment = I::Sampled::splat(1.0);
+            medium.transmittance(ray, sampler, indices.wavelengths(), segment.as_mut_slice());
+            transmittance = transmittance.mul(segment);
+        });
+
+        if unoccluded {
+            transmittance
+        } else {
+            I::Sampled::splat(0.0)
+        }
+    }
+
+    /// Traverses the ray through all receivers that only bound a medium.
+    ///
+    /// # Arguments
+    /// * `scene` - The scene to intersect against
+    /// * `f` - Called for every ray segment inside a medium
+    ///
+    /// # Returns
+    /// * Whether the ray is unoccluded
+    fn traverse<F>(&self, scene: &Scene, mut f: F) -> bool
+    where
+        F: FnMut(&dyn Medium, Ray),
+    {
+        if !scene.has_media() {
+            return !scene.intersects(self.ray);
+        }
+
+        let mut ray = self.ray;
+        loop {
+            let hit = scene.intersect(ray);
+
+            let t_end = hit.as_ref().map_or(ray.t_end, |h| h.i.t);
+            let segment = Ray::new2(ray.origin, ray.direction, ray.t_start, t_end);
+            if let Some(medium) = scene.medium_along(segment) {
+                f(medium, segment);
+            }
+
+            match hit {
+                None => return true,
+                Some(h) => match h.object {
+                    SceneObject::Receiver(r) if r.is_interface() => {
+                        ray.t_start = h.i.t + floats::BIG_EPSILON;
+                    }
+                    _ => return false,
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::{Disk, Sun};
+    use crate::medium::{HenyeyGreenstein, Homogeneous, MediumEvent};
+    use crate::{Rot3, PACKET_SIZE};
+    use cgmath::One;
+
+    fn disk(unit: EmissionUnit, profile: EmissionProfile) -> Emitter {
+        Emitter {
+            geometry: Box::new(Disk::new(Vec3::new(0.0, 0.0, 0.0), Vec3::unit_y(), 0.5)),
+            emission: Spectrum::splat(2.0),
+            unit,
+            profile,
+            ..Emitter::dummy()
+        }
+    }
+
+    #[test]
+    fn units_convert_to_power() {
+        let watts = disk(EmissionUnit::Watts(50.0), EmissionProfile::OneSided);
+        assert!((watts.power() - 50.0).abs() < 1e-3);
+
+        let lumens_per_watt = luminous(&watts.emission) / watts.emission.average();
+        let lumens = disk(
+            EmissionUnit::Lumens(50.0 * lumens_per_watt),
+            EmissionProfile::CosinePower(4.0),
+        );
+        assert!((lumens.power() - 50.0).abs() < 1e-3);
+
+        // a lambertian disk seen head on has the intensity of its radiance times its area
+        let candela = disk(EmissionUnit::Candela(100.0), EmissionProfile::OneSided);
+        let nits = disk(
+            EmissionUnit::Nits(100.0 / candela.geometry.area()),
+            EmissionProfile::TwoSided,
+        );
+        assert!((candela.radiance(&0) - nits.radiance(&0)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn profile_samples_its_flux() {
+        let profiles = [
+            EmissionProfile::TwoSided,
+            EmissionProfile::OneSided,
+            EmissionProfile::CosinePower(3.0),
+        ];
+
+        for profile in profiles {
+            let n = 128;
+            let mut flux = 0.0;
+            for i in 0..n {
+                for j in 0..n {
+                    let sample = Vec2::new(
+                        (i as Float + 0.5) / n as Float,
+                        (j as Float + 0.5) / n as Float,
+                    );
+                    let (direction, pdf) = profile.sample(sample);
+                    assert!((direction.magnitude() - 1.0).abs() < 1e-4);
+
+                    flux += profile.evaluate(direction.y) * direction.y.abs() / pdf;
+                }
+            }
+            flux /= (n * n) as Float;
+
+            assert!((flux - profile.flux()).abs() < 1e-2, "{:?}", profile);
+        }
+    }
+
+    #[test]
+    fn ies_profile_samples_its_power() {
+        // a rotationally symmetric downlight
+        let ies = || {
+            IesProfile::new(
+                vec![0.0, 45.0, 90.0],
+                vec![0.0],
+                vec![1.0, 0.5, 0.0],
+                Rot3::one(),
+            )
+            .ok()
+            .map(Box::new)
+        };
+        let point = Emitter {
+            emission: Spectrum::splat(2.0),
+            unit: EmissionUnit::Watts(10.0),
+            ies: ies(),
+            ..Emitter::dummy()
+        };
+        let mut disk = disk(EmissionUnit::Watts(10.0), EmissionProfile::TwoSided);
+        disk.ies = ies();
+
+        for emitter in [point, disk] {
+            assert!((emitter.power() - 10.0).abs() < 1e-3);
+
+            let n = 128;
+            let mut power = 0.0;
+            for i in 0..n {
+                for j in 0..n {
+                    let sample = Vec2::new(
+                        (i as Float + 0.5) / n as Float,
+                        (j as Float + 0.5) / n as Float,
+                    );
+                    let ray = emitter.sample_ray(Vec2::new(0.5, 0.5), sample);
+                    let (pdf_point, pdf_direction) =
+                        emitter.pdf_ray(ray.ray.origin, ray.normal, ray.ray.direction);
+                    assert!((pdf_direction - ray.pdf_direction).abs() < 1e-2 * pdf_direction);
+
+                    let cos = if emitter.is_delta() {
+                        1.0
+                    } else {
+                        ray.normal.dot(ray.ray.direction).abs()
+                    };
+                    power += emitter.radiance_towards(&0, ray.normal, ray.ray.direction) * cos
+                        / (pdf_point * pdf_direction);
+                }
+            }
+            power /= (n * n) as Float;
+
+            assert!((power - 10.0).abs() < 5e-2, "{}", power);
+        }
+    }
+
+    #[test]
+    fn infinite_emitters_behind_coloured_scene_medium() {
+        // only the upper half of the spectrum is absorbed, the lower half has no extinction
+        let mut sigma_a = Spectrum::splat(0.0);
+        for i in Spectrum::size() / 2..Spectrum::size() {
+            sigma_a[i] = 0.5;
+        }
+        let medium = Homogeneous::new(sigma_a, Spectrum::splat(0.0), HenyeyGreenstein::new(0.0));
+
+        let mut scene = Scene::default();
+        scene.set_medium(Some(Box::new(medium.clone())));
+
+        let sun = Emitter {
+            geometry: Box::new(Sun::new(Vec3::unit_y(), 0.5)),
+            emission: Spectrum::splat(1.0),
+            ..Emitter::dummy()
+        };
+        let sample = sun.sample(Vec3::new(0.0, 0.0, 0.0), Vec2::new(0.3, 0.7), &());
+        let transmittance = sample
+            .occlusion
+            .unoccluded(&scene, FloatSampler::Random, &());
+        for i in 0..Spectrum::size() {
+            let expected = if i < Spectrum::size() / 2 { 1.0 } else { 0.0 };
+            assert_eq!(expected, transmittance[i], "wavelength {}", i);
+        }
+
+        // a packet without extinction escaping the scene
+        let escaping = Ray::new(Vec3::new(0.0, 0.0, 0.0), Vec3::unit_y());
+        let mut beta = [1.0; PACKET_SIZE];
+        let event =
+            medium.sample_packet(escaping, FloatSampler::Random, &[0; PACKET_SIZE], &mut beta);
+        assert_eq!(MediumEvent::Pass, event);
+        assert_eq!([1.0; PACKET_SIZE], beta);
+
+        // only the wavelengths without extinction of a mixed packet escape, unbiased on average
+        let mut indices = [0; PACKET_SIZE];
+        for i in (1..PACKET_SIZE).step_by(2) {
+            indices[i] = Spectrum::size() - 1;
+        }
+        let n = 4096;
+        let mut escaped = 0.0;
+        for _ in 0..n {
+            let mut beta = [1.0; PACKET_SIZE];
+            let event = medium.sample_packet(escaping, FloatSampler::Random, &indices, &mut beta);
+            if event == MediumEvent::Pass {
+                assert!((1..PACKET_SIZE).step_by(2).all(|i| beta[i] == 0.0));
+                escaped += beta[0];
+            }
+        }
+        escaped /= n as Float;
+        assert!((escaped - 1.0).abs() < 0.1, "{}", escaped);
     }
 }
@@ -0,0 +1,535 @@
+//! # Summary
+//! Photometric profiles of luminaires, read from IES LM-63 files.
+//!
+//! A profile scales the emission of an [Emitter](crate::scene::Emitter) into each direction by the
+//! luminous intensity the manufacturer measured, relative to its maximum.
+//!
+//! # File format
+//! Only type C photometry is supported, which covers almost all architectural luminaires.
+//! Tilt data is skipped, as the luminaire is assumed to be mounted as measured.
+//!
+//! # Placement
+//! The luminaire points towards `-y`, i.e. the vertical angle `0°` (the nadir) lies towards `-y`
+//! and `180°` towards `+y`.
+//! The horizontal angles run counter-clockwise seen from above, from `+x` (`0°`) to `-z` (`90°`).
+//! The profile is then rotated into the scene.
+
+use crate::geometry::{
+    direction_to_equirectangular, equirectangular_solid_angle, equirectangular_to_direction,
+};
+use crate::scene::equirectangular_to_solid_angle_pdf;
+use crate::util::distribution::PiecewiseConstant2D;
+use crate::{Float, Rot3, Vec2, Vec3};
+use cgmath::{One, Rotation};
+use core::convert::TryFrom;
+use serde::{Deserialize, Serialize, Serializer};
+
+/// The number of texels per row of the equirectangular grid the profile is sampled with.
+const WIDTH: usize = 256;
+/// The number of rows of the equirectangular grid the profile is sampled with.
+const HEIGHT: usize = 128;
+
+/// The photometric type of the file, of which only type C is supported.
+const PHOTOMETRIC_TYPE_C: u32 = 1;
+
+/// A measured distribution of luminous intensity, normalized to a maximum of `1`.
+///
+/// The intensity is interpolated bilinearly between the measured angles and sampled
+/// proportional to a tabulation of it.
+#[derive(Deserialize)]
+#[serde(try_from = "IesProfileSerde")]
+pub struct IesProfile {
+    /// The vertical angles in degrees, ascending
+    vertical: Vec<Float>,
+    /// The horizontal angles in degrees, ascending
+    horizontal: Vec<Float>,
+    /// The relative intensities, for each horizontal angle all vertical ones
+    candela: Vec<Float>,
+    rotation: Rot3,
+    rotation_inv: Rot3,
+    /// The distribution over the equirectangular grid in the local space of the profile
+    distribution: PiecewiseConstant2D,
+    /// The intensity integrated over all directions
+    solid_angle: Float,
+}
+
+impl IesProfile {
+    /// Creates a new profile.
+    ///
+    /// # Constraints
+    /// * `vertical` - Should contain at least two angles within `[0, 180]`.
+    /// * `horizontal` - Should not be empty.
+    ///   All angles should be within `[0, 360]`.
+    /// * `candela` - Should not be negative, with some values positive.
+    ///
+    /// # Arguments
+    /// * `vertical` - The ascending vertical angles in degrees
+    /// * `horizontal` - The ascending horizontal angles in degrees
+    /// * `candela` - The intensities, for each horizontal angle all vertical ones
+    /// * `rotation` - The rotation of the profile into the scene
+    pub fn new(
+        vertical: Vec<Float>,
+        horizontal: Vec<Float>,
+        candela: Vec<Float>,
+        rotation: Rot3,
+    ) -> Result<Self, String> {
+        let ascending = |angles: &[Float], max: Float| {
+            angles.windows(2).all(|w| w[0] < w[1])
+                && angles.iter().all(|&a| (0.0..=max).contains(&a))
+        };
+        if vertical.len() < 2 || !ascending(&vertical, 180.0) {
+            return Err("IES vertical angles must be at least two within [0, 180]".into());
+        }
+        if horizontal.is_empty() || !ascending(&horizontal, 360.0) {
+            return Err("IES horizontal angles must be ascending within [0, 360]".into());
+        }
+        if candela.len() != vertical.len() * horizontal.len() {
+            return Err(format!(
+                "Expected {} IES candela values, got {}",
+                vertical.len() * horizontal.len(),
+                candela.len()
+            ));
+        }
+        if candela.iter().any(|c| !c.is_finite() || *c < 0.0) {
+            return Err("IES candela values must be finite and not negative".into());
+        }
+
+        let max = candela.iter().copied().fold(0.0, Float::max);
+        if max <= 0.0 {
+            return Err("IES profile does not emit any light".into());
+        }
+
+        let mut profile = Self {
+            vertical,
+            horizontal,
+            candela: candela.into_iter().map(|c| c / max).collect(),
+            rotation,
+            rotation_inv: rotation.invert(),
+            distribution: PiecewiseConstant2D::default(),
+            solid_angle: 0.0,
+        };
+
+        // tabulate the profile to integrate and sample it, covering the maximum within each texel
+        // so that every direction with some intensity can be sampled
+        let mut weights = Vec::with_capacity(WIDTH * HEIGHT);
+        for row in 0..HEIGHT {
+            for column in 0..WIDTH {
+                let (x, y) = (column as Float, row as Float);
+                let (_, sin_theta) = equirectangular_to_direction(Vec2::new(
+                    (x + 0.5) / WIDTH as Float,
+                    (y + 0.5) / HEIGHT as Float,
+                ));
+
+                let max = [(0.5, 0.5), (0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (1.0, 1.0)]
+                    .iter()
+                    .map(|&(dx, dy)| {
+                        let point =
+                            Vec2::new((x + dx) / WIDTH as Float, (y + dy) / HEIGHT as Float);
+                        profile.evaluate_local(equirectangular_to_direction(point).0)
+                    })
+                    .fold(0.0, Float::max);
+
+                weights.push(max * sin_theta);
+            }
+        }
+        profile.distribution = PiecewiseConstant2D::new(&weights, WIDTH, HEIGHT);
+        profile.solid_angle = profile.integrate(|_| 1.0);
+
+        Ok(profile)
+    }
+
+    /// Loads the measurements of an IES LM-63 file.
+    ///
+    /// # Arguments
+    /// * `path` - The path of the `.ies` file
+    ///
+    /// # Returns
+    /// * The vertical angles, horizontal angles and intensities (see [Self::new])
+    #[allow(clippy::type_complexity)]
+    pub fn load(path: &str) -> Result<(Vec<Float>, Vec<Float>, Vec<Float>), String> {
+        let text = std::fs::read(path).map_err(|e| format!("Cannot load IES file {path}: {e}"))?;
+
+        // files are often written in some legacy encoding, but only the keywords are affected
+        Self::parse(&String::from_utf8_lossy(&text))
+            .map_err(|e| format!("Cannot parse IES file {path}: {e}"))
+    }
+
+    /// Parses the measurements of an IES LM-63 file (see [Self::load]).
+    #[allow(clippy::type_complexity)]
+    pub fn parse(text: &str) -> Result<(Vec<Float>, Vec<Float>, Vec<Float>), String> {
+        let mut lines = text.lines();
+        let tilt = lines
+            .by_ref()
+            .map(str::trim)
+            .find(|l| l.starts_with("TILT="))
+            .ok_or("missing TILT line")?;
+
+        let mut numbers = lines
+            .flat_map(|l| l.split(|c: char| c.is_whitespace() || c == ','))
+            .filter(|t| !t.is_empty())
+            .map(|t| {
+                t.parse::<Float>()
+                    .map_err(|_| format!("invalid number {t}"))
+            });
+        let mut next = || {
+            numbers
+                .next()
+                .unwrap_or(Err("unexpected end of file".into()))
+        };
+
+        match &tilt[5..] {
+            "NONE" => {}
+            "INCLUDE" => {
+                // the lamp geometry followed by pairs of angles and multipliers
+                next()?;
+                let pairs = next()? as usize;
+                for _ in 0..2 * pairs {
+                    next()?;
+                }
+            }
+            file => {
+                log::warn!(target: "IesProfile", "ignoring the tilt data of {}", file);
+            }
+        }
+
+        let [_lamps, _lumens, multiplier, vertical, horizontal, photometric_type, _units, _width, _length, _height] =
+            [(); 10].map(|_| next());
+        let (vertical, horizontal) = (vertical? as usize, horizontal? as usize);
+        if photometric_type? as u32 != PHOTOMETRIC_TYPE_C {
+            return Err("only type C photometry is supported".into());
+        }
+        let multiplier = multiplier?;
+
+        // the ballast factor, a reserved value and the input watts
+        for _ in 0..3 {
+            next()?;
+        }
+
+        let mut read = |n: usize| (0..n).map(|_| next()).collect::<Result<Vec<_>, _>>();
+        let vertical_angles = read(vertical)?;
+        let horizontal_angles = read(horizontal)?;
+        let candela = read(vertical * horizontal)?
+            .into_iter()
+            .map(|c| c * multiplier)
+            .collect();
+
+        Ok((vertical_angles, horizontal_angles, candela))
+    }
+
+    /// Returns the intensity integrated over all directions.
+    #[inline]
+    pub fn solid_angle(&self) -> Float {
+        self.solid_angle
+    }
+
+    /// Computes the relative intensity emitted into a direction.
+    ///
+    /// # Constraints
+    /// * `direction` - Should be normalized.
+    #[inline]
+    pub fn evaluate(&self, direction: Vec3) -> Float {
+        self.evaluate_local(self.rotation_inv.rotate_vector(direction))
+    }
+
+    /// Computes the relative intensity emitted into a direction in the local space of the profile.
+    fn evaluate_local(&self, direction: Vec3) -> Float {
+        let vertical = (-direction.y).clamp(-1.0, 1.0).acos().to_degrees();
+        let horizontal = (-direction.z)
+            .atan2(direction.x)
+            .to_degrees()
+            .rem_euclid(360.0);
+
+        let (v0, v1, tv) = match interpolate(&self.vertical, vertical) {
+            Some(v) => v,
+            None => return 0.0,
+        };
+        let (h0, h1, th) = self.interpolate_horizontal(horizontal);
+
+        let n = self.vertical.len();
+        let at = |h: usize, v: usize| self.candela[h * n + v];
+        let lerp = |a: Float, b: Float, t: Float| a + t * (b - a);
+
+        lerp(
+            lerp(at(h0, v0), at(h0, v1), tv),
+            lerp(at(h1, v0), at(h1, v1), tv),
+            th,
+        )
+    }
+
+    /// Finds the horizontal angles to interpolate between, following the symmetry of the profile.
+    ///
+    /// # Arguments
+    /// * `angle` - The horizontal angle in degrees within `[0, 360)`
+    ///
+    /// # Returns
+    /// * The indices of both angles
+    /// * The weight of the second one
+    fn interpolate_horizontal(&self, angle: Float) -> (usize, usize, Float) {
+        let n = self.horizontal.len();
+        let (first, last) = (self.horizontal[0], self.horizontal[n - 1]);
+        if n == 1 {
+            // rotationally symmetric
+            return (0, 0, 0.0);
+        }
+
+        let angle = if last <= 90.0 {
+            // symmetric in each quadrant
+            let angle = if angle > 180.0 { 360.0 - angle } else { angle };
+            if angle > 90.0 {
+                180.0 - angle
+            } else {
+                angle
+            }
+        } else if first >= 90.0 && last <= 270.0 {
+            // symmetric about the plane from 90° to 270°
+            if angle < 90.0 {
+                180.0 - angle
+            } else if angle > 270.0 {
+                540.0 - angle
+            } else {
+                angle
+            }
+        } else if last <= 180.0 {
+            // symmetric about the plane from 0° to 180°
+            if angle > 180.0 {
+                360.0 - angle
+            } else {
+                angle
+            }
+        } else {
+            angle
+        };
+
+        match interpolate(&self.horizontal, angle) {
+            Some(h) => h,
+            // wrap around between the last and the first angle of a full profile
+            None if last > 180.0 => {
+                let width = first + 360.0 - last;
+                let offset = if angle > last {
+                    angle - last
+                } else {
+                    angle + 360.0 - last
+                };
+                (n - 1, 0, (offset / width).clamp(0.0, 1.0))
+            }
+            None if angle < first => (0, 0, 0.0),
+            None => (n - 1, n - 1, 0.0),
+        }
+    }
+
+    /// Integrates the intensity over all directions, weighted by a function.
+    ///
+    /// # Arguments
+    /// * `f` - The weight of a direction in the scene
+    pub fn integrate<F>(&self, f: F) -> Float
+    where
+        F: Fn(Vec3) -> Float,
+    {
+        let mut integral = 0.0;
+        for row in 0..HEIGHT {
+            let solid_angle = equirectangular_solid_angle(row, WIDTH, HEIGHT);
+
+            for column in 0..WIDTH {
+                let point = Vec2::new(
+                    (column as Float + 0.5) / WIDTH as Float,
+                    (row as Float + 0.5) / HEIGHT as Float,
+                );
+                let local = equirectangular_to_direction(point).0;
+                let intensity = self.evaluate_local(local);
+
+                if intensity > 0.0 {
+                    integral += intensity * f(self.rotation.rotate_vector(local)) * solid_angle;
+                }
+            }
+        }
+
+        integral
+    }
+
+    /// Samples a direction proportional to the intensity.
+    ///
+    /// # Constraints
+    /// * `sample` - All values should be within `[0, 1)`.
+    ///
+    /// # Returns
+    /// * The direction
+    /// * The pdf with respect to the solid angle
+    pub fn sample(&self, sample: Vec2) -> (Vec3, Float) {
+        let (point, pdf) = self.distribution.sample(sample);
+        let (local, sin_theta) = equirectangular_to_direction(point);
+
+        (
+            self.rotation.rotate_vector(local),
+            equirectangular_to_solid_angle_pdf(pdf, sin_theta),
+        )
+    }
+
+    /// Computes the pdf (with respect to the solid angle) of [Self::sample] sampling a direction.
+    ///
+    /// # Constraints
+    /// * `direction` - Should be normalized.
+    pub fn pdf(&self, direction: Vec3) -> Float {
+        let (point, sin_theta) =
+            direction_to_equirectangular(self.rotation_inv.rotate_vector(direction));
+
+        equirectangular_to_solid_angle_pdf(self.distribution.pdf(point), sin_theta)
+    }
+}
+
+/// Finds the angles to interpolate between.
+///
+/// # Arguments
+/// * `angles` - The ascending angles
+/// * `angle` - The angle to interpolate at
+///
+/// # Returns
+/// * The indices of both angles
+/// * The weight of the second one
+/// * `None` if `angle` lies outside of the angles
+fn interpolate(angles: &[Float], angle: Float) -> Option<(usize, usize, Float)> {
+    let n = angles.len();
+    if angle < angles[0] || angle > angles[n - 1] {
+        return None;
+    }
+
+    let i = angles.partition_point(|&a| a <= angle).clamp(1, n - 1);
+    let (a0, a1) = (angles[i - 1], angles[i]);
+    let t = if a1 > a0 {
+        (angle - a0) / (a1 - a0)
+    } else {
+        0.0
+    };
+
+    Some((i - 1, i, t.clamp(0.0, 1.0)))
+}
+
+impl Serialize for IesProfile {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        IesProfileSerde::Checkpoint(IesProfileCheckpoint {
+            vertical: self.vertical.clone(),
+            horizontal: self.horizontal.clone(),
+            candela: self.candela.clone(),
+            rotation: self.rotation,
+        })
+        .serialize(serializer)
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+enum IesProfileSerde {
+    Config(IesProfileConfig),
+    Checkpoint(IesProfileCheckpoint),
+}
+impl TryFrom<IesProfileSerde> for IesProfile {
+    type Error = String;
+
+    fn try_from(serde: IesProfileSerde) -> Result<Self, Self::Error> {
+        match serde {
+            IesProfileSerde::Config(c) => {
+                let (vertical, horizontal, candela) = IesProfile::load(&c.path)?;
+
+                IesProfile::new(
+                    vertical,
+                    horizontal,
+                    candela,
+                    c.rotation.unwrap_or_else(Rot3::one),
+                )
+            }
+            IesProfileSerde::Checkpoint(c) => {
+                IesProfile::new(c.vertical, c.horizontal, c.candela, c.rotation)
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+struct IesProfileConfig {
+    /// The path of the `.ies` file
+    path: String,
+    /// Optional rotation into the scene
+    #[serde(default)]
+    rotation: Option<Rot3>,
+}
+
+#[derive(Deserialize, Serialize)]
+struct IesProfileCheckpoint {
+    vertical: Vec<Float>,
+    horizontal: Vec<Float>,
+    candela: Vec<Float>,
+    rotation: Rot3,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::{Deg, InnerSpace, Rotation3};
+
+    /// A downlight with a cosine distribution, symmetric in each quadrant and brighter towards 0°.
+    const DOWNLIGHT: &str = "IESNA:LM-63-2002
+[TEST] test
+[MANUFAC] none
+TILT=INCLUDE
+1
+2
+0 90
+1.0 1.0
+1 1000 2.0 4 2 1 2 0.1 0.1 0.0
+1.0 1.0 20
+0 30 60 90
+0, 90
+100 86.6 50 0
+50 43.3 25 0
+";
+
+    #[test]
+    fn parses_and_interpolates() {
+        let (vertical, horizontal, candela) = IesProfile::parse(DOWNLIGHT).unwrap();
+        assert_eq!(vertical, vec![0.0, 30.0, 60.0, 90.0]);
+        assert_eq!(horizontal, vec![0.0, 90.0]);
+        assert_eq!(candela[0], 200.0);
+
+        let profile = IesProfile::new(vertical, horizontal, candela, Rot3::one()).unwrap();
+        assert!((profile.evaluate(Vec3::new(0.0, -1.0, 0.0)) - 1.0).abs() < 1e-5);
+        assert_eq!(profile.evaluate(Vec3::new(0.0, 1.0, 0.0)), 0.0);
+
+        // 45° below the horizon towards +x, -x, -z and +z
+        let s = Float::sqrt(0.5);
+        let expected = 0.5 * (0.866 + 0.5);
+        for direction in [(s, 0.0), (-s, 0.0)] {
+            let intensity = profile.evaluate(Vec3::new(direction.0, -s, direction.1));
+            assert!((intensity - expected).abs() < 1e-3);
+        }
+        for direction in [(0.0, -s), (0.0, s)] {
+            let intensity = profile.evaluate(Vec3::new(direction.0, -s, direction.1));
+            assert!((intensity - 0.5 * expected).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn sample_matches_pdf() {
+        let (vertical, horizontal, candela) = IesProfile::parse(DOWNLIGHT).unwrap();
+        let rotation = Rot3::from_axis_angle(Vec3::new(1.0, 0.2, 0.0).normalize(), Deg(30.0));
+        let profile = IesProfile::new(vertical, horizontal, candela, rotation).unwrap();
+
+        let n = 256;
+        let mut solid_angle = 0.0;
+        for i in 0..n {
+            for j in 0..n {
+                let sample = Vec2::new(
+                    (i as Float + 0.5) / n as Float,
+                    (j as Float + 0.5) / n as Float,
+                );
+                let (direction, pdf) = profile.sample(sample);
+                assert!((profile.pdf(direction) - pdf).abs() < 1e-2 * pdf);
+
+                solid_angle += profile.evaluate(direction) / pdf;
+            }
+        }
+        solid_angle /= (n * n) as Float;
+
+        assert!((solid_angle - profile.solid_angle()).abs() < 1e-2 * profile.solid_angle());
+    }
+}
@@ -0,0 +1,295 @@
+//! Strategies to choose a single emitter for direct illumination in scenes with many emitters.
+//!
+//! Emitters are identified by their slot in [Scene::emitters](crate::scene::Scene::emitters).
+
+use crate::geometry::Aabb;
+use crate::{Float, Vec3};
+use cgmath::InnerSpace;
+
+/// Replaces powers that cannot be chosen proportionally (e.g. of unbounded surfaces) with the
+/// largest finite power.
+fn finite_powers(powers: &[Float]) -> Vec<Float> {
+    let max = powers
+        .iter()
+        .copied()
+        .filter(|p| p.is_finite())
+        .fold(0.0, Float::max);
+    let max = if max > 0.0 { max } else { 1.0 };
+
+    powers
+        .iter()
+        .map(|&p| if p.is_finite() { p.max(0.0) } else { max })
+        .collect()
+}
+
+/// Chooses emitters proportional to their power.
+#[derive(Default)]
+pub struct PowerDistribution {
+    /// The cumulative distribution over the emitter slots
+    cdf: Vec<Float>,
+}
+
+impl PowerDistribution {
+    /// Creates a distribution over the powers of the emitters.
+    /// If no emitter has any power, they are chosen uniformly.
+    pub fn new(powers: &[Float]) -> Self {
+        let mut powers = finite_powers(powers);
+        if powers.iter().all(|&p| p == 0.0) {
+            powers.fill(1.0);
+        }
+
+        let total: Float = powers.iter().sum();
+        let mut sum = 0.0;
+        let cdf = powers
+            .iter()
+            .map(|p| {
+                sum += p;
+                sum / total
+            })
+            .collect();
+
+        Self { cdf }
+    }
+
+    /// Chooses an emitter slot.
+    ///
+    /// # Constraints
+    /// * `sample` - Should be within `[0, 1)`.
+    pub fn sample(&self, sample: Float) -> Option<usize> {
+        if self.cdf.is_empty() {
+            return None;
+        }
+
+        let slot = self.cdf.partition_point(|&c| c <= sample);
+        Some(slot.min(self.cdf.len() - 1))
+    }
+
+    /// Returns the probability of choosing an emitter slot.
+    pub fn pdf(&self, slot: usize) -> Float {
+        match slot {
+            0 => self.cdf.first().copied().unwrap_or(0.0),
+            _ => self.cdf.get(slot).map_or(0.0, |c| c - self.cdf[slot - 1]),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Content {
+    /// The slot of the emitter
+    Leaf(u32),
+    /// The indices of both child nodes
+    Inner(u32, u32),
+}
+
+#[derive(Copy, Clone, Debug)]
+struct LightNode {
+    bounds: Aabb,
+    power: Float,
+    content: Content,
+}
+
+impl LightNode {
+    /// Estimates the contribution of the emitters of this node to a point, by their power and
+    /// the squared distance towards the bounds (clamped to the size of the bounds).
+    fn importance(&self, point: Vec3) -> Float {
+        let radius2 = 0.25 * self.bounds.size().magnitude2();
+        if !radius2.is_finite() {
+            // unbounded emitters are equally close to every point
+            return self.power;
+        }
+
+        let distance2 = (self.bounds.center() - point).magnitude2();
+
+        self.power / distance2.max(radius2).max(Float::EPSILON)
+    }
+}
+
+/// A bounding volume hierarchy over the emitters, choosing them by their estimated contribution
+/// to a point.
+#[derive(Default)]
+pub struct LightTree {
+    nodes: Vec<LightNode>,
+    /// The parent of every node (the root is its own parent)
+    parents: Vec<u32>,
+    /// The leaf node of every emitter slot
+    leaves: Vec<u32>,
+}
+
+impl LightTree {
+    /// Builds the hierarchy by splitting the emitters at the median of their centers along the
+    /// largest extent.
+    ///
+    /// # Arguments
+    /// * `bounds` - The bounds of every emitter
+    /// * `powers` - The power of every emitter
+    pub fn new(bounds: &[Aabb], powers: &[Float]) -> Self {
+        let powers = finite_powers(powers);
+        let mut tree = Self {
+            nodes: Vec::with_capacity(2 * bounds.len()),
+            parents: Vec::with_capacity(2 * bounds.len()),
+            leaves: vec![0; bounds.len()],
+        };
+
+        let mut slots: Vec<u32> = (0..bounds.len() as u32).collect();
+        if !slots.is_empty() {
+            tree.build(&mut slots, bounds, &powers, 0);
+        }
+
+        tree
+    }
+
+    fn build(&mut self, slots: &mut [u32], bounds: &[Aabb], powers: &[Float], parent: u32) -> u32 {
+        let index = self.nodes.len() as u32;
+        self.parents.push(parent);
+
+        if let [slot] = slots {
+            self.leaves[*slot as usize] = index;
+            self.nodes.push(LightNode {
+                bounds: bounds[*slot as usize],
+                power: powers[*slot as usize],
+                content: Content::Leaf(*slot),
+            });
+
+            return index;
+        }
+
+        let centers = slots
+            .iter()
+            .fold(Aabb::empty(), |b, &s| b.join2(bounds[s as usize].center()));
+        let size = centers.size();
+        let axis = if size.x > size.y && size.x > size.z {
+            0
+        } else if size.y > size.z {
+            1
+        } else {
+            2
+        };
+
+        let center = |s: &u32| bounds[*s as usize].center()[axis];
+        slots.sort_unstable_by(|a, b| center(a).total_cmp(&center(b)));
+
+        // placeholder until the children are built
+        self.nodes.push(LightNode {
+            bounds: Aabb::empty(),
+            power: 0.0,
+            content: Content::Leaf(0),
+        });
+
+        let (left_slots, right_slots) = slots.split_at_mut(slots.len() / 2);
+        let left = self.build(left_slots, bounds, powers, index);
+        let right = self.build(right_slots, bounds, powers, index);
+
+        let (l, r) = (self.nodes[left as usize], self.nodes[right as usize]);
+        self.nodes[index as usize] = LightNode {
+            bounds: l.bounds.join(r.bounds),
+            power: l.power + r.power,
+            content: Content::Inner(left, right),
+        };
+
+        index
+    }
+
+    /// Returns the probabilities of choosing the left and the right child of an inner node.
+    fn split(&self, left: u32, right: u32, point: Vec3) -> (Float, Float) {
+        let left = self.nodes[left as usize].importance(point);
+        let right = self.nodes[right as usize].importance(point);
+        let total = left + right;
+
+        if total > 0.0 && total.is_finite() {
+            (left / total, right / total)
+        } else {
+            (0.5, 0.5)
+        }
+    }
+
+    /// Chooses an emitter slot for a point by descending the hierarchy.
+    ///
+    /// # Constraints
+    /// * `point` - All values should be finite (neither infinite nor `NaN`).
+    /// * `sample` - Should be within `[0, 1)`.
+    pub fn sample(&self, point: Vec3, mut sample: Float) -> Option<usize> {
+        let mut node = self.nodes.first()?;
+
+        loop {
+            match node.content {
+                Content::Leaf(slot) => return Some(slot as usize),
+                Content::Inner(left, right) => {
+                    let (p_left, p_right) = self.split(left, right, point);
+
+                    let child = if sample < p_left {
+                        sample /= p_left;
+                        left
+                    } else {
+                        sample = (sample - p_left) / p_right;
+                        right
+                    };
+                    sample = sample.min(1.0 - Float::EPSILON);
+                    node = &self.nodes[child as usize];
+                }
+            }
+        }
+    }
+
+    /// Returns the probability of choosing an emitter slot for a point.
+    pub fn pdf(&self, point: Vec3, slot: usize) -> Float {
+        let mut node = match self.leaves.get(slot) {
+            Some(&leaf) => leaf,
+            None => return 0.0,
+        };
+
+        let mut pdf = 1.0;
+        while node != 0 {
+            let parent = self.parents[node as usize];
+            if let Content::Inner(left, right) = self.nodes[parent as usize].content {
+                let (p_left, p_right) = self.split(left, right, point);
+                pdf *= if node == left { p_left } else { p_right };
+            }
+            node = parent;
+        }
+
+        pdf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bounds(x: Float) -> Aabb {
+        Aabb::new(Vec3::new(x, 0.0, 0.0), Vec3::new(x + 0.1, 0.1, 0.1))
+    }
+
+    #[test]
+    fn power_proportional() {
+        let distribution = PowerDistribution::new(&[1.0, 3.0, Float::INFINITY]);
+
+        assert!((distribution.pdf(0) - 1.0 / 7.0).abs() < 1e-6);
+        assert!((distribution.pdf(1) - 3.0 / 7.0).abs() < 1e-6);
+        assert_eq!(Some(0), distribution.sample(0.1));
+        assert_eq!(Some(1), distribution.sample(0.2));
+        assert_eq!(Some(2), distribution.sample(0.9));
+    }
+
+    #[test]
+    fn tree_pdfs_sum_to_one() {
+        let bounds: Vec<_> = (0..7).map(|i| bounds(i as Float)).collect();
+        let powers = [1.0, 2.0, 0.5, 4.0, 1.0, 0.0, 3.0];
+        let tree = LightTree::new(&bounds, &powers);
+        let point = Vec3::new(2.3, 1.0, 0.0);
+
+        let sum: Float = (0..bounds.len()).map(|slot| tree.pdf(point, slot)).sum();
+        assert!((sum - 1.0).abs() < 1e-5);
+
+        // the sampled slots are chosen with their pdf
+        let samples = 10000;
+        let mut counts = [0; 7];
+        for i in 0..samples {
+            let sample = (i as Float + 0.5) / samples as Float;
+            counts[tree.sample(point, sample).unwrap()] += 1;
+        }
+        for (slot, &count) in counts.iter().enumerate() {
+            let expected = tree.pdf(point, slot);
+            assert!((count as Float / samples as Float - expected).abs() < 1e-2);
+        }
+    }
+}
@@ -1,29 +1,36 @@
 use crate::geometry::{Aabb, Geometry, Intersection, Ray};
+use crate::medium::Medium;
 use crate::Vec3;
+use cgmath::{InnerSpace, Zero};
 use serde::{Deserialize, Deserializer, Serialize};
 
 pub mod emitter;
+pub mod ies;
+pub mod lights;
 pub mod object;
 pub mod receiver;
 pub mod sampleable;
 
 use crate::geometry::bvh::Tree;
 pub use emitter::*;
+pub use ies::*;
+pub use lights::*;
 pub use object::*;
 pub use receiver::*;
 pub use sampleable::*;
 
 /// A scene intersection is a more detailed `Intersection`, also containing a reference to the
-/// intersected object.
+/// intersected object and its index.
 #[derive(Clone)]
 pub struct SceneIntersection<'a> {
     pub i: Intersection,
     pub object: &'a SceneObject,
+    pub index: u32,
 }
 
 impl<'a> SceneIntersection<'a> {
-    pub const fn new(i: Intersection, object: &'a SceneObject) -> Self {
-        Self { i, object }
+    pub const fn new(i: Intersection, object: &'a SceneObject, index: u32) -> Self {
+        Self { i, object, index }
     }
 }
 
@@ -31,10 +38,21 @@ impl<'a> SceneIntersection<'a> {
 pub struct Scene {
     #[serde(skip_serializing)]
     emitters: Vec<u32>,
+    #[serde(skip_serializing)]
+    infinite_emitters: Vec<u32>,
+    #[serde(skip_serializing)]
+    media: Vec<u32>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     objects: Vec<SceneObject>,
+    /// The medium filling the whole scene.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    medium: Option<Box<dyn Medium>>,
     #[serde(skip_serializing)]
     bvh: Tree,
+    #[serde(skip_serializing)]
+    power_distribution: PowerDistribution,
+    #[serde(skip_serializing)]
+    light_tree: LightTree,
 }
 
 impl Scene {
@@ -56,6 +74,30 @@ impl Scene {
         &self.emitters
     }
 
+    /// Returns the indices of all emitters that are infinitely far away (see
+    /// [Emitter::is_infinite]), which rays escaping the scene see.
+    pub fn infinite_emitters(&self) -> &[u32] {
+        &self.infinite_emitters
+    }
+
+    /// Returns the slot of an emitter in [Self::emitters].
+    ///
+    /// # Arguments
+    /// * `index` - The object index of the emitter
+    pub fn emitter_slot(&self, index: u32) -> Option<usize> {
+        self.emitters.binary_search(&index).ok()
+    }
+
+    /// Returns the distribution choosing emitters proportional to their power.
+    pub fn power_distribution(&self) -> &PowerDistribution {
+        &self.power_distribution
+    }
+
+    /// Returns the hierarchy choosing emitters by their estimated contribution to a point.
+    pub fn light_tree(&self) -> &LightTree {
+        &self.light_tree
+    }
+
     pub fn get_emitter(&self, index: usize) -> Option<&Emitter> {
         match self.get_object(index) {
             SceneObject::Emitter(e) => Some(e),
@@ -63,10 +105,58 @@ impl Scene {
         }
     }
 
+    /// Returns whether the scene contains any medium.
+    pub fn has_media(&self) -> bool {
+        self.medium.is_some() || !self.media.is_empty()
+    }
+
+    /// Sets the medium filling the whole scene.
+    pub fn set_medium(&mut self, medium: Option<Box<dyn Medium>>) {
+        self.medium = medium;
+    }
+
+    /// Returns the medium at the given point.
+    ///
+    /// Nested media take precedence if they are added after the media containing them.
+    /// Points outside of every receiver medium are inside the medium of the whole scene.
+    ///
+    /// # Constraints
+    /// * `point` - All values should be finite (neither infinite nor `NaN`).
+    ///
+    /// # Arguments
+    /// * `point` - The point to look up
+    pub fn medium_at(&self, point: Vec3) -> Option<&dyn Medium> {
+        self.media
+            .iter()
+            .rev()
+            .map(|&i| self.get_object(i as usize))
+            .find(|o| o.contains(point) == Some(true))
+            .map_or(self.medium.as_deref(), |o| o.medium())
+    }
+
+    /// Returns the medium along the given ray segment `[t_start, t_end]`.
+    ///
+    /// # Constraints
+    /// * `ray` - Should not cross the surface of any object.
+    ///
+    /// # Arguments
+    /// * `ray` - The ray segment
+    pub fn medium_along(&self, ray: Ray) -> Option<&dyn Medium> {
+        if ray.t_end.is_finite() {
+            self.medium_at(ray.at(0.5 * (ray.t_start + ray.t_end)))
+        } else {
+            // escaping rays cannot be inside a closed receiver
+            self.medium.as_deref()
+        }
+    }
+
     pub fn add_object(&mut self, o: SceneObject) {
         if o.emitter() {
             self.emitters.push(self.objects.len() as u32);
         }
+        if o.medium().is_some() {
+            self.media.push(self.objects.len() as u32);
+        }
 
         self.objects.push(o);
     }
@@ -80,7 +170,44 @@ impl Scene {
         self.bvh = Tree::new(&indices, |i| self.objects[i as usize].bounds());
     }
 
-    pub fn intersect(&self, mut ray: Ray) -> Option<SceneIntersection> {
+    fn build_lights(&mut self) {
+        // infinite emitters emit their rays through the finite part of the scene
+        let bounds = self
+            .objects
+            .iter()
+            .map(|o| o.bounds())
+            .filter(|b| b.size().magnitude2().is_finite())
+            .fold(Aabb::empty(), |a, b| a.join(b));
+        let bounds = if bounds.min.x <= bounds.max.x {
+            bounds
+        } else {
+            Aabb::new(Vec3::zero(), Vec3::zero())
+        };
+
+        self.infinite_emitters = self
+            .emitters
+            .iter()
+            .copied()
+            .filter(|&i| {
+                self.get_emitter(i as usize)
+                    .is_some_and(Emitter::is_infinite)
+            })
+            .collect();
+
+        let emitters: Vec<_> = self
+            .emitters
+            .iter()
+            .filter_map(|&i| self.get_emitter(i as usize))
+            .collect();
+        emitters.iter().for_each(|e| e.set_scene_bounds(bounds));
+        let powers: Vec<_> = emitters.iter().map(|e| e.power()).collect();
+        let bounds: Vec<_> = emitters.iter().map(|e| e.bounds()).collect();
+
+        self.power_distribution = PowerDistribution::new(&powers);
+        self.light_tree = LightTree::new(&bounds, &powers);
+    }
+
+    pub fn intersect(&self, mut ray: Ray) -> Option<SceneIntersection<'_>> {
         let mut intersection = None;
 
         for hit_index in self.bvh.intersect(ray) {
@@ -88,12 +215,39 @@ impl Scene {
 
             if let Some(i) = object.intersect(ray) {
                 ray.t_end = i.t;
-                intersection = Some(SceneIntersection::new(i, object));
+                intersection = Some(SceneIntersection::new(i, object, hit_index));
             }
         }
 
         intersection
     }
+
+    /// Intersects the scene like [Self::intersect], but also keeps track of the traversal.
+    ///
+    /// # Returns
+    /// * The index of the intersected object and the intersection
+    /// * The number of objects tested for an intersection
+    pub fn intersect_traced(
+        &self,
+        mut ray: Ray,
+    ) -> (Option<(usize, SceneIntersection<'_>)>, usize) {
+        let mut intersection = None;
+
+        let candidates = self.bvh.intersect(ray);
+        for &hit_index in &candidates {
+            let object = self.get_object(hit_index as usize);
+
+            if let Some(i) = object.intersect(ray) {
+                ray.t_end = i.t;
+                intersection = Some((
+                    hit_index as usize,
+                    SceneIntersection::new(i, object, hit_index),
+                ));
+            }
+        }
+
+        (intersection, candidates.len())
+    }
 }
 
 #[typetag::serde]
@@ -134,14 +288,16 @@ impl<'de> Deserialize<'de> for Scene {
     where
         D: Deserializer<'de>,
     {
-        let objects = {
+        let (objects, medium) = {
             #[derive(Deserialize)]
             struct Scene {
                 #[serde(default)]
                 objects: Vec<SceneObject>,
+                #[serde(default)]
+                medium: Option<Box<dyn Medium>>,
             }
             let s = Scene::deserialize(deserializer)?;
-            s.objects
+            (s.objects, s.medium)
         };
 
         let emitters = objects
@@ -149,13 +305,24 @@ impl<'de> Deserialize<'de> for Scene {
             .enumerate()
             .filter_map(|(i, o)| if o.emitter() { Some(i as u32) } else { None })
             .collect();
+        let media = objects
+            .iter()
+            .enumerate()
+            .filter_map(|(i, o)| o.medium().map(|_| i as u32))
+            .collect();
 
         let mut scene = Scene {
             emitters,
+            infinite_emitters: Vec::new(),
+            media,
             objects,
+            medium,
             bvh: Default::default(),
+            power_distribution: Default::default(),
+            light_tree: Default::default(),
         };
         scene.build_tree();
+        scene.build_lights();
 
         Ok(scene)
     }
@@ -214,4 +381,37 @@ mod tests {
         assert_eq!(Aabb::new(Vec3::zero(), Vec3::zero()), o.bounds());
         assert_eq!("dummy", o.tag());
     }
+
+    #[test]
+    fn serde_medium() {
+        use crate::medium::{HenyeyGreenstein, Homogeneous};
+        use crate::Spectrum;
+
+        let medium = || {
+            Box::new(Homogeneous::new(
+                Spectrum::splat(0.1),
+                Spectrum::splat(0.5),
+                HenyeyGreenstein::new(0.3),
+            ))
+        };
+
+        let mut receiver = Receiver::dummy();
+        receiver.medium = Some(medium());
+
+        let mut scene = Scene::default();
+        scene.add_object(SceneObject::Receiver(receiver));
+        scene.set_medium(Some(medium()));
+
+        let ser = ron::to_string(&scene).unwrap();
+        eprintln!("serde_medium():\t\t{:?}", &ser);
+        let de = ron::from_str::<Scene>(&ser).unwrap();
+
+        assert!(de.has_media());
+        assert_eq!(vec![0], de.media);
+        assert!(de.medium.is_some());
+
+        let inside = de.medium_at(Vec3::new(0.5, 0.5, 0.5)).unwrap();
+        assert_eq!(&HenyeyGreenstein::new(0.3), inside.phase());
+        assert!(de.medium_at(Vec3::new(2.0, 2.0, 2.0)).is_some());
+    }
 }
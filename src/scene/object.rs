@@ -1,12 +1,13 @@
 use crate::bxdf::BSDF;
 use crate::geometry::{Aabb, Geometry, Intersection, Ray};
+use crate::medium::Medium;
 use crate::scene::{Emitter, Receiver};
 use crate::Vec3;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize)]
 pub enum SceneObject {
-    Emitter(Emitter),
+    Emitter(Box<Emitter>),
     Receiver(Receiver),
 }
 
@@ -20,7 +21,7 @@ impl SceneObject {
     #[cold]
     #[inline(never)]
     pub fn dummy_emitter() -> Self {
-        Self::Emitter(Emitter::dummy())
+        Self::Emitter(Box::new(Emitter::dummy()))
     }
 
     #[inline]
@@ -31,6 +32,15 @@ impl SceneObject {
         }
     }
 
+    /// Returns the medium filling this object.
+    #[inline]
+    pub fn medium(&self) -> Option<&dyn Medium> {
+        match self {
+            SceneObject::Emitter(_) => None,
+            SceneObject::Receiver(r) => r.medium.as_deref(),
+        }
+    }
+
     #[inline]
     pub fn tag(&self) -> &str {
         match self {
@@ -1,15 +1,21 @@
 use crate::bxdf::BSDF;
 use crate::geometry::{Aabb, Geometry, Intersection, Ray};
+use crate::medium::Medium;
 use crate::Vec3;
 use serde::{Deserialize, Serialize};
 
 /// A receiver consists of a geometry and a BSDF.
+/// It may be filled with a medium, which requires the geometry to
+/// [contain](Geometry::contains) points.
 #[derive(Serialize, Deserialize)]
 pub struct Receiver {
     pub geometry: Box<dyn Geometry>,
     #[serde(default)]
     pub bsdf: BSDF,
     #[serde(default)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub medium: Option<Box<dyn Medium>>,
+    #[serde(default)]
     pub tag: String,
 }
 
@@ -20,9 +26,17 @@ impl Receiver {
         Self {
             geometry: Box::new(Aabb::unit()),
             bsdf: Default::default(),
+            medium: None,
             tag: "dummy".into(),
         }
     }
+
+    /// Returns whether this receiver only bounds its medium, letting rays pass through its
+    /// surface unaffected.
+    #[inline]
+    pub fn is_interface(&self) -> bool {
+        self.medium.is_some() && self.bsdf.is_empty()
+    }
 }
 
 #[typetag::serde]
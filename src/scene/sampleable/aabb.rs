@@ -0,0 +1,247 @@
+use crate::geometry::{Aabb, Geometry, Ray};
+use crate::scene::{area_to_solid_angle_pdf, Sampleable, SurfaceSample};
+use crate::util::spherical::{
+    rectangle_solid_angle, sample_spherical_rectangle, MAX_SOLID_ANGLE, MIN_SOLID_ANGLE,
+};
+use crate::{Float, Vec2, Vec3};
+
+impl Aabb {
+    /// Returns the areas of the faces in the order `-x`, `+x`, `-y`, `+y`, `-z`, `+z`.
+    /// If the box is flat along an axis, both of its faces coincide and only the positive one is
+    /// kept.
+    fn face_areas(&self) -> [Float; 6] {
+        let size = self.size();
+        let mut areas = [0.0; 6];
+
+        for axis in 0..3 {
+            let area = size[(axis + 1) % 3] * size[(axis + 2) % 3];
+            areas[2 * axis] = if size[axis] > 0.0 { area } else { 0.0 };
+            areas[2 * axis + 1] = area;
+        }
+
+        areas
+    }
+
+    /// Returns a face (see [Self::face_areas] for the order).
+    ///
+    /// # Returns
+    /// * A corner of the face
+    /// * Both edges from the corner
+    /// * The outward normal
+    fn face(&self, face: usize) -> (Vec3, Vec3, Vec3, Vec3) {
+        let axis = face / 2;
+        let positive = face % 2 == 1;
+        let (b, c) = ((axis + 1) % 3, (axis + 2) % 3);
+        let size = self.size();
+
+        let mut corner = self.min;
+        if positive {
+            corner[axis] = self.max[axis];
+        }
+
+        (
+            corner,
+            unit(b, size[b]),
+            unit(c, size[c]),
+            unit(axis, if positive { 1.0 } else { -1.0 }),
+        )
+    }
+
+    /// Returns the axis of the face a point on the surface lies on.
+    fn face_axis(&self, point: Vec3) -> usize {
+        let half_size = self.size() / 2.0;
+        let offset = point - self.center();
+        let distance = |axis: usize| {
+            if half_size[axis] > 0.0 {
+                offset[axis].abs() / half_size[axis]
+            } else {
+                Float::INFINITY
+            }
+        };
+
+        (0..3)
+            .max_by(|&a, &b| distance(a).total_cmp(&distance(b)))
+            .unwrap_or(0)
+    }
+
+    /// Computes the solid angles of the faces as seen from an origin, which are `0` for the faces
+    /// facing away from it.
+    ///
+    /// # Returns
+    /// * The solid angles (see [Self::face_areas] for the order)
+    /// * `None` if they cannot be sampled robustly, so that the faces are sampled by area instead
+    fn solid_angles(&self, origin: Vec3) -> Option<[Float; 6]> {
+        let areas = self.face_areas();
+        let size = self.size();
+        let inside =
+            (0..3).all(|axis| self.min[axis] < origin[axis] && origin[axis] < self.max[axis]);
+
+        let mut solid_angles = [0.0; 6];
+        for (face, solid_angle) in solid_angles.iter_mut().enumerate() {
+            let axis = face / 2;
+            let visible = if size[axis] == 0.0 {
+                origin[axis] != self.max[axis]
+            } else if face % 2 == 1 {
+                inside || origin[axis] > self.max[axis]
+            } else {
+                inside || origin[axis] < self.min[axis]
+            };
+
+            if visible && areas[face] > 0.0 {
+                let (corner, u, v, _) = self.face(face);
+                *solid_angle = rectangle_solid_angle(origin, corner, u, v);
+            }
+        }
+
+        let total: Float = solid_angles.iter().sum();
+        if total < MIN_SOLID_ANGLE || solid_angles.iter().any(|&s| s > MAX_SOLID_ANGLE) {
+            None
+        } else {
+            Some(solid_angles)
+        }
+    }
+}
+
+/// Returns a vector along an axis.
+#[inline]
+fn unit(axis: usize, length: Float) -> Vec3 {
+    let mut vector = Vec3::new(0.0, 0.0, 0.0);
+    vector[axis] = length;
+    vector
+}
+
+/// Chooses a face proportional to its weight.
+///
+/// # Returns
+/// * The face
+/// * The sample remapped to `[0, 1]` within the face, so that it can be reused
+fn choose_face(weights: &[Float; 6], sample: Float) -> (usize, Float) {
+    let mut x = sample * weights.iter().sum::<Float>();
+    let mut face = 5;
+    for (i, &w) in weights.iter().enumerate() {
+        if x < w {
+            face = i;
+            break;
+        }
+        x -= w;
+    }
+
+    (face, (x / weights[face]).clamp(0.0, 1.0))
+}
+
+/// The faces facing the origin are chosen proportional to the solid angle they cover and sampled
+/// uniformly by it (see [sample_spherical_rectangle]), unless the box is too small or too close to
+/// be sampled robustly.
+/// Otherwise, all faces are chosen proportional to their area and sampled uniformly.
+/// Surface normals point outwards.
+#[typetag::serde]
+impl Sampleable for Aabb {
+    fn sample_surface(&self, origin: Vec3, sample: Vec2) -> SurfaceSample {
+        let solid_angles = match self.solid_angles(origin) {
+            Some(s) => s,
+            None => {
+                let area_sample = self.sample_area(sample);
+                let pdf = area_to_solid_angle_pdf(
+                    area_sample.pdf,
+                    origin,
+                    area_sample.point,
+                    area_sample.normal,
+                );
+
+                return SurfaceSample::new(area_sample.point, area_sample.normal, pdf);
+            }
+        };
+
+        let (face, x) = choose_face(&solid_angles, sample.x);
+        let (corner, u, v, normal) = self.face(face);
+        let sample = Vec2::new(x, sample.y);
+        let uv = sample_spherical_rectangle(origin, corner, u, v, sample).unwrap_or(sample);
+
+        SurfaceSample::new(
+            corner + uv.x * u + uv.y * v,
+            normal,
+            1.0 / solid_angles.iter().sum::<Float>(),
+        )
+    }
+
+    fn pdf(&self, origin: Vec3, direction: Vec3) -> Float {
+        let i = match self.intersect(Ray::new(origin, direction)) {
+            Some(i) => i,
+            None => return 0.0,
+        };
+
+        match self.solid_angles(origin) {
+            Some(solid_angles) => 1.0 / solid_angles.iter().sum::<Float>(),
+            None => {
+                let normal = unit(self.face_axis(i.point), 1.0);
+                area_to_solid_angle_pdf(self.pdf_area(i.point), origin, i.point, normal)
+            }
+        }
+    }
+
+    fn sample_area(&self, sample: Vec2) -> SurfaceSample {
+        let areas = self.face_areas();
+        let area: Float = areas.iter().sum();
+        if area <= 0.0 {
+            return SurfaceSample::new(self.min, Vec3::new(0.0, 1.0, 0.0), 0.0);
+        }
+
+        let (face, x) = choose_face(&areas, sample.x);
+        let (corner, u, v, normal) = self.face(face);
+
+        SurfaceSample::new(corner + x * u + sample.y * v, normal, 1.0 / area)
+    }
+
+    #[inline]
+    fn pdf_area(&self, _: Vec3) -> Float {
+        1.0 / Sampleable::area(self)
+    }
+
+    #[inline]
+    fn area(&self) -> Float {
+        self.face_areas().iter().sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::mc::sample_unit_sphere;
+    use cgmath::InnerSpace;
+
+    #[test]
+    fn sampled_solid_angle_matches() {
+        let aabb = Aabb::new(Vec3::new(-1.0, 0.5, -0.5), Vec3::new(0.5, 1.5, 2.0));
+        let origin = Vec3::new(0.3, -0.4, 0.2);
+
+        let n = 128;
+        let mut sampled = 0.0;
+        let mut hits = 0;
+        for i in 0..n {
+            for j in 0..n {
+                let sample = Vec2::new(
+                    (i as Float + 0.5) / n as Float,
+                    (j as Float + 0.5) / n as Float,
+                );
+
+                // the box is convex, so only the points facing the origin are visible
+                let surface_sample = aabb.sample_surface(origin, sample);
+                let direction = (surface_sample.point - origin).normalize();
+                if surface_sample.normal.dot(direction) < 0.0 {
+                    let pdf = aabb.pdf(origin, direction);
+                    assert!((pdf - surface_sample.pdf).abs() < 1e-2 * pdf);
+
+                    sampled += 1.0 / surface_sample.pdf;
+                }
+
+                if aabb.pdf(origin, sample_unit_sphere(sample)) > 0.0 {
+                    hits += 1;
+                }
+            }
+        }
+        sampled /= (n * n) as Float;
+
+        let expected = 4.0 * std::f64::consts::PI as Float * hits as Float / (n * n) as Float;
+        assert!((sampled - expected).abs() < 2e-2 * expected);
+    }
+}
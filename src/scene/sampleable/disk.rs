@@ -1,16 +1,47 @@
-use crate::geometry::Disk;
-use crate::scene::{Sampleable, SurfaceSample};
-use crate::{Vec2, Vec3};
+use crate::geometry::{Disk, Geometry, Ray};
+use crate::scene::{area_to_solid_angle_pdf, Sampleable, SurfaceSample};
+use crate::{Float, Vec2, Vec3};
 
 use crate::bxdf::bxdf_to_world;
 use crate::util::mc::sample_unit_disk_concentric;
+#[cfg(not(feature = "f64"))]
+use std::f32::consts::PI;
+#[cfg(feature = "f64")]
+use std::f64::consts::PI;
 
+/// Unlike triangles and rectangles, a disk has no closed-form parametrization by the solid angle it
+/// covers, so it is sampled uniformly by area.
 #[typetag::serde]
 impl Sampleable for Disk {
-    fn sample_surface(&self, _point: Vec3, sample: Vec2) -> SurfaceSample {
+    fn sample_surface(&self, origin: Vec3, sample: Vec2) -> SurfaceSample {
+        let area_sample = self.sample_area(sample);
+        let pdf = area_to_solid_angle_pdf(area_sample.pdf, origin, area_sample.point, self.normal);
+
+        SurfaceSample::new(area_sample.point, self.normal, pdf)
+    }
+
+    fn pdf(&self, origin: Vec3, direction: Vec3) -> Float {
+        match self.intersect(Ray::new(origin, direction)) {
+            Some(i) => area_to_solid_angle_pdf(1.0 / self.area(), origin, i.point, self.normal),
+            None => 0.0,
+        }
+    }
+
+    fn sample_area(&self, sample: Vec2) -> SurfaceSample {
         let uv = sample_unit_disk_concentric(sample) * self.radius;
-        let point = bxdf_to_world(self.normal).rotate_vector(uv.extend(0.0));
+        let point =
+            self.center + bxdf_to_world(self.normal).rotate_vector(Vec3::new(uv.x, 0.0, uv.y));
+
+        SurfaceSample::new(point, self.normal, 1.0 / self.area())
+    }
+
+    #[inline]
+    fn pdf_area(&self, _: Vec3) -> Float {
+        1.0 / self.area()
+    }
 
-        SurfaceSample::new(point, self.normal)
+    #[inline]
+    fn area(&self) -> Float {
+        PI * self.radius * self.radius
     }
 }
@@ -0,0 +1,130 @@
+use crate::geometry::Environment;
+use crate::scene::{equirectangular_to_solid_angle_pdf, Sampleable, SurfaceSample};
+use crate::{Float, Spectrum, Vec2, Vec3};
+
+impl Environment {
+    /// Samples a direction towards the map proportional to the luminance of its texels.
+    ///
+    /// # Returns
+    /// * The direction
+    /// * The pdf with respect to the solid angle
+    fn sample_map(&self, sample: Vec2) -> (Vec3, Float) {
+        let (point, pdf) = self.distribution.sample(sample);
+        let (direction, sin_theta) = self.to_direction(point);
+
+        (
+            direction,
+            equirectangular_to_solid_angle_pdf(pdf, sin_theta),
+        )
+    }
+
+    /// Computes the pdf (with respect to the solid angle) of [Self::sample_map].
+    fn pdf_map(&self, direction: Vec3) -> Float {
+        let (point, sin_theta) = self.to_image(direction);
+
+        equirectangular_to_solid_angle_pdf(self.distribution.pdf(point), sin_theta)
+    }
+}
+
+/// As the map is infinitely far away, it cannot be sampled by area.
+/// Its directions are sampled instead, with the radiance of a direction given by its
+/// [color](Sampleable::color).
+#[typetag::serde]
+impl Sampleable for Environment {
+    fn sample_surface(&self, origin: Vec3, sample: Vec2) -> SurfaceSample {
+        let (incident, pdf) = self.sample_map(sample);
+
+        SurfaceSample::new(origin, -incident, pdf)
+    }
+
+    #[inline]
+    fn pdf(&self, _origin: Vec3, direction: Vec3) -> Float {
+        self.pdf_map(direction)
+    }
+
+    #[inline]
+    fn sample_area(&self, _: Vec2) -> SurfaceSample {
+        SurfaceSample::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, -1.0, 0.0), 0.0)
+    }
+
+    #[inline(always)]
+    fn pdf_area(&self, _: Vec3) -> Float {
+        0.0
+    }
+
+    #[inline(always)]
+    fn area(&self) -> Float {
+        Float::INFINITY
+    }
+
+    #[inline(always)]
+    fn is_infinite(&self) -> bool {
+        true
+    }
+
+    #[inline]
+    fn irradiance(&self) -> Float {
+        Environment::irradiance(self)
+    }
+
+    #[inline]
+    fn color(&self, direction: Vec3) -> Option<Spectrum> {
+        Some(self.lookup(self.to_image(-direction).0))
+    }
+
+    /// Samples the directions leaving the map proportional to the luminance of its texels.
+    #[inline]
+    fn sample_direction(&self, sample: Vec2) -> (Vec3, Float) {
+        let (incident, pdf) = self.sample_map(sample);
+
+        (-incident, pdf)
+    }
+
+    #[inline]
+    fn pdf_direction(&self, direction: Vec3) -> Float {
+        self.pdf_map(-direction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::SampledSpectrum;
+    use crate::Rot3;
+    use cgmath::{Deg, InnerSpace, Rotation3};
+
+    #[test]
+    fn sampled_irradiance_matches() {
+        let (width, height) = (8, 4);
+        let texels = (0..width * height)
+            .map(|i| {
+                let v = (i % 5) as Float;
+                [v, 0.5 * v, 0.25]
+            })
+            .collect();
+        let rotation = Rot3::from_axis_angle(Vec3::new(0.3, 1.0, 0.2).normalize(), Deg(40.0));
+        let environment = Environment::new(width, height, texels, rotation).unwrap();
+
+        let n = 256;
+        let mut irradiance = 0.0;
+        for i in 0..n {
+            for j in 0..n {
+                let sample = Vec2::new(
+                    (i as Float + 0.5) / n as Float,
+                    (j as Float + 0.5) / n as Float,
+                );
+                let surface_sample = environment.sample_surface(Vec3::new(0.0, 0.0, 0.0), sample);
+                let incident = -surface_sample.normal;
+                let pdf = environment.pdf(Vec3::new(0.0, 0.0, 0.0), incident);
+                assert!((pdf - surface_sample.pdf).abs() < 1e-2 * pdf);
+
+                let color = environment.color(-incident).unwrap();
+                irradiance += color.average() / surface_sample.pdf;
+            }
+        }
+        irradiance /= (n * n) as Float;
+
+        let expected = environment.irradiance();
+        assert!((irradiance - expected).abs() < 1e-2 * expected);
+    }
+}
@@ -0,0 +1,147 @@
+use crate::geometry::{Face, Mesh, Ray};
+use crate::scene::{area_to_solid_angle_pdf, Sampleable, SurfaceSample};
+use crate::util::mc::sample_unit_triangle;
+use crate::util::spherical::{is_samplable, sample_spherical_triangle, triangle_solid_angle};
+use crate::{Float, Vec2, Vec3};
+use cgmath::InnerSpace;
+
+impl Mesh {
+    /// Computes the solid angle a face covers as seen from an origin.
+    ///
+    /// # Returns
+    /// * The solid angle
+    /// * `None` if it cannot be sampled robustly, so that the face is sampled by area instead
+    fn face_solid_angle(&self, origin: Vec3, face: &Face) -> Option<Float> {
+        let (v0, v1, v2) = face.get_vertices(self.vertices());
+        let solid_angle = triangle_solid_angle(origin, v0, v1, v2);
+
+        is_samplable(solid_angle).then_some(solid_angle)
+    }
+
+    /// Computes the pdf (with respect to the solid angle) of sampling a point on a face by area.
+    fn pdf_face_area(&self, origin: Vec3, face: &Face, point: Vec3) -> Float {
+        let face_normal = face.face_normal(self.vertices()).normalize();
+
+        area_to_solid_angle_pdf(1.0 / self.surface_area(), origin, point, face_normal)
+    }
+}
+
+/// Faces are chosen proportional to their area.
+/// They are then sampled uniformly by the solid angle they cover (see
+/// [sample_spherical_triangle]), unless they are too small or too close to be sampled robustly.
+/// Otherwise, they are sampled uniformly by area.
+/// The surface normal of a sample follows the shading mode, while its pdf is converted with the
+/// normal of the face.
+#[typetag::serde]
+impl Sampleable for Mesh {
+    fn sample_surface(&self, origin: Vec3, sample: Vec2) -> SurfaceSample {
+        let invalid = SurfaceSample::new(origin, Vec3::new(0.0, 1.0, 0.0), 0.0);
+        let (face, remapped) = match self.choose_face(sample.x) {
+            Some(c) => c,
+            None => return invalid,
+        };
+        let (v0, v1, v2) = face.get_vertices(self.vertices());
+        let sample = Vec2::new(remapped, sample.y);
+        let at = |barycentric: Vec2| v0 + barycentric.x * (v1 - v0) + barycentric.y * (v2 - v0);
+
+        let (barycentric, pdf) = match self.face_solid_angle(origin, face) {
+            Some(solid_angle) => match sample_spherical_triangle(origin, v0, v1, v2, sample) {
+                Some(b) => (b, self.face_probability(face) / solid_angle),
+                None => return invalid,
+            },
+            None => {
+                let b = sample_unit_triangle(sample);
+                (b, self.pdf_face_area(origin, face, at(b)))
+            }
+        };
+
+        SurfaceSample::new(
+            at(barycentric),
+            face.normal_at(self, barycentric.x, barycentric.y),
+            pdf,
+        )
+    }
+
+    fn pdf(&self, origin: Vec3, direction: Vec3) -> Float {
+        match self.intersect_face(Ray::new(origin, direction)) {
+            Some((i, face)) => match self.face_solid_angle(origin, face) {
+                Some(solid_angle) => self.face_probability(face) / solid_angle,
+                None => self.pdf_face_area(origin, face, i.point),
+            },
+            None => 0.0,
+        }
+    }
+
+    fn sample_area(&self, sample: Vec2) -> SurfaceSample {
+        match self.sample_face(sample) {
+            Some((point, face, barycentric)) => SurfaceSample::new(
+                point,
+                face.normal_at(self, barycentric.x, barycentric.y),
+                1.0 / self.surface_area(),
+            ),
+            None => SurfaceSample::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, 1.0, 0.0), 0.0),
+        }
+    }
+
+    #[inline]
+    fn pdf_area(&self, _: Vec3) -> Float {
+        1.0 / self.surface_area()
+    }
+
+    #[inline]
+    fn area(&self) -> Float {
+        self.surface_area()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::{Face, ShadingMode};
+
+    #[test]
+    fn sample_matches_pdf() {
+        // a quad of area 2 and a triangle of area 1 below it, facing up
+        let vertices = vec![
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(0.0, 1.0, -1.0),
+            Vec3::new(2.0, 1.0, -1.0),
+            Vec3::new(2.0, 1.0, 0.0),
+            Vec3::new(-1.0, 0.0, 0.0),
+            Vec3::new(1.0, 0.0, 0.0),
+            Vec3::new(-1.0, 0.0, -1.0),
+        ];
+        let faces = vec![
+            Face::new((0, 3, 2), (0, 3, 2)),
+            Face::new((0, 2, 1), (0, 2, 1)),
+            Face::new((4, 5, 6), (4, 5, 6)),
+        ];
+        let mesh = Mesh::new(vertices, vec![], faces, ShadingMode::Flat).build();
+        assert!((Sampleable::area(&mesh) - 3.0).abs() < 1e-5);
+
+        let origin = Vec3::new(0.5, 3.0, 1.0);
+        let n = 32;
+        let mut upper = 0;
+        for i in 0..n {
+            for j in 0..n {
+                let sample = Vec2::new(
+                    (i as Float + 0.5) / n as Float,
+                    (j as Float + 0.5) / n as Float,
+                );
+                let surface_sample = mesh.sample_surface(origin, sample);
+                if surface_sample.point.y > 0.5 {
+                    upper += 1;
+
+                    // the quad occludes parts of the triangle, so only it is compared
+                    let direction = (surface_sample.point - origin).normalize();
+                    let pdf = mesh.pdf(origin, direction);
+                    assert!((pdf - surface_sample.pdf).abs() < 1e-3 * pdf);
+                }
+            }
+        }
+
+        // the faces are chosen proportional to their area
+        let fraction = upper as Float / (n * n) as Float;
+        assert!((fraction - 2.0 / 3.0).abs() < 2e-2);
+    }
+}
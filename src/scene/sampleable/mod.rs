@@ -1,23 +1,208 @@
+mod aabb;
 mod disk;
+mod environment;
+mod mesh;
 mod plane;
 mod point;
+mod sky;
 mod sphere;
+mod spot;
+mod sun;
 
 use crate::geometry::Geometry;
-use crate::{Vec2, Vec3};
+use crate::util::mc::sample_unit_sphere;
+use crate::{Float, Spectrum, Vec2, Vec3};
+use cgmath::InnerSpace;
+#[cfg(not(feature = "f64"))]
+use std::f32::consts::{FRAC_1_PI, PI};
+#[cfg(feature = "f64")]
+use std::f64::consts::{FRAC_1_PI, PI};
 
 #[derive(Copy, Clone)]
 pub struct SurfaceSample {
     pub point: Vec3,
     pub normal: Vec3,
+    /// The pdf with respect to the solid angle of the origin.
+    pub pdf: Float,
 }
 impl SurfaceSample {
-    pub const fn new(point: Vec3, normal: Vec3) -> Self {
-        Self { point, normal }
+    pub const fn new(point: Vec3, normal: Vec3, pdf: Float) -> Self {
+        Self { point, normal, pdf }
     }
 }
 
 #[typetag::serde]
 pub trait Sampleable: Geometry {
+    /// Samples a point on the surface as seen from an origin.
+    ///
+    /// # Constraints
+    /// * `origin`: All values should be finite (neither infinite nor `NaN`).
+    /// * `sample`: All values should be within `[0, 1)`.
+    ///
+    /// # Arguments
+    /// * `origin`: The point from which we sample the surface
+    /// * `sample`: A random sample
+    ///
+    /// # Returns
+    /// * The surface sample with its pdf with respect to the solid angle of `origin`
+    ///   (the point of infinite surfaces is meaningless, but their normal points towards `origin`)
     fn sample_surface(&self, origin: Vec3, sample: Vec2) -> SurfaceSample;
+
+    /// Computes the pdf (with respect to the solid angle) of [Self::sample_surface] sampling a
+    /// point in the given direction.
+    ///
+    /// # Constraints
+    /// * `origin`: All values should be finite (neither infinite nor `NaN`).
+    /// * `direction`: All values should be finite.
+    ///                Should be normalized.
+    ///
+    /// # Arguments
+    /// * `origin`: The point from which we sample the surface
+    /// * `direction`: The direction from the origin towards the surface
+    fn pdf(&self, origin: Vec3, direction: Vec3) -> Float;
+
+    /// Samples a point on the surface independent of any origin.
+    ///
+    /// # Constraints
+    /// * `sample`: All values should be within `[0, 1)`.
+    ///
+    /// # Arguments
+    /// * `sample`: A random sample
+    ///
+    /// # Returns
+    /// * The surface sample with its pdf with respect to the surface area
+    fn sample_area(&self, sample: Vec2) -> SurfaceSample;
+
+    /// Computes the pdf (with respect to the surface area) of [Self::sample_area] sampling the
+    /// given point.
+    ///
+    /// # Constraints
+    /// * `point`: Should be on the surface.
+    ///
+    /// # Arguments
+    /// * `point`: The point on the surface
+    fn pdf_area(&self, point: Vec3) -> Float;
+
+    /// Returns the surface area (`0` for delta surfaces and infinite for unbounded ones).
+    fn area(&self) -> Float;
+
+    /// Returns whether this surface is described by a delta distribution (e.g. a point), making it
+    /// impossible to be hit by a ray.
+    #[inline]
+    fn is_delta(&self) -> bool {
+        false
+    }
+
+    /// Returns whether this surface is infinitely far away (e.g. the sun), so that it can only be
+    /// seen by rays escaping the scene.
+    #[inline]
+    fn is_infinite(&self) -> bool {
+        false
+    }
+
+    /// Returns the fraction of the intensity a delta surface emits into a direction (`1` if it
+    /// emits uniformly).
+    /// Infinite surfaces return the radiance they emit into a direction per unit of irradiance.
+    ///
+    /// # Constraints
+    /// * `direction`: Should be normalized.
+    ///
+    /// # Arguments
+    /// * `direction`: The direction leaving the surface
+    #[inline]
+    fn intensity(&self, _direction: Vec3) -> Float {
+        1.0
+    }
+
+    /// Returns the integral of [Self::intensity] over the sphere of directions.
+    #[inline]
+    fn solid_angle(&self) -> Float {
+        4.0 * PI
+    }
+
+    /// Returns the irradiance an infinite surface emits through a unit area facing each of its
+    /// directions, i.e. its radiance integrated over all directions and averaged over the
+    /// wavelengths.
+    #[inline]
+    fn irradiance(&self) -> Float {
+        1.0
+    }
+
+    /// Returns the spectral distribution emitted into a direction, which scales the emission if
+    /// it varies between directions (e.g. for an environment map).
+    ///
+    /// # Constraints
+    /// * `direction`: Should be normalized.
+    ///
+    /// # Arguments
+    /// * `direction`: The direction leaving the surface
+    #[inline]
+    fn color(&self, _direction: Vec3) -> Option<Spectrum> {
+        None
+    }
+
+    /// Samples a direction a delta surface emits into.
+    ///
+    /// # Constraints
+    /// * `sample`: All values should be within `[0, 1)`.
+    ///
+    /// # Arguments
+    /// * `sample`: A random sample
+    ///
+    /// # Returns
+    /// * The direction
+    /// * The pdf with respect to the solid angle
+    #[inline]
+    fn sample_direction(&self, sample: Vec2) -> (Vec3, Float) {
+        (sample_unit_sphere(sample), 0.25 * FRAC_1_PI)
+    }
+
+    /// Computes the pdf (with respect to the solid angle) of [Self::sample_direction] sampling a
+    /// direction.
+    #[inline]
+    fn pdf_direction(&self, _direction: Vec3) -> Float {
+        0.25 * FRAC_1_PI
+    }
+}
+
+/// Converts a pdf with respect to the area of a surface to a pdf with respect to the solid angle
+/// of the origin.
+///
+/// # Arguments
+/// * `pdf`: The pdf with respect to the area
+/// * `origin`: The point from which we see the surface
+/// * `point`: The point on the surface
+/// * `normal`: The surface normal at `point`
+///
+/// # Returns
+/// * The pdf with respect to the solid angle (`0` if perpendicular)
+#[inline]
+pub fn area_to_solid_angle_pdf(pdf: Float, origin: Vec3, point: Vec3, normal: Vec3) -> Float {
+    let incident = point - origin;
+    let distance2 = incident.magnitude2();
+    let cos = normal.dot(incident).abs() / distance2.sqrt();
+
+    if cos == 0.0 {
+        0.0
+    } else {
+        pdf * distance2 / cos
+    }
+}
+
+/// Converts a pdf with respect to the area of an equirectangular image to a pdf with respect to
+/// the solid angle, as a point in row `θ` covers the solid angle `2π² sin(θ)` times its area.
+///
+/// # Arguments
+/// * `pdf`: The pdf with respect to the area of the image
+/// * `sin_theta`: The sine of the angle between the direction of the point and `+y`
+///
+/// # Returns
+/// * The pdf with respect to the solid angle (`0` at the poles)
+#[inline]
+pub fn equirectangular_to_solid_angle_pdf(pdf: Float, sin_theta: Float) -> Float {
+    if sin_theta > 0.0 {
+        pdf / (2.0 * PI * PI * sin_theta)
+    } else {
+        0.0
+    }
 }
@@ -1,18 +1,70 @@
-use crate::bxdf::face_forward;
+use crate::bxdf::bxdf_to_world;
 use crate::geometry::Plane;
 use crate::scene::{Sampleable, SurfaceSample};
-use crate::{Vec2, Vec3};
+use crate::util::mc::sample_unit_hemisphere;
+use crate::{Float, Vec2, Vec3};
 use cgmath::InnerSpace;
+#[cfg(not(feature = "f64"))]
+use std::f32::consts::FRAC_1_PI;
+#[cfg(feature = "f64")]
+use std::f64::consts::FRAC_1_PI;
 
+impl Plane {
+    /// Returns the plane normal pointing away from the origin (towards the plane).
+    #[inline]
+    fn normal_towards(&self, origin: Vec3) -> Vec3 {
+        if (origin - self.point).dot(self.normal) > 0.0 {
+            -self.normal
+        } else {
+            self.normal
+        }
+    }
+}
+
+/// As a plane is infinite, it cannot be sampled by area.
+/// Instead, a cosine-weighted direction towards the plane is sampled, of which every single one
+/// intersects the plane.
+/// Sampling independent of an origin always results in a pdf of `0`.
 #[typetag::serde]
 impl Sampleable for Plane {
-    fn sample_surface(&self, origin: Vec3, _sample: Vec2) -> SurfaceSample {
-        let v = origin - self.point;
-        let distance = v.dot(self.normal);
+    fn sample_surface(&self, origin: Vec3, sample: Vec2) -> SurfaceSample {
+        let towards = self.normal_towards(origin);
+        let distance = (self.point - origin).dot(towards);
+
+        let direction = bxdf_to_world(towards).rotate_vector(sample_unit_hemisphere(sample));
+        let cos = direction.dot(towards);
+
+        if cos <= 0.0 || distance <= 0.0 {
+            return SurfaceSample::new(origin, -towards, 0.0);
+        }
+
+        let point = origin + direction * (distance / cos);
+
+        SurfaceSample::new(point, -towards, cos * FRAC_1_PI)
+    }
+
+    fn pdf(&self, origin: Vec3, direction: Vec3) -> Float {
+        let cos = direction.dot(self.normal_towards(origin));
+
+        if cos > 0.0 {
+            cos * FRAC_1_PI
+        } else {
+            0.0
+        }
+    }
 
-        let point = origin - distance * self.normal;
-        let normal = face_forward(v, self.normal);
+    #[inline]
+    fn sample_area(&self, _: Vec2) -> SurfaceSample {
+        SurfaceSample::new(self.point, self.normal, 0.0)
+    }
+
+    #[inline(always)]
+    fn pdf_area(&self, _: Vec3) -> Float {
+        0.0
+    }
 
-        SurfaceSample::new(point, normal)
+    #[inline(always)]
+    fn area(&self) -> Float {
+        Float::INFINITY
     }
 }
@@ -1,7 +1,7 @@
 use crate::geometry::Point;
 use crate::scene::{Sampleable, SurfaceSample};
-use crate::{Vec2, Vec3};
-use cgmath::InnerSpace;
+use crate::{Float, Vec2, Vec3};
+use cgmath::{InnerSpace, Zero};
 
 #[typetag::serde]
 impl Sampleable for Point {
@@ -9,6 +9,34 @@ impl Sampleable for Point {
     fn sample_surface(&self, point: Vec3, _sample: Vec2) -> SurfaceSample {
         let normal = point - self.0;
 
-        SurfaceSample::new(self.0, normal.normalize())
+        SurfaceSample::new(self.0, normal.normalize(), 1.0)
+    }
+
+    /// A point can never be hit by a ray.
+    #[inline(always)]
+    fn pdf(&self, _: Vec3, _: Vec3) -> Float {
+        0.0
+    }
+
+    /// The point itself with a (delta) pdf of `1`.
+    /// The normal is undefined and therefore zero.
+    #[inline]
+    fn sample_area(&self, _: Vec2) -> SurfaceSample {
+        SurfaceSample::new(self.0, Vec3::zero(), 1.0)
+    }
+
+    #[inline(always)]
+    fn pdf_area(&self, _: Vec3) -> Float {
+        1.0
+    }
+
+    #[inline(always)]
+    fn area(&self) -> Float {
+        0.0
+    }
+
+    #[inline(always)]
+    fn is_delta(&self) -> bool {
+        true
     }
 }
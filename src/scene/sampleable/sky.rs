@@ -0,0 +1,137 @@
+use crate::geometry::{direction_to_equirectangular, equirectangular_to_direction, Sky};
+use crate::scene::{equirectangular_to_solid_angle_pdf, Sampleable, SurfaceSample};
+use crate::{Float, Spectrum, Vec2, Vec3};
+
+impl Sky {
+    /// Samples a direction towards the sky proportional to its tabulated luminance.
+    ///
+    /// # Returns
+    /// * The direction
+    /// * The pdf with respect to the solid angle
+    fn sample_sky(&self, sample: Vec2) -> (Vec3, Float) {
+        let (point, pdf) = self.distribution.sample(sample);
+        let (direction, sin_theta) = equirectangular_to_direction(point);
+
+        (
+            direction,
+            equirectangular_to_solid_angle_pdf(pdf, sin_theta),
+        )
+    }
+
+    /// Computes the pdf (with respect to the solid angle) of [Self::sample_sky].
+    fn pdf_sky(&self, direction: Vec3) -> Float {
+        let (point, sin_theta) = direction_to_equirectangular(direction);
+
+        equirectangular_to_solid_angle_pdf(self.distribution.pdf(point), sin_theta)
+    }
+}
+
+/// As the sky is infinitely far away, it cannot be sampled by area.
+/// Its directions are sampled instead, with the radiance of a direction given by its
+/// [color](Sampleable::color).
+#[typetag::serde]
+impl Sampleable for Sky {
+    fn sample_surface(&self, origin: Vec3, sample: Vec2) -> SurfaceSample {
+        let (incident, pdf) = self.sample_sky(sample);
+
+        SurfaceSample::new(origin, -incident, pdf)
+    }
+
+    #[inline]
+    fn pdf(&self, _origin: Vec3, direction: Vec3) -> Float {
+        self.pdf_sky(direction)
+    }
+
+    #[inline]
+    fn sample_area(&self, _: Vec2) -> SurfaceSample {
+        SurfaceSample::new(Vec3::new(0.0, 0.0, 0.0), Vec3::new(0.0, -1.0, 0.0), 0.0)
+    }
+
+    #[inline(always)]
+    fn pdf_area(&self, _: Vec3) -> Float {
+        0.0
+    }
+
+    #[inline(always)]
+    fn area(&self) -> Float {
+        Float::INFINITY
+    }
+
+    #[inline(always)]
+    fn is_infinite(&self) -> bool {
+        true
+    }
+
+    #[inline]
+    fn irradiance(&self) -> Float {
+        Sky::irradiance(self)
+    }
+
+    #[inline]
+    #[allow(clippy::useless_conversion)]
+    fn color(&self, direction: Vec3) -> Option<Spectrum> {
+        Some(Spectrum::from(self.radiance(-direction)))
+    }
+
+    /// Samples the directions leaving the sky proportional to its tabulated luminance.
+    #[inline]
+    fn sample_direction(&self, sample: Vec2) -> (Vec3, Float) {
+        let (incident, pdf) = self.sample_sky(sample);
+
+        (-incident, pdf)
+    }
+
+    #[inline]
+    fn pdf_direction(&self, direction: Vec3) -> Float {
+        self.pdf_sky(-direction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::cie::{CIE_Y_INTEGRAL, MAX_LUMINOUS_EFFICACY};
+    use crate::color::color_data::LAMBDA_RANGE;
+    use crate::color::{self, Xyz};
+    use cgmath::InnerSpace;
+
+    #[test]
+    fn sampled_irradiance_matches() {
+        let sky = Sky::new(Vec3::new(0.5, 0.6, -0.3), 3.0, 0.3);
+
+        let n = 256;
+        let mut irradiance = 0.0;
+        for i in 0..n {
+            for j in 0..n {
+                let sample = Vec2::new(
+                    (i as Float + 0.5) / n as Float,
+                    (j as Float + 0.5) / n as Float,
+                );
+                let surface_sample = sky.sample_surface(Vec3::new(0.0, 0.0, 0.0), sample);
+                let incident = -surface_sample.normal;
+                let pdf = sky.pdf(Vec3::new(0.0, 0.0, 0.0), incident);
+                assert!((pdf - surface_sample.pdf).abs() < 1e-2 * pdf);
+
+                irradiance += sky.radiance(incident).sum_values()
+                    / (color::Spectrum::size() as Float * surface_sample.pdf);
+            }
+        }
+        irradiance /= (n * n) as Float;
+
+        let expected = sky.irradiance();
+        assert!((irradiance - expected).abs() < 2e-2 * expected);
+    }
+
+    #[test]
+    fn zenith_is_blue_and_bright() {
+        let sky = Sky::new(Vec3::new(0.0, 1.0, 1.0).normalize(), 2.5, 0.0);
+        let zenith = Xyz::from(sky.radiance(Vec3::new(0.0, 1.0, 0.0)));
+        let sum = zenith.sum_values();
+
+        // the clear zenith lies at a luminance of a few thousand cd/m² and blueish chromaticity
+        let luminance = zenith[1] * MAX_LUMINOUS_EFFICACY * CIE_Y_INTEGRAL / LAMBDA_RANGE;
+        assert!((2000.0..10000.0).contains(&luminance), "{}", luminance);
+        assert!(zenith[0] / sum < 0.3 && zenith[1] / sum < 0.32);
+        assert!(sky.radiance(Vec3::new(0.0, -1.0, 0.0)).is_black());
+    }
+}
@@ -1,21 +1,35 @@
-use crate::geometry::{spherical_to_cartesian_frame_trig, CoordinateSystem, Sphere};
-use crate::scene::{Sampleable, SurfaceSample};
-use crate::util::mc::sample_unit_sphere;
+use crate::geometry::{spherical_to_cartesian_frame_trig, CoordinateSystem, Geometry, Ray, Sphere};
+use crate::scene::{area_to_solid_angle_pdf, Sampleable, SurfaceSample};
+use crate::util::mc::{sample_unit_sphere, uniform_cone_pdf};
 use crate::{Float, Vec2, Vec3};
 use cgmath::InnerSpace;
 #[cfg(not(feature = "f64"))]
-use std::f32::consts::TAU;
+use std::f32::consts::{PI, TAU};
 #[cfg(feature = "f64")]
-use std::f64::consts::TAU;
+use std::f64::consts::{PI, TAU};
 
-fn sample_surface_inside(sphere: &Sphere, sample: Vec2) -> SurfaceSample {
+impl Sphere {
+    /// Returns the cosine of the cone angle the sphere covers as seen from the given origin.
+    ///
+    /// # Constraints
+    /// * `origin`: Should be outside of the sphere.
+    #[inline]
+    fn cos_theta_max(&self, origin: Vec3) -> Float {
+        let sin_theta_max2 = self.radius2() / (self.center - origin).magnitude2();
+
+        Float::max(0.0, 1.0 - sin_theta_max2).sqrt()
+    }
+}
+
+fn sample_surface_inside(sphere: &Sphere, origin: Vec3, sample: Vec2) -> SurfaceSample {
     let mut normal = sample_unit_sphere(sample);
     let point = sphere.center + sphere.radius * normal;
+    let pdf = area_to_solid_angle_pdf(1.0 / sphere.area(), origin, point, normal);
     if sphere.inverse {
         normal = -normal;
     }
 
-    SurfaceSample::new(point, normal)
+    SurfaceSample::new(point, normal, pdf)
 }
 
 #[typetag::serde]
@@ -28,7 +42,7 @@ impl Sampleable for Sphere {
 
         if dist_sq < r2 {
             // inside the sphere (may happen)
-            sample_surface_inside(self, sample)
+            sample_surface_inside(self, origin, sample)
         } else {
             let distance = dist_sq.sqrt();
             let axis = oc / -distance;
@@ -65,7 +79,42 @@ impl Sampleable for Sphere {
 
             let point = self.center + self.radius * normal;
 
-            SurfaceSample::new(point, normal)
+            SurfaceSample::new(point, normal, uniform_cone_pdf(cos_theta_max))
+        }
+    }
+
+    fn pdf(&self, origin: Vec3, direction: Vec3) -> Float {
+        let ray = Ray::new(origin, direction);
+
+        if (self.center - origin).magnitude2() < self.radius2() {
+            match self.intersect(ray) {
+                Some(i) => area_to_solid_angle_pdf(1.0 / self.area(), origin, i.point, i.normal),
+                None => 0.0,
+            }
+        } else if self.intersects(ray) {
+            uniform_cone_pdf(self.cos_theta_max(origin))
+        } else {
+            0.0
+        }
+    }
+
+    fn sample_area(&self, sample: Vec2) -> SurfaceSample {
+        let mut normal = sample_unit_sphere(sample);
+        let point = self.center + self.radius * normal;
+        if self.inverse {
+            normal = -normal;
         }
+
+        SurfaceSample::new(point, normal, 1.0 / self.area())
+    }
+
+    #[inline]
+    fn pdf_area(&self, _: Vec3) -> Float {
+        1.0 / self.area()
+    }
+
+    #[inline]
+    fn area(&self) -> Float {
+        4.0 * PI * self.radius2()
     }
 }
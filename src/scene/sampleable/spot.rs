@@ -0,0 +1,118 @@
+use crate::bxdf::bxdf_to_world;
+use crate::geometry::Spot;
+use crate::scene::{Sampleable, SurfaceSample};
+use crate::util::floats;
+use crate::util::mc::{sample_unit_cone, uniform_cone_pdf};
+use crate::{Float, Vec2, Vec3};
+use cgmath::{InnerSpace, Zero};
+#[cfg(not(feature = "f64"))]
+use std::f32::consts::TAU;
+#[cfg(feature = "f64")]
+use std::f64::consts::TAU;
+
+#[typetag::serde]
+impl Sampleable for Spot {
+    #[inline]
+    fn sample_surface(&self, point: Vec3, _sample: Vec2) -> SurfaceSample {
+        let normal = point - self.position;
+
+        SurfaceSample::new(self.position, normal.normalize(), 1.0)
+    }
+
+    /// A spot can never be hit by a ray.
+    #[inline(always)]
+    fn pdf(&self, _: Vec3, _: Vec3) -> Float {
+        0.0
+    }
+
+    /// The position itself with a (delta) pdf of `1`.
+    /// The normal is undefined and therefore zero.
+    #[inline]
+    fn sample_area(&self, _: Vec2) -> SurfaceSample {
+        SurfaceSample::new(self.position, Vec3::zero(), 1.0)
+    }
+
+    #[inline(always)]
+    fn pdf_area(&self, _: Vec3) -> Float {
+        1.0
+    }
+
+    #[inline(always)]
+    fn area(&self) -> Float {
+        0.0
+    }
+
+    #[inline(always)]
+    fn is_delta(&self) -> bool {
+        true
+    }
+
+    /// Falls off smoothly from the inner to the outer angle.
+    fn intensity(&self, direction: Vec3) -> Float {
+        let (cos_inner, cos_outer) = self.cos_angles();
+        let cos = self.direction.normalize().dot(direction);
+
+        if cos >= cos_inner {
+            1.0
+        } else if cos <= cos_outer {
+            0.0
+        } else {
+            let t = floats::lerp_inv(cos, cos_outer, cos_inner);
+            t * t * (3.0 - 2.0 * t)
+        }
+    }
+
+    /// The full cone up to the inner angle and half of the falloff.
+    fn solid_angle(&self) -> Float {
+        let (cos_inner, cos_outer) = self.cos_angles();
+
+        TAU * ((1.0 - cos_inner) + 0.5 * (cos_inner - cos_outer))
+    }
+
+    /// Samples the cone of the outer angle uniformly.
+    fn sample_direction(&self, sample: Vec2) -> (Vec3, Float) {
+        let (_, cos_outer) = self.cos_angles();
+        let local = sample_unit_cone(sample, cos_outer);
+        let direction = bxdf_to_world(self.direction.normalize()).rotate_vector(local);
+
+        (direction, uniform_cone_pdf(cos_outer))
+    }
+
+    fn pdf_direction(&self, direction: Vec3) -> Float {
+        let (_, cos_outer) = self.cos_angles();
+
+        if self.direction.normalize().dot(direction) > cos_outer {
+            uniform_cone_pdf(cos_outer)
+        } else {
+            0.0
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sampled_intensity_matches_solid_angle() {
+        let spot = Spot::new(Vec3::zero(), Vec3::new(1.0, 2.0, -0.5), 20.0, 35.0);
+
+        let n = 256;
+        let mut solid_angle = 0.0;
+        for i in 0..n {
+            for j in 0..n {
+                let sample = Vec2::new(
+                    (i as Float + 0.5) / n as Float,
+                    (j as Float + 0.5) / n as Float,
+                );
+                let (direction, pdf) = spot.sample_direction(sample);
+                assert!((spot.pdf_direction(direction) - pdf).abs() < 1e-3);
+
+                solid_angle += spot.intensity(direction) / pdf;
+            }
+        }
+        solid_angle /= (n * n) as Float;
+
+        assert!((solid_angle - spot.solid_angle()).abs() < 1e-3);
+    }
+}
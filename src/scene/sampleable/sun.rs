@@ -0,0 +1,158 @@
+use crate::bxdf::bxdf_to_world;
+use crate::geometry::Sun;
+use crate::scene::{Sampleable, SurfaceSample};
+use crate::util::mc::{sample_unit_cone, uniform_cone_pdf};
+use crate::{Float, Vec2, Vec3};
+use cgmath::InnerSpace;
+#[cfg(not(feature = "f64"))]
+use std::f32::consts::PI;
+#[cfg(feature = "f64")]
+use std::f64::consts::PI;
+
+impl Sun {
+    /// Samples a direction within the disk.
+    ///
+    /// # Arguments
+    /// * `axis` - The direction towards (or away from) the center of the disk
+    /// * `sample` - A random sample
+    ///
+    /// # Returns
+    /// * The direction
+    /// * The pdf with respect to the solid angle (`1` if directional)
+    fn sample_disk(&self, axis: Vec3, sample: Vec2) -> (Vec3, Float) {
+        if self.is_directional() {
+            return (axis, 1.0);
+        }
+
+        let cos_radius = self.cos_radius();
+        let local = sample_unit_cone(sample, cos_radius);
+
+        (
+            bxdf_to_world(axis).rotate_vector(local),
+            uniform_cone_pdf(cos_radius),
+        )
+    }
+
+    /// Returns whether a direction lies within the disk.
+    #[inline]
+    fn within(&self, axis: Vec3, direction: Vec3) -> bool {
+        // the sun only covers about half a degree, which is close to the precision of the cosine
+        axis.dot(direction) > self.cos_radius() - 4.0 * Float::EPSILON
+    }
+
+    /// Computes the pdf (with respect to the solid angle) of [Self::sample_disk].
+    fn pdf_disk(&self, axis: Vec3, direction: Vec3) -> Float {
+        if !self.is_directional() && self.within(axis, direction) {
+            uniform_cone_pdf(self.cos_radius())
+        } else {
+            0.0
+        }
+    }
+}
+
+/// As the disk is infinitely far away, it cannot be sampled by area.
+/// Its directions are sampled instead, where a directional sun has a (delta) pdf of `1`.
+#[typetag::serde]
+impl Sampleable for Sun {
+    fn sample_surface(&self, origin: Vec3, sample: Vec2) -> SurfaceSample {
+        let (incident, pdf) = self.sample_disk(self.direction.normalize(), sample);
+
+        SurfaceSample::new(origin, -incident, pdf)
+    }
+
+    #[inline]
+    fn pdf(&self, _origin: Vec3, direction: Vec3) -> Float {
+        self.pdf_disk(self.direction.normalize(), direction)
+    }
+
+    #[inline]
+    fn sample_area(&self, _: Vec2) -> SurfaceSample {
+        SurfaceSample::new(Vec3::new(0.0, 0.0, 0.0), -self.direction.normalize(), 0.0)
+    }
+
+    #[inline(always)]
+    fn pdf_area(&self, _: Vec3) -> Float {
+        0.0
+    }
+
+    #[inline(always)]
+    fn area(&self) -> Float {
+        Float::INFINITY
+    }
+
+    #[inline]
+    fn is_delta(&self) -> bool {
+        self.is_directional()
+    }
+
+    #[inline(always)]
+    fn is_infinite(&self) -> bool {
+        true
+    }
+
+    /// The irradiance of a disk of uniform radiance is its radiance times its projected solid
+    /// angle.
+    fn intensity(&self, direction: Vec3) -> Float {
+        if self.is_directional() {
+            return 1.0;
+        }
+
+        if self.within(-self.direction.normalize(), direction) {
+            let cos_radius = self.cos_radius();
+            1.0 / (PI * (1.0 - cos_radius) * (1.0 + cos_radius))
+        } else {
+            0.0
+        }
+    }
+
+    /// Samples the directions leaving the disk uniformly.
+    #[inline]
+    fn sample_direction(&self, sample: Vec2) -> (Vec3, Float) {
+        self.sample_disk(-self.direction.normalize(), sample)
+    }
+
+    #[inline]
+    fn pdf_direction(&self, direction: Vec3) -> Float {
+        if self.is_directional() {
+            1.0
+        } else {
+            self.pdf_disk(-self.direction.normalize(), direction)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sampled_irradiance_is_one() {
+        for angle in [0.0, 0.53, 30.0] {
+            let sun = Sun::new(Vec3::new(0.3, 1.0, -0.2), angle);
+            let normal = sun.direction.normalize();
+
+            let n = 64;
+            let mut irradiance = 0.0;
+            for i in 0..n {
+                for j in 0..n {
+                    let sample = Vec2::new(
+                        (i as Float + 0.5) / n as Float,
+                        (j as Float + 0.5) / n as Float,
+                    );
+                    let surface_sample = sun.sample_surface(Vec3::new(0.0, 0.0, 0.0), sample);
+                    let incident = -surface_sample.normal;
+                    if !sun.is_delta() {
+                        let pdf = sun.pdf(Vec3::new(0.0, 0.0, 0.0), incident);
+                        assert!((pdf - surface_sample.pdf).abs() < 1e-3 * pdf);
+                    }
+
+                    irradiance +=
+                        sun.intensity(-incident) * incident.dot(normal) / surface_sample.pdf;
+                }
+            }
+            irradiance /= (n * n) as Float;
+
+            assert!((irradiance - 1.0).abs() < 1e-3, "{}: {}", angle, irradiance);
+        }
+    }
+}
@@ -0,0 +1,221 @@
+//! Piecewise-constant distributions over the unit interval and the unit square.
+
+use crate::{Float, Vec2};
+
+/// A piecewise-constant distribution over `[0, 1)`, sampled by inverting its cdf.
+#[derive(Clone, Debug, Default)]
+pub struct PiecewiseConstant1D {
+    /// The (non-negative) function values of the equally sized pieces
+    values: Vec<Float>,
+    /// The cumulative distribution at the end of each piece
+    cdf: Vec<Float>,
+    /// The integral of the function over `[0, 1)`
+    integral: Float,
+}
+
+impl PiecewiseConstant1D {
+    /// Creates a distribution proportional to the given values.
+    /// If all values are `0`, the distribution is uniform.
+    ///
+    /// # Constraints
+    /// * `values` - Should not be empty.
+    ///   Negative and non-finite values are treated as `0`.
+    pub fn new(values: &[Float]) -> Self {
+        let values: Vec<Float> = values
+            .iter()
+            .map(|&v| if v.is_finite() { v.max(0.0) } else { 0.0 })
+            .collect();
+        let n = values.len() as Float;
+
+        let integral = values.iter().sum::<Float>() / n;
+        let mut sum = 0.0;
+        let cdf = if integral > 0.0 {
+            values
+                .iter()
+                .map(|v| {
+                    sum += v;
+                    sum / (n * integral)
+                })
+                .collect()
+        } else {
+            (1..=values.len()).map(|i| i as Float / n).collect()
+        };
+
+        Self {
+            values,
+            cdf,
+            integral,
+        }
+    }
+
+    /// Returns the integral of the function over `[0, 1)`.
+    #[inline]
+    pub fn integral(&self) -> Float {
+        self.integral
+    }
+
+    /// Returns the number of pieces.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns whether there are no pieces.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Samples a point.
+    ///
+    /// # Constraints
+    /// * `sample` - Should be within `[0, 1)`.
+    ///
+    /// # Returns
+    /// * The point within `[0, 1)`
+    /// * The index of its piece
+    /// * The pdf of the point
+    pub fn sample(&self, sample: Float) -> (Float, usize, Float) {
+        let (index, _, offset) = self.sample_discrete(sample);
+
+        let x = ((index as Float + offset) / self.len() as Float).min(1.0 - Float::EPSILON);
+        (x, index, self.pdf_piece(index))
+    }
+
+    /// Chooses a piece.
+    ///
+    /// # Constraints
+    /// * `sample` - Should be within `[0, 1)`.
+    ///
+    /// # Returns
+    /// * The index of the piece
+    /// * The probability of choosing it
+    /// * The sample remapped to `[0, 1)` within the piece, so that it can be reused
+    pub fn sample_discrete(&self, sample: Float) -> (usize, Float, Float) {
+        let index = self
+            .cdf
+            .partition_point(|&c| c <= sample)
+            .min(self.len() - 1);
+
+        let start = if index == 0 { 0.0 } else { self.cdf[index - 1] };
+        let width = self.cdf[index] - start;
+        let remapped = if width > 0.0 {
+            ((sample - start) / width).clamp(0.0, 1.0 - Float::EPSILON)
+        } else {
+            0.5
+        };
+
+        (index, width, remapped)
+    }
+
+    /// Computes the pdf of [Self::sample] sampling a point.
+    ///
+    /// # Constraints
+    /// * `x` - Should be within `[0, 1)`.
+    #[inline]
+    pub fn pdf(&self, x: Float) -> Float {
+        self.pdf_piece(self.piece(x))
+    }
+
+    /// Returns the index of the piece containing a point.
+    #[inline]
+    pub fn piece(&self, x: Float) -> usize {
+        ((x * self.len() as Float) as usize).min(self.len() - 1)
+    }
+
+    /// Computes the pdf of the points within a piece.
+    #[inline]
+    fn pdf_piece(&self, index: usize) -> Float {
+        if self.integral > 0.0 {
+            self.values[index] / self.integral
+        } else {
+            1.0
+        }
+    }
+}
+
+/// A piecewise-constant distribution over `[0, 1)²`, sampling `y` by the marginal distribution of
+/// the rows and then `x` within the row.
+#[derive(Clone, Debug, Default)]
+pub struct PiecewiseConstant2D {
+    /// The distributions within each row
+    conditional: Vec<PiecewiseConstant1D>,
+    /// The distribution of the rows
+    marginal: PiecewiseConstant1D,
+}
+
+impl PiecewiseConstant2D {
+    /// Creates a distribution proportional to the given values.
+    ///
+    /// # Constraints
+    /// * `values` - Should contain `width * height` values, rows first.
+    /// * `width` - Should be positive.
+    /// * `height` - Should be positive.
+    pub fn new(values: &[Float], width: usize, height: usize) -> Self {
+        let conditional: Vec<_> = values
+            .chunks_exact(width)
+            .take(height)
+            .map(PiecewiseConstant1D::new)
+            .collect();
+        let integrals: Vec<_> = conditional.iter().map(|c| c.integral()).collect();
+
+        Self {
+            marginal: PiecewiseConstant1D::new(&integrals),
+            conditional,
+        }
+    }
+
+    /// Returns the integral of the function over `[0, 1)²`.
+    #[inline]
+    pub fn integral(&self) -> Float {
+        self.marginal.integral()
+    }
+
+    /// Samples a point.
+    ///
+    /// # Constraints
+    /// * `sample` - All values should be within `[0, 1)`.
+    ///
+    /// # Returns
+    /// * The point within `[0, 1)²`
+    /// * The pdf of the point
+    pub fn sample(&self, sample: Vec2) -> (Vec2, Float) {
+        let (y, row, pdf_y) = self.marginal.sample(sample.y);
+        let (x, _, pdf_x) = self.conditional[row].sample(sample.x);
+
+        (Vec2::new(x, y), pdf_x * pdf_y)
+    }
+
+    /// Computes the pdf of [Self::sample] sampling a point.
+    ///
+    /// # Constraints
+    /// * `point` - All values should be within `[0, 1)`.
+    pub fn pdf(&self, point: Vec2) -> Float {
+        let row = self.marginal.piece(point.y);
+
+        self.marginal.pdf(point.y) * self.conditional[row].pdf(point.x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sample_matches_pdf() {
+        let values = [0.0, 1.0, 3.0, 0.5, 2.0, 0.0];
+        let distribution = PiecewiseConstant2D::new(&values, 3, 2);
+        assert!((distribution.integral() - 6.5 / 6.0).abs() < 1e-5);
+
+        for i in 0..16 {
+            for j in 0..16 {
+                let sample = Vec2::new((i as Float + 0.5) / 16.0, (j as Float + 0.5) / 16.0);
+                let (point, pdf) = distribution.sample(sample);
+
+                assert!(pdf > 0.0);
+                assert!((distribution.pdf(point) - pdf).abs() < 1e-4 * pdf);
+            }
+        }
+        assert_eq!(distribution.pdf(Vec2::new(0.1, 0.1)), 0.0);
+    }
+}
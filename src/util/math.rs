@@ -9,7 +9,6 @@ pub trait Lerp {
 macro_rules! impl_lerp {
     ($($t:ident),+) => {$(
         impl Lerp for $t {
-            #[must_use]
             fn lerp(self, start: Self, end: Self) -> Self {
                 // consistent
                 if start == end {
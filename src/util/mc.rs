@@ -104,7 +104,8 @@ pub fn sample_unit_cone(sample: Vec2, cos_theta_max: Float) -> Vec3 {
     let sin_theta = Float::sqrt(1.0 - cos_theta * cos_theta);
     let (sin_phi, cos_phi) = Float::sin_cos(sample.y * TAU);
 
-    spherical_to_cartesian_trig(sin_theta, cos_theta, sin_phi, cos_phi)
+    // spherical_to_cartesian_trig measures its phi from the axis
+    spherical_to_cartesian_trig(sin_phi, cos_phi, sin_theta, cos_theta)
 }
 
 /// Computes the pdf a uniformly sampled unit cone.
@@ -115,3 +116,96 @@ pub fn sample_unit_cone(sample: Vec2, cos_theta_max: Float) -> Vec3 {
 pub fn uniform_cone_pdf(cos_theta: Float) -> Float {
     1.0 / (TAU * (1.0 - cos_theta))
 }
+
+/// Samples a point uniformly on a triangle.
+///
+/// # Constraints
+/// * `sample`: All values should be within `[0, 1]`.
+///
+/// # Arguments
+/// * `sample`: A random sample
+///
+/// # Returns
+/// * The barycentric coordinates of the second and third vertex
+#[inline]
+pub fn sample_unit_triangle(sample: Vec2) -> Vec2 {
+    let sqrt = sample.x.sqrt();
+
+    Vec2::new(1.0 - sqrt, sample.y * sqrt)
+}
+
+/// Samples a point on the unit hemisphere around the `(0, 1, 0)` axis with a distribution
+/// proportional to a power of the cosine.
+///
+/// # Constraints
+/// * `sample`: All values should be within `[0, 1]`.
+/// * `exponent`: Should be non-negative.
+///
+/// # Arguments
+/// * `sample`: A random sample
+/// * `exponent`: The exponent of the cosine
+#[inline]
+pub fn sample_unit_hemisphere_power(sample: Vec2, exponent: Float) -> Vec3 {
+    let cos_theta = (1.0 - sample.x).powf(1.0 / (exponent + 1.0));
+    let sin_theta = Float::sqrt((1.0 - cos_theta * cos_theta).max(0.0));
+    let (sin_phi, cos_phi) = Float::sin_cos(sample.y * TAU);
+
+    Vec3::new(sin_theta * cos_phi, cos_theta, sin_theta * sin_phi)
+}
+
+/// Computes the pdf of [sample_unit_hemisphere_power].
+///
+/// # Arguments
+/// * `cos_theta`: The cosine of the angle to the axis
+/// * `exponent`: The exponent of the cosine
+#[inline]
+pub fn hemisphere_power_pdf(cos_theta: Float, exponent: Float) -> Float {
+    if cos_theta > 0.0 {
+        (exponent + 1.0) / TAU * cos_theta.powf(exponent)
+    } else {
+        0.0
+    }
+}
+
+/// Computes the balance heuristic weight of a sample of strategy `f` when combined with strategy `g`.
+///
+/// # Arguments
+/// * `nf`: The number of samples taken with strategy `f`
+/// * `f_pdf`: The pdf of strategy `f`
+/// * `ng`: The number of samples taken with strategy `g`
+/// * `g_pdf`: The pdf of strategy `g`
+#[inline]
+pub fn balance_heuristic(nf: u32, f_pdf: Float, ng: u32, g_pdf: Float) -> Float {
+    let f = nf as Float * f_pdf;
+    let g = ng as Float * g_pdf;
+
+    if f == 0.0 {
+        0.0
+    } else if f.is_infinite() {
+        1.0
+    } else {
+        f / (f + g)
+    }
+}
+
+/// Computes the power heuristic weight (with exponent `2`) of a sample of strategy `f` when
+/// combined with strategy `g`.
+///
+/// # Arguments
+/// * `nf`: The number of samples taken with strategy `f`
+/// * `f_pdf`: The pdf of strategy `f`
+/// * `ng`: The number of samples taken with strategy `g`
+/// * `g_pdf`: The pdf of strategy `g`
+#[inline]
+pub fn power_heuristic(nf: u32, f_pdf: Float, ng: u32, g_pdf: Float) -> Float {
+    let f = nf as Float * f_pdf;
+    let g = ng as Float * g_pdf;
+
+    if f == 0.0 {
+        0.0
+    } else if f.is_infinite() {
+        1.0
+    } else {
+        (f * f) / (f * f + g * g)
+    }
+}
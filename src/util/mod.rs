@@ -1,11 +1,14 @@
+pub mod distribution;
 pub mod floats;
 pub mod math;
 pub mod mc;
 mod packet_impl;
+pub mod spherical;
 pub mod threadpool;
 
 pub use packet_impl::PacketOps;
 
+#[derive(Default)]
 pub struct Index(usize);
 
 #[allow(dead_code)]
@@ -25,6 +25,8 @@ pub trait PacketOps<T> {
     fn neg_assign(&mut self);
     #[must_use]
     fn is_black(&self) -> bool;
+    #[must_use]
+    fn max_value(&self) -> T;
 }
 
 #[allow(clippy::needless_range_loop)]
@@ -124,4 +126,9 @@ impl PacketOps<Float> for [Float; PACKET_SIZE] {
     fn is_black(&self) -> bool {
         self.iter().all(|&f| f == 0.0)
     }
+
+    #[inline]
+    fn max_value(&self) -> Float {
+        self.iter().fold(Float::MIN, |a, &b| a.max(b))
+    }
 }
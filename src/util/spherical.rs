@@ -0,0 +1,316 @@
+//! Sampling of triangles and rectangles uniformly by the solid angle they cover as seen from an
+//! origin, which avoids the noise of sampling large and nearby surfaces by area.
+//!
+//! Very small solid angles are numerically unstable, as are solid angles approaching a
+//! hemisphere.
+//! Outside of [MIN_SOLID_ANGLE] and [MAX_SOLID_ANGLE], surfaces should be sampled by area instead.
+
+use crate::{Float, Vec2, Vec3};
+use cgmath::InnerSpace;
+#[cfg(not(feature = "f64"))]
+use std::f32::consts::{PI, TAU};
+#[cfg(feature = "f64")]
+use std::f64::consts::{PI, TAU};
+
+/// The smallest solid angle that can be sampled robustly.
+pub const MIN_SOLID_ANGLE: Float = 3e-4;
+/// The largest solid angle that can be sampled robustly, just below a hemisphere.
+pub const MAX_SOLID_ANGLE: Float = 6.22;
+
+/// Returns whether a solid angle can be sampled robustly (see [MIN_SOLID_ANGLE] and
+/// [MAX_SOLID_ANGLE]).
+#[inline]
+pub fn is_samplable(solid_angle: Float) -> bool {
+    (MIN_SOLID_ANGLE..=MAX_SOLID_ANGLE).contains(&solid_angle)
+}
+
+/// Computes the angle between two normalized vectors, accurate for small and large angles.
+#[inline]
+fn angle_between(a: Vec3, b: Vec3) -> Float {
+    if a.dot(b) < 0.0 {
+        PI - 2.0 * ((a + b).magnitude() / 2.0).min(1.0).asin()
+    } else {
+        2.0 * ((b - a).magnitude() / 2.0).min(1.0).asin()
+    }
+}
+
+/// Computes the solid angle a triangle covers as seen from an origin.
+///
+/// # Arguments
+/// * `origin` - The point from which the triangle is seen
+/// * `v0` - The first vertex
+/// * `v1` - The second vertex
+/// * `v2` - The third vertex
+pub fn triangle_solid_angle(origin: Vec3, v0: Vec3, v1: Vec3, v2: Vec3) -> Float {
+    let a = (v0 - origin).normalize();
+    let b = (v1 - origin).normalize();
+    let c = (v2 - origin).normalize();
+
+    let solid_angle = 2.0
+        * a.dot(b.cross(c))
+            .abs()
+            .atan2(1.0 + a.dot(b) + b.dot(c) + c.dot(a));
+
+    if solid_angle.is_finite() {
+        solid_angle.abs()
+    } else {
+        0.0
+    }
+}
+
+/// Computes the solid angle a parallelogram covers as seen from an origin.
+///
+/// # Arguments
+/// * `origin` - The point from which the parallelogram is seen
+/// * `corner` - A corner of the parallelogram
+/// * `u` - The first edge from the corner
+/// * `v` - The second edge from the corner
+#[inline]
+pub fn rectangle_solid_angle(origin: Vec3, corner: Vec3, u: Vec3, v: Vec3) -> Float {
+    triangle_solid_angle(origin, corner, corner + u, corner + u + v)
+        + triangle_solid_angle(origin, corner, corner + u + v, corner + v)
+}
+
+/// Samples a point on a triangle uniformly by the solid angle it covers as seen from an origin,
+/// following Arvo (1995), "Stratified Sampling of Spherical Triangles".
+///
+/// # Constraints
+/// * `sample` - All values should be within `[0, 1)`.
+///
+/// # Arguments
+/// * `origin` - The point from which the triangle is seen
+/// * `v0` - The first vertex
+/// * `v1` - The second vertex
+/// * `v2` - The third vertex
+/// * `sample` - A random sample
+///
+/// # Returns
+/// * The barycentric coordinates of the second and third vertex of the point
+/// * `None` if the triangle is degenerate as seen from the origin
+pub fn sample_spherical_triangle(
+    origin: Vec3,
+    v0: Vec3,
+    v1: Vec3,
+    v2: Vec3,
+    sample: Vec2,
+) -> Option<Vec2> {
+    let a = (v0 - origin).normalize();
+    let b = (v1 - origin).normalize();
+    let c = (v2 - origin).normalize();
+
+    let (n_ab, n_bc, n_ca) = (a.cross(b), b.cross(c), c.cross(a));
+    if ![n_ab, n_bc, n_ca].iter().all(|n| n.magnitude2() > 0.0) {
+        return None;
+    }
+    let (n_ab, n_bc, n_ca) = (n_ab.normalize(), n_bc.normalize(), n_ca.normalize());
+
+    // the interior angles of the spherical triangle
+    let alpha = angle_between(n_ab, -n_ca);
+    let beta = angle_between(n_bc, -n_ab);
+    let gamma = angle_between(n_ca, -n_bc);
+
+    // choose the area of a sub-triangle uniformly and find its vertex on the arc from a to c
+    let area_pi = PI + sample.x * (alpha + beta + gamma - PI);
+    let (sin_area, cos_area) = area_pi.sin_cos();
+    let (sin_alpha, cos_alpha) = alpha.sin_cos();
+
+    let sin_phi = sin_area * cos_alpha - cos_area * sin_alpha;
+    let cos_phi = cos_area * cos_alpha + sin_area * sin_alpha;
+    let k1 = cos_phi + cos_alpha;
+    let k2 = sin_phi - sin_alpha * a.dot(b);
+    let cos_b = ((k2 + (k2 * cos_phi - k1 * sin_phi) * cos_alpha)
+        / ((k2 * sin_phi + k1 * cos_phi) * sin_alpha))
+        .clamp(-1.0, 1.0);
+    let sin_b = (1.0 - cos_b * cos_b).max(0.0).sqrt();
+    let c_sub = cos_b * a + sin_b * orthonormal(c, a)?;
+
+    // choose a direction on the arc from b to the sub-triangle vertex
+    let cos_theta = 1.0 - sample.y * (1.0 - c_sub.dot(b));
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let direction = cos_theta * b + sin_theta * orthonormal(c_sub, b)?;
+
+    // intersect the triangle to find the barycentric coordinates
+    let (e1, e2) = (v1 - v0, v2 - v0);
+    let s1 = direction.cross(e2);
+    let divisor = s1.dot(e1);
+    if divisor == 0.0 || !divisor.is_finite() {
+        return Some(Vec2::new(1.0 / 3.0, 1.0 / 3.0));
+    }
+
+    let s = origin - v0;
+    let b1 = (s.dot(s1) / divisor).clamp(0.0, 1.0);
+    let b2 = (direction.dot(s.cross(e1)) / divisor).clamp(0.0, 1.0);
+    let sum = b1 + b2;
+
+    Some(if sum > 1.0 {
+        Vec2::new(b1 / sum, b2 / sum)
+    } else {
+        Vec2::new(b1, b2)
+    })
+}
+
+/// Samples a point on a rectangle uniformly by the solid angle it covers as seen from an origin,
+/// following Ureña et al. (2013), "An Area-Preserving Parametrization for Spherical Rectangles".
+///
+/// # Constraints
+/// * `u` - Should be orthogonal to `v`.
+/// * `sample` - All values should be within `[0, 1)`.
+///
+/// # Arguments
+/// * `origin` - The point from which the rectangle is seen
+/// * `corner` - A corner of the rectangle
+/// * `u` - The first edge from the corner
+/// * `v` - The second edge from the corner
+/// * `sample` - A random sample
+///
+/// # Returns
+/// * The coordinates of the point along `u` and `v` within `[0, 1]`
+/// * `None` if the rectangle is degenerate as seen from the origin
+pub fn sample_spherical_rectangle(
+    origin: Vec3,
+    corner: Vec3,
+    u: Vec3,
+    v: Vec3,
+    sample: Vec2,
+) -> Option<Vec2> {
+    let (u_length, v_length) = (u.magnitude(), v.magnitude());
+    if !(u_length > 0.0 && v_length > 0.0) {
+        return None;
+    }
+
+    // the local frame of the rectangle with the origin on its negative side
+    let (x, y) = (u / u_length, v / v_length);
+    let offset = corner - origin;
+    let z0 = -offset.dot(x.cross(y)).abs();
+    if z0 == 0.0 {
+        return None;
+    }
+    let (x0, y0) = (offset.dot(x), offset.dot(y));
+    let (x1, y1) = (x0 + u_length, y0 + v_length);
+
+    // the normals of the planes through the origin and each edge
+    let v00 = Vec3::new(x0, y0, z0);
+    let v01 = Vec3::new(x0, y1, z0);
+    let v10 = Vec3::new(x1, y0, z0);
+    let v11 = Vec3::new(x1, y1, z0);
+    let n0 = v00.cross(v10).normalize();
+    let n1 = v10.cross(v11).normalize();
+    let n2 = v11.cross(v01).normalize();
+    let n3 = v01.cross(v00).normalize();
+
+    // the interior angles of the spherical rectangle
+    let g0 = angle_between(-n0, n1);
+    let g1 = angle_between(-n1, n2);
+    let g2 = angle_between(-n2, n3);
+    let g3 = angle_between(-n3, n0);
+
+    // choose the area along x uniformly
+    let area = sample.x * (g0 + g1 - TAU) + (sample.x - 1.0) * (g2 + g3);
+    let (b0, b1) = (n0.z, n2.z);
+    let f = (area.cos() * b0 - b1) / area.sin();
+    let cu = (1.0 / (f * f + b0 * b0).sqrt())
+        .copysign(f)
+        .clamp(-1.0 + Float::EPSILON, 1.0 - Float::EPSILON);
+    let xu = (-(cu * z0) / (1.0 - cu * cu).max(0.0).sqrt()).clamp(x0, x1);
+
+    // choose the height along y uniformly
+    let d = (xu * xu + z0 * z0).sqrt();
+    let h0 = y0 / (d * d + y0 * y0).sqrt();
+    let h1 = y1 / (d * d + y1 * y1).sqrt();
+    let hv = h0 + sample.y * (h1 - h0);
+    let yv = if hv * hv < 1.0 - Float::EPSILON {
+        hv * d / (1.0 - hv * hv).sqrt()
+    } else {
+        y1
+    };
+
+    let point = Vec2::new((xu - x0) / u_length, (yv - y0) / v_length);
+    if point.x.is_finite() && point.y.is_finite() {
+        Some(Vec2::new(point.x.clamp(0.0, 1.0), point.y.clamp(0.0, 1.0)))
+    } else {
+        None
+    }
+}
+
+/// Returns the normalized part of `a` orthogonal to the normalized `b`.
+#[inline]
+fn orthonormal(a: Vec3, b: Vec3) -> Option<Vec3> {
+    let orthogonal = a - a.dot(b) * b;
+    let length = orthogonal.magnitude();
+
+    if length > 0.0 {
+        Some(orthogonal / length)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Estimates the solid angle of the sampled points by their area density.
+    fn sampled_solid_angle<F>(origin: Vec3, normal: Vec3, area: Float, sample_point: F) -> Float
+    where
+        F: Fn(Vec2) -> Vec3,
+    {
+        // with uniform samples by solid angle, the mean of the inverse solid angle density
+        // (cos / distance²) over the area is the inverse of the solid angle
+        let n = 256;
+        let mut density = 0.0;
+        for i in 0..n {
+            for j in 0..n {
+                let sample = Vec2::new(
+                    (i as Float + 0.5) / n as Float,
+                    (j as Float + 0.5) / n as Float,
+                );
+                let incident = sample_point(sample) - origin;
+                let cos = normal.dot(incident.normalize()).abs();
+
+                density += incident.magnitude2() / (cos * area);
+            }
+        }
+
+        (n * n) as Float / density
+    }
+
+    #[test]
+    fn triangle_is_sampled_uniformly() {
+        let origin = Vec3::new(0.2, 0.5, 0.1);
+        let (v0, v1, v2) = (
+            Vec3::new(-1.0, -0.3, -1.0),
+            Vec3::new(2.0, 0.1, -0.5),
+            Vec3::new(0.0, 0.4, 1.5),
+        );
+        let solid_angle = triangle_solid_angle(origin, v0, v1, v2);
+        assert!(is_samplable(solid_angle));
+
+        let normal = (v1 - v0).cross(v2 - v0);
+        let area = 0.5 * normal.magnitude();
+        let sampled = sampled_solid_angle(origin, normal.normalize(), area, |sample| {
+            let b = sample_spherical_triangle(origin, v0, v1, v2, sample).unwrap();
+            v0 + b.x * (v1 - v0) + b.y * (v2 - v0)
+        });
+
+        assert!((sampled - solid_angle).abs() < 1e-2 * solid_angle);
+    }
+
+    #[test]
+    fn rectangle_is_sampled_uniformly() {
+        let origin = Vec3::new(0.3, 0.4, 0.5);
+        let (corner, u, v) = (
+            Vec3::new(-1.0, -0.5, 0.0),
+            Vec3::new(2.0, 0.0, 0.0),
+            Vec3::new(0.0, 1.5, 0.0),
+        );
+        let solid_angle = rectangle_solid_angle(origin, corner, u, v);
+        assert!(is_samplable(solid_angle));
+
+        let sampled = sampled_solid_angle(origin, Vec3::unit_z(), 3.0, |sample| {
+            let p = sample_spherical_rectangle(origin, corner, u, v, sample).unwrap();
+            corner + p.x * u + p.y * v
+        });
+
+        assert!((sampled - solid_angle).abs() < 1e-2 * solid_angle);
+    }
+}
@@ -124,7 +124,7 @@ impl Threadpool {
     /// # Arguments
     /// - `num_workers`: The number of workers/threads in the pool
     /// - `max_num_jobs`: The optional cap of tasks waiting inside the pool.
-    ///                   When full, [Self::execute] blocks until insertion is possible.
+    ///   When full, [Self::execute] blocks until insertion is possible.
     /// - `on_terminate`: The function to call *first* when [Self::terminate] is called.
     pub fn new(
         num_workers: usize,